@@ -0,0 +1,10 @@
+use std::{env, fs, path::PathBuf};
+
+// Put memory.x where the linker can find it, as this crate isn't built from the directory
+// containing it when it's part of a larger build.
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::copy("memory.x", out.join("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory.x");
+}
@@ -0,0 +1,14 @@
+MEMORY
+{
+  /* NOTE K = KiBi = 1024 bytes */
+  /* The first 16K of flash belong to the bootloader. Its last 1K page holds the image info
+     record, and the application starts right after it, see ../memory.x */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 15K
+  /* The first word of RAM is the bootload flag, shared with the application. */
+  RAM : ORIGIN = 0x20000004, LENGTH = 20K - 4
+}
+
+/* This is where the call stack will be allocated. */
+/* The stack is of the full descending type. */
+/* NOTE Do NOT modify `_stack_start` unless you know what you are doing */
+_stack_start = ORIGIN(RAM) + LENGTH(RAM);
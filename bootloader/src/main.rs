@@ -0,0 +1,299 @@
+#![no_main]
+#![no_std]
+
+// A small resident bootloader which either starts the application, or accepts a new application
+// image over USB serial. The image is sent as a 12 byte header (the ASCII magic "PNLU", then the
+// image length and its CRC-32, both little endian u32s) followed by the image itself. Once the
+// whole image is written and its CRC verified, the bootloader replies with "K" and resets into the
+// new application, otherwise it replies with "E" and waits for another attempt.
+
+use core::{convert::TryInto, panic::PanicInfo, ptr};
+use cortex_m::{
+    asm::{self, delay},
+    peripheral::SCB,
+};
+use cortex_m_rt::entry;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use stm32f1xx_hal::{
+    pac::{self, flash::RegisterBlock},
+    prelude::*,
+    usb::{Peripheral, UsbBus},
+};
+use usb_device::device::{UsbDeviceBuilder, UsbVidPid};
+use usbd_serial::{SerialPort, USB_CLASS_CDC};
+
+// These have to match the application, see ../src/bootloader.rs and the two memory.x files.
+const BOOTLOAD_FLAG_ADDRESS: u32 = 0x2000_0000;
+const BOOTLOAD_MAGIC: u32 = 0xB007_10AD;
+const SYSTEM_BOOTLOAD_MAGIC: u32 = 0x5157_B007;
+const IMAGE_INFO_ADDRESS: u32 = 0x0800_3C00;
+const APP_ADDRESS: u32 = 0x0800_4000;
+/// The application may use flash up to the settings store.
+const APP_END: u32 = 0x0801_F400;
+
+/// The chip's own bootloader in system memory, which takes images over USART1.
+/// https://www.st.com/resource/en/application_note/an2606.pdf
+const SYSTEM_MEMORY_ADDRESS: u32 = 0x1FFF_F000;
+
+const PAGE_SIZE: u32 = 1024;
+const HEADER_MAGIC: &[u8; 4] = b"PNLU";
+const HEADER_LEN: usize = 12;
+
+// Update status flags in the image info page, programmed from their erased state to zero.
+const UPDATE_STARTED_ADDRESS: u32 = IMAGE_INFO_ADDRESS;
+const UPDATE_VERIFIED_ADDRESS: u32 = IMAGE_INFO_ADDRESS + 2;
+
+const FLASH_KEY1: u32 = 0x4567_0123;
+const FLASH_KEY2: u32 = 0xCDEF_89AB;
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    SCB::sys_reset();
+}
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().expect("failed to get stm32 peripherals");
+
+    let flag = unsafe { ptr::read_volatile(BOOTLOAD_FLAG_ADDRESS as *const u32) };
+    unsafe { ptr::write_volatile(BOOTLOAD_FLAG_ADDRESS as *mut u32, 0) };
+
+    // Nothing has been set up since the reset, which is how the system bootloader expects to
+    // find the chip.
+    if flag == SYSTEM_BOOTLOAD_MAGIC {
+        unsafe { jump_to(SYSTEM_MEMORY_ADDRESS) };
+    }
+
+    let mut rcc = dp.RCC.constrain();
+    let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
+
+    // Holding the encoder button (A3) at power on forces the bootloader to stay resident, in
+    // case the application is too broken to reboot into it.
+    let button_pin = gpioa.pa3.into_pull_up_input(&mut gpioa.crl);
+    delay(1000);
+    let button_held = button_pin.is_low().unwrap();
+
+    if flag != BOOTLOAD_MAGIC && !button_held && application_is_valid() {
+        unsafe { jump_to(APP_ADDRESS) };
+    }
+
+    let mut flash = dp.FLASH.constrain();
+    let clocks = rcc.cfgr.use_hse(8.mhz()).sysclk(48.mhz()).pclk1(24.mhz()).freeze(&mut flash.acr);
+
+    assert!(clocks.usbclk_valid());
+
+    // Pull the USB D+ pin low to indicate to the USB host that this device
+    // is resetting (sends a RESET condition on the USB bus).
+    let mut usb_pin_d_plus = gpioa.pa12.into_push_pull_output(&mut gpioa.crh);
+    usb_pin_d_plus.set_low().unwrap();
+    delay(clocks.sysclk().0 / 100);
+    let usb_pin_d_plus = usb_pin_d_plus.into_floating_input(&mut gpioa.crh);
+
+    let usb = Peripheral { usb: dp.USB, pin_dm: gpioa.pa11, pin_dp: usb_pin_d_plus };
+    let usb_bus = UsbBus::new(usb);
+    let mut serial = SerialPort::new(&usb_bus);
+
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .manufacturer("tonari")
+        .product("tonari dashboard controller bootloader")
+        .serial_number("tonari-dashboard-controller-v1")
+        .device_class(USB_CLASS_CDC)
+        .build();
+
+    let mut updater = Updater::new();
+    let mut buf = [0u8; 64];
+
+    loop {
+        if !usb_dev.poll(&mut [&mut serial]) {
+            continue;
+        }
+
+        let count = match serial.read(&mut buf) {
+            Ok(count) => count,
+            Err(_) => continue,
+        };
+
+        for &byte in &buf[..count] {
+            match updater.push(byte) {
+                Some(Ok(())) => {
+                    let _ = serial.write(b"K");
+                    let _ = serial.flush();
+                    // Give the host a moment to read the reply before disconnecting.
+                    for _ in 0..1000 {
+                        usb_dev.poll(&mut [&mut serial]);
+                        delay(clocks.sysclk().0 / 10_000);
+                    }
+                    SCB::sys_reset();
+                },
+                Some(Err(())) => {
+                    let _ = serial.write(b"E");
+                    updater = Updater::new();
+                },
+                None => {},
+            }
+        }
+    }
+}
+
+/// An application flashed directly (e.g. with stm32flash) is trusted as long as its vector table
+/// looks sane, but one written by the bootloader must also have passed its CRC check.
+fn application_is_valid() -> bool {
+    let update_started = read_half_word(UPDATE_STARTED_ADDRESS) == 0;
+    let update_verified = read_half_word(UPDATE_VERIFIED_ADDRESS) == 0;
+
+    let stack_pointer = unsafe { ptr::read_volatile(APP_ADDRESS as *const u32) };
+    let stack_pointer_in_ram = (0x2000_0000..=0x2000_5000).contains(&stack_pointer);
+
+    (!update_started || update_verified) && stack_pointer_in_ram
+}
+
+fn read_half_word(address: u32) -> u16 {
+    unsafe { ptr::read_volatile(address as *const u16) }
+}
+
+/// Starts the code whose vector table is at `address`, as if it had been reset into.
+unsafe fn jump_to(address: u32) -> ! {
+    (*SCB::ptr()).vtor.write(address);
+    asm::bootload(address as *const u32)
+}
+
+enum State {
+    Header { buf: [u8; HEADER_LEN], len: usize },
+    Image { length: u32, crc: u32, written: u32, pending_byte: Option<u8> },
+}
+
+/// Receives the update header and image one byte at a time, writing the image into flash.
+struct Updater {
+    state: State,
+}
+
+impl Updater {
+    fn new() -> Self {
+        Self { state: State::Header { buf: [0u8; HEADER_LEN], len: 0 } }
+    }
+
+    /// Returns the result of the update once the whole image has been received.
+    fn push(&mut self, byte: u8) -> Option<Result<(), ()>> {
+        match &mut self.state {
+            State::Header { buf, len } => {
+                buf[*len] = byte;
+                *len += 1;
+
+                if *len < HEADER_LEN {
+                    return None;
+                }
+
+                let length = u32::from_le_bytes(buf[4..8].try_into().unwrap());
+                let crc = u32::from_le_bytes(buf[8..12].try_into().unwrap());
+
+                if &buf[0..4] != HEADER_MAGIC
+                    || length == 0
+                    || length > APP_END - APP_ADDRESS
+                    || length % 2 != 0
+                {
+                    return Some(Err(()));
+                }
+
+                // Mark the update as started first, so that an interrupted update leaves the
+                // bootloader resident rather than jumping into half an image.
+                let mut flash = Flash::unlock();
+                flash.erase_page(IMAGE_INFO_ADDRESS);
+                flash.program_half_word(UPDATE_STARTED_ADDRESS, 0);
+
+                self.state = State::Image { length, crc, written: 0, pending_byte: None };
+                None
+            },
+            State::Image { length, crc, written, pending_byte } => {
+                let low = match pending_byte.take() {
+                    Some(low) => low,
+                    None => {
+                        *pending_byte = Some(byte);
+                        return None;
+                    },
+                };
+
+                let address = APP_ADDRESS + *written;
+                let mut flash = Flash::unlock();
+                if address.is_multiple_of(PAGE_SIZE) {
+                    flash.erase_page(address);
+                }
+                flash.program_half_word(address, low as u16 | (byte as u16) << 8);
+                *written += 2;
+
+                if *written < *length {
+                    return None;
+                }
+
+                let image = unsafe {
+                    core::slice::from_raw_parts(APP_ADDRESS as *const u8, *length as usize)
+                };
+                if crc32(image) != *crc {
+                    return Some(Err(()));
+                }
+
+                flash.program_half_word(UPDATE_VERIFIED_ADDRESS, 0);
+                Some(Ok(()))
+            },
+        }
+    }
+}
+
+/// The standard (zlib) CRC-32.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// Programs and erases flash through the FLASH registers directly, locking it again on drop.
+struct Flash {
+    regs: &'static RegisterBlock,
+}
+
+impl Flash {
+    fn unlock() -> Self {
+        let regs = unsafe { &*pac::FLASH::ptr() };
+
+        if regs.cr.read().lock().bit_is_set() {
+            regs.keyr.write(|w| unsafe { w.bits(FLASH_KEY1) });
+            regs.keyr.write(|w| unsafe { w.bits(FLASH_KEY2) });
+        }
+
+        Self { regs }
+    }
+
+    fn erase_page(&mut self, address: u32) {
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.per().set_bit());
+        self.regs.ar.write(|w| unsafe { w.bits(address) });
+        self.regs.cr.modify(|_, w| w.strt().set_bit());
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.per().clear_bit());
+    }
+
+    fn program_half_word(&mut self, address: u32, value: u16) {
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.pg().set_bit());
+        unsafe { ptr::write_volatile(address as *mut u16, value) };
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.pg().clear_bit());
+    }
+
+    fn wait_until_ready(&self) {
+        while self.regs.sr.read().bsy().bit_is_set() {}
+    }
+}
+
+impl Drop for Flash {
+    fn drop(&mut self) {
+        self.regs.cr.modify(|_, w| w.lock().set_bit());
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Embeds which firmware this is, for the panel to tell the host, see src/version.rs. Builds
+// which need to be reproducible can fix the timestamp with SOURCE_DATE_EPOCH.
+fn main() {
+    let commit = git(&["rev-parse", "--short=8", "HEAD"])
+        .filter(|commit| commit.len() == 8 && commit.chars().all(|c| c.is_ascii_hexdigit()))
+        .unwrap_or_else(|| "00000000".to_string());
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+        .is_some_and(|status| !status.is_empty());
+    let timestamp = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u32>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32);
+
+    println!("cargo:rustc-env=PANEL_GIT_COMMIT={}", commit.to_lowercase());
+    println!("cargo:rustc-env=PANEL_GIT_DIRTY={}", dirty as u8);
+    println!("cargo:rustc-env=PANEL_BUILD_TIMESTAMP={}", timestamp);
+
+    for path in [".git/HEAD", ".git/index", ".git/refs", "src", "core/src", "Cargo.toml"] {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // defmt keeps its strings in a section of their own, which its linker script places.
+    if env::var_os("CARGO_FEATURE_LOGGING").is_some() {
+        println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
+    }
+}
+
+/// The output of a git command, or `None` if git isn't there or this isn't a checkout.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok().map(|output| output.trim().to_string())
+}
@@ -0,0 +1,290 @@
+// A client for host applications, which talks to the panel over its USB serial port. It asks the
+// panel which protocol version it speaks, switches to the newest both sides know, and takes care
+// of the frames, CRC and report numbers which come with the newer versions, see the README.
+// Reports are read on their own thread, and handed out as an async stream.
+
+mod port;
+
+use panel_core::{
+    framing::{
+        self, FrameReader, COMMANDS_FRAME, FRAMED_PROTOCOL_VERSION, LED_FRAME, MAX_FRAME_LEN,
+        MAX_PAYLOAD_LEN, SEQUENCE_LEN,
+    },
+    input::{DEFAULT_PROTOCOL_VERSION, LATEST_PROTOCOL_VERSION},
+};
+use panel_protocol::{Command, Report, ReportReader};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    future::Future,
+    io::{self, Read, Write},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for the panel to answer `Command::Hello`. Panels without the command don't,
+/// and are spoken to at version 1.
+const HELLO_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The most read from the port at a time, a USB packet.
+const READ_LEN: usize = 64;
+
+/// What the reader thread hands over to `Reports`.
+#[derive(Default)]
+struct Shared {
+    reports: VecDeque<io::Result<Report>>,
+    /// Set once the port has failed, after which nothing more is read.
+    closed: bool,
+    /// Woken when a report comes in.
+    waker: Option<Waker>,
+    missed: u32,
+}
+
+impl Shared {
+    fn push(&mut self, report: io::Result<Report>) {
+        self.reports.push_back(report);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A panel, to send commands to. Its reports come out of the `Reports` it's opened with.
+pub struct Panel {
+    port: File,
+    version: u8,
+    /// Whether the panel has been told to expect LED frames, see `send_led_frame()`.
+    streaming: bool,
+}
+
+impl Panel {
+    /// Opens the panel's USB serial port at `path`, such as `/dev/ttyACM0`, and agrees on a
+    /// protocol version with it. Returns the panel along with the stream of its reports.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<(Self, Reports)> {
+        let mut port = port::open(path.as_ref())?;
+        let shared = Arc::new(Mutex::new(Shared::default()));
+
+        port.write_all(&Command::Hello { version: LATEST_PROTOCOL_VERSION }.as_arrayvec())?;
+        let version = wait_for_hello(&mut port, &shared)?.min(LATEST_PROTOCOL_VERSION);
+        // The panel switches after this command, so it's the last one sent without a frame.
+        if version > DEFAULT_PROTOCOL_VERSION {
+            port.write_all(&Command::SetProtocolVersion { version }.as_arrayvec())?;
+        }
+
+        let reader_port = port.try_clone()?;
+        let reader_shared = shared.clone();
+        thread::spawn(move || read_reports(reader_port, version, &reader_shared));
+
+        Ok((Self { port, version, streaming: false }, Reports { shared }))
+    }
+
+    /// The protocol version agreed with the panel.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn send(&mut self, command: &Command) -> io::Result<()> {
+        let bytes = command.as_arrayvec();
+        if self.version < FRAMED_PROTOCOL_VERSION {
+            return self.port.write_all(&bytes);
+        }
+
+        if self.streaming {
+            self.write_frame(&[&[COMMANDS_FRAME], &bytes[..]].concat())?;
+        } else {
+            self.write_frame(&bytes)?;
+        }
+        if let Command::StreamLeds { enabled } = *command {
+            self.streaming = enabled;
+        }
+        Ok(())
+    }
+
+    /// Shows `colors` on the LED strip straight away, from the first LED on. The first call
+    /// turns streaming on, which panels before protocol version 3 can't do.
+    pub fn send_led_frame(&mut self, colors: &[(u8, u8, u8)]) -> io::Result<()> {
+        if self.version < FRAMED_PROTOCOL_VERSION {
+            let message = "LED frames need protocol version 3";
+            return Err(io::Error::new(io::ErrorKind::Unsupported, message));
+        }
+        if !self.streaming {
+            self.send(&Command::StreamLeds { enabled: true })?;
+        }
+
+        let mut payload = vec![LED_FRAME];
+        payload.extend(colors.iter().flat_map(|&(r, g, b)| [r, g, b]));
+        if payload.len() > MAX_PAYLOAD_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many LEDs for a frame"));
+        }
+        self.write_frame(&payload)
+    }
+
+    pub fn set_brightness(&mut self, target: u8, value: u16) -> io::Result<()> {
+        self.send(&Command::Brightness { target, value })
+    }
+
+    pub fn set_color_temperature(&mut self, target: u8, value: u16) -> io::Result<()> {
+        self.send(&Command::Temperature { target, value })
+    }
+
+    pub fn set_led(&mut self, r: u8, g: u8, b: u8, pulse: bool) -> io::Result<()> {
+        self.send(&Command::Led { r, g, b, pulse })
+    }
+
+    fn write_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut frame_buf = [0u8; MAX_FRAME_LEN];
+        self.port.write_all(framing::encode(payload, &mut frame_buf))
+    }
+}
+
+/// The reports from a panel, in the order it sent them. Bytes which don't parse as a report come
+/// out as an `InvalidData` error, and the stream carries on after them. It ends after the first
+/// error reading the port, such as when the panel is unplugged.
+pub struct Reports {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Reports {
+    /// The same as `futures::Stream::poll_next()`, for wrapping in `futures::stream::poll_fn()`
+    /// where a `Stream` is wanted.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Report>>> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.reports.pop_front() {
+            Some(report) => Poll::Ready(Some(report)),
+            None if shared.closed => Poll::Ready(None),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+
+    /// Waits for the next report, or `None` once the port has closed.
+    pub fn next_report(&mut self) -> NextReport<'_> {
+        NextReport(self)
+    }
+
+    /// How many reports have gone missing on the way from the panel, as told by their numbers.
+    /// Panels before protocol version 5 don't number them, and this stays at 0 for them.
+    pub fn missed(&self) -> u32 {
+        self.shared.lock().unwrap().missed
+    }
+}
+
+/// The future returned by `Reports::next_report()`.
+pub struct NextReport<'a>(&'a mut Reports);
+
+impl Future for NextReport<'_> {
+    type Output = Option<io::Result<Report>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().0.poll_next(cx)
+    }
+}
+
+/// Returns the version in the panel's `Report::Hello`, or version 1 if it doesn't send one. Any
+/// other reports which come in the meantime are kept for `Reports`.
+fn wait_for_hello(port: &mut File, shared: &Mutex<Shared>) -> io::Result<u8> {
+    let mut reader = ReportReader::new();
+    let mut read_buf = [0u8; READ_LEN];
+    let mut version = None;
+    let start = Instant::now();
+
+    while version.is_none() && start.elapsed() < HELLO_TIMEOUT {
+        let received = port.read(&mut read_buf)?;
+        let reports = match reader.process_bytes(&read_buf[..received]) {
+            Ok(reports) => reports,
+            Err(_) => {
+                reader = ReportReader::new();
+                continue;
+            },
+        };
+
+        let mut shared = shared.lock().unwrap();
+        for report in reports {
+            match report {
+                Report::Hello { version: panel_version } => version = Some(panel_version),
+                report => shared.push(Ok(report)),
+            }
+        }
+    }
+
+    Ok(version.unwrap_or(DEFAULT_PROTOCOL_VERSION))
+}
+
+/// Reads reports from the port until it fails, or `Reports` is dropped.
+fn read_reports(mut port: File, version: u8, shared: &Arc<Mutex<Shared>>) {
+    let mut frames = FrameReader::new();
+    let mut reader = ReportReader::new();
+    let mut read_buf = [0u8; READ_LEN];
+    // The number the next report from the panel should have.
+    let mut next_number: Option<u16> = None;
+
+    // Only the reader thread is left holding it once `Reports` is gone.
+    while Arc::strong_count(shared) > 1 {
+        let received = match port.read(&mut read_buf) {
+            Ok(received) => received,
+            Err(e) => {
+                let mut shared = shared.lock().unwrap();
+                shared.push(Err(e));
+                shared.closed = true;
+                return;
+            },
+        };
+
+        if version < FRAMED_PROTOCOL_VERSION {
+            // Reports can be split across reads.
+            let reports = reader.process_bytes(&read_buf[..received]);
+            if reports.is_err() {
+                reader = ReportReader::new();
+            }
+            push_reports(shared, reports);
+            continue;
+        }
+
+        for &byte in &read_buf[..received] {
+            let mut payload = match frames.push(byte) {
+                Some(payload) => payload,
+                None => continue,
+            };
+
+            if version >= framing::SEQUENCE_PROTOCOL_VERSION && payload.len() >= SEQUENCE_LEN {
+                let number = u16::from_be_bytes([payload[0], payload[1]]);
+                if let Some(expected) = next_number {
+                    let missed = number.wrapping_sub(expected) as u32;
+                    let mut shared = shared.lock().unwrap();
+                    shared.missed = shared.missed.saturating_add(missed);
+                }
+                payload = &payload[SEQUENCE_LEN..];
+
+                // Worked out again from the reports below, once they're parsed.
+                next_number = Some(number);
+            }
+
+            // Frames hold whole reports, so each is read on its own.
+            let reports = ReportReader::new().process_bytes(payload);
+            if let (Some(number), Ok(reports)) = (next_number.as_mut(), &reports) {
+                *number = number.wrapping_add(reports.len() as u16);
+            }
+            push_reports(shared, reports);
+        }
+    }
+}
+
+fn push_reports<R: IntoIterator<Item = Report>>(
+    shared: &Mutex<Shared>,
+    reports: Result<R, panel_protocol::Error>,
+) {
+    let mut shared = shared.lock().unwrap();
+    match reports {
+        Ok(reports) => reports.into_iter().for_each(|report| shared.push(Ok(report))),
+        Err(e) => {
+            let message = format!("malformed report: {:?}", e);
+            shared.push(Err(io::Error::new(io::ErrorKind::InvalidData, message)));
+        },
+    }
+}
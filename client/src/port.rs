@@ -0,0 +1,33 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    mem::MaybeUninit,
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::Path,
+};
+
+/// Opens the panel's USB serial port, passing bytes through untouched. Reads wait at most 100 ms,
+/// and return nothing if none came in the meantime.
+pub fn open(path: &Path) -> io::Result<File> {
+    let port = OpenOptions::new().read(true).write(true).custom_flags(libc::O_NOCTTY).open(path)?;
+    let fd = port.as_raw_fd();
+
+    unsafe {
+        let mut termios = MaybeUninit::uninit();
+        if libc::tcgetattr(fd, termios.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut termios = termios.assume_init();
+        libc::cfmakeraw(&mut termios);
+        // The panel picks its mode from the baud rate, and 9600 and 1200 aren't the protocol,
+        // see src/serial.rs.
+        libc::cfsetspeed(&mut termios, libc::B115200);
+        termios.c_cc[libc::VMIN] = 0;
+        termios.c_cc[libc::VTIME] = 1;
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(port)
+}
@@ -0,0 +1,38 @@
+#![no_main]
+
+// Whatever the fuzzer sends first, the parser mustn't panic, and once it's reading frames, a
+// zero and then a frame must get the command in that frame through.
+
+use libfuzzer_sys::fuzz_target;
+use panel_core::{
+    command_parser::CommandParser,
+    framing::{self, COMMANDS_FRAME, MAX_FRAME_LEN},
+};
+use panel_protocol::Command;
+
+fuzz_target!(|data: &[u8]| {
+    let mut parser = CommandParser::new();
+    let mut bytes = data;
+    while !bytes.is_empty() {
+        let _ = parser.push(&mut bytes, |_| {});
+    }
+    if !parser.framed() {
+        return;
+    }
+
+    let command = Command::Brightness { target: 0, value: 0x1234 };
+    let mut payload = command.as_arrayvec().to_vec();
+    if parser.is_streaming() {
+        payload.insert(0, COMMANDS_FRAME);
+    }
+    let frame = framing::encode(&payload, &mut [0; MAX_FRAME_LEN]).to_vec();
+
+    let mut bytes = &[0][..];
+    let _ = parser.push(&mut bytes, |_| {});
+    let mut bytes = &frame[..];
+    let mut parsed = Vec::new();
+    while !bytes.is_empty() {
+        let _ = parser.push(&mut bytes, |command| parsed.push(format!("{:?}", command)));
+    }
+    assert_eq!(parsed, [format!("{:?}", command)]);
+});
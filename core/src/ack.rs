@@ -0,0 +1,42 @@
+use panel_protocol::{Command, Report};
+
+// Acknowledges every command, for hosts which ask for it, so that they can retry or show a
+// failure rather than assume a brightness or color was applied. A command is acknowledged once
+// it has been applied, with `Report::Ack`, or rejected, with `Report::Nack` and the reason.
+
+/// Hosts from this version on get a `Report::Ack` or `Report::Nack` for every command.
+pub const ACK_PROTOCOL_VERSION: u8 = 4;
+
+/// Stands in for the command in a `Report::Nack` for bytes which didn't parse as one.
+pub const UNKNOWN_COMMAND: u8 = 0xFF;
+
+/// Why a command wasn't applied, as sent in `Report::Nack`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Rejection {
+    /// The bytes didn't parse as a command.
+    Malformed = 1,
+    /// There's no light, output or other target with that number.
+    InvalidTarget = 2,
+    /// A value out of range, such as an hour past 23.
+    InvalidValue = 3,
+    /// A destructive command which wasn't unlocked first, or was sent with the wrong key.
+    Locked = 4,
+    /// The panel doesn't have the hardware for it, or doesn't know the command.
+    Unsupported = 5,
+    /// Applying it failed, as a firmware update can.
+    Failed = 6,
+}
+
+/// The number which tells the host which kind of command is acknowledged, its first byte.
+pub fn command_id(command: &Command) -> u8 {
+    command.as_arrayvec()[0]
+}
+
+/// The acknowledgement of command `command_id`.
+pub fn report(command_id: u8, result: Result<(), Rejection>) -> Report {
+    match result {
+        Ok(()) => Report::Ack { command: command_id },
+        Err(rejection) => Report::Nack { command: command_id, reason: rejection as u8 },
+    }
+}
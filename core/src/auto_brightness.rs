@@ -0,0 +1,87 @@
+/// The ambient light at and above which the lights are at their maximum brightness.
+const FULL_LUX: u32 = 500;
+
+/// Measurements are smoothed over roughly this many, so that passing shadows don't make the
+/// lights flicker.
+const SMOOTHING: u32 = 8;
+
+/// Smaller changes in brightness are held back, so that the lights don't hunt around a level.
+const DEADBAND: u16 = u16::MAX / 100;
+
+/// Sets the brightness of the lights from the ambient light, between a minimum and maximum set
+/// by the host. The host can also override it with a fixed brightness. Off until the host sets
+/// a range.
+pub struct AutoBrightness {
+    range: Option<(u16, u16)>,
+    override_brightness: Option<u16>,
+    /// The smoothed measurement, times `SMOOTHING`.
+    smoothed_lux: Option<u32>,
+    /// The brightness most recently returned by `poll()`.
+    applied: Option<u16>,
+}
+
+impl AutoBrightness {
+    pub const fn new() -> Self {
+        Self { range: None, override_brightness: None, smoothed_lux: None, applied: None }
+    }
+
+    /// Follows the ambient light from `min` brightness in the dark to `max` in a bright room.
+    pub fn enable(&mut self, min: u16, max: u16) {
+        self.range = Some((min.min(max), min.max(max)));
+        self.applied = None;
+    }
+
+    /// Leaves the lights at whatever brightness they have until the host sets another.
+    pub fn disable(&mut self) {
+        self.range = None;
+    }
+
+    /// Holds the lights at `brightness` until the override is cleared with `None`.
+    pub fn set_override(&mut self, brightness: Option<u16>) {
+        self.override_brightness = brightness;
+        self.applied = None;
+    }
+
+    pub fn measure(&mut self, lux: u16) {
+        let lux = lux as u32;
+        self.smoothed_lux = Some(match self.smoothed_lux {
+            Some(smoothed) => smoothed - smoothed / SMOOTHING + lux,
+            None => lux * SMOOTHING,
+        });
+    }
+
+    /// Returns the brightness the lights should be set to, if it has changed.
+    pub fn poll(&mut self) -> Option<u16> {
+        let target = match (self.override_brightness, self.range, self.smoothed_lux) {
+            (Some(brightness), _, _) => brightness,
+            (None, Some((min, max)), Some(smoothed)) => {
+                let lux = (smoothed / SMOOTHING).min(FULL_LUX);
+                min + ((max - min) as u32 * lux / FULL_LUX) as u16
+            },
+            _ => return None,
+        };
+
+        let changed = match self.applied {
+            None => true,
+            Some(_) if self.override_brightness.is_some() => false,
+            Some(applied) => {
+                let at_limit = self.range.is_some_and(|(min, max)| target == min || target == max);
+                let difference = (target as i32 - applied as i32).unsigned_abs() as u16;
+                difference > DEADBAND || (at_limit && target != applied)
+            },
+        };
+        if !changed {
+            return None;
+        }
+
+        trace!("auto brightness: {}", target);
+        self.applied = Some(target);
+        Some(target)
+    }
+}
+
+impl Default for AutoBrightness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,16 +1,15 @@
+use crate::tick::{Duration, Instant};
 use core::convert::Infallible;
-use stm32f1xx_hal as hal;
 
-use embedded_hal::digital::v2::InputPin;
-use hal::time::{Instant, MonoTimer};
+use embedded_hal::digital::InputPin;
 
 pub struct Button<T: InputPin> {
     pin: Debouncer<T>,
-    timer: MonoTimer,
     button_state: ButtonState,
-    long_press_timeout_ticks: u32,
+    long_press_timeout: Duration,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum ButtonEvent {
     /// The button has just been pressed down.
     Pressed,
@@ -32,41 +31,52 @@ enum ButtonState {
 }
 
 impl<T: InputPin<Error = Infallible>> Button<T> {
-    pub fn new(pin: Debouncer<T>, long_press_timeout_ms: u32, timer: MonoTimer) -> Self {
+    pub fn new(pin: Debouncer<T>, long_press_timeout_ms: u32) -> Self {
         let button_state = ButtonState::Released;
-        let long_press_timeout_ticks =
-            (timer.frequency().0 as f32 * (long_press_timeout_ms as f32 / 1000.0)) as u32;
+        let long_press_timeout = Duration::from_ms(long_press_timeout_ms);
 
-        Self { pin, timer, button_state, long_press_timeout_ticks }
+        Self { pin, button_state, long_press_timeout }
     }
 
     pub fn is_pressed(&self) -> bool {
         self.pin.is_pressed()
     }
 
+    /// Also applies to a press which is already under way.
+    pub fn set_long_press_timeout(&mut self, long_press_timeout_ms: u32) {
+        self.long_press_timeout = Duration::from_ms(long_press_timeout_ms);
+    }
+
+    pub fn set_debounce_time(&mut self, debounce_time_ms: u16) {
+        self.pin.set_debounce_time(debounce_time_ms);
+    }
+
     pub fn poll(&mut self) -> Option<ButtonEvent> {
         self.pin.poll();
 
         match self.button_state {
             ButtonState::Released => {
                 if self.pin.is_pressed() {
-                    let now = self.timer.now();
-                    self.button_state = ButtonState::Pressed(now);
+                    self.button_state = ButtonState::Pressed(Instant::now());
+                    debug!("button pressed");
                     return Some(ButtonEvent::Pressed);
                 }
             },
             ButtonState::Pressed(press_start) => {
                 if !self.pin.is_pressed() {
                     self.button_state = ButtonState::Released;
+                    debug!("button released");
                     return Some(ButtonEvent::ShortRelease);
-                } else if press_start.elapsed() > self.long_press_timeout_ticks {
+                } else if press_start.elapsed() > self.long_press_timeout {
                     self.button_state = ButtonState::LongPressed;
+                    debug!("button long pressed");
                     return Some(ButtonEvent::LongPress);
                 }
             },
             ButtonState::LongPressed => {
                 if !self.pin.is_pressed() {
                     self.button_state = ButtonState::Released;
+                    debug!("button released after long press");
                     return Some(ButtonEvent::LongRelease);
                 }
             },
@@ -84,17 +94,19 @@ pub struct Debouncer<T: InputPin> {
     max: u8,
     output: bool,
     active_mode: Active,
+    sample_frequency: u16,
 }
 
-#[allow(dead_code)]
 pub enum Active {
     Low,
     High,
 }
 
 impl<T: InputPin<Error = Infallible>> Debouncer<T> {
+    /// `debounce_time_ms` is rounded down to a whole number of samples at `sample_frequency`,
+    /// from 1 to 255.
     pub fn new(pin: T, active_mode: Active, debounce_time_ms: u16, sample_frequency: u16) -> Self {
-        let max = ((debounce_time_ms as f32 / 1000.0) * sample_frequency as f32) as u8;
+        let max = max_samples(debounce_time_ms, sample_frequency);
 
         let integrator = match active_mode {
             Active::Low => max,
@@ -106,7 +118,13 @@ impl<T: InputPin<Error = Infallible>> Debouncer<T> {
             Active::High => false,
         };
 
-        Self { pin, integrator, max, output, active_mode }
+        Self { pin, integrator, max, output, active_mode, sample_frequency }
+    }
+
+    /// Changes the debounce time, keeping the current output.
+    pub fn set_debounce_time(&mut self, debounce_time_ms: u16) {
+        self.max = max_samples(debounce_time_ms, self.sample_frequency);
+        self.integrator = if self.output { self.max } else { 0 };
     }
 
     pub fn poll(&mut self) {
@@ -124,10 +142,12 @@ impl<T: InputPin<Error = Infallible>> Debouncer<T> {
     }
 
     pub fn is_pressed(&self) -> bool {
-        match (&self.active_mode, self.output) {
-            (Active::High, true) => true,
-            (Active::Low, false) => true,
-            _ => false,
-        }
+        matches!((&self.active_mode, self.output), (Active::High, true) | (Active::Low, false))
     }
 }
+
+/// At least one sample, as with none the integrator never leaves 0, and the button reads as
+/// pressed for good if it's active low.
+fn max_samples(debounce_time_ms: u16, sample_frequency: u16) -> u8 {
+    (debounce_time_ms as u32 * sample_frequency as u32 / 1000).clamp(1, u8::MAX as u32) as u8
+}
@@ -0,0 +1,134 @@
+use crate::{
+    framing::{self, FrameReader, FRAMED_PROTOCOL_VERSION},
+    input::DEFAULT_PROTOCOL_VERSION,
+};
+use panel_protocol::{ArrayVec, Command, CommandReader, Error, MAX_COMMAND_QUEUE_LEN};
+
+// Turns the bytes from the host into commands, read straight out of the bytes it's given rather
+// than copied first, and follows the protocol version and LED streaming the host asks for as it
+// goes, so that the byte after a command which switches to frames is read as part of a frame. It's
+// bytes in and commands out, with no USB types, so that it can be tested and fuzzed on the host,
+// see tests/command_parser.rs and fuzz/. Whatever garbage it's sent, it can't get stuck: an error
+// drops the command so far, and frames get back in step at the next zero, see framing.rs.
+
+pub type Commands = ArrayVec<[Command; MAX_COMMAND_QUEUE_LEN]>;
+
+/// What the bytes from the host complete, besides commands, which go to the caller's callback.
+pub enum Parsed<'a> {
+    Nothing,
+    /// The colors from an LED frame, three bytes to an LED, see `Command::StreamLeds`.
+    LedFrame(&'a [u8]),
+}
+
+pub struct CommandParser {
+    reader: CommandReader,
+    frames: FrameReader,
+    version: u8,
+    /// Whether the host's frames say what they hold.
+    streaming: bool,
+}
+
+impl CommandParser {
+    /// Starts off without frames, as a host which has just connected does.
+    pub fn new() -> Self {
+        Self {
+            reader: CommandReader::new(),
+            frames: FrameReader::new(),
+            version: DEFAULT_PROTOCOL_VERSION,
+            streaming: false,
+        }
+    }
+
+    /// Starts over for the next host, dropping any partial command or frame.
+    pub fn reset(&mut self) {
+        self.reader = CommandReader::new();
+        self.frames.reset();
+        self.version = DEFAULT_PROTOCOL_VERSION;
+        self.streaming = false;
+    }
+
+    /// The version of the protocol the host has asked for.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn framed(&self) -> bool {
+        self.version >= FRAMED_PROTOCOL_VERSION
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.streaming
+    }
+
+    /// Returns how many frames from the host have been dropped for failing their CRC since the
+    /// last call.
+    pub fn take_corrupt_frames(&mut self) -> u32 {
+        self.frames.take_corrupt_frames()
+    }
+
+    /// Reads from the front of `bytes`, and moves `bytes` on past what it read: up to the end of
+    /// the next command, or a byte of a frame once the host sends them. The commands read go to
+    /// `on_command`, in order. Stopping after each command means that the bytes after one which
+    /// switches to frames are read as a frame. An error means a command was malformed, and
+    /// parsing starts over from the byte after it.
+    pub fn push(
+        &mut self,
+        bytes: &mut &[u8],
+        mut on_command: impl FnMut(Command),
+    ) -> Result<Parsed<'_>, Error> {
+        if !self.framed() {
+            // Straight out of `bytes`, unless the command started in an earlier read.
+            if let Some(command) = self.reader.next_command(bytes)? {
+                follow(&command, &mut self.version, &mut self.streaming);
+                on_command(command);
+            }
+            return Ok(Parsed::Nothing);
+        }
+
+        // Frames are decoded in place as their bytes come in, see framing.rs.
+        let (&byte, rest) = match bytes.split_first() {
+            Some(split) => split,
+            None => return Ok(Parsed::Nothing),
+        };
+        *bytes = rest;
+        let mut payload = match self.frames.push(byte) {
+            Some(payload) => payload,
+            None => return Ok(Parsed::Nothing),
+        };
+        if self.streaming {
+            match payload.split_first() {
+                Some((&framing::COMMANDS_FRAME, rest)) => payload = rest,
+                Some((&framing::LED_FRAME, colors)) => return Ok(Parsed::LedFrame(colors)),
+                _ => return Ok(Parsed::Nothing),
+            }
+        }
+
+        // Frames hold whole commands, so they're read straight out of the payload, and nothing
+        // left over from the last one carries on into this one. The commands before a malformed
+        // one are handed on before the error, which drops the rest of the frame.
+        let mut reader = CommandReader::new();
+        while let Some(command) = reader.next_command(&mut payload)? {
+            follow(&command, &mut self.version, &mut self.streaming);
+            on_command(command);
+        }
+        Ok(Parsed::Nothing)
+    }
+}
+
+impl Default for CommandParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Switches to what the host asks for with `command`, in time for the byte after it.
+fn follow(command: &Command, version: &mut u8, streaming: &mut bool) {
+    match *command {
+        Command::SetProtocolVersion { version: requested } => *version = requested,
+        // Only frames can say what they hold, so hosts which don't send them can't stream.
+        Command::StreamLeds { enabled } => {
+            *streaming = enabled && *version >= FRAMED_PROTOCOL_VERSION;
+        },
+        _ => {},
+    }
+}
@@ -0,0 +1,389 @@
+use crate::{
+    ack::{self, Rejection},
+    auto_brightness::AutoBrightness,
+    daylight::{Daylight, DaylightCurve},
+    dmx::Universe,
+    haptics::{self, HapticEffects, HapticEvent},
+    input,
+    ir::{IrAction, IrBinding, IrBindings, IrProtocol},
+    power::{PowerManager, SuspendPolicy},
+    schedule::{Schedule, ScheduleEntry, MINUTES_PER_DAY},
+    standalone::Standalone,
+    subscriptions::Category,
+    unlock::Unlock,
+};
+use panel_protocol::{Command, Report};
+
+// Applies the host's commands: checks each one, changes what it asks for, answers it, and
+// acknowledges it for hosts which ask for that. The state which is only the panel's logic is
+// in `Ctx`, and everything which needs the hardware, the settings or other tasks goes through
+// `Panel`, which the firmware implements over its RTIC resources and the tests fake.
+
+/// Has to accompany `Command::Unlock`, shortly before each command which could brick or wipe
+/// the panel, see unlock.rs.
+pub const UNLOCK_KEY: u32 = 0x55_4e_4c_4b;
+
+/// Has to accompany `Command::EnableReadoutProtection`, on top of the unlock.
+pub const READOUT_PROTECTION_KEY: u32 = 0x52_44_50_31;
+
+/// Hosts from this version on can reset the panel with `Command::Reset`. Older hosts don't know
+/// it's there, and use `Command::Reboot`, which does the same.
+pub const RESET_PROTOCOL_VERSION: u8 = 6;
+
+/// The panel's own lights, targets 0 and 1. Targets from 2 on are DMX fixtures, see dmx.rs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Front = 0,
+    Back = 1,
+}
+
+impl Light {
+    pub const ALL: [Light; 2] = [Light::Front, Light::Back];
+
+    pub fn from_target(target: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|&light| light as u8 == target)
+    }
+}
+
+/// A setting a command changed, which the panel keeps across resets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    ScheduleEntry(usize, Option<ScheduleEntry>),
+    UtcOffset(i16),
+    SuspendPolicy(SuspendPolicy),
+    IrBinding(IrAction, Option<IrBinding>),
+    HapticEffect(HapticEvent, u8),
+    StatusOnConnect(bool),
+    DefaultLed { color: (u8, u8, u8), pulse: bool },
+}
+
+/// What the commands need from the rest of the panel.
+pub trait Panel {
+    fn set_brightness(&mut self, light: Light, brightness: u16);
+    fn set_color_temperature(&mut self, light: Light, color_temperature: u16);
+    /// The brightness and color temperature the host set for `light`.
+    fn levels(&mut self, light: Light) -> (u16, u16);
+
+    /// Sets every LED of the strip to `color`, replacing a frame set one LED at a time.
+    fn set_led(&mut self, color: (u8, u8, u8), pulse: bool);
+    fn led(&mut self) -> ((u8, u8, u8), bool);
+    /// Sets LED `index` of the next frame, without showing it. Returns false if there's no such
+    /// LED.
+    fn set_pixel(&mut self, index: u16, color: (u8, u8, u8)) -> bool;
+    /// Shows the frame set with `set_pixel()`.
+    fn commit_pixels(&mut self);
+    fn configure_sound_level(&mut self, enabled: bool, sensitivity: u16, smoothing: u8);
+
+    /// Returns false if there's no such slot.
+    fn save_scene(&mut self, slot: u8) -> bool;
+    /// Returns false if there's no such slot, or nothing was saved in it.
+    fn recall_scene(&mut self, slot: u8) -> bool;
+    fn save(&mut self, setting: Setting);
+
+    fn power<R>(&mut self, f: impl FnOnce(&mut PowerManager) -> R) -> R;
+    /// Sets the wall clock. Returns false if the external RTC, on boards with one, didn't take
+    /// the time.
+    fn set_time(&mut self, unix_seconds: u32) -> bool;
+    fn beep(&mut self, freq: u16, duration_ms: u16);
+    fn set_dial_rate(&mut self, max_per_second: u16);
+    /// Returns false if there's no such key, or `value` is out of its range.
+    fn set_config(&mut self, key: u8, value: u16) -> bool;
+    fn config(&mut self, key: u8) -> Option<u16>;
+    /// Returns false if there's no such output.
+    fn set_output(&mut self, channel: u8, on: bool) -> bool;
+    /// Whether output `channel` is on, or `None` past the last one.
+    fn output(&mut self, channel: u8) -> Option<bool>;
+    /// `None` if there's no door sensor.
+    fn door_open(&mut self) -> Option<bool>;
+
+    /// Doesn't return on the panel, which resets to load the option bytes.
+    fn enable_readout_protection(&mut self);
+
+    /// `Report::Diagnostics`, from the counts the panel keeps.
+    fn diagnostics(&mut self) -> Report;
+    fn device_id(&mut self) -> Report;
+    fn firmware_version(&mut self) -> Report;
+    fn readout_protection(&mut self) -> Report;
+    fn capabilities(&mut self) -> Report;
+    /// Sends a `Report::Profile` for each section of the firmware it measures, or returns false
+    /// if it was built without the profiler.
+    fn send_profile(&mut self) -> bool;
+
+    /// These happen later, in tasks of their own.
+    fn reset(&mut self, into_bootloader: bool);
+    fn start_self_test(&mut self);
+    fn send_status(&mut self);
+
+    fn subscribe(&mut self, category: Category, subscribed: bool);
+    /// Queues a report for the host, dropping it if there's no room.
+    fn send(&mut self, report: Report);
+}
+
+/// Everything the commands change besides the `Panel`.
+pub struct Ctx<'a, P: Panel> {
+    pub panel: P,
+    pub unlock: &'a mut Unlock,
+    pub schedule: &'a mut Schedule,
+    /// Added to UTC to get the local time the schedule follows.
+    pub utc_offset_minutes: &'a mut i16,
+    pub auto_brightness: &'a mut AutoBrightness,
+    pub daylight: &'a mut Daylight,
+    pub ir_bindings: &'a mut IrBindings,
+    pub haptic_effects: &'a mut HapticEffects,
+    pub standalone: &'a mut Standalone,
+    pub dmx: &'a mut Universe,
+    pub click_feedback: &'a mut bool,
+    pub status_on_connect: &'a mut bool,
+    /// Which version of the protocol the host speaks, see input.rs.
+    pub protocol_version: &'a mut u8,
+}
+
+/// Applies each command `next()` returns until it returns `None`, acknowledging them for hosts
+/// which ask for it.
+pub fn apply_all<P: Panel>(ctx: &mut Ctx<P>, mut next: impl FnMut() -> Option<Command>) {
+    while let Some(command) = next() {
+        let command_id = ack::command_id(&command);
+        let result = apply(ctx, command);
+
+        // Including the command which asked for acknowledgements.
+        if *ctx.protocol_version >= ack::ACK_PROTOCOL_VERSION {
+            ctx.panel.send(ack::report(command_id, result));
+        }
+    }
+
+    // An override takes effect straight away, rather than at the next measurement.
+    if let Some(brightness) = ctx.auto_brightness.poll() {
+        for &light in &Light::ALL {
+            ctx.panel.set_brightness(light, brightness);
+        }
+    }
+}
+
+pub fn apply<P: Panel>(ctx: &mut Ctx<P>, command: Command) -> Result<(), Rejection> {
+    let panel = &mut ctx.panel;
+
+    match command {
+        Command::Brightness { target, value } => match Light::from_target(target) {
+            Some(light) => panel.set_brightness(light, value),
+            None if ctx.dmx.set_brightness(target, value) => {},
+            None => return Err(Rejection::InvalidTarget),
+        },
+        Command::Temperature { target, value } => match Light::from_target(target) {
+            Some(light) => panel.set_color_temperature(light, value),
+            None if ctx.dmx.set_color_temperature(target, value) => {},
+            None => return Err(Rejection::InvalidTarget),
+        },
+        Command::Led { r, g, b, pulse } => panel.set_led((r, g, b), pulse),
+        // Nothing changes until the host commits, so a frame is never shown half set.
+        Command::LedAt { index, r, g, b } => {
+            if !panel.set_pixel(index, (r, g, b)) {
+                return Err(Rejection::InvalidTarget);
+            }
+        },
+        Command::CommitLeds => panel.commit_pixels(),
+        Command::SaveScene { slot } => {
+            if !panel.save_scene(slot) {
+                return Err(Rejection::InvalidValue);
+            }
+        },
+        Command::RecallScene { slot } => {
+            if !panel.recall_scene(slot) {
+                return Err(Rejection::InvalidValue);
+            }
+        },
+        Command::SetDefaultLed { r, g, b, pulse } => {
+            panel.save(Setting::DefaultLed { color: (r, g, b), pulse });
+        },
+        Command::SetSoundReactive { enabled, sensitivity, smoothing } => {
+            panel.configure_sound_level(enabled, sensitivity, smoothing);
+        },
+        Command::Unlock { key } => {
+            if !ctx.unlock.unlock(key) {
+                warn!("wrong unlock key");
+                return Err(Rejection::Locked);
+            }
+        },
+        Command::Bootload => {
+            if !ctx.unlock.take() {
+                warn!("bootload wasn't unlocked");
+                return Err(Rejection::Locked);
+            }
+            panel.reset(true);
+        },
+        Command::SelfTest => panel.start_self_test(),
+        Command::SetTime { unix_seconds } => {
+            if !panel.set_time(unix_seconds) {
+                warn!("DS3231 didn't respond");
+                return Err(Rejection::Failed);
+            }
+        },
+        Command::SetScheduleEntry { index, hour, minute, brightness, color_temperature } => {
+            let minute_of_day = hour as u16 * 60 + minute as u16;
+            let entry = ScheduleEntry { minute_of_day, brightness, color_temperature };
+            if hour >= 24 || minute >= 60 || !ctx.schedule.set(index as usize, Some(entry)) {
+                return Err(Rejection::InvalidValue);
+            }
+            panel.save(Setting::ScheduleEntry(index as usize, Some(entry)));
+        },
+        Command::ClearScheduleEntry { index } => {
+            if !ctx.schedule.set(index as usize, None) {
+                return Err(Rejection::InvalidValue);
+            }
+            panel.save(Setting::ScheduleEntry(index as usize, None));
+        },
+        Command::SetUtcOffset { minutes } => {
+            if minutes.unsigned_abs() >= MINUTES_PER_DAY {
+                return Err(Rejection::InvalidValue);
+            }
+            *ctx.utc_offset_minutes = minutes;
+            panel.save(Setting::UtcOffset(minutes));
+        },
+        Command::Sleep => panel.power(|power| power.sleep()),
+        Command::Wake => panel.power(|power| power.wake()),
+        Command::SetSuspendPolicy { policy } => {
+            let policy = SuspendPolicy::from_u8(policy).ok_or(Rejection::InvalidValue)?;
+            panel.power(|power| power.set_suspend_policy(policy));
+            panel.save(Setting::SuspendPolicy(policy));
+        },
+        Command::Heartbeat { timeout_ms } => ctx.standalone.heartbeat(timeout_ms as u32),
+        Command::GetDiagnostics => {
+            let report = panel.diagnostics();
+            panel.send(report);
+        },
+        Command::GetDeviceId => {
+            let report = panel.device_id();
+            panel.send(report);
+        },
+        Command::GetFirmwareVersion => {
+            let report = panel.firmware_version();
+            panel.send(report);
+        },
+        Command::GetReadoutProtection => {
+            let report = panel.readout_protection();
+            panel.send(report);
+        },
+        // Enabling readout protection can't be undone without erasing the panel.
+        Command::EnableReadoutProtection { key } => {
+            if !ctx.unlock.take() || key != READOUT_PROTECTION_KEY {
+                warn!("readout protection wasn't unlocked, or the key was wrong");
+                return Err(Rejection::Locked);
+            }
+            panel.enable_readout_protection();
+        },
+        Command::SetAutoBrightness { min, max } => ctx.auto_brightness.enable(min, max),
+        Command::DisableAutoBrightness => ctx.auto_brightness.disable(),
+        Command::OverrideBrightness { value } => ctx.auto_brightness.set_override(Some(value)),
+        Command::ClearBrightnessOverride => ctx.auto_brightness.set_override(None),
+        Command::SetDaylightCurve {
+            sunrise_hour,
+            sunrise_minute,
+            sunset_hour,
+            sunset_minute,
+            night,
+            midday,
+        } => {
+            if sunrise_hour >= 24
+                || sunrise_minute >= 60
+                || sunset_hour >= 24
+                || sunset_minute >= 60
+            {
+                return Err(Rejection::InvalidValue);
+            }
+            let sunrise = sunrise_hour as u16 * 60 + sunrise_minute as u16;
+            let sunset = sunset_hour as u16 * 60 + sunset_minute as u16;
+            ctx.daylight.enable(DaylightCurve { sunrise, sunset, night, midday });
+        },
+        Command::DisableDaylightCurve => ctx.daylight.disable(),
+        Command::OverrideColorTemperature { value } => ctx.daylight.set_override(Some(value)),
+        Command::ClearColorTemperatureOverride => ctx.daylight.set_override(None),
+        Command::GetProfile => {
+            if !panel.send_profile() {
+                return Err(Rejection::Unsupported);
+            }
+        },
+        Command::Beep { freq, duration } => panel.beep(freq, duration),
+        Command::SetClickFeedback { enabled } => *ctx.click_feedback = enabled,
+        Command::SetDialRate { max_per_second } => panel.set_dial_rate(max_per_second),
+        // The host picks the version to speak from this, with SetProtocolVersion.
+        Command::Hello { version: _version } => {
+            info!("host speaks protocol version {}", _version);
+            panel.send(Report::Hello { version: input::LATEST_PROTOCOL_VERSION });
+        },
+        Command::SetProtocolVersion { version } => *ctx.protocol_version = version,
+        Command::BindIrCode { protocol, address, command, action } => {
+            let action = IrAction::from_u8(action).ok_or(Rejection::InvalidValue)?;
+            let protocol = IrProtocol::from_u8(protocol).ok_or(Rejection::InvalidValue)?;
+            let binding = Some(IrBinding { protocol, address, command });
+            ctx.ir_bindings.set(action, binding);
+            panel.save(Setting::IrBinding(action, binding));
+        },
+        Command::UnbindIrCode { action } => {
+            let action = IrAction::from_u8(action).ok_or(Rejection::InvalidValue)?;
+            ctx.ir_bindings.set(action, None);
+            panel.save(Setting::IrBinding(action, None));
+        },
+        Command::SetHapticEffect { event, effect } => match HapticEvent::from_u8(event) {
+            Some(event) if effect <= haptics::MAX_EFFECT => {
+                ctx.haptic_effects.set(event, effect);
+                panel.save(Setting::HapticEffect(event, effect));
+            },
+            _ => return Err(Rejection::InvalidValue),
+        },
+        Command::SetOutput { channel, on } => {
+            if !panel.set_output(channel, on) {
+                return Err(Rejection::InvalidTarget);
+            }
+            panel.send(Report::Output { channel, on });
+        },
+        Command::GetOutputs => {
+            for channel in 0..=u8::MAX {
+                match panel.output(channel) {
+                    Some(on) => panel.send(Report::Output { channel, on }),
+                    None => break,
+                }
+            }
+        },
+        Command::GetDoorState => {
+            let open = panel.door_open().ok_or(Rejection::Unsupported)?;
+            panel.send(Report::DoorState { open });
+        },
+        Command::GetState { target } => {
+            let levels = match Light::from_target(target) {
+                Some(light) => panel.levels(light),
+                None => ctx.dmx.levels(target).ok_or(Rejection::InvalidTarget)?,
+            };
+            let (brightness, color_temperature) = levels;
+            let ((r, g, b), pulse) = panel.led();
+            panel.send(Report::State { target, brightness, color_temperature, r, g, b, pulse });
+        },
+        Command::GetStatus => panel.send_status(),
+        Command::SetStatusOnConnect { enabled } => {
+            *ctx.status_on_connect = enabled;
+            panel.save(Setting::StatusOnConnect(enabled));
+        },
+        Command::SetConfig { key, value } => {
+            if !panel.set_config(key, value) {
+                return Err(Rejection::InvalidValue);
+            }
+        },
+        Command::GetConfig { key } => {
+            let value = panel.config(key).ok_or(Rejection::InvalidValue)?;
+            panel.send(Report::Config { key, value });
+        },
+        Command::GetCapabilities => {
+            let report = panel.capabilities();
+            panel.send(report);
+        },
+        Command::Subscribe { category } | Command::Unsubscribe { category } => {
+            let subscribed = matches!(command, Command::Subscribe { .. });
+            let category = Category::from_u8(category).ok_or(Rejection::InvalidValue)?;
+            panel.subscribe(category, subscribed);
+        },
+        // A verified update is swapped in by the bootloader on the way back up.
+        Command::Reboot => panel.reset(false),
+        Command::Reset if *ctx.protocol_version >= RESET_PROTOCOL_VERSION => panel.reset(false),
+        _ => return Err(Rejection::Unsupported),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use crate::button::Active;
+use core::convert::Infallible;
+use embedded_hal::digital::InputPin;
+
+/// Counts between two polls beyond which the direction of travel is ambiguous, because the
+/// 16 bit hardware counter may have wrapped.
+const MAX_COUNT_JUMP: i16 = i16::MAX / 2;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum Error {
+    /// The absolute position or the unreported diff no longer fits in its integer type.
+    Overflow,
+
+    /// The hardware count jumped further than the encoder can plausibly turn between polls.
+    CountJump,
+}
+
+/// A hardware quadrature encoder counter. This stands in for the `Qei` trait, which embedded-hal
+/// dropped in 1.0.
+pub trait QuadratureCounter {
+    /// The current count, which wraps around in either direction.
+    fn count(&self) -> u16;
+}
+
+pub struct Counter<Q, Z> {
+    qei: Q,
+    last_count: u16,
+    position: i32,
+    /// Detents which have been counted but not yet reported.
+    pending_diff: i16,
+    index: Option<IndexPin<Z>>,
+}
+
+/// The index (Z) channel of an encoder, which pulses once per revolution at a fixed angle.
+struct IndexPin<Z> {
+    pin: Z,
+    active_mode: Active,
+    was_active: bool,
+}
+
+impl<Z: InputPin<Error = Infallible>> IndexPin<Z> {
+    fn is_active(&mut self) -> bool {
+        match self.active_mode {
+            Active::Low => self.pin.is_low().unwrap(),
+            Active::High => self.pin.is_high().unwrap(),
+        }
+    }
+}
+
+impl<Q: QuadratureCounter, Z: InputPin<Error = Infallible>> Counter<Q, Z> {
+    pub fn new(qei: Q, index: Option<(Z, Active)>) -> Self {
+        let last_count = qei.count();
+        let index =
+            index.map(|(pin, active_mode)| IndexPin { pin, active_mode, was_active: false });
+
+        Counter { qei, last_count, position: 0, pending_diff: 0, index }
+    }
+
+    /// Returns the number of detents turned since the last reported diff, if any. On error the
+    /// counted movement is discarded and the absolute position should be considered unknown
+    /// until the next index pulse.
+    pub fn poll(&mut self) -> Result<Option<i8>, Error> {
+        let count = self.qei.count();
+        let diff = count.wrapping_sub(self.last_count) as i16;
+
+        // `abs()` would overflow on a jump of exactly half the counter's range, i16::MIN.
+        if diff.unsigned_abs() > MAX_COUNT_JUMP as u16 {
+            warn!("dial count jumped by {}", diff);
+            self.last_count = count;
+            self.pending_diff = 0;
+            return Err(Error::CountJump);
+        }
+
+        if diff.unsigned_abs() >= 4 {
+            self.last_count = count;
+
+            let position = self.position.checked_add((diff / 4) as i32);
+            let pending_diff = self.pending_diff.checked_add(diff / 4);
+
+            match (position, pending_diff) {
+                (Some(position), Some(pending_diff)) => {
+                    self.position = position;
+                    self.pending_diff = pending_diff;
+                },
+                _ => {
+                    warn!("dial position overflowed");
+                    self.position = 0;
+                    self.pending_diff = 0;
+                    return Err(Error::Overflow);
+                },
+            }
+        }
+
+        if self.pending_diff == 0 {
+            return Ok(None);
+        }
+
+        let diff = self.pending_diff.max(i8::MIN as i16).min(i8::MAX as i16);
+        self.pending_diff -= diff;
+        Ok(Some(diff as i8))
+    }
+
+    /// Puts back a diff returned by `poll()` which couldn't be reported, so that it gets summed
+    /// into the next one instead of being lost.
+    pub fn defer(&mut self, diff: i8) {
+        self.pending_diff = self.pending_diff.saturating_add(diff as i16);
+    }
+
+    /// Returns true once for each index pulse, at which point the absolute position is reset
+    /// to zero. Always returns false if the encoder has no index channel.
+    pub fn poll_index(&mut self) -> bool {
+        let index = match self.index.as_mut() {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let active = index.is_active();
+        let rising_edge = active && !index.was_active;
+        index.was_active = active;
+
+        if rising_edge {
+            debug!("dial homed at position {}", self.position);
+            self.last_count = self.qei.count();
+            self.position = 0;
+        }
+
+        rising_edge
+    }
+
+    /// The absolute position in detents since boot or the last index pulse.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+}
@@ -0,0 +1,47 @@
+/// The standard (zlib) CRC-32, which firmware images are checked with. It can be computed in
+/// pieces, by passing the result of one call as the `crc` of the next, starting from 0.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// The Dallas/Maxim CRC-8, which 1-Wire devices check their ROM codes and data with. Running it
+/// over data followed by its CRC gives 0.
+pub fn crc8(crc: u8, data: &[u8]) -> u8 {
+    let mut crc = crc;
+
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0x8C & mask);
+        }
+    }
+
+    crc
+}
+
+/// CRC-16/CCITT-FALSE, which frames to and from the host are checked with, starting from
+/// `0xFFFF`.
+pub fn crc16(crc: u16, data: &[u8]) -> u16 {
+    let mut crc = crc;
+
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            let mask = (crc >> 15).wrapping_neg();
+            crc = (crc << 1) ^ (0x1021 & mask);
+        }
+    }
+
+    crc
+}
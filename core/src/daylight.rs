@@ -0,0 +1,156 @@
+use crate::schedule::MINUTES_PER_DAY;
+
+// Shifts the color temperature of the lights through the day along a curve the host sets: warm
+// at night, rising from sunrise to its coolest at midday, and back down to warm at sunset. The
+// time of day comes from the wall clock; until that's set, a bright room counts as midday and a
+// dark one as night. The lights move towards the curve a little at a time, so the change is
+// never noticeable.
+
+/// The ambient light at and above which it counts as midday, when the time isn't known.
+const FULL_LUX: u32 = 500;
+
+/// The most the color temperature moves in one poll. Polled every second, going from one end of
+/// the range to the other takes about 5 minutes.
+const MAX_STEP: u16 = u16::MAX / 300;
+
+/// Smaller changes in the setpoint aren't reported, so that the host isn't told every minute.
+const REPORT_DEADBAND: u16 = u16::MAX / 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DaylightCurve {
+    /// Minutes after local midnight.
+    pub sunrise: u16,
+    pub sunset: u16,
+    /// The color temperature between sunset and sunrise.
+    pub night: u16,
+    /// The color temperature halfway between sunrise and sunset.
+    pub midday: u16,
+}
+
+impl DaylightCurve {
+    fn at_minute(&self, minute_of_day: u16) -> u16 {
+        let (sunrise, sunset) =
+            (self.sunrise.min(MINUTES_PER_DAY), self.sunset.min(MINUTES_PER_DAY));
+        if minute_of_day <= sunrise || minute_of_day >= sunset {
+            return self.night;
+        }
+
+        let half_day = (sunset - sunrise) as u32 / 2;
+        let from_edge = (minute_of_day - sunrise).min(sunset - minute_of_day) as u32;
+        self.between(from_edge.min(half_day), half_day)
+    }
+
+    fn at_lux(&self, lux: u16) -> u16 {
+        self.between((lux as u32).min(FULL_LUX), FULL_LUX)
+    }
+
+    /// The color temperature `numerator / denominator` of the way from night to midday.
+    fn between(&self, numerator: u32, denominator: u32) -> u16 {
+        let (night, midday) = (self.night as i32, self.midday as i32);
+        (night + (midday - night) * numerator as i32 / denominator.max(1) as i32) as u16
+    }
+}
+
+/// Off until the host sets a curve. The host can also override it with a fixed color
+/// temperature.
+pub struct Daylight {
+    curve: Option<DaylightCurve>,
+    override_temperature: Option<u16>,
+    minute_of_day: Option<u16>,
+    lux: Option<u16>,
+    /// The setpoint most recently returned by `setpoint_change()`.
+    reported: Option<u16>,
+}
+
+impl Daylight {
+    pub const fn new() -> Self {
+        Self {
+            curve: None,
+            override_temperature: None,
+            minute_of_day: None,
+            lux: None,
+            reported: None,
+        }
+    }
+
+    /// Follows `curve`, starting from wherever the lights are.
+    pub fn enable(&mut self, curve: DaylightCurve) {
+        self.curve = Some(curve);
+    }
+
+    /// Leaves the lights at whatever color temperature they have until the host sets another.
+    pub fn disable(&mut self) {
+        self.curve = None;
+        self.reported = None;
+    }
+
+    /// Holds the lights at `temperature` until the override is cleared with `None`.
+    pub fn set_override(&mut self, temperature: Option<u16>) {
+        self.override_temperature = temperature;
+    }
+
+    pub fn set_time(&mut self, minute_of_day: u16) {
+        self.minute_of_day = Some(minute_of_day);
+    }
+
+    pub fn measure(&mut self, lux: u16) {
+        self.lux = Some(lux);
+    }
+
+    /// The color temperature the lights are heading for, if there's one.
+    pub fn setpoint(&self) -> Option<u16> {
+        if let Some(temperature) = self.override_temperature {
+            return Some(temperature);
+        }
+
+        let curve = self.curve?;
+        match (self.minute_of_day, self.lux) {
+            (Some(minute_of_day), _) => Some(curve.at_minute(minute_of_day)),
+            (None, Some(lux)) => Some(curve.at_lux(lux)),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns the color temperature the lights should be set to from `current`, if they aren't
+    /// there yet. An override is applied at once, otherwise the lights take a step towards the
+    /// setpoint.
+    pub fn poll(&self, current: u16) -> Option<u16> {
+        let setpoint = self.setpoint()?;
+        let next = if self.override_temperature.is_some() {
+            setpoint
+        } else if setpoint > current {
+            current.saturating_add(MAX_STEP).min(setpoint)
+        } else {
+            current.saturating_sub(MAX_STEP).max(setpoint)
+        };
+
+        if next == current {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    /// Returns the setpoint, once each time it has moved far enough to tell the host about.
+    pub fn setpoint_change(&mut self) -> Option<u16> {
+        let setpoint = self.setpoint()?;
+        // An override is reported however small the change.
+        let changed = self.reported.is_none_or(|reported| {
+            let difference = (setpoint as i32 - reported as i32).unsigned_abs() as u16;
+            difference > REPORT_DEADBAND || (self.override_temperature.is_some() && difference > 0)
+        });
+        if !changed {
+            return None;
+        }
+
+        trace!("daylight setpoint: {}", setpoint);
+        self.reported = Some(setpoint);
+        Some(setpoint)
+    }
+}
+
+impl Default for Daylight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
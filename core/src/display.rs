@@ -0,0 +1,134 @@
+use core::fmt::{self, Write};
+
+// Renders the panel's status for a 128x64 monochrome display, such as an SSD1306. The frame is
+// laid out the way those controllers take it: eight pages of eight pixel rows, where each byte is
+// a column of a page with the top pixel in the least significant bit. Text is drawn one line
+// per page, so only the pages whose line changed need to be sent to the display.
+
+pub const WIDTH: usize = 128;
+pub const PAGES: usize = 8;
+
+/// Each character is 5 pixels wide, followed by a column of space.
+const CHAR_WIDTH: usize = 6;
+
+/// What the display shows, from the same state that's reported to the host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Status {
+    pub host_connected: bool,
+    /// The brightness and color temperature of the front and back lights.
+    pub lights: [(u16, u16); 2],
+    /// Detents since boot or the dial's index.
+    pub dial_position: i32,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Frame {
+    pages: [[u8; WIDTH]; PAGES],
+}
+
+impl Frame {
+    pub const fn blank() -> Self {
+        Self { pages: [[0; WIDTH]; PAGES] }
+    }
+
+    pub fn page(&self, page: usize) -> &[u8; WIDTH] {
+        &self.pages[page]
+    }
+
+    /// Draws `status` on a blank frame.
+    pub fn render(status: &Status) -> Self {
+        let mut frame = Self::blank();
+
+        let host = if status.host_connected { "HOST CONNECTED" } else { "NO HOST" };
+        let _ = write!(frame.line(0), "{}", host);
+        for (page, (name, &(brightness, temperature))) in
+            ["FRONT", "BACK"].iter().zip(&status.lights).enumerate()
+        {
+            let _ = write!(
+                frame.line(2 + page),
+                "{:<5} {:>3}% T{:>3}%",
+                name,
+                percent(brightness),
+                percent(temperature)
+            );
+        }
+        let _ = write!(frame.line(5), "DIAL {}", status.dial_position);
+
+        frame
+    }
+
+    fn line(&mut self, page: usize) -> Line<'_> {
+        Line { page: &mut self.pages[page], x: 0 }
+    }
+}
+
+fn percent(value: u16) -> u32 {
+    (value as u32 * 100 + u16::MAX as u32 / 2) / u16::MAX as u32
+}
+
+/// Draws text along a page from the left, cutting it off at the edge of the display.
+struct Line<'a> {
+    page: &'a mut [u8; WIDTH],
+    x: usize,
+}
+
+impl Write for Line<'_> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for c in text.chars() {
+            if self.x + CHAR_WIDTH > WIDTH {
+                break;
+            }
+            self.page[self.x..self.x + 5].copy_from_slice(&glyph(c));
+            self.x += CHAR_WIDTH;
+        }
+
+        Ok(())
+    }
+}
+
+/// The columns of each character, from the classic 5x7 font. Lower case is drawn as upper case,
+/// and anything else which isn't in here as a space.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '%' => [0x23, 0x13, 0x08, 0x64, 0x62],
+        '-' => [0x08, 0x08, 0x08, 0x08, 0x08],
+        ':' => [0x00, 0x36, 0x36, 0x00, 0x00],
+        '0' => [0x3e, 0x51, 0x49, 0x45, 0x3e],
+        '1' => [0x00, 0x42, 0x7f, 0x40, 0x00],
+        '2' => [0x42, 0x61, 0x51, 0x49, 0x46],
+        '3' => [0x21, 0x41, 0x45, 0x4b, 0x31],
+        '4' => [0x18, 0x14, 0x12, 0x7f, 0x10],
+        '5' => [0x27, 0x45, 0x45, 0x45, 0x39],
+        '6' => [0x3c, 0x4a, 0x49, 0x49, 0x30],
+        '7' => [0x01, 0x71, 0x09, 0x05, 0x03],
+        '8' => [0x36, 0x49, 0x49, 0x49, 0x36],
+        '9' => [0x06, 0x49, 0x49, 0x29, 0x1e],
+        'A' => [0x7e, 0x11, 0x11, 0x11, 0x7e],
+        'B' => [0x7f, 0x49, 0x49, 0x49, 0x36],
+        'C' => [0x3e, 0x41, 0x41, 0x41, 0x22],
+        'D' => [0x7f, 0x41, 0x41, 0x22, 0x1c],
+        'E' => [0x7f, 0x49, 0x49, 0x49, 0x41],
+        'F' => [0x7f, 0x09, 0x09, 0x09, 0x01],
+        'G' => [0x3e, 0x41, 0x49, 0x49, 0x7a],
+        'H' => [0x7f, 0x08, 0x08, 0x08, 0x7f],
+        'I' => [0x00, 0x41, 0x7f, 0x41, 0x00],
+        'J' => [0x20, 0x40, 0x41, 0x3f, 0x01],
+        'K' => [0x7f, 0x08, 0x14, 0x22, 0x41],
+        'L' => [0x7f, 0x40, 0x40, 0x40, 0x40],
+        'M' => [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+        'N' => [0x7f, 0x04, 0x08, 0x10, 0x7f],
+        'O' => [0x3e, 0x41, 0x41, 0x41, 0x3e],
+        'P' => [0x7f, 0x09, 0x09, 0x09, 0x06],
+        'Q' => [0x3e, 0x41, 0x51, 0x21, 0x5e],
+        'R' => [0x7f, 0x09, 0x19, 0x29, 0x46],
+        'S' => [0x46, 0x49, 0x49, 0x49, 0x31],
+        'T' => [0x01, 0x01, 0x7f, 0x01, 0x01],
+        'U' => [0x3f, 0x40, 0x40, 0x40, 0x3f],
+        'V' => [0x1f, 0x20, 0x40, 0x20, 0x1f],
+        'W' => [0x3f, 0x40, 0x38, 0x40, 0x3f],
+        'X' => [0x63, 0x14, 0x08, 0x14, 0x63],
+        'Y' => [0x07, 0x08, 0x70, 0x08, 0x07],
+        'Z' => [0x61, 0x51, 0x49, 0x45, 0x43],
+        _ => [0; 5],
+    }
+}
@@ -0,0 +1,86 @@
+use crate::fraction::Fraction;
+
+// The levels of DMX512 fixtures driven by the panel, for light targets beyond its own front and
+// back lights. Target `FIRST_TARGET` gets DMX channels 1 and 2, the next one 3 and 4, and so on:
+// brightness, then color temperature, each the high byte of the value the host sets. Frames only
+// carry as many channels as the targets need, which the standard allows, so each one is short.
+// https://tsp.esta.org/tsp/documents/docs/ANSI-ESTA_E1-11_2008R2018.pdf
+
+/// Targets 0 and 1 are the front and back lights.
+pub const FIRST_TARGET: u8 = 2;
+pub const TARGETS: usize = 16;
+const CHANNELS_PER_TARGET: usize = 2;
+
+/// The start code, then a slot for each channel.
+pub const FRAME_LEN: usize = 1 + TARGETS * CHANNELS_PER_TARGET;
+
+/// Dimmer data, as opposed to one of the alternate start codes.
+const NULL_START_CODE: u8 = 0;
+
+#[derive(Clone, Copy)]
+struct Fixture {
+    brightness: u16,
+    color_temperature: u16,
+}
+
+pub struct Universe {
+    fixtures: [Fixture; TARGETS],
+    brightness_scale: Fraction,
+}
+
+impl Universe {
+    /// Every fixture starts off.
+    pub const fn new() -> Self {
+        Self {
+            fixtures: [Fixture { brightness: 0, color_temperature: 0 }; TARGETS],
+            brightness_scale: Fraction::ONE,
+        }
+    }
+
+    /// Returns false if `target` isn't one of the DMX fixtures.
+    pub fn set_brightness(&mut self, target: u8, brightness: u16) -> bool {
+        self.fixture(target).map(|fixture| fixture.brightness = brightness).is_some()
+    }
+
+    /// Returns false if `target` isn't one of the DMX fixtures.
+    pub fn set_color_temperature(&mut self, target: u8, color_temperature: u16) -> bool {
+        self.fixture(target).map(|fixture| fixture.color_temperature = color_temperature).is_some()
+    }
+
+    /// The brightness and color temperature the host set for `target`, before scaling, if it's
+    /// one of the DMX fixtures.
+    pub fn levels(&self, target: u8) -> Option<(u16, u16)> {
+        let index = target.checked_sub(FIRST_TARGET)?;
+        let fixture = self.fixtures.get(index as usize)?;
+        Some((fixture.brightness, fixture.color_temperature))
+    }
+
+    /// Scales the brightness of every fixture, as the power state does for the local lights.
+    pub fn set_brightness_scale(&mut self, scale: Fraction) {
+        self.brightness_scale = scale;
+    }
+
+    /// The next frame to send, from the start code on.
+    pub fn frame(&self) -> [u8; FRAME_LEN] {
+        let mut frame = [0; FRAME_LEN];
+        frame[0] = NULL_START_CODE;
+        for (slots, fixture) in frame[1..].chunks_exact_mut(CHANNELS_PER_TARGET).zip(&self.fixtures)
+        {
+            let brightness = self.brightness_scale.of_u16(fixture.brightness);
+            slots[0] = (brightness >> 8) as u8;
+            slots[1] = (fixture.color_temperature >> 8) as u8;
+        }
+        frame
+    }
+
+    fn fixture(&mut self, target: u8) -> Option<&mut Fixture> {
+        let index = target.checked_sub(FIRST_TARGET)?;
+        self.fixtures.get_mut(index as usize)
+    }
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,41 @@
+use crate::button::Debouncer;
+use core::convert::Infallible;
+use embedded_hal::digital::InputPin;
+
+// A hall effect or reed switch which is active while a door is closed, such as a reed switch to
+// ground on a pulled up pin with a magnet on the door. It's debounced like the button, and each
+// change is reported to the host, so that the panel can tell it when a room is in use.
+
+pub struct DoorSensor<T: InputPin> {
+    pin: Debouncer<T>,
+    open: bool,
+}
+
+impl<T: InputPin<Error = Infallible>> DoorSensor<T> {
+    /// The door starts out open, as the debouncer starts out released.
+    pub fn new(pin: Debouncer<T>) -> Self {
+        let open = !pin.is_pressed();
+        Self { pin, open }
+    }
+
+    pub fn set_debounce_time(&mut self, debounce_time_ms: u16) {
+        self.pin.set_debounce_time(debounce_time_ms);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Samples the sensor. Returns whether the door is open if that's changed since the last poll.
+    pub fn poll(&mut self) -> Option<bool> {
+        self.pin.poll();
+
+        let open = !self.pin.is_pressed();
+        if open == self.open {
+            return None;
+        }
+
+        self.open = open;
+        Some(open)
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    crc::crc8,
+    onewire::{self, OneWire},
+};
+
+// DS18B20 temperature probes, any number of which can share a 1-Wire bus. Conversions are
+// started on every probe at once, and each probe's result read back once they've finished.
+// https://www.analog.com/media/en/technical-documentation/data-sheets/ds18b20.pdf
+
+const FAMILY: u8 = 0x28;
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xBE;
+const SCRATCHPAD_LEN: usize = 9;
+/// The low bits of the configuration register, which always read as 1.
+const CONFIG_RESERVED: u8 = 0x1f;
+
+/// How long a conversion takes, at the default 12 bit resolution.
+pub const CONVERSION_MS: u32 = 750;
+
+/// The probes found on a bus, up to `N` of them.
+pub struct Probes<const N: usize> {
+    roms: [u64; N],
+    count: usize,
+}
+
+impl<const N: usize> Probes<N> {
+    /// Searches the bus for probes, ignoring any other kind of device on it.
+    pub fn find<B: OneWire>(bus: &mut B) -> Self {
+        let mut roms = [0; N];
+        let mut count = 0;
+        onewire::search(bus, |rom| {
+            if rom as u8 == FAMILY {
+                roms[count] = rom;
+                count += 1;
+            }
+            count < N
+        });
+
+        Self { roms, count }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Starts every probe measuring, which takes `CONVERSION_MS`.
+    pub fn start_conversion<B: OneWire>(&self, bus: &mut B) {
+        if onewire::select(bus, None) {
+            onewire::write_byte(bus, CONVERT_T);
+        }
+    }
+
+    /// The temperature of probe `index` from its last conversion, in hundredths of a degree
+    /// Celsius. `None` if the probe didn't answer, or its data was corrupted on the way.
+    pub fn temperature<B: OneWire>(&self, bus: &mut B, index: usize) -> Option<i16> {
+        let rom = self.roms[..self.count].get(index)?;
+        if !onewire::select(bus, Some(*rom)) {
+            return None;
+        }
+        onewire::write_byte(bus, READ_SCRATCHPAD);
+
+        let mut scratchpad = [0u8; SCRATCHPAD_LEN];
+        for byte in scratchpad.iter_mut() {
+            *byte = onewire::read_byte(bus);
+        }
+        // A line held low reads as all zeros, whose CRC checks out too.
+        if crc8(0, &scratchpad) != 0 || scratchpad[4] & CONFIG_RESERVED != CONFIG_RESERVED {
+            return None;
+        }
+
+        // Sixteenths of a degree.
+        let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]);
+        Some((raw as i32 * 100 / 16) as i16)
+    }
+}
@@ -0,0 +1,32 @@
+use crate::fraction::Fraction;
+
+// What the LED ring around the dial shows: a dot which follows the dial round, over a dim glow in
+// the color of the panel's mode. The frame is worked out here, and sent to the ring by the
+// firmware whenever it changes.
+
+/// How bright the LEDs away from the dot are.
+const GLOW: Fraction = Fraction::from_ratio(1, 8);
+
+/// Colors each of the `N` LEDs of a ring, for a dial `position` detents from zero. LED 0 is at
+/// position zero, and the LEDs go round the way the position counts up, once every
+/// `detents_per_turn`.
+pub fn render<const N: usize>(
+    position: i32,
+    detents_per_turn: u16,
+    color: (u8, u8, u8),
+    intensity: Fraction,
+) -> [(u8, u8, u8); N] {
+    let detents_per_turn = detents_per_turn.max(1) as u32;
+    let detent = position.rem_euclid(detents_per_turn as i32) as u32;
+    // To the nearest LED, going round to LED 0 again after the last one.
+    let dot = ((detent * N as u32 * 2 + detents_per_turn) / (detents_per_turn * 2)) as usize % N;
+
+    let scale = |brightness: Fraction| {
+        let (r, g, b) = color;
+        (brightness.of_u8(r), brightness.of_u8(g), brightness.of_u8(b))
+    };
+    let mut frame = [scale(intensity * GLOW); N];
+    frame[dot] = scale(intensity);
+
+    frame
+}
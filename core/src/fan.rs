@@ -0,0 +1,58 @@
+use crate::fraction::Fraction;
+
+/// Once running, the fan keeps running until it's this much cooler than where it started, so that
+/// it doesn't keep starting and stopping around that temperature.
+const HYSTERESIS_CELSIUS: i16 = 3;
+
+/// Fan tachometers pulse twice per revolution.
+const PULSES_PER_REVOLUTION: u32 = 2;
+
+/// How the speed of the fan follows the temperature.
+#[derive(Debug, Clone, Copy)]
+pub struct FanCurve {
+    /// The fan starts, at `min_percent`, at this temperature...
+    pub start_celsius: i16,
+    /// ...and speeds up from there to full speed at this one.
+    pub full_celsius: i16,
+    /// The slowest the fan runs at, as many fans stall below some duty cycle.
+    pub min_percent: u8,
+}
+
+pub struct FanController {
+    curve: FanCurve,
+    running: bool,
+}
+
+impl FanController {
+    pub const fn new(curve: FanCurve) -> Self {
+        Self { curve, running: false }
+    }
+
+    /// The duty cycle for the fan at `celsius`.
+    pub fn duty(&mut self, celsius: i16) -> Fraction {
+        let FanCurve { start_celsius, full_celsius, min_percent } = self.curve;
+
+        self.running = if self.running {
+            celsius > start_celsius - HYSTERESIS_CELSIUS
+        } else {
+            celsius >= start_celsius
+        };
+
+        if !self.running {
+            Fraction::ZERO
+        } else if celsius >= full_celsius {
+            Fraction::ONE
+        } else {
+            let span = (full_celsius - start_celsius).max(1) as u32;
+            let above = ((celsius - start_celsius).max(0) as u32).min(span);
+            let min_percent = min_percent.min(100) as u32;
+            Fraction::from_ratio(min_percent * span + (100 - min_percent) * above, 100 * span)
+        }
+    }
+}
+
+/// The speed of the fan, from the tachometer pulses counted over `period_ms`.
+pub fn rpm(pulses: u32, period_ms: u32) -> u16 {
+    let rpm = pulses as u64 * 60_000 / (PULSES_PER_REVOLUTION * period_ms) as u64;
+    rpm.min(u16::MAX as u64) as u16
+}
@@ -0,0 +1,62 @@
+use core::ops::{Mul, MulAssign};
+
+/// Fractions are stored as multiples of 1 / 2^15, so that 1 is exact and scaling is a
+/// multiplication and a shift.
+const SHIFT: u32 = 15;
+const ONE_RAW: u32 = 1 << SHIFT;
+
+/// A number from 0 to 1, for scaling brightness. The Cortex-M3 has no FPU, so this is used
+/// instead of `f32`, which it would have to emulate in software.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub struct Fraction(u16);
+
+impl Fraction {
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(ONE_RAW as u16);
+
+    /// `numerator / denominator`, capped at 1. This is rounded up, so that e.g. 3/10 of 10 is 3
+    /// rather than 2.
+    pub const fn from_ratio(numerator: u32, denominator: u32) -> Self {
+        let denominator = denominator as u64;
+        let raw = (numerator as u64 * ONE_RAW as u64).div_ceil(denominator);
+        if raw > ONE_RAW as u64 {
+            Self::ONE
+        } else {
+            Self(raw as u16)
+        }
+    }
+
+    pub(crate) const fn from_raw(raw: u16) -> Self {
+        Self(raw)
+    }
+
+    /// `value` scaled by this fraction, rounded down.
+    pub fn of_u8(self, value: u8) -> u8 {
+        ((value as u32 * self.0 as u32) >> SHIFT) as u8
+    }
+
+    /// `value` scaled by this fraction, rounded down.
+    pub fn of_u16(self, value: u16) -> u16 {
+        ((value as u32 * self.0 as u32) >> SHIFT) as u16
+    }
+
+    /// `value` scaled by this fraction, rounded down.
+    pub fn of_u32(self, value: u32) -> u32 {
+        ((value as u64 * self.0 as u64) >> SHIFT) as u32
+    }
+}
+
+impl Mul for Fraction {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self(((self.0 as u32 * other.0 as u32) >> SHIFT) as u16)
+    }
+}
+
+impl MulAssign for Fraction {
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other;
+    }
+}
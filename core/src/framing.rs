@@ -0,0 +1,176 @@
+use crate::crc::crc16;
+
+// Wraps the protocol's bytes in frames, in both directions, for hosts which ask for them. Each
+// frame's payload is one or more whole commands or reports, followed by its CRC-16, high byte
+// first. That is COBS encoded, which leaves no zero bytes in it, and ends with a zero. Wherever
+// the reader starts, or whatever garbage it's sent, it's back in step after the next zero. A
+// frame whose CRC doesn't match is dropped, so a corrupted byte can't turn into a wrong
+// brightness or color. From `SEQUENCE_PROTOCOL_VERSION` on, frames from the panel start with the
+// sequence number of their first report, so that the host can tell when it has missed some.
+// While the host streams LED frames, each of its frames starts with a byte saying what it holds.
+// http://www.stuartcheshire.org/papers/COBSforToN.pdf
+
+/// Hosts from this version on send and receive frames, from the byte after their
+/// `Command::SetProtocolVersion`.
+pub const FRAMED_PROTOCOL_VERSION: u8 = 3;
+
+/// Hosts from this version on get the sequence number at the start of every frame from the
+/// panel.
+pub const SEQUENCE_PROTOCOL_VERSION: u8 = 5;
+
+/// The sequence number is a `u16`, high byte first, which wraps. It counts each report sent since
+/// the host connected, starting from 0.
+pub const SEQUENCE_LEN: usize = 2;
+
+pub const MAX_PAYLOAD_LEN: usize = 255;
+
+/// The first byte of each frame from a host which has turned on `Command::StreamLeds`: the rest
+/// is either commands, as in any other frame, or a color for each LED on the strip.
+pub const COMMANDS_FRAME: u8 = 0;
+pub const LED_FRAME: u8 = 1;
+
+const CRC_LEN: usize = 2;
+
+/// What a frame adds to a payload short enough for COBS to add one byte: the CRC, that byte and
+/// the zero at the end.
+pub const OVERHEAD: usize = CRC_LEN + 2;
+
+/// COBS adds a byte for every 254, and one to start with.
+pub const MAX_FRAME_LEN: usize = MAX_PAYLOAD_LEN + CRC_LEN + (MAX_PAYLOAD_LEN + CRC_LEN) / 254 + 2;
+
+const CRC_INIT: u16 = 0xFFFF;
+
+const DELIMITER: u8 = 0;
+
+/// The longest run COBS encodes in one block, after which the block's code byte is this.
+const MAX_CODE: u8 = 0xFF;
+
+/// Wraps `payload` in a frame in `out`, and returns the frame.
+pub fn encode<'a>(payload: &[u8], out: &'a mut [u8; MAX_FRAME_LEN]) -> &'a [u8] {
+    assert!(payload.len() <= MAX_PAYLOAD_LEN);
+
+    let crc = crc16(CRC_INIT, payload).to_be_bytes();
+
+    // Each block starts with a code byte, which is one more than the number of bytes after it
+    // before the next zero, or `MAX_CODE` for a block which doesn't end in one.
+    let mut code_at = 0;
+    let mut len = 1;
+    for &byte in payload.iter().chain(&crc) {
+        if byte != DELIMITER {
+            out[len] = byte;
+            len += 1;
+        }
+        if byte == DELIMITER || len - code_at == MAX_CODE as usize {
+            out[code_at] = (len - code_at) as u8;
+            code_at = len;
+            len += 1;
+        }
+    }
+    out[code_at] = (len - code_at) as u8;
+    out[len] = DELIMITER;
+
+    &out[..len + 1]
+}
+
+/// Undoes the COBS encoding of `buf` in place, returning how long the result is, or `None` if
+/// it isn't valid COBS.
+fn decode(buf: &mut [u8]) -> Option<usize> {
+    let (mut read, mut write) = (0, 0);
+
+    while read < buf.len() {
+        let code = buf[read] as usize;
+        if read + code > buf.len() {
+            return None;
+        }
+
+        buf.copy_within(read + 1..read + code, write);
+        write += code - 1;
+        read += code;
+
+        if code < MAX_CODE as usize && read < buf.len() {
+            buf[write] = DELIMITER;
+            write += 1;
+        }
+    }
+
+    Some(write)
+}
+
+/// Puts frames back together from the bytes as they arrive, however they're split.
+pub struct FrameReader {
+    /// The frame so far, still encoded and without the zero at the end.
+    buf: [u8; MAX_FRAME_LEN - 1],
+    len: usize,
+    /// Set if the frame so far didn't fit in `buf`, in which case the rest of it is dropped.
+    overflowed: bool,
+    /// Frames dropped since `take_corrupt_frames()` was last called.
+    corrupt_frames: u32,
+}
+
+impl FrameReader {
+    pub const fn new() -> Self {
+        Self { buf: [0; MAX_FRAME_LEN - 1], len: 0, overflowed: false, corrupt_frames: 0 }
+    }
+
+    /// Adds the next byte from the host, and returns the payload of the frame it completes, if it
+    /// completes one which is intact.
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if byte != DELIMITER {
+            match self.buf.get_mut(self.len) {
+                Some(slot) => {
+                    *slot = byte;
+                    self.len += 1;
+                },
+                None => self.overflowed = true,
+            }
+            return None;
+        }
+
+        let len = core::mem::replace(&mut self.len, 0);
+        let overflowed = core::mem::replace(&mut self.overflowed, false);
+        // Zeros on their own only mark where frames start, which hosts can send to get back in
+        // step with the reader.
+        if len == 0 && !overflowed {
+            return None;
+        }
+
+        let decoded = if overflowed { None } else { decode(&mut self.buf[..len]) };
+        let payload_len = match decoded {
+            Some(len) if len >= CRC_LEN => {
+                let (payload, crc) = self.buf[..len].split_at(len - CRC_LEN);
+                if crc16(CRC_INIT, payload).to_be_bytes() == crc {
+                    Some(payload.len())
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        match payload_len {
+            Some(len) => Some(&self.buf[..len]),
+            None => {
+                warn!("dropped a corrupt frame");
+                self.corrupt_frames = self.corrupt_frames.saturating_add(1);
+                None
+            },
+        }
+    }
+
+    /// Drops any partial frame.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+
+    /// Returns how many frames have been dropped since the last call.
+    pub fn take_corrupt_frames(&mut self) -> u32 {
+        core::mem::replace(&mut self.corrupt_frames, 0)
+    }
+}
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
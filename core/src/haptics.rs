@@ -0,0 +1,62 @@
+use crate::{button::ButtonEvent, input::InputEvent};
+
+// Which haptic effect is felt on each kind of input. Effects are numbered as in the DRV2605's
+// built-in waveform library, from 1 to 123, with 0 for none.
+// https://www.ti.com/lit/ds/symlink/drv2605.pdf
+
+/// Plays nothing.
+pub const NO_EFFECT: u8 = 0;
+pub const MAX_EFFECT: u8 = 123;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HapticEvent {
+    /// The button was clicked.
+    Click = 0,
+    /// The dial was turned by a detent or more.
+    Detent = 1,
+    /// The button was held long enough to count as a long press.
+    LongPress = 2,
+}
+
+impl HapticEvent {
+    pub const ALL: [HapticEvent; 3] =
+        [HapticEvent::Click, HapticEvent::Detent, HapticEvent::LongPress];
+
+    pub fn from_u8(event: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|&e| e as u8 == event)
+    }
+
+    /// How `event` is felt, if it is.
+    pub fn for_input(event: &InputEvent) -> Option<Self> {
+        match event {
+            InputEvent::Button(ButtonEvent::ShortRelease) => Some(HapticEvent::Click),
+            InputEvent::Button(ButtonEvent::LongPress) => Some(HapticEvent::LongPress),
+            InputEvent::Dial(_) => Some(HapticEvent::Detent),
+            _ => None,
+        }
+    }
+}
+
+/// The effect played for each `HapticEvent`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HapticEffects {
+    effects: [u8; HapticEvent::ALL.len()],
+}
+
+impl Default for HapticEffects {
+    /// Strong click, sharp tick and double click.
+    fn default() -> Self {
+        Self { effects: [1, 24, 10] }
+    }
+}
+
+impl HapticEffects {
+    /// The effect for `event`, or `None` if it's felt as nothing.
+    pub fn get(&self, event: HapticEvent) -> Option<u8> {
+        Some(self.effects[event as usize]).filter(|&effect| effect != NO_EFFECT)
+    }
+
+    pub fn set(&mut self, event: HapticEvent, effect: u8) {
+        self.effects[event as usize] = effect;
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    button::{Button, ButtonEvent},
+    commands,
+    counter::{self, Counter, QuadratureCounter},
+    tick::{Duration, Instant},
+};
+use core::convert::Infallible;
+use embedded_hal::digital::InputPin;
+use panel_protocol::Report;
+
+/// What a host speaks until it says otherwise, with `Command::SetProtocolVersion`.
+pub const DEFAULT_PROTOCOL_VERSION: u8 = 1;
+
+/// Hosts from this version on are told about dial movement while the button is held, with
+/// `Report::DialTurned`, and decide for themselves whether to ignore it.
+pub const BUTTON_HELD_PROTOCOL_VERSION: u8 = 2;
+
+/// The newest version the firmware speaks, which it answers `Command::Hello` with. Keep it at
+/// the version of the newest feature.
+pub const LATEST_PROTOCOL_VERSION: u8 = commands::RESET_PROTOCOL_VERSION;
+
+pub enum InputEvent {
+    Button(ButtonEvent),
+    DialHomed,
+    Dial(i8),
+    /// The dial was turned while the button was held, which is easy to do by accident.
+    HeldDial(i8),
+}
+
+impl InputEvent {
+    /// The report to send a host which speaks `protocol_version` for this event, if it wants to
+    /// know about it.
+    pub fn report(&self, protocol_version: u8) -> Option<Report> {
+        let button_flag = protocol_version >= BUTTON_HELD_PROTOCOL_VERSION;
+        match self {
+            InputEvent::Button(ButtonEvent::ShortRelease) => Some(Report::Press),
+            InputEvent::Button(ButtonEvent::LongPress) => Some(Report::LongPress),
+            InputEvent::Button(ButtonEvent::Pressed) => None,
+            InputEvent::Button(ButtonEvent::LongRelease) => None,
+            InputEvent::DialHomed => Some(Report::DialHomed),
+            InputEvent::Dial(diff) if button_flag => {
+                Some(Report::DialTurned { diff: *diff, button_held: false })
+            },
+            InputEvent::Dial(diff) => Some(Report::DialValue { diff: *diff }),
+            InputEvent::HeldDial(diff) if button_flag => {
+                Some(Report::DialTurned { diff: *diff, button_held: true })
+            },
+            InputEvent::HeldDial(_) => None,
+        }
+    }
+}
+
+/// Holds dial movement back in the counter, where it's summed, so that a fast spin is reported
+/// at most so many times a second rather than once for every detent.
+pub struct DialRateLimit {
+    min_interval: Duration,
+    /// When the dial was last reported, if it has been.
+    last_report: Option<Instant>,
+}
+
+impl DialRateLimit {
+    /// Limits dial reports to `max_per_second`, or reports every detent if that's 0.
+    pub fn new(max_per_second: u16) -> Self {
+        let mut limit = Self { min_interval: Duration::from_ms(0), last_report: None };
+        limit.set_max_per_second(max_per_second);
+        limit
+    }
+
+    pub fn set_max_per_second(&mut self, max_per_second: u16) {
+        let interval_ms = match max_per_second {
+            0 => 0,
+            _ => 1000 / max_per_second as u32,
+        };
+        self.min_interval = Duration::from_ms(interval_ms);
+    }
+
+    fn is_due(&self) -> bool {
+        self.last_report.is_none_or(|last_report| last_report.elapsed() >= self.min_interval)
+    }
+}
+
+/// Samples the button and the dial once. Each event is passed to `emit`, which gives it back if
+/// there's no room for it: button events and movement while the button is held are then
+/// dropped, and other dial movement is put back into the counter. So is movement which
+/// `dial_limit` holds back, to go out with the next.
+pub fn poll<B, Q, Z>(
+    button: &mut Button<B>,
+    counter: &mut Counter<Q, Z>,
+    dial_limit: &mut DialRateLimit,
+    mut emit: impl FnMut(InputEvent) -> Result<(), InputEvent>,
+) -> Result<(), counter::Error>
+where
+    B: InputPin<Error = Infallible>,
+    Q: QuadratureCounter,
+    Z: InputPin<Error = Infallible>,
+{
+    if let Some(event) = button.poll() {
+        if emit(InputEvent::Button(event)).is_err() {
+            warn!("input queue full, dropping button event");
+        }
+    }
+
+    if counter.poll_index() && emit(InputEvent::DialHomed).is_err() {
+        warn!("input queue full, dropping dial homed event");
+    }
+
+    if let Some(diff) = counter.poll()? {
+        if !dial_limit.is_due() {
+            counter.defer(diff);
+        } else if button.is_pressed() {
+            dial_limit.last_report = Some(Instant::now());
+            if emit(InputEvent::HeldDial(diff)).is_err() {
+                warn!("input queue full, dropping dial movement");
+            }
+        } else if emit(InputEvent::Dial(diff)).is_err() {
+            counter.defer(diff);
+        } else {
+            dial_limit.last_report = Some(Instant::now());
+        }
+    }
+
+    Ok(())
+}
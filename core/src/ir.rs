@@ -0,0 +1,309 @@
+use panel_protocol::Report;
+
+// Decodes the frames of IR remotes from a TSOP style receiver, whose output is low while it sees
+// the carrier (a mark) and high otherwise (a space). The receiver's pin interrupt passes the
+// length of every pulse to `IrDecoder::edge()`, which looks for NEC and RC5 frames at the same
+// time.
+// https://www.sbprojects.net/knowledge/ir/nec.php
+// https://www.sbprojects.net/knowledge/ir/rc5.php
+
+const NEC_LEADER_MARK_US: u32 = 9000;
+const NEC_LEADER_SPACE_US: u32 = 4500;
+const NEC_REPEAT_SPACE_US: u32 = 2250;
+const NEC_BIT_MARK_US: u32 = 560;
+const NEC_ZERO_SPACE_US: u32 = 560;
+const NEC_ONE_SPACE_US: u32 = 1690;
+const NEC_BITS: u8 = 32;
+
+const RC5_HALF_BIT_US: u32 = 889;
+const RC5_BITS: u8 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IrProtocol {
+    Nec = 0,
+    Rc5 = 1,
+}
+
+impl IrProtocol {
+    pub fn from_u8(protocol: u8) -> Option<Self> {
+        match protocol {
+            0 => Some(IrProtocol::Nec),
+            1 => Some(IrProtocol::Rc5),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IrCode {
+    pub protocol: IrProtocol,
+    /// 8 bits for NEC, or 16 for extended NEC, and 5 bits for RC5.
+    pub address: u16,
+    /// 8 bits for NEC, and 7 bits for RC5.
+    pub command: u8,
+    /// Whether this is a remote repeating the code while its button is held.
+    pub repeat: bool,
+}
+
+impl IrCode {
+    pub fn report(&self) -> Report {
+        Report::IrCode {
+            protocol: self.protocol as u8,
+            address: self.address,
+            command: self.command,
+            repeat: self.repeat,
+        }
+    }
+}
+
+/// Whether `duration_us` is within a quarter of `nominal_us`, which is looser than any remote is
+/// off by, and still tells apart every pulse these protocols use.
+fn near(duration_us: u32, nominal_us: u32) -> bool {
+    duration_us > nominal_us * 3 / 4 && duration_us < nominal_us * 5 / 4
+}
+
+pub struct IrDecoder {
+    nec: Nec,
+    rc5: Rc5,
+}
+
+impl IrDecoder {
+    pub const fn new() -> Self {
+        Self { nec: Nec::new(), rc5: Rc5::new() }
+    }
+
+    /// Takes an edge on the receiver's output, which went `high` or low after `duration_us` at
+    /// the other level. Returns the code once a whole frame has been received.
+    pub fn edge(&mut self, high: bool, duration_us: u32) -> Option<IrCode> {
+        // After a rising edge, the pulse which ended was a mark.
+        let mark = high;
+        let nec = self.nec.pulse(mark, duration_us);
+        let rc5 = self.rc5.pulse(mark, duration_us);
+
+        nec.or(rc5)
+    }
+}
+
+impl Default for IrDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NecState {
+    Idle,
+    LeaderSpace,
+    /// Received a repeat space, and waiting for the mark which ends it.
+    RepeatMark,
+    BitMark,
+    BitSpace,
+}
+
+struct Nec {
+    state: NecState,
+    bits: u32,
+    count: u8,
+    /// The most recent code, which a repeat frame stands for.
+    last: Option<IrCode>,
+}
+
+impl Nec {
+    const fn new() -> Self {
+        Self { state: NecState::Idle, bits: 0, count: 0, last: None }
+    }
+
+    fn pulse(&mut self, mark: bool, duration_us: u32) -> Option<IrCode> {
+        let lasted = |nominal_us| near(duration_us, nominal_us);
+
+        let (state, code) = match (self.state, mark) {
+            (NecState::LeaderSpace, false) if lasted(NEC_LEADER_SPACE_US) => {
+                self.bits = 0;
+                self.count = 0;
+                (NecState::BitMark, None)
+            },
+            (NecState::LeaderSpace, false) if lasted(NEC_REPEAT_SPACE_US) => {
+                (NecState::RepeatMark, None)
+            },
+            (NecState::RepeatMark, true) if lasted(NEC_BIT_MARK_US) => {
+                (NecState::Idle, self.last.map(|code| IrCode { repeat: true, ..code }))
+            },
+            (NecState::BitMark, true) if lasted(NEC_BIT_MARK_US) => {
+                if self.count < NEC_BITS {
+                    (NecState::BitSpace, None)
+                } else {
+                    (NecState::Idle, self.decode())
+                }
+            },
+            (NecState::BitSpace, false)
+                if lasted(NEC_ZERO_SPACE_US) || lasted(NEC_ONE_SPACE_US) =>
+            {
+                // The bits are sent least significant first.
+                if lasted(NEC_ONE_SPACE_US) {
+                    self.bits |= 1 << self.count;
+                }
+                self.count += 1;
+                (NecState::BitMark, None)
+            },
+            // Anything else ends the frame, but may be the start of the next one.
+            (_, true) if lasted(NEC_LEADER_MARK_US) => (NecState::LeaderSpace, None),
+            _ => (NecState::Idle, None),
+        };
+
+        self.state = state;
+        code
+    }
+
+    fn decode(&mut self) -> Option<IrCode> {
+        let [address, address_inverse, command, command_inverse] = self.bits.to_le_bytes();
+        if command != !command_inverse {
+            return None;
+        }
+
+        // Extended NEC uses the inverted address as the high byte of a 16 bit one instead.
+        let address = if address == !address_inverse {
+            address as u16
+        } else {
+            u16::from_le_bytes([address, address_inverse])
+        };
+
+        let code = IrCode { protocol: IrProtocol::Nec, address, command, repeat: false };
+        self.last = Some(code);
+        Some(code)
+    }
+}
+
+struct Rc5 {
+    active: bool,
+    /// Each half bit received so far, set for a mark. The first is the oldest.
+    halves: u32,
+    count: u8,
+    /// The most recent frame, whose toggle bit tells a held button from a new press.
+    last: Option<IrCode>,
+    last_toggle: bool,
+}
+
+impl Rc5 {
+    const fn new() -> Self {
+        Self { active: false, halves: 0, count: 0, last: None, last_toggle: false }
+    }
+
+    fn pulse(&mut self, mark: bool, duration_us: u32) -> Option<IrCode> {
+        let halves = if !self.active {
+            0
+        } else if near(duration_us, RC5_HALF_BIT_US) {
+            1
+        } else if near(duration_us, 2 * RC5_HALF_BIT_US) {
+            2
+        } else {
+            0
+        };
+
+        if halves == 0 || self.count + halves > 2 * RC5_BITS {
+            // The first start bit is always a one, whose first half is the idle space, so a
+            // frame starts with the first mark.
+            self.active = !mark;
+            self.halves = 0;
+            self.count = 1;
+            return None;
+        }
+
+        for _ in 0..halves {
+            self.halves = (self.halves << 1) | mark as u32;
+            self.count += 1;
+        }
+
+        // If the last bit is a zero, its second half is the space after the frame.
+        if self.count == 2 * RC5_BITS - 1 && mark {
+            self.halves <<= 1;
+            self.count += 1;
+        }
+
+        if self.count < 2 * RC5_BITS {
+            return None;
+        }
+
+        self.active = false;
+        self.decode()
+    }
+
+    fn decode(&mut self) -> Option<IrCode> {
+        let mut bits = 0u16;
+        for bit in (0..RC5_BITS).rev() {
+            // A one is a space followed by a mark, and a zero the other way around.
+            bits = (bits << 1)
+                | match (self.halves >> (2 * bit)) & 0b11 {
+                    0b01 => 1,
+                    0b10 => 0,
+                    _ => return None,
+                };
+        }
+
+        // The second start bit is the inverse of the seventh command bit, in extended RC5.
+        let field = bits & (1 << 12) != 0;
+        let toggle = bits & (1 << 11) != 0;
+        let address = (bits >> 6) & 0x1f;
+        let command = (bits & 0x3f) as u8 | if field { 0 } else { 0x40 };
+
+        let repeat = self.last.is_some_and(|last| {
+            last.address == address && last.command == command && self.last_toggle == toggle
+        });
+        let code = IrCode { protocol: IrProtocol::Rc5, address, command, repeat };
+        self.last = Some(code);
+        self.last_toggle = toggle;
+        Some(code)
+    }
+}
+
+/// What a button on a remote can be bound to, instead of being sent to the host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IrAction {
+    /// Puts the panel to sleep, or wakes it.
+    ToggleLights = 0,
+    /// Sets the lights and LED strip to the default scene.
+    RecallScene = 1,
+}
+
+impl IrAction {
+    pub const ALL: [IrAction; 2] = [IrAction::ToggleLights, IrAction::RecallScene];
+
+    pub fn from_u8(action: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|&a| a as u8 == action)
+    }
+}
+
+/// A button on a remote, as the codes it sends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IrBinding {
+    pub protocol: IrProtocol,
+    pub address: u16,
+    pub command: u8,
+}
+
+/// Which remote button, if any, triggers each `IrAction`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IrBindings {
+    bindings: [Option<IrBinding>; IrAction::ALL.len()],
+}
+
+impl IrBindings {
+    pub fn get(&self, action: IrAction) -> Option<IrBinding> {
+        self.bindings[action as usize]
+    }
+
+    pub fn set(&mut self, action: IrAction, binding: Option<IrBinding>) {
+        self.bindings[action as usize] = binding;
+    }
+
+    /// The action bound to `code`, if any. Repeats are matched too, so that they can be told
+    /// apart from the codes of buttons which aren't bound.
+    pub fn action(&self, code: &IrCode) -> Option<IrAction> {
+        IrAction::ALL.iter().copied().find(|&action| {
+            self.get(action).is_some_and(|binding| {
+                binding.protocol == code.protocol
+                    && binding.address == code.address
+                    && binding.command == code.command
+            })
+        })
+    }
+}
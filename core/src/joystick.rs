@@ -0,0 +1,83 @@
+// Turns a two axis analog joystick into nudges in one of eight directions, for menu style
+// interfaces on the host. The stick's position at the first reading is taken as its centre. It
+// nudges once each time it's pushed out of the deadzone around the centre, in the direction it
+// was pushed, and has to come most of the way back before it can nudge again.
+
+/// How far the stick has to be pushed from the centre to nudge, in 12 bit readings.
+const DEADZONE: i32 = 0x1000 / 4;
+
+/// How close to the centre it has to come back to before the next nudge.
+const RELEASE: i32 = DEADZONE / 2;
+
+/// tan(22.5 degrees), about 12/29, where the sectors of each direction meet.
+const SECTOR_EDGE: (i32, i32) = (12, 29);
+
+/// Where the stick was pushed, clockwise from up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up = 0,
+    UpRight = 1,
+    Right = 2,
+    DownRight = 3,
+    Down = 4,
+    DownLeft = 5,
+    Left = 6,
+    UpLeft = 7,
+}
+
+impl Direction {
+    /// The nearest direction to an offset from the centre.
+    fn quantize(x: i32, y: i32) -> Self {
+        let (numerator, denominator) = SECTOR_EDGE;
+        let horizontal = y.abs() * denominator < x.abs() * numerator;
+        let vertical = x.abs() * denominator < y.abs() * numerator;
+
+        match (horizontal, vertical, x > 0, y > 0) {
+            (true, _, true, _) => Direction::Right,
+            (true, _, false, _) => Direction::Left,
+            (_, true, _, true) => Direction::Up,
+            (_, true, _, false) => Direction::Down,
+            (_, _, true, true) => Direction::UpRight,
+            (_, _, true, false) => Direction::DownRight,
+            (_, _, false, true) => Direction::UpLeft,
+            (_, _, false, false) => Direction::DownLeft,
+        }
+    }
+}
+
+pub struct Joystick {
+    centre: Option<(i32, i32)>,
+    /// Whether the stick has nudged since it was last back near the centre.
+    pushed: bool,
+}
+
+impl Joystick {
+    pub const fn new() -> Self {
+        Self { centre: None, pushed: false }
+    }
+
+    /// Takes a 12 bit reading of each axis, which grow to the right and upwards, and returns the
+    /// direction of a nudge, if there is one.
+    pub fn sample(&mut self, x: u16, y: u16) -> Option<Direction> {
+        let (x, y) = (x as i32, y as i32);
+        let (centre_x, centre_y) = *self.centre.get_or_insert((x, y));
+        let (x, y) = (x - centre_x, y - centre_y);
+        let distance_squared = x * x + y * y;
+
+        if self.pushed {
+            self.pushed = distance_squared > RELEASE * RELEASE;
+            None
+        } else if distance_squared > DEADZONE * DEADZONE {
+            self.pushed = true;
+            Some(Direction::quantize(x, y))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Joystick {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,42 @@
+#![no_std]
+
+// The hardware independent parts of the panel firmware. Everything in here only depends on
+// embedded-hal traits and the host protocol, so it also builds on the host and is tested there,
+// see `make test`.
+
+// Declared first so that its macros are available to all of the other modules.
+#[macro_use]
+mod log;
+
+pub mod ack;
+pub mod auto_brightness;
+pub mod button;
+pub mod calendar;
+pub mod command_parser;
+pub mod commands;
+pub mod counter;
+pub mod crc;
+pub mod daylight;
+pub mod display;
+pub mod dmx;
+pub mod door;
+pub mod ds18b20;
+pub mod encoder_ring;
+pub mod fan;
+pub mod fraction;
+pub mod framing;
+pub mod haptics;
+pub mod input;
+pub mod ir;
+pub mod joystick;
+pub mod onewire;
+pub mod power;
+pub mod power_budget;
+pub mod pulser;
+pub mod schedule;
+pub mod slider;
+pub mod sound_level;
+pub mod standalone;
+pub mod subscriptions;
+pub mod tick;
+pub mod unlock;
@@ -0,0 +1,63 @@
+// Log macros which forward to defmt, for developers with a debug probe attached. Without the
+// "logging" feature they compile to nothing, so they can be used anywhere. They are exported for
+// the firmware crate, which has to depend on defmt itself and provide the global logger.
+
+#[cfg(feature = "logging")]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => { defmt::trace!($($arg)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {{}};
+}
+
+#[cfg(feature = "logging")]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => { defmt::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {{}};
+}
+
+#[cfg(feature = "logging")]
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => { defmt::info!($($arg)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {{}};
+}
+
+#[cfg(feature = "logging")]
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => { defmt::warn!($($arg)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {{}};
+}
+
+#[cfg(feature = "logging")]
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => { defmt::error!($($arg)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {{}};
+}
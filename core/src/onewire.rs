@@ -0,0 +1,102 @@
+use crate::crc::crc8;
+
+// The bus side of 1-Wire: resets, bytes, and finding the ROM code of every device on the bus with
+// the search algorithm from Maxim's application note 187. The timing of each slot is left to the
+// platform, which implements `OneWire` on an open drain pin.
+// https://www.analog.com/en/resources/app-notes/1wire-search-algorithm.html
+
+const SEARCH_ROM: u8 = 0xF0;
+const MATCH_ROM: u8 = 0x55;
+const SKIP_ROM: u8 = 0xCC;
+
+/// Time slots on a 1-Wire bus.
+pub trait OneWire {
+    /// Sends a reset pulse, returning whether any device answered it.
+    fn reset(&mut self) -> bool;
+
+    fn write_bit(&mut self, bit: bool);
+
+    /// Reads a bit, which any device on the bus can pull to 0.
+    fn read_bit(&mut self) -> bool;
+}
+
+/// Bytes go least significant bit first.
+pub fn write_byte<B: OneWire>(bus: &mut B, byte: u8) {
+    for bit in 0..8 {
+        bus.write_bit(byte & (1 << bit) != 0);
+    }
+}
+
+pub fn read_byte<B: OneWire>(bus: &mut B) -> u8 {
+    (0..8).fold(0, |byte, bit| byte | (bus.read_bit() as u8) << bit)
+}
+
+/// Resets the bus and addresses the device with `rom`, or every device if it's `None`. Returns
+/// false if nothing is on the bus.
+pub fn select<B: OneWire>(bus: &mut B, rom: Option<u64>) -> bool {
+    if !bus.reset() {
+        return false;
+    }
+
+    match rom {
+        Some(rom) => {
+            write_byte(bus, MATCH_ROM);
+            for &byte in &rom.to_le_bytes() {
+                write_byte(bus, byte);
+            }
+        },
+        None => write_byte(bus, SKIP_ROM),
+    }
+
+    true
+}
+
+/// Finds the devices on the bus, passing the ROM code of each to `found` until it returns false.
+/// Each code holds the family in its low byte, and its CRC in its high byte.
+pub fn search<B: OneWire>(bus: &mut B, mut found: impl FnMut(u64) -> bool) {
+    let mut rom = 0u64;
+    // The last bit, counted from 1, at which devices differed and the 0 branch was taken.
+    let mut last_discrepancy = 0;
+
+    loop {
+        if !bus.reset() {
+            return;
+        }
+        write_byte(bus, SEARCH_ROM);
+
+        let mut last_zero = 0;
+        for bit in 1..=64 {
+            // Every device sends its bit of the ROM code, then the complement of it.
+            let (id, complement) = (bus.read_bit(), bus.read_bit());
+            let take = match (id, complement) {
+                // Nothing answered, as a device left the bus or the line is stuck.
+                (true, true) => return,
+                (id, complement) if id != complement => id,
+                // Some devices have a 0 here and others a 1. Follow the previous pass up to its
+                // last 0 branch, then take the 1 branch there and 0 branches after it.
+                _ if bit < last_discrepancy => rom & (1 << (bit - 1)) != 0,
+                _ => bit == last_discrepancy,
+            };
+            if id == complement && !take {
+                last_zero = bit;
+            }
+
+            if take {
+                rom |= 1 << (bit - 1);
+            } else {
+                rom &= !(1 << (bit - 1));
+            }
+            // Devices whose bit differs drop out until the next reset.
+            bus.write_bit(take);
+        }
+
+        if crc8(0, &rom.to_le_bytes()) == 0 && !found(rom) {
+            return;
+        }
+
+        last_discrepancy = last_zero;
+        if last_discrepancy == 0 {
+            return;
+        }
+    }
+}
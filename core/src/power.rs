@@ -0,0 +1,170 @@
+use crate::{
+    fraction::Fraction,
+    tick::{Duration, Instant},
+};
+
+/// How awake the panel is. It dims once it's been left alone for a while, and goes to sleep if
+/// nobody touches it and the host isn't there either.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum PowerState {
+    Active,
+    /// The LED strip is dimmed, the lights are left as the host set them.
+    Dimmed,
+    /// The LED strip and the lights are off, until the panel is touched or woken by the host.
+    Sleep,
+}
+
+impl PowerState {
+    /// The fraction of its normal brightness the LED strip should show.
+    pub fn strip_brightness(self) -> Fraction {
+        match self {
+            PowerState::Active => Fraction::ONE,
+            PowerState::Dimmed => Fraction::from_ratio(3, 10),
+            PowerState::Sleep => Fraction::ZERO,
+        }
+    }
+
+    /// The fraction of the brightness set by the host the lights should show.
+    pub fn light_brightness(self) -> Fraction {
+        match self {
+            PowerState::Active | PowerState::Dimmed => Fraction::ONE,
+            PowerState::Sleep => Fraction::ZERO,
+        }
+    }
+}
+
+/// What the panel does while the host has suspended the USB bus, such as when the computer it's
+/// plugged into is asleep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SuspendPolicy {
+    /// Carries on as though the host were awake.
+    Hold = 0,
+    /// Dims, as when it's left alone.
+    Dim = 1,
+    /// Sleeps, with everything off.
+    Off = 2,
+}
+
+impl SuspendPolicy {
+    pub const ALL: [SuspendPolicy; 3] =
+        [SuspendPolicy::Hold, SuspendPolicy::Dim, SuspendPolicy::Off];
+
+    pub fn from_u8(policy: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|&p| p as u8 == policy)
+    }
+}
+
+/// Decides the power state from input activity, whether the host is connected, and its
+/// commands. Everything which depends on the state should follow `poll()`.
+pub struct PowerManager {
+    state: PowerState,
+    /// `None` once the panel has been idle for long enough to sleep, so that the idle time can't
+    /// wrap around.
+    last_activity: Option<Instant>,
+    host_present: bool,
+    host_suspended: bool,
+    suspend_policy: SuspendPolicy,
+    cable_present: bool,
+    sleep_requested: bool,
+    dim_after: Duration,
+    sleep_after: Duration,
+}
+
+impl PowerManager {
+    pub fn new(dim_after_ms: u32, sleep_after_ms: u32) -> Self {
+        Self {
+            state: PowerState::Active,
+            last_activity: Some(Instant::now()),
+            host_present: false,
+            host_suspended: false,
+            suspend_policy: SuspendPolicy::Hold,
+            cable_present: true,
+            sleep_requested: false,
+            dim_after: Duration::from_ms(dim_after_ms),
+            sleep_after: Duration::from_ms(sleep_after_ms),
+        }
+    }
+
+    pub fn state(&self) -> PowerState {
+        self.state
+    }
+
+    /// The button was pressed or the dial turned, which wakes the panel.
+    pub fn input_activity(&mut self) {
+        self.last_activity = Some(Instant::now());
+        self.sleep_requested = false;
+    }
+
+    /// The panel doesn't sleep while the host is connected, and wakes up when it connects.
+    pub fn set_host_present(&mut self, present: bool) {
+        if present && !self.host_present {
+            self.input_activity();
+        }
+        self.host_present = present;
+    }
+
+    /// While the host has the bus suspended, it counts as present and the panel follows the
+    /// suspend policy. It wakes up when the host resumes.
+    pub fn set_host_suspended(&mut self, suspended: bool) {
+        if !suspended && self.host_suspended {
+            self.input_activity();
+        }
+        self.host_suspended = suspended;
+    }
+
+    pub fn set_suspend_policy(&mut self, policy: SuspendPolicy) {
+        self.suspend_policy = policy;
+    }
+
+    /// Whether a USB cable is plugged in, on boards which sense VBUS. Without one there's no host
+    /// which could be asleep, so the panel is being used on its own, like a wall dimmer. It then
+    /// only dims when left alone, rather than turning the lights off.
+    pub fn set_cable_present(&mut self, present: bool) {
+        self.cable_present = present;
+    }
+
+    /// Sends the panel to sleep straight away, until it's woken.
+    pub fn sleep(&mut self) {
+        self.sleep_requested = true;
+    }
+
+    pub fn wake(&mut self) {
+        self.input_activity();
+    }
+
+    /// Returns the new state if it has changed since the last poll.
+    pub fn poll(&mut self) -> Option<PowerState> {
+        if let Some(last_activity) = self.last_activity {
+            if last_activity.elapsed() >= self.sleep_after {
+                self.last_activity = None;
+            }
+        }
+
+        let host_present = self.host_present || self.host_suspended;
+        let state = if self.sleep_requested {
+            PowerState::Sleep
+        } else {
+            match self.last_activity.map(|last_activity| last_activity.elapsed()) {
+                Some(idle) if idle < self.dim_after => PowerState::Active,
+                Some(_) => PowerState::Dimmed,
+                None if host_present || !self.cable_present => PowerState::Dimmed,
+                None => PowerState::Sleep,
+            }
+        };
+
+        let state = match (self.host_suspended, self.suspend_policy, state) {
+            (true, SuspendPolicy::Dim, PowerState::Active) => PowerState::Dimmed,
+            (true, SuspendPolicy::Off, _) => PowerState::Sleep,
+            _ => state,
+        };
+
+        if state == self.state {
+            return None;
+        }
+
+        debug!("power state: {}", state);
+        self.state = state;
+        Some(state)
+    }
+}
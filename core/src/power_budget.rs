@@ -0,0 +1,41 @@
+use crate::fraction::Fraction;
+
+/// The brightness limit, in 65536ths, when the strip isn't limited at all.
+const FULL: u32 = 1 << 16;
+
+/// While the rail draws less than this fraction of its budget, the limit is raised again by
+/// `RECOVERY_STEP` per measurement. The gap keeps the limit from hunting around the budget.
+const HEADROOM_PERCENT: u32 = 90;
+const RECOVERY_STEP: u32 = FULL / 16;
+
+/// Holds the LED strip back while the rail which powers it draws more than its budget, as
+/// measured by a current monitor on the rail, and lets it brighten again once there's headroom.
+pub struct PowerBudget {
+    budget_ma: u32,
+    limit: u32,
+}
+
+impl PowerBudget {
+    pub const fn new(budget_ma: u16) -> Self {
+        Self { budget_ma: budget_ma as u32, limit: FULL }
+    }
+
+    /// The fraction of its brightness the strip is limited to.
+    pub fn scale(&self) -> Fraction {
+        Fraction::from_ratio(self.limit, FULL)
+    }
+
+    /// Takes a measurement of the rail, which was drawing `current_ma` with the strip at
+    /// `scale()`.
+    pub fn measure(&mut self, current_ma: u16) {
+        let current_ma = current_ma as u32;
+
+        if current_ma > self.budget_ma {
+            // The strip's current is roughly in proportion to its brightness, and whatever else
+            // is on the rail is brought in by the next measurements.
+            self.limit = (self.limit as u64 * self.budget_ma as u64 / current_ma as u64) as u32;
+        } else if current_ma * 100 < self.budget_ma * HEADROOM_PERCENT {
+            self.limit = (self.limit + RECOVERY_STEP).min(FULL);
+        }
+    }
+}
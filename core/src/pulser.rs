@@ -0,0 +1,139 @@
+use crate::{fraction::Fraction, tick::Instant};
+
+/// Angles are in units of 1 / 2^16 of a turn. 2π in millionths, for converting from
+/// milliseconds to angles without floating point math.
+const TURN_MICRORADIANS: u64 = 6_283_185;
+
+/// sin(x) for x from 0 to π/2 in 64 steps, multiplied by 2^15.
+const QUARTER_SINE: [i32; 65] = [
+    0, 804, 1608, 2411, 3212, 4011, 4808, 5602, 6393, 7180, 7962, 8740, 9512, 10279, 11039, 11793,
+    12540, 13279, 14010, 14733, 15447, 16151, 16846, 17531, 18205, 18868, 19520, 20160, 20788,
+    21403, 22006, 22595, 23170, 23732, 24279, 24812, 25330, 25833, 26320, 26791, 27246, 27684,
+    28106, 28511, 28899, 29269, 29622, 29957, 30274, 30572, 30853, 31114, 31357, 31581, 31786,
+    31972, 32138, 32286, 32413, 32522, 32610, 32679, 32729, 32758, 32768,
+];
+
+/// One turn of the sine wave, in the units of the angles.
+const TURN: u32 = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum Waveform {
+    /// A sine wave, of which every second pulse is skipped.
+    SkipAlternate,
+    /// A plain sine wave.
+    Sine,
+}
+
+impl Waveform {
+    /// How many turns of the sine wave there are before the waveform repeats.
+    fn turns(self) -> u32 {
+        match self {
+            Waveform::SkipAlternate => 2,
+            Waveform::Sine => 1,
+        }
+    }
+}
+
+pub struct Pulser {
+    start: Instant,
+    /// How far into the pattern it was at `start`.
+    start_angle: u32,
+    interval_ms: u32,
+    waveform: Waveform,
+    /// The length of one full pulse pattern, after which it repeats.
+    pattern_ms: u32,
+}
+
+impl Pulser {
+    pub fn new(interval_ms: u32) -> Self {
+        let waveform = Waveform::SkipAlternate;
+        debug!("pulser interval: {} ms", interval_ms);
+
+        Self {
+            start: Instant::now(),
+            start_angle: 0,
+            interval_ms,
+            waveform,
+            pattern_ms: pattern_ms(interval_ms, waveform),
+        }
+    }
+
+    /// Switches to a new interval, keeping the waveform.
+    pub fn set_interval(&mut self, interval_ms: u32) {
+        self.reconfigure(interval_ms, self.waveform);
+    }
+
+    /// Switches to a new interval and waveform, carrying on from the same intensity rather than
+    /// starting over.
+    pub fn reconfigure(&mut self, interval_ms: u32, waveform: Waveform) {
+        let angle = self.angle();
+        let start_angle = match (self.waveform, waveform) {
+            // The skipped pulse is flat at the bottom of the wave, which a plain sine only
+            // touches once, so it picks up from there.
+            (Waveform::SkipAlternate, Waveform::Sine) if (0xC000..0x1_C000).contains(&angle) => {
+                0xC000
+            },
+            // Rising from the bottom of the wave, which is the end of the skipped pulse.
+            (Waveform::Sine, Waveform::SkipAlternate) if angle >= 0xC000 => angle + TURN,
+            _ => angle % (waveform.turns() * TURN),
+        };
+        debug!("pulser interval: {} ms, waveform: {}", interval_ms, waveform);
+
+        self.start = Instant::now();
+        self.start_angle = start_angle;
+        self.interval_ms = interval_ms;
+        self.waveform = waveform;
+        self.pattern_ms = pattern_ms(interval_ms, waveform);
+    }
+
+    /// A sine wave with a period of 2π intervals, of which every second pulse may be skipped.
+    pub fn intensity(&mut self) -> Fraction {
+        let angle = self.angle();
+
+        // The second pulse is skipped from its peak to the one after it, where the sine wave is
+        // at its lowest, which is from 3/4 of the first turn to 3/4 of the second.
+        if self.waveform == Waveform::SkipAlternate && (0xC000..0x1_C000).contains(&angle) {
+            return Fraction::ZERO;
+        }
+
+        let pulse = (sin(angle as u16) + (1 << 15)) / 2;
+        Fraction::from_raw(pulse as u16)
+    }
+
+    /// How far into the pattern it is now.
+    fn angle(&self) -> u32 {
+        // Work within one repetition of the pattern, so that the conversion can't overflow.
+        let elapsed_ms = self.start.elapsed().as_ms() % self.pattern_ms;
+        let elapsed = (elapsed_ms as u64 * TURN as u64 * 1_000_000
+            / (self.interval_ms as u64 * TURN_MICRORADIANS)) as u32;
+
+        (self.start_angle + elapsed) % (self.waveform.turns() * TURN)
+    }
+}
+
+fn pattern_ms(interval_ms: u32, waveform: Waveform) -> u32 {
+    (interval_ms as u64 * waveform.turns() as u64 * TURN_MICRORADIANS / 1_000_000) as u32
+}
+
+/// sin(angle), multiplied by 2^15.
+fn sin(angle: u16) -> i32 {
+    let quadrant = angle >> 14;
+    let within = (angle & 0x3FFF) as i32;
+
+    // Mirror the quarter wave into the second and fourth quadrants.
+    let within = if quadrant % 2 == 1 { 0x4000 - within } else { within };
+
+    // Interpolate between the two nearest entries of the table.
+    let index = (within >> 8) as usize;
+    let step = within & 0xFF;
+    let low = QUARTER_SINE[index];
+    let high = QUARTER_SINE[(index + 1).min(64)];
+    let value = low + (((high - low) * step) >> 8);
+
+    if quadrant >= 2 {
+        -value
+    } else {
+        value
+    }
+}
@@ -0,0 +1,80 @@
+// Daily changes to the lights, which the panel follows from its wall clock whether or not the
+// host is there, e.g. dimming to a warm light at 19:00. The host sets the entries, and they're
+// saved with the settings.
+
+pub const MAX_ENTRIES: usize = 4;
+pub const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// The local time of day at `unix_seconds`, in minutes after midnight.
+pub fn minute_of_day(unix_seconds: u32, utc_offset_minutes: i16) -> u16 {
+    let local_minutes = unix_seconds as i64 / 60 + utc_offset_minutes as i64;
+    local_minutes.rem_euclid(MINUTES_PER_DAY as i64) as u16
+}
+
+/// What the lights are set to, and when.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduleEntry {
+    /// Minutes after local midnight.
+    pub minute_of_day: u16,
+    pub brightness: u16,
+    pub color_temperature: u16,
+}
+
+pub struct Schedule {
+    entries: [Option<ScheduleEntry>; MAX_ENTRIES],
+    /// The time of day at the last poll.
+    last_minute: Option<u16>,
+}
+
+impl Schedule {
+    pub const fn new() -> Self {
+        Self { entries: [None; MAX_ENTRIES], last_minute: None }
+    }
+
+    pub fn get(&self, index: usize) -> Option<ScheduleEntry> {
+        self.entries.get(index).copied().flatten()
+    }
+
+    /// Sets or clears entry `index`. Returns false if there's no such entry.
+    pub fn set(&mut self, index: usize, entry: Option<ScheduleEntry>) -> bool {
+        match self.entries.get_mut(index) {
+            Some(slot) => {
+                *slot = entry;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Takes the local time of day, and returns the entry whose time has come since the last
+    /// poll, if any. The first poll returns the latest entry before now, so that after a reset
+    /// the lights are where the schedule would have left them.
+    pub fn poll(&mut self, minute_of_day: u16) -> Option<ScheduleEntry> {
+        let last_minute = self.last_minute.replace(minute_of_day);
+        // Entries from just after the last poll up to now are due, or every entry at first.
+        let window = match last_minute {
+            Some(last_minute) => minutes_between(last_minute, minute_of_day),
+            None => MINUTES_PER_DAY,
+        };
+
+        // If more than one is due, the latest wins.
+        self.entries
+            .iter()
+            .flatten()
+            .map(|entry| (minutes_between(entry.minute_of_day, minute_of_day), entry))
+            .filter(|&(age, _)| age < window)
+            .min_by_key(|&(age, _)| age)
+            .map(|(_, entry)| *entry)
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Minutes from `earlier` to `later`, going past midnight if need be.
+fn minutes_between(earlier: u16, later: u16) -> u16 {
+    (later % MINUTES_PER_DAY + MINUTES_PER_DAY - earlier % MINUTES_PER_DAY) % MINUTES_PER_DAY
+}
@@ -0,0 +1,63 @@
+/// The largest reading of the 12 bit ADC.
+const MAX_READING: u32 = 0xfff;
+
+/// Readings this close to either end of the track count as the end, as a slider which is pushed
+/// all the way rarely reads exactly 0 or the maximum.
+const END_READINGS: u32 = 16;
+
+/// Readings are smoothed over roughly this many, to filter out noise on the ADC.
+const SMOOTHING: u32 = 4;
+
+/// Smaller changes in position aren't reported, so that a slider left alone stays quiet.
+const THRESHOLD: u16 = u16::MAX / 256;
+
+/// Turns ADC readings of an analog slider or potentiometer into positions for the host, from 0
+/// at one end of its track to `u16::MAX` at the other.
+pub struct Slider {
+    /// The smoothed position, times `SMOOTHING`.
+    smoothed: Option<u32>,
+    /// The position most recently returned by `sample()`.
+    reported: Option<u16>,
+}
+
+impl Slider {
+    pub const fn new() -> Self {
+        Self { smoothed: None, reported: None }
+    }
+
+    /// Takes a 12 bit reading, and returns the new position if it has moved far enough to
+    /// report.
+    pub fn sample(&mut self, reading: u16) -> Option<u16> {
+        let track = MAX_READING - 2 * END_READINGS;
+        let position =
+            (reading as u32).saturating_sub(END_READINGS).min(track) * u16::MAX as u32 / track;
+
+        let smoothed = match self.smoothed {
+            Some(smoothed) => smoothed - smoothed / SMOOTHING + position,
+            None => position * SMOOTHING,
+        };
+        self.smoothed = Some(smoothed);
+        let position = (smoothed / SMOOTHING) as u16;
+
+        let moved = match self.reported {
+            None => true,
+            Some(reported) => {
+                let at_end = position == 0 || position == u16::MAX;
+                let difference = (position as i32 - reported as i32).unsigned_abs() as u16;
+                difference > THRESHOLD || (at_end && position != reported)
+            },
+        };
+        if !moved {
+            return None;
+        }
+
+        self.reported = Some(position);
+        Some(position)
+    }
+}
+
+impl Default for Slider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
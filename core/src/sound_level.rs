@@ -0,0 +1,72 @@
+use crate::fraction::Fraction;
+
+// An LED effect which follows how loud the room is, from a microphone whose envelope detector
+// gives a voltage that rises with the sound level, read by the ADC. The host turns it on and
+// sets how sensitive and how smooth it is.
+
+/// The largest reading of the 12 bit ADC.
+const MAX_READING: u32 = 0xfff;
+
+/// The smoothed level is kept with this many more bits than a reading, so that slow changes
+/// aren't lost to rounding.
+const LEVEL_SHIFT: u32 = 8;
+
+/// Sensitivity is a gain in 256ths, so that the default of 256 shows a full scale reading at
+/// full brightness.
+pub const UNITY_SENSITIVITY: u16 = 256;
+
+/// Each reading moves the level by 1/2^smoothing of the way to it, so this is roughly the
+/// log2 of how many readings it follows.
+pub const MAX_SMOOTHING: u8 = 8;
+
+pub struct SoundLevel {
+    enabled: bool,
+    sensitivity: u16,
+    smoothing: u8,
+    /// The smoothed reading, shifted up by `LEVEL_SHIFT`.
+    level: u32,
+}
+
+impl SoundLevel {
+    pub const fn new() -> Self {
+        Self { enabled: false, sensitivity: UNITY_SENSITIVITY, smoothing: 3, level: 0 }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turns the effect on or off. `smoothing` is capped at `MAX_SMOOTHING`.
+    pub fn configure(&mut self, enabled: bool, sensitivity: u16, smoothing: u8) {
+        self.enabled = enabled;
+        self.sensitivity = sensitivity;
+        self.smoothing = smoothing.min(MAX_SMOOTHING);
+    }
+
+    /// Takes a 12 bit reading of the envelope.
+    pub fn sample(&mut self, reading: u16) {
+        let target = (reading as u32).min(MAX_READING) << LEVEL_SHIFT;
+        if target > self.level {
+            self.level += (target - self.level) >> self.smoothing;
+        } else {
+            self.level -= (self.level - target) >> self.smoothing;
+        }
+    }
+
+    /// How bright the LEDs should be for the current level, or full brightness while the
+    /// effect is off.
+    pub fn intensity(&self) -> Fraction {
+        if !self.enabled {
+            return Fraction::ONE;
+        }
+
+        let level = self.level as u64 * self.sensitivity as u64 / UNITY_SENSITIVITY as u64;
+        Fraction::from_ratio(level.min(u32::MAX as u64) as u32, MAX_READING << LEVEL_SHIFT)
+    }
+}
+
+impl Default for SoundLevel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
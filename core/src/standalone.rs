@@ -0,0 +1,131 @@
+use crate::{
+    button::ButtonEvent,
+    input::InputEvent,
+    tick::{Duration, Instant},
+};
+
+/// How much one step of the dial changes the brightness of the lights.
+const DIAL_STEP: u16 = u16::MAX / 32;
+
+/// Lets the panel be used on its own if no host opens the serial port for a while after boot or
+/// after the host goes away: the dial dims the lights, and the button turns them on and off. It
+/// ends as soon as a host connects, which takes over the lights again. A host which sends
+/// heartbeats also counts as gone once they stop, even with the port still open.
+pub struct Standalone {
+    after: Duration,
+    /// When the host was last connected, or when the panel booted.
+    last_connected: Instant,
+    /// When the host last sent a heartbeat, and how long it has until the next one, if it sends
+    /// them.
+    heartbeat: Option<(Instant, Duration)>,
+    cable_present: bool,
+    active: bool,
+    lights_on: bool,
+    brightness: u16,
+}
+
+impl Standalone {
+    /// Starts standalone mode once no host has been connected for `after_ms`, with the lights
+    /// at `brightness`.
+    pub fn new(after_ms: u32, brightness: u16) -> Self {
+        Self {
+            after: Duration::from_ms(after_ms),
+            last_connected: Instant::now(),
+            heartbeat: None,
+            cable_present: true,
+            active: false,
+            lights_on: true,
+            brightness,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// The brightness the lights should be set to while standalone.
+    pub fn light_brightness(&self) -> u16 {
+        if self.lights_on {
+            self.brightness
+        } else {
+            0
+        }
+    }
+
+    /// Whether a USB cable is plugged in, on boards which sense VBUS. Without one no host can
+    /// connect, so standalone mode starts without waiting for it.
+    pub fn set_cable_present(&mut self, present: bool) {
+        self.cable_present = present;
+    }
+
+    /// The host promises another heartbeat within `timeout_ms`, or none at all from now on if
+    /// that's 0.
+    pub fn heartbeat(&mut self, timeout_ms: u32) {
+        self.heartbeat = match timeout_ms {
+            0 => None,
+            _ => Some((Instant::now(), Duration::from_ms(timeout_ms))),
+        };
+    }
+
+    /// Returns whether standalone mode is now active, if that has changed since the last poll.
+    pub fn poll(&mut self, host_connected: bool) -> Option<bool> {
+        // A host which stops sending heartbeats has crashed or hung rather than not connected
+        // yet, so there's no waiting for it.
+        let heartbeat_missed =
+            self.heartbeat.is_some_and(|(last, timeout)| last.elapsed() >= timeout);
+
+        if host_connected && !heartbeat_missed {
+            self.last_connected = Instant::now();
+            if !self.active {
+                return None;
+            }
+
+            info!("host connected, leaving standalone mode");
+            self.active = false;
+            return Some(false);
+        }
+
+        // The next host to connect might not send them.
+        if !host_connected {
+            self.heartbeat = None;
+        }
+
+        let waiting = self.cable_present && self.last_connected.elapsed() < self.after;
+        if self.active || (waiting && !heartbeat_missed) {
+            return None;
+        }
+
+        if heartbeat_missed {
+            warn!("host stopped sending heartbeats, entering standalone mode");
+        } else {
+            info!("no host, entering standalone mode");
+        }
+        self.active = true;
+        self.lights_on = true;
+        Some(true)
+    }
+
+    /// Adjusts the lights for an input event. Returns the brightness the lights should be set
+    /// to, if it has changed. Events are ignored unless standalone mode is active.
+    pub fn handle(&mut self, event: &InputEvent) -> Option<u16> {
+        if !self.active {
+            return None;
+        }
+
+        match *event {
+            InputEvent::Button(ButtonEvent::ShortRelease) => self.lights_on = !self.lights_on,
+            InputEvent::Dial(diff) => {
+                let step = DIAL_STEP.saturating_mul(diff.unsigned_abs() as u16);
+                self.brightness = if diff > 0 {
+                    self.brightness.saturating_add(step)
+                } else {
+                    self.brightness.saturating_sub(step)
+                };
+                self.lights_on = true;
+            },
+            _ => return None,
+        }
+
+        Some(self.light_brightness())
+    }
+}
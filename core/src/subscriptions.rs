@@ -0,0 +1,99 @@
+use panel_protocol::Report;
+
+// Which of the reports the panel sends on its own the host wants, so that one which only cares
+// about the dial isn't sent every button press, reading and debug message just to throw them
+// away. Answers to the host's own commands, and rare events such as the door opening, are always
+// sent, so a host can't end up waiting on a report it unsubscribed from.
+
+/// A kind of report the host can subscribe to, as sent in `Command::Subscribe` and
+/// `Command::Unsubscribe`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Category {
+    /// Presses of the dial's button, the expander's buttons, the joystick and the IR remote.
+    Button = 0,
+    Dial = 1,
+    /// Readings which are sent as they change or every so often, such as ambient light, the fan
+    /// and the sliders.
+    Telemetry = 2,
+    /// Debug messages, what the panel found at boot, and counts of dropped reports, commands and
+    /// frames.
+    Diagnostics = 3,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] =
+        [Category::Button, Category::Dial, Category::Telemetry, Category::Diagnostics];
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|&category| category as u8 == value)
+    }
+
+    /// The category of `report`, or `None` if it's always sent.
+    pub fn of(report: &Report) -> Option<Self> {
+        match report {
+            Report::Press
+            | Report::LongPress
+            | Report::ExpanderButton { .. }
+            | Report::Nudge { .. }
+            | Report::IrCode { .. } => Some(Category::Button),
+            Report::DialValue { .. } | Report::DialTurned { .. } | Report::DialHomed => {
+                Some(Category::Dial)
+            },
+            Report::AmbientLight { .. }
+            | Report::FixtureTemperature { .. }
+            | Report::Fan { .. }
+            | Report::LedRail { .. }
+            | Report::CpuLoad { .. }
+            | Report::SliderValue { .. }
+            | Report::ColorTemperatureSetpoint { .. } => Some(Category::Telemetry),
+            Report::Debug { .. }
+            | Report::DeviceInfo { .. }
+            | Report::BootDiagnostics { .. }
+            | Report::ReportsDropped { .. }
+            | Report::ReceiveOverflow { .. }
+            | Report::CorruptFrames { .. } => Some(Category::Diagnostics),
+            _ => None,
+        }
+    }
+}
+
+/// The categories the host is subscribed to, one bit each.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Subscriptions {
+    bits: u8,
+}
+
+impl Subscriptions {
+    /// Subscribed to everything, as a host which has just connected is, so that hosts which
+    /// don't know about subscriptions are sent what they always were.
+    pub const ALL: Subscriptions = Subscriptions { bits: 0b1111 };
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self { bits: bits & Self::ALL.bits }
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    pub fn set(&mut self, category: Category, subscribed: bool) {
+        if subscribed {
+            self.bits |= 1 << category as u8;
+        } else {
+            self.bits &= !(1 << category as u8);
+        }
+    }
+
+    pub fn is_subscribed(&self, category: Category) -> bool {
+        self.bits & (1 << category as u8) != 0
+    }
+
+    /// Whether `report` should be sent to the host.
+    pub fn wants(&self, report: &Report) -> bool {
+        match Category::of(report) {
+            Some(category) => self.is_subscribed(category),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Milliseconds since boot, incremented by the SysTick interrupt.
+static MILLIS: AtomicU32 = AtomicU32::new(0);
+
+/// Advances the clock by one millisecond. Only the SysTick interrupt should call this.
+pub fn increment() {
+    advance(1);
+}
+
+/// Advances the clock by `ms` milliseconds at once. This is for tests, which would otherwise
+/// have to tick through hours to get to where the clock wraps.
+pub fn advance(ms: u32) {
+    MILLIS.fetch_add(ms, Ordering::Relaxed);
+}
+
+/// The time since boot, which wraps along with the clock.
+pub fn uptime() -> Duration {
+    Duration { millis: MILLIS.load(Ordering::Relaxed) }
+}
+
+/// A point in time, with millisecond resolution. The clock wraps every ~49 days, so instants
+/// can't be ordered, only the durations between them, which are correct as long as they're
+/// shorter than that.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instant {
+    millis: u32,
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Self { millis: MILLIS.load(Ordering::Relaxed) }
+    }
+
+    /// The time from `earlier` to this instant.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration { millis: self.millis.wrapping_sub(earlier.millis) }
+    }
+
+    /// The time since this instant.
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+}
+
+/// The time between two instants, with millisecond resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Duration {
+    millis: u32,
+}
+
+impl Duration {
+    pub const fn from_ms(millis: u32) -> Self {
+        Self { millis }
+    }
+
+    pub const fn as_ms(&self) -> u32 {
+        self.millis
+    }
+}
+
+/// Runs a task every `period_ms` milliseconds, when polled from the tick.
+pub struct Every {
+    period_ms: u32,
+    next_millis: u32,
+}
+
+impl Every {
+    pub const fn new(period_ms: u32) -> Self {
+        Self { period_ms, next_millis: 0 }
+    }
+
+    /// Returns true if the task is due to run at `now`, and schedules the next run.
+    pub fn is_due(&mut self, now: Instant) -> bool {
+        // Compare in wrapping arithmetic so that this keeps working when the clock wraps.
+        if (now.millis.wrapping_sub(self.next_millis) as i32) < 0 {
+            return false;
+        }
+
+        self.next_millis = self.next_millis.wrapping_add(self.period_ms);
+        true
+    }
+}
@@ -0,0 +1,35 @@
+use crate::tick::{Duration, Instant};
+
+// Commands which could brick or wipe a panel, such as rebooting into the bootloader or
+// rewriting the option bytes, only run shortly after the host unlocks them with a key, so that
+// a buggy host can't send one by accident. Each unlock lets one of them through.
+
+/// How long an unlock lasts.
+pub const WINDOW: Duration = Duration::from_ms(2_000);
+
+pub struct Unlock {
+    key: u32,
+    unlocked_at: Option<Instant>,
+}
+
+impl Unlock {
+    pub const fn new(key: u32) -> Self {
+        Self { key, unlocked_at: None }
+    }
+
+    /// Returns false, and stays locked, if `key` is wrong.
+    pub fn unlock(&mut self, key: u32) -> bool {
+        if key != self.key {
+            self.unlocked_at = None;
+            return false;
+        }
+
+        self.unlocked_at = Some(Instant::now());
+        true
+    }
+
+    /// Whether a destructive command may run now. Locks again either way.
+    pub fn take(&mut self) -> bool {
+        self.unlocked_at.take().is_some_and(|unlocked_at| unlocked_at.elapsed() <= WINDOW)
+    }
+}
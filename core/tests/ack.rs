@@ -0,0 +1,20 @@
+use panel_core::ack::{self, Rejection};
+use panel_protocol::{Command, Report};
+
+#[test]
+fn identifies_commands_by_kind() {
+    let dim = ack::command_id(&Command::Brightness { target: 0, value: 0 });
+    let bright = ack::command_id(&Command::Brightness { target: 1, value: 0xffff });
+    let led = ack::command_id(&Command::Led { r: 1, g: 2, b: 3, pulse: false });
+
+    assert_eq!(dim, bright);
+    assert_ne!(dim, led);
+    assert_ne!(dim, ack::UNKNOWN_COMMAND);
+    assert_ne!(led, ack::UNKNOWN_COMMAND);
+
+    assert!(matches!(ack::report(led, Ok(())), Report::Ack { command } if command == led));
+    assert!(matches!(
+        ack::report(dim, Err(Rejection::InvalidTarget)),
+        Report::Nack { command, reason: 2 } if command == dim
+    ));
+}
@@ -0,0 +1,54 @@
+use panel_core::auto_brightness::AutoBrightness;
+
+#[test]
+fn follows_the_ambient_light_within_the_range() {
+    let mut auto = AutoBrightness::new();
+    auto.measure(100);
+    assert_eq!(auto.poll(), None);
+
+    auto.enable(10_000, 60_000);
+    assert_eq!(auto.poll(), Some(20_000));
+    assert_eq!(auto.poll(), None);
+
+    // It takes a few measurements to follow a change.
+    auto.measure(500);
+    let first = auto.poll().unwrap();
+    assert!(first > 20_000 && first < 60_000);
+    let mut brightness = first;
+    for _ in 0..100 {
+        auto.measure(500);
+        brightness = auto.poll().unwrap_or(brightness);
+    }
+    assert_eq!(brightness, 60_000);
+    auto.measure(5000);
+    assert_eq!(auto.poll(), None);
+
+    // Small changes are held back.
+    for _ in 0..100 {
+        auto.measure(0);
+        brightness = auto.poll().unwrap_or(brightness);
+    }
+    assert_eq!(brightness, 10_000);
+    auto.measure(2);
+    assert_eq!(auto.poll(), None);
+}
+
+#[test]
+fn override_holds_until_cleared() {
+    let mut auto = AutoBrightness::new();
+    auto.enable(0, 50_000);
+    auto.measure(0);
+    assert_eq!(auto.poll(), Some(0));
+
+    auto.set_override(Some(40_000));
+    assert_eq!(auto.poll(), Some(40_000));
+    auto.measure(500);
+    assert_eq!(auto.poll(), None);
+
+    auto.set_override(None);
+    assert!(auto.poll().is_some());
+
+    auto.disable();
+    auto.measure(500);
+    assert_eq!(auto.poll(), None);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{advance_ms, FakePin};
+use panel_core::button::{Active, Button, ButtonEvent, Debouncer};
+use std::cell::Cell;
+
+#[test]
+fn debouncer_ignores_glitches() {
+    let level = Cell::new(true);
+    let mut debouncer = Debouncer::new(FakePin(&level), Active::Low, 30, 1000);
+
+    level.set(false);
+    for _ in 0..10 {
+        debouncer.poll();
+    }
+    assert!(!debouncer.is_pressed());
+
+    level.set(true);
+    for _ in 0..10 {
+        debouncer.poll();
+    }
+    assert!(!debouncer.is_pressed());
+
+    level.set(false);
+    for _ in 0..29 {
+        debouncer.poll();
+    }
+    assert!(!debouncer.is_pressed());
+    debouncer.poll();
+    assert!(debouncer.is_pressed());
+}
+
+#[test]
+fn debounce_time_changes_without_glitching() {
+    let level = Cell::new(false);
+    let mut debouncer = Debouncer::new(FakePin(&level), Active::Low, 30, 1000);
+    for _ in 0..30 {
+        debouncer.poll();
+    }
+    assert!(debouncer.is_pressed());
+
+    // Held down, it stays down, and a release now takes the new time.
+    debouncer.set_debounce_time(5);
+    debouncer.poll();
+    assert!(debouncer.is_pressed());
+
+    level.set(true);
+    for _ in 0..4 {
+        debouncer.poll();
+    }
+    assert!(debouncer.is_pressed());
+    debouncer.poll();
+    assert!(!debouncer.is_pressed());
+}
+
+#[test]
+fn button_events() {
+    let level = Cell::new(true);
+    // A single sample is enough to change the debounced state.
+    let debouncer = Debouncer::new(FakePin(&level), Active::Low, 1, 1000);
+    let mut button = Button::new(debouncer, 1000);
+
+    assert_eq!(button.poll(), None);
+
+    level.set(false);
+    assert_eq!(button.poll(), Some(ButtonEvent::Pressed));
+    assert_eq!(button.poll(), None);
+
+    level.set(true);
+    assert_eq!(button.poll(), Some(ButtonEvent::ShortRelease));
+
+    level.set(false);
+    assert_eq!(button.poll(), Some(ButtonEvent::Pressed));
+    advance_ms(1000);
+    assert_eq!(button.poll(), None);
+    advance_ms(1);
+    assert_eq!(button.poll(), Some(ButtonEvent::LongPress));
+    advance_ms(1000);
+    assert_eq!(button.poll(), None);
+
+    level.set(true);
+    assert_eq!(button.poll(), Some(ButtonEvent::LongRelease));
+    assert_eq!(button.poll(), None);
+}
+
+#[test]
+fn debounce_samples() {
+    // The number of samples it takes to register a press, for the debounce time and sample rate.
+    for &(debounce_time_ms, sample_frequency, samples) in
+        &[(30, 1000, 30), (30, 100, 3), (5, 300, 1)]
+    {
+        let level = Cell::new(true);
+        let mut debouncer =
+            Debouncer::new(FakePin(&level), Active::Low, debounce_time_ms, sample_frequency);
+
+        level.set(false);
+        for _ in 0..samples - 1 {
+            debouncer.poll();
+        }
+        assert!(!debouncer.is_pressed());
+        debouncer.poll();
+        assert!(debouncer.is_pressed());
+    }
+}
+
+#[test]
+fn debounce_times_shorter_than_a_sample_take_one() {
+    // As set with `Command::SetConfig { key: 0, value: 0 }`, and 4 ms for the expander's buttons,
+    // which are sampled at 200 Hz.
+    for &(debounce_time_ms, sample_frequency) in &[(0, 1000), (0, 200), (4, 200)] {
+        let level = Cell::new(true);
+        let mut debouncer = Debouncer::new(FakePin(&level), Active::Low, 30, sample_frequency);
+        debouncer.set_debounce_time(debounce_time_ms);
+        debouncer.poll();
+        assert!(!debouncer.is_pressed());
+
+        level.set(false);
+        debouncer.poll();
+        assert!(debouncer.is_pressed());
+
+        level.set(true);
+        debouncer.poll();
+        assert!(!debouncer.is_pressed());
+
+        let debouncer = Debouncer::new(FakePin(&level), Active::Low, debounce_time_ms, 1000);
+        assert!(!debouncer.is_pressed());
+    }
+}
@@ -0,0 +1,33 @@
+use panel_core::calendar::DateTime;
+
+fn date_time(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+    DateTime { year, month, day, hour, minute, second }
+}
+
+#[test]
+fn converts_both_ways() {
+    let cases = [
+        (0, date_time(1970, 1, 1, 0, 0, 0)),
+        (951_782_400, date_time(2000, 2, 29, 0, 0, 0)),
+        (1_700_000_000, date_time(2023, 11, 14, 22, 13, 20)),
+        (1_709_251_199, date_time(2024, 2, 29, 23, 59, 59)),
+        (4_102_444_800, date_time(2100, 1, 1, 0, 0, 0)),
+        (u32::MAX, date_time(2106, 2, 7, 6, 28, 15)),
+    ];
+
+    for &(unix_seconds, time) in &cases {
+        assert_eq!(DateTime::from_unix(unix_seconds), time);
+        assert_eq!(time.to_unix(), unix_seconds);
+    }
+}
+
+#[test]
+fn counts_every_day() {
+    let mut previous = DateTime::from_unix(0);
+    for day in 1..=u32::MAX / 86_400 {
+        let time = DateTime::from_unix(day * 86_400);
+        assert_eq!(time.to_unix(), day * 86_400);
+        assert!(time.day == previous.day + 1 || time.day == 1);
+        previous = time;
+    }
+}
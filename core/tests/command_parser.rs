@@ -0,0 +1,114 @@
+// The parser is fed whatever comes over the wire, so these check that it follows the host from
+// plain bytes to frames and LED streaming, and that no garbage leaves it unable to read the
+// commands sent after it. fuzz/ does the same with the garbage picked by a fuzzer.
+
+use panel_core::{
+    command_parser::{CommandParser, Parsed},
+    framing::{self, COMMANDS_FRAME, FRAMED_PROTOCOL_VERSION, LED_FRAME, MAX_FRAME_LEN},
+};
+use panel_protocol::Command;
+
+/// Feeds `bytes` to `parser`, and returns the commands and LED frames they complete, and how
+/// many commands were malformed.
+fn parse(parser: &mut CommandParser, mut bytes: &[u8]) -> (Vec<String>, Vec<Vec<u8>>, usize) {
+    let (mut commands, mut led_frames, mut errors) = (Vec::new(), Vec::new(), 0);
+    while !bytes.is_empty() {
+        match parser.push(&mut bytes, |command| commands.push(format!("{:?}", command))) {
+            Ok(Parsed::Nothing) => {},
+            Ok(Parsed::LedFrame(colors)) => led_frames.push(colors.to_vec()),
+            Err(_) => errors += 1,
+        }
+    }
+    (commands, led_frames, errors)
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    framing::encode(payload, &mut [0; MAX_FRAME_LEN]).to_vec()
+}
+
+/// Bytes which look nothing like the protocol, the same every run.
+fn garbage(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn follows_the_host_into_frames_and_streaming() {
+    let mut parser = CommandParser::new();
+    let brightness = Command::Brightness { target: 0, value: 0x1234 };
+    let stream_leds = Command::StreamLeds { enabled: true };
+
+    // The bytes straight after the switch are already a frame, even in the same read.
+    let switch = Command::SetProtocolVersion { version: FRAMED_PROTOCOL_VERSION };
+    let bytes = [&switch.as_arrayvec()[..], &frame(&brightness.as_arrayvec())].concat();
+    let (commands, _, errors) = parse(&mut parser, &bytes);
+    assert_eq!(commands, [format!("{:?}", switch), format!("{:?}", brightness)]);
+    assert_eq!(errors, 0);
+    assert!(parser.framed());
+
+    let bytes = [
+        frame(&stream_leds.as_arrayvec()),
+        frame(&[LED_FRAME, 255, 0, 0]),
+        frame(&[&[COMMANDS_FRAME], &brightness.as_arrayvec()[..]].concat()),
+    ]
+    .concat();
+    let (commands, led_frames, _) = parse(&mut parser, &bytes);
+    assert_eq!(commands, [format!("{:?}", stream_leds), format!("{:?}", brightness)]);
+    assert_eq!(led_frames, [vec![255, 0, 0]]);
+
+    parser.reset();
+    assert!(!parser.framed());
+    assert!(!parser.is_streaming());
+}
+
+#[test]
+fn keeps_the_commands_before_a_malformed_one_in_a_frame() {
+    let mut parser = CommandParser::new();
+    let switch = Command::SetProtocolVersion { version: FRAMED_PROTOCOL_VERSION };
+    parse(&mut parser, &switch.as_arrayvec());
+
+    // No command is numbered 0x7f, so it can't be the start of one.
+    let brightness = Command::Brightness { target: 0, value: 0x1234 };
+    let payload = [&brightness.as_arrayvec()[..], &[0x7f], &brightness.as_arrayvec()].concat();
+    let (commands, _, errors) = parse(&mut parser, &frame(&payload));
+    assert_eq!(commands, [format!("{:?}", brightness)]);
+    assert_eq!(errors, 1);
+}
+
+#[test]
+fn recovers_from_garbage() {
+    let brightness = Command::Brightness { target: 1, value: 0xabcd };
+
+    // Without frames there's no telling where a command starts, but after a malformed one the
+    // parser starts over, so the next command is read whole.
+    let mut parser = CommandParser::new();
+    let mut errors = 0;
+    let noise = garbage(4096);
+    let mut bytes = &noise[..];
+    while !bytes.is_empty() {
+        if parser.push(&mut bytes, |_| {}).is_err() {
+            errors += 1;
+            if !parser.framed() {
+                let (commands, _, _) = parse(&mut parser, &brightness.as_arrayvec());
+                assert_eq!(commands, [format!("{:?}", brightness)]);
+            }
+        }
+    }
+    assert!(errors > 0);
+
+    // With them, a zero is always enough.
+    let mut parser = CommandParser::new();
+    let switch = Command::SetProtocolVersion { version: FRAMED_PROTOCOL_VERSION };
+    parse(&mut parser, &switch.as_arrayvec());
+    let bytes = [&garbage(4096)[..], &[0], &frame(&brightness.as_arrayvec())].concat();
+    let (commands, _, _) = parse(&mut parser, &bytes);
+    assert_eq!(commands, [format!("{:?}", brightness)]);
+    assert!(parser.take_corrupt_frames() > 0);
+}
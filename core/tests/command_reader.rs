@@ -0,0 +1,83 @@
+// The firmware feeds CommandReader whatever each USB read returns, so a command can arrive split
+// anywhere, over any number of reads. These check it's put back together wherever it's split.
+
+use panel_protocol::{Command, CommandReader};
+
+fn commands() -> Vec<Command> {
+    vec![
+        Command::Brightness { target: 0, value: 0x1234 },
+        Command::Led { r: 255, g: 140, b: 40, pulse: true },
+        Command::Bootload,
+        Command::Temperature { target: 1, value: 0xfedc },
+    ]
+}
+
+fn stream() -> Vec<u8> {
+    commands().iter().flat_map(|command| command.as_arrayvec()).collect()
+}
+
+/// Parses `chunks` in turn with one reader, as successive reads would be.
+fn parse<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<String> {
+    let mut reader = CommandReader::new();
+    let mut parsed = Vec::new();
+    for chunk in chunks {
+        let commands = reader.process_bytes(chunk).unwrap();
+        parsed.extend(commands.iter().map(|command| format!("{:?}", command)));
+    }
+    parsed
+}
+
+/// Like `parse()`, but with `next_command()`, which reads straight out of each chunk.
+fn parse_each<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<String> {
+    let mut reader = CommandReader::new();
+    let mut parsed = Vec::new();
+    for mut chunk in chunks {
+        while let Some(command) = reader.next_command(&mut chunk).unwrap() {
+            parsed.push(format!("{:?}", command));
+        }
+        assert!(chunk.is_empty());
+    }
+    parsed
+}
+
+fn expected() -> Vec<String> {
+    commands().iter().map(|command| format!("{:?}", command)).collect()
+}
+
+#[test]
+fn parses_a_stream_split_at_every_pair_of_points() {
+    let bytes = stream();
+    for first in 0..=bytes.len() {
+        for second in first..=bytes.len() {
+            let chunks = [&bytes[..first], &bytes[first..second], &bytes[second..]];
+            assert_eq!(parse(chunks.iter().copied()), expected(), "split at {}, {}", first, second);
+            assert_eq!(
+                parse_each(chunks.iter().copied()),
+                expected(),
+                "split at {}, {}",
+                first,
+                second
+            );
+        }
+    }
+}
+
+#[test]
+fn parses_a_stream_a_byte_at_a_time() {
+    let bytes = stream();
+    assert_eq!(parse(bytes.chunks(1)), expected());
+}
+
+#[test]
+fn starts_on_the_byte_after_a_malformed_command() {
+    // No command is numbered 0x7f, so it can't be the start of one.
+    let bytes = [&[0x7f][..], &stream()].concat();
+    let mut reader = CommandReader::new();
+    let mut chunk = &bytes[..];
+    assert!(reader.next_command(&mut chunk).is_err());
+    let mut parsed = Vec::new();
+    while let Some(command) = reader.next_command(&mut chunk).unwrap() {
+        parsed.push(format!("{:?}", command));
+    }
+    assert_eq!(parsed, expected());
+}
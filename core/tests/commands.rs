@@ -0,0 +1,135 @@
+mod common;
+
+use common::{FakePanel, FakeState};
+use panel_core::{
+    ack::{self, Rejection, ACK_PROTOCOL_VERSION},
+    commands::{self, Setting, READOUT_PROTECTION_KEY, RESET_PROTOCOL_VERSION, UNLOCK_KEY},
+    schedule::ScheduleEntry,
+};
+use panel_protocol::{Command, Report};
+
+#[test]
+fn sets_the_lights_and_rejects_missing_targets() {
+    let mut state = FakeState::new();
+    let mut ctx = state.ctx(FakePanel::new());
+
+    assert_eq!(commands::apply(&mut ctx, Command::Brightness { target: 1, value: 900 }), Ok(()));
+    assert_eq!(commands::apply(&mut ctx, Command::Temperature { target: 0, value: 40 }), Ok(()));
+    assert_eq!(ctx.panel.lights, [(0, 40), (900, 0)]);
+
+    assert_eq!(
+        commands::apply(&mut ctx, Command::Brightness { target: 200, value: 1 }),
+        Err(Rejection::InvalidTarget)
+    );
+    assert_eq!(
+        commands::apply(&mut ctx, Command::LedAt { index: 8, r: 1, g: 2, b: 3 }),
+        Err(Rejection::InvalidTarget)
+    );
+
+    assert_eq!(commands::apply(&mut ctx, Command::GetState { target: 1 }), Ok(()));
+    assert!(matches!(ctx.panel.sent[..], [Report::State { brightness: 900, .. }]));
+}
+
+#[test]
+fn saves_what_it_changes_only_when_valid() {
+    let mut state = FakeState::new();
+    let mut ctx = state.ctx(FakePanel::new());
+
+    let entry = Command::SetScheduleEntry {
+        index: 1,
+        hour: 7,
+        minute: 30,
+        brightness: 5,
+        color_temperature: 6,
+    };
+    assert_eq!(commands::apply(&mut ctx, entry), Ok(()));
+    let late = Command::SetScheduleEntry {
+        index: 1,
+        hour: 24,
+        minute: 0,
+        brightness: 5,
+        color_temperature: 6,
+    };
+    assert_eq!(commands::apply(&mut ctx, late), Err(Rejection::InvalidValue));
+    assert_eq!(
+        commands::apply(&mut ctx, Command::SetUtcOffset { minutes: -24 * 60 }),
+        Err(Rejection::InvalidValue)
+    );
+    assert_eq!(
+        commands::apply(&mut ctx, Command::SetHapticEffect { event: 0, effect: 200 }),
+        Err(Rejection::InvalidValue)
+    );
+
+    let saved = ScheduleEntry { minute_of_day: 7 * 60 + 30, brightness: 5, color_temperature: 6 };
+    assert_eq!(ctx.panel.saved, [Setting::ScheduleEntry(1, Some(saved))]);
+    assert_eq!(ctx.schedule.get(1), Some(saved));
+}
+
+#[test]
+fn destructive_commands_need_an_unlock_each() {
+    let mut state = FakeState::new();
+    let mut ctx = state.ctx(FakePanel::new());
+
+    assert_eq!(commands::apply(&mut ctx, Command::Bootload), Err(Rejection::Locked));
+    assert_eq!(commands::apply(&mut ctx, Command::Unlock { key: 1 }), Err(Rejection::Locked));
+    assert_eq!(commands::apply(&mut ctx, Command::Unlock { key: UNLOCK_KEY }), Ok(()));
+    assert_eq!(commands::apply(&mut ctx, Command::Bootload), Ok(()));
+    assert_eq!(commands::apply(&mut ctx, Command::Bootload), Err(Rejection::Locked));
+
+    // A wrong key uses up the unlock too.
+    assert_eq!(commands::apply(&mut ctx, Command::Unlock { key: UNLOCK_KEY }), Ok(()));
+    let wrong = Command::EnableReadoutProtection { key: READOUT_PROTECTION_KEY + 1 };
+    assert_eq!(commands::apply(&mut ctx, wrong), Err(Rejection::Locked));
+    let right = Command::EnableReadoutProtection { key: READOUT_PROTECTION_KEY };
+    assert_eq!(commands::apply(&mut ctx, right), Err(Rejection::Locked));
+
+    assert_eq!(ctx.panel.resets, [true]);
+}
+
+#[test]
+fn resets_for_hosts_which_speak_the_version_with_reset() {
+    let mut state = FakeState::new();
+    let mut ctx = state.ctx(FakePanel::new());
+
+    assert_eq!(commands::apply(&mut ctx, Command::Reset), Err(Rejection::Unsupported));
+    assert_eq!(ctx.panel.resets, []);
+
+    *ctx.protocol_version = RESET_PROTOCOL_VERSION;
+    assert_eq!(commands::apply(&mut ctx, Command::Reset), Ok(()));
+    assert_eq!(commands::apply(&mut ctx, Command::Reboot), Ok(()));
+    assert_eq!(ctx.panel.resets, [false, false]);
+}
+
+#[test]
+fn acknowledges_once_the_host_asks_to_be() {
+    let mut state = FakeState::new();
+    let mut ctx = state.ctx(FakePanel::new());
+
+    let mut queued = vec![
+        Command::GetDoorState,
+        Command::SetProtocolVersion { version: ACK_PROTOCOL_VERSION },
+        Command::GetDoorState,
+        Command::SetOutput { channel: 1, on: true },
+        Command::OverrideBrightness { value: 77 },
+    ]
+    .into_iter();
+    commands::apply_all(&mut ctx, || queued.next());
+
+    let door = ack::command_id(&Command::GetDoorState);
+    let version = ack::command_id(&Command::SetProtocolVersion { version: 0 });
+    let output = ack::command_id(&Command::SetOutput { channel: 0, on: false });
+    let brightness = ack::command_id(&Command::OverrideBrightness { value: 0 });
+    assert_eq!(
+        ctx.panel.sent,
+        [
+            Report::Ack { command: version },
+            ack::report(door, Err(Rejection::Unsupported)),
+            Report::Output { channel: 1, on: true },
+            Report::Ack { command: output },
+            Report::Ack { command: brightness },
+        ]
+    );
+
+    // The override is applied once the queue is empty, without waiting for a measurement.
+    assert_eq!(ctx.panel.lights, [(77, 0), (77, 0)]);
+}
@@ -0,0 +1,281 @@
+// Not every test uses every fake.
+#![allow(dead_code)]
+
+use core::convert::Infallible;
+use embedded_hal::digital::{ErrorType, InputPin};
+use panel_core::{
+    auto_brightness::AutoBrightness,
+    commands::{self, Ctx, Light, Panel, Setting},
+    counter::QuadratureCounter,
+    daylight::Daylight,
+    dmx::Universe,
+    haptics::HapticEffects,
+    input::DEFAULT_PROTOCOL_VERSION,
+    ir::IrBindings,
+    power::PowerManager,
+    schedule::Schedule,
+    standalone::Standalone,
+    subscriptions::Category,
+    tick,
+    unlock::Unlock,
+};
+use panel_protocol::Report;
+use std::cell::Cell;
+
+/// An input pin which reads whatever level the test has set.
+pub struct FakePin<'a>(pub &'a Cell<bool>);
+
+impl ErrorType for FakePin<'_> {
+    type Error = Infallible;
+}
+
+impl InputPin for FakePin<'_> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.get())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.0.get())
+    }
+}
+
+/// A quadrature encoder counter which reads whatever count the test has set.
+pub struct FakeQei<'a>(pub &'a Cell<u16>);
+
+impl QuadratureCounter for FakeQei<'_> {
+    fn count(&self) -> u16 {
+        self.0.get()
+    }
+}
+
+/// Stands in for the SysTick interrupt. The clock is global, so each test binary should only
+/// have one test which depends on time.
+pub fn advance_ms(ms: u32) {
+    tick::advance(ms);
+}
+
+/// A panel which does what the commands ask of it in memory, and keeps what it was asked to
+/// send, save and start.
+pub struct FakePanel {
+    pub lights: [(u16, u16); 2],
+    pub led: ((u8, u8, u8), bool),
+    pub pixels: Vec<Option<(u8, u8, u8)>>,
+    pub scenes: Vec<Option<[(u16, u16); 2]>>,
+    pub power: PowerManager,
+    pub outputs: Vec<bool>,
+    pub door_open: Option<bool>,
+    pub sent: Vec<Report>,
+    pub saved: Vec<Setting>,
+    pub resets: Vec<bool>,
+    pub self_tests: u32,
+}
+
+impl FakePanel {
+    pub fn new() -> Self {
+        Self {
+            lights: [(0, 0); 2],
+            led: ((0, 0, 0), false),
+            pixels: vec![None; 8],
+            scenes: vec![None; 4],
+            power: PowerManager::new(60_000, 15 * 60_000),
+            outputs: vec![false; 2],
+            door_open: None,
+            sent: Vec::new(),
+            saved: Vec::new(),
+            resets: Vec::new(),
+            self_tests: 0,
+        }
+    }
+}
+
+impl Panel for FakePanel {
+    fn set_brightness(&mut self, light: Light, brightness: u16) {
+        self.lights[light as usize].0 = brightness;
+    }
+
+    fn set_color_temperature(&mut self, light: Light, color_temperature: u16) {
+        self.lights[light as usize].1 = color_temperature;
+    }
+
+    fn levels(&mut self, light: Light) -> (u16, u16) {
+        self.lights[light as usize]
+    }
+
+    fn set_led(&mut self, color: (u8, u8, u8), pulse: bool) {
+        self.led = (color, pulse);
+        self.pixels.iter_mut().for_each(|pixel| *pixel = None);
+    }
+
+    fn led(&mut self) -> ((u8, u8, u8), bool) {
+        self.led
+    }
+
+    fn set_pixel(&mut self, index: u16, color: (u8, u8, u8)) -> bool {
+        self.pixels.get_mut(index as usize).map(|pixel| *pixel = Some(color)).is_some()
+    }
+
+    fn commit_pixels(&mut self) {}
+
+    fn configure_sound_level(&mut self, _enabled: bool, _sensitivity: u16, _smoothing: u8) {}
+
+    fn save_scene(&mut self, slot: u8) -> bool {
+        let lights = self.lights;
+        self.scenes.get_mut(slot as usize).map(|scene| *scene = Some(lights)).is_some()
+    }
+
+    fn recall_scene(&mut self, slot: u8) -> bool {
+        match self.scenes.get(slot as usize).copied().flatten() {
+            Some(lights) => {
+                self.lights = lights;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn save(&mut self, setting: Setting) {
+        self.saved.push(setting);
+    }
+
+    fn power<R>(&mut self, f: impl FnOnce(&mut PowerManager) -> R) -> R {
+        f(&mut self.power)
+    }
+
+    fn set_time(&mut self, _unix_seconds: u32) -> bool {
+        true
+    }
+
+    fn beep(&mut self, _freq: u16, _duration_ms: u16) {}
+
+    fn set_dial_rate(&mut self, _max_per_second: u16) {}
+
+    fn set_config(&mut self, _key: u8, _value: u16) -> bool {
+        false
+    }
+
+    fn config(&mut self, _key: u8) -> Option<u16> {
+        None
+    }
+
+    fn set_output(&mut self, channel: u8, on: bool) -> bool {
+        self.outputs.get_mut(channel as usize).map(|output| *output = on).is_some()
+    }
+
+    fn output(&mut self, channel: u8) -> Option<bool> {
+        self.outputs.get(channel as usize).copied()
+    }
+
+    fn door_open(&mut self) -> Option<bool> {
+        self.door_open
+    }
+
+    fn enable_readout_protection(&mut self) {
+        self.resets.push(false);
+    }
+
+    fn diagnostics(&mut self) -> Report {
+        Report::Diagnostics { clean_boots: 1, watchdog_resets: 0, panics: 0, low_voltage_events: 0 }
+    }
+
+    fn device_id(&mut self) -> Report {
+        Report::DeviceId { unique_id: [0; 12] }
+    }
+
+    fn firmware_version(&mut self) -> Report {
+        Report::FirmwareVersion {
+            major: 0,
+            minor: 1,
+            patch: 0,
+            build_timestamp: 0,
+            commit: 0,
+            dirty: false,
+        }
+    }
+
+    fn readout_protection(&mut self) -> Report {
+        Report::ReadoutProtection { enabled: false }
+    }
+
+    fn capabilities(&mut self) -> Report {
+        Report::Capabilities {
+            lights: 2,
+            led_count: self.pixels.len() as u16,
+            outputs: self.outputs.len() as u8,
+            sliders: 0,
+            features: 0,
+        }
+    }
+
+    fn send_profile(&mut self) -> bool {
+        false
+    }
+
+    fn reset(&mut self, into_bootloader: bool) {
+        self.resets.push(into_bootloader);
+    }
+
+    fn start_self_test(&mut self) {
+        self.self_tests += 1;
+    }
+
+    fn send_status(&mut self) {}
+
+    fn subscribe(&mut self, _category: Category, _subscribed: bool) {}
+
+    fn send(&mut self, report: Report) {
+        self.sent.push(report);
+    }
+}
+
+/// What `commands::Ctx` borrows, as a panel which has just booted has it.
+pub struct FakeState {
+    pub unlock: Unlock,
+    pub schedule: Schedule,
+    pub utc_offset_minutes: i16,
+    pub auto_brightness: AutoBrightness,
+    pub daylight: Daylight,
+    pub ir_bindings: IrBindings,
+    pub haptic_effects: HapticEffects,
+    pub standalone: Standalone,
+    pub dmx: Universe,
+    pub click_feedback: bool,
+    pub status_on_connect: bool,
+    pub protocol_version: u8,
+}
+
+impl FakeState {
+    pub fn new() -> Self {
+        Self {
+            unlock: Unlock::new(commands::UNLOCK_KEY),
+            schedule: Schedule::new(),
+            utc_offset_minutes: 0,
+            auto_brightness: AutoBrightness::new(),
+            daylight: Daylight::new(),
+            ir_bindings: IrBindings::default(),
+            haptic_effects: HapticEffects::default(),
+            standalone: Standalone::new(10_000, u16::MAX / 2),
+            dmx: Universe::new(),
+            click_feedback: false,
+            status_on_connect: false,
+            protocol_version: DEFAULT_PROTOCOL_VERSION,
+        }
+    }
+
+    pub fn ctx<P: Panel>(&mut self, panel: P) -> Ctx<'_, P> {
+        Ctx {
+            panel,
+            unlock: &mut self.unlock,
+            schedule: &mut self.schedule,
+            utc_offset_minutes: &mut self.utc_offset_minutes,
+            auto_brightness: &mut self.auto_brightness,
+            daylight: &mut self.daylight,
+            ir_bindings: &mut self.ir_bindings,
+            haptic_effects: &mut self.haptic_effects,
+            standalone: &mut self.standalone,
+            dmx: &mut self.dmx,
+            click_feedback: &mut self.click_feedback,
+            status_on_connect: &mut self.status_on_connect,
+            protocol_version: &mut self.protocol_version,
+        }
+    }
+}
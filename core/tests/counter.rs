@@ -0,0 +1,100 @@
+mod common;
+
+use common::{FakePin, FakeQei};
+use panel_core::{
+    button::Active,
+    counter::{Counter, Error},
+};
+use std::cell::Cell;
+
+#[test]
+fn reports_whole_detents() {
+    let count = Cell::new(0);
+    let mut counter = Counter::<_, FakePin>::new(FakeQei(&count), None);
+
+    assert_eq!(counter.poll().unwrap(), None);
+
+    count.set(2);
+    assert_eq!(counter.poll().unwrap(), None);
+
+    count.set(4);
+    assert_eq!(counter.poll().unwrap(), Some(1));
+    assert_eq!(counter.poll().unwrap(), None);
+
+    count.set(12);
+    assert_eq!(counter.poll().unwrap(), Some(2));
+    assert_eq!(counter.position(), 3);
+}
+
+#[test]
+fn handles_counter_wrapping() {
+    let count = Cell::new(2);
+    let mut counter = Counter::<_, FakePin>::new(FakeQei(&count), None);
+
+    count.set(2u16.wrapping_sub(8));
+    assert_eq!(counter.poll().unwrap(), Some(-2));
+
+    count.set(2);
+    assert_eq!(counter.poll().unwrap(), Some(2));
+    assert_eq!(counter.position(), 0);
+}
+
+#[test]
+fn splits_large_diffs() {
+    let count = Cell::new(0);
+    let mut counter = Counter::<_, FakePin>::new(FakeQei(&count), None);
+
+    count.set(4 * 200);
+    assert_eq!(counter.poll().unwrap(), Some(127));
+    assert_eq!(counter.poll().unwrap(), Some(73));
+    assert_eq!(counter.poll().unwrap(), None);
+}
+
+#[test]
+fn deferred_diffs_are_summed() {
+    let count = Cell::new(0);
+    let mut counter = Counter::<_, FakePin>::new(FakeQei(&count), None);
+
+    count.set(4);
+    let diff = counter.poll().unwrap().unwrap();
+    counter.defer(diff);
+
+    count.set(8);
+    assert_eq!(counter.poll().unwrap(), Some(2));
+}
+
+#[test]
+fn rejects_implausible_jumps() {
+    let count = Cell::new(0);
+    let mut counter = Counter::<_, FakePin>::new(FakeQei(&count), None);
+
+    count.set(20_000);
+    assert!(matches!(counter.poll(), Err(Error::CountJump)));
+    assert_eq!(counter.poll().unwrap(), None);
+
+    // Half the counter's range away, which is i16::MIN as a diff.
+    count.set(20_000 + 0x8000);
+    assert!(matches!(counter.poll(), Err(Error::CountJump)));
+}
+
+#[test]
+fn index_pulse_resets_position() {
+    let count = Cell::new(0);
+    let index = Cell::new(true);
+    let mut counter = Counter::new(FakeQei(&count), Some((FakePin(&index), Active::Low)));
+
+    assert!(!counter.poll_index());
+
+    count.set(8);
+    assert_eq!(counter.poll().unwrap(), Some(2));
+    assert_eq!(counter.position(), 2);
+
+    index.set(false);
+    assert!(counter.poll_index());
+    assert_eq!(counter.position(), 0);
+    assert!(!counter.poll_index());
+
+    count.set(12);
+    assert_eq!(counter.poll().unwrap(), Some(1));
+    assert_eq!(counter.position(), 1);
+}
@@ -0,0 +1,26 @@
+use panel_core::crc::{crc16, crc32, crc8};
+
+#[test]
+fn matches_zlib() {
+    assert_eq!(crc32(0, b""), 0);
+    assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn can_be_computed_in_pieces() {
+    let data = b"The quick brown fox jumps over the lazy dog";
+    let (first, second) = data.split_at(10);
+
+    assert_eq!(crc32(crc32(0, first), second), crc32(0, data));
+}
+
+#[test]
+fn matches_maxim() {
+    assert_eq!(crc8(0, b"123456789"), 0xA1);
+    assert_eq!(crc8(0, b"123456789\xA1"), 0);
+}
+
+#[test]
+fn matches_ccitt() {
+    assert_eq!(crc16(0xFFFF, b"123456789"), 0x29B1);
+}
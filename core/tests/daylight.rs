@@ -0,0 +1,75 @@
+use panel_core::daylight::{Daylight, DaylightCurve};
+
+const CURVE: DaylightCurve =
+    DaylightCurve { sunrise: 6 * 60, sunset: 18 * 60, night: 0, midday: 60_000 };
+
+/// Polls until the lights stop moving, returning where they ended up and how many polls it took.
+fn settle(daylight: &Daylight, mut current: u16) -> (u16, usize) {
+    let mut polls = 0;
+    while let Some(next) = daylight.poll(current) {
+        current = next;
+        polls += 1;
+    }
+    (current, polls)
+}
+
+#[test]
+fn follows_the_curve_through_the_day() {
+    let mut daylight = Daylight::new();
+    daylight.set_time(12 * 60);
+    assert_eq!(daylight.poll(0), None);
+
+    daylight.enable(CURVE);
+    assert_eq!(daylight.setpoint(), Some(60_000));
+    assert_eq!(daylight.setpoint_change(), Some(60_000));
+
+    // The lights get there slowly.
+    let (temperature, polls) = settle(&daylight, 0);
+    assert_eq!(temperature, 60_000);
+    assert!(polls > 100);
+
+    daylight.set_time(9 * 60);
+    assert_eq!(daylight.setpoint(), Some(30_000));
+    assert_eq!(daylight.setpoint_change(), Some(30_000));
+    daylight.set_time(9 * 60 + 1);
+    assert_eq!(daylight.setpoint_change(), None);
+
+    daylight.set_time(21 * 60);
+    assert_eq!(daylight.setpoint(), Some(0));
+    daylight.set_time(3 * 60);
+    assert_eq!(daylight.setpoint(), Some(0));
+
+    daylight.disable();
+    assert_eq!(daylight.poll(30_000), None);
+}
+
+#[test]
+fn uses_the_ambient_light_until_the_time_is_known() {
+    let mut daylight = Daylight::new();
+    daylight.enable(CURVE);
+    assert_eq!(daylight.setpoint(), None);
+
+    daylight.measure(250);
+    assert_eq!(daylight.setpoint(), Some(30_000));
+    daylight.measure(5000);
+    assert_eq!(daylight.setpoint(), Some(60_000));
+
+    daylight.set_time(0);
+    assert_eq!(daylight.setpoint(), Some(0));
+}
+
+#[test]
+fn override_applies_at_once() {
+    let mut daylight = Daylight::new();
+    daylight.enable(CURVE);
+    daylight.set_time(12 * 60);
+    assert_eq!(daylight.setpoint_change(), Some(60_000));
+
+    daylight.set_override(Some(59_990));
+    assert_eq!(daylight.poll(10_000), Some(59_990));
+    assert_eq!(daylight.poll(59_990), None);
+    assert_eq!(daylight.setpoint_change(), Some(59_990));
+
+    daylight.set_override(None);
+    assert_eq!(daylight.poll(59_990), Some(60_000));
+}
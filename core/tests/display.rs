@@ -0,0 +1,27 @@
+use panel_core::display::{Frame, Status, PAGES};
+
+const STATUS: Status =
+    Status { host_connected: false, lights: [(u16::MAX, 0), (u16::MAX / 2, 0)], dial_position: 0 };
+
+#[test]
+fn draws_a_line_of_text_per_page() {
+    let frame = Frame::render(&STATUS);
+
+    // "NO HOST", with a column of space after each character.
+    assert_eq!(
+        frame.page(0)[..12],
+        [0x7f, 0x04, 0x08, 0x10, 0x7f, 0, 0x3e, 0x41, 0x41, 0x41, 0x3e, 0]
+    );
+    assert!(frame.page(0)[7 * 6..].iter().all(|&column| column == 0));
+    assert!(frame.page(1).iter().all(|&column| column == 0));
+}
+
+#[test]
+fn changes_only_the_pages_whose_line_changed() {
+    let frame = Frame::render(&STATUS);
+    let turned = Frame::render(&Status { dial_position: -12, ..STATUS });
+
+    let changed: Vec<usize> =
+        (0..PAGES).filter(|&page| frame.page(page) != turned.page(page)).collect();
+    assert_eq!(changed, vec![5]);
+}
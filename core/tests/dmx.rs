@@ -0,0 +1,43 @@
+use panel_core::{
+    dmx::{Universe, FIRST_TARGET, FRAME_LEN, TARGETS},
+    fraction::Fraction,
+};
+
+#[test]
+fn maps_targets_onto_channels() {
+    let mut dmx = Universe::new();
+    assert_eq!(dmx.frame(), [0; FRAME_LEN]);
+
+    assert!(dmx.set_brightness(FIRST_TARGET, 0xffff));
+    assert!(dmx.set_color_temperature(FIRST_TARGET, 0x8000));
+    assert!(dmx.set_brightness(FIRST_TARGET + 1, 0x1234));
+    let last = FIRST_TARGET + TARGETS as u8 - 1;
+    assert!(dmx.set_color_temperature(last, 0xabcd));
+
+    let frame = dmx.frame();
+    assert_eq!(frame[..5], [0, 0xff, 0x80, 0x12, 0]);
+    assert_eq!(frame[FRAME_LEN - 1], 0xab);
+
+    // The local lights, and targets past the last fixture, aren't DMX channels.
+    assert!(!dmx.set_brightness(0, 0xffff));
+    assert!(!dmx.set_brightness(1, 0xffff));
+    assert!(!dmx.set_color_temperature(last + 1, 0xffff));
+    assert_eq!(dmx.frame(), frame);
+}
+
+#[test]
+fn scales_brightness_only() {
+    let mut dmx = Universe::new();
+    dmx.set_brightness(FIRST_TARGET, 0xffff);
+    dmx.set_color_temperature(FIRST_TARGET, 0xffff);
+
+    dmx.set_brightness_scale(Fraction::from_ratio(1, 2));
+    assert_eq!(dmx.frame()[1..3], [0x7f, 0xff]);
+
+    dmx.set_brightness_scale(Fraction::ZERO);
+    assert_eq!(dmx.frame()[1..3], [0, 0xff]);
+
+    // The host is told the levels it set, not the scaled ones.
+    assert_eq!(dmx.levels(FIRST_TARGET), Some((0xffff, 0xffff)));
+    assert_eq!(dmx.levels(0), None);
+}
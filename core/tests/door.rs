@@ -0,0 +1,32 @@
+mod common;
+
+use common::FakePin;
+use panel_core::{
+    button::{Active, Debouncer},
+    door::DoorSensor,
+};
+use std::cell::Cell;
+
+#[test]
+fn reports_each_change() {
+    // Pulled up while the door is open.
+    let level = Cell::new(true);
+    let mut door = DoorSensor::new(Debouncer::new(FakePin(&level), Active::Low, 5, 1000));
+    assert!(door.is_open());
+    assert_eq!(door.poll(), None);
+
+    // A bounce is ignored.
+    level.set(false);
+    door.poll();
+    level.set(true);
+    assert_eq!(door.poll(), None);
+
+    level.set(false);
+    let changes: Vec<_> = (0..10).filter_map(|_| door.poll()).collect();
+    assert_eq!(changes, [false]);
+    assert!(!door.is_open());
+
+    level.set(true);
+    let changes: Vec<_> = (0..10).filter_map(|_| door.poll()).collect();
+    assert_eq!(changes, [true]);
+}
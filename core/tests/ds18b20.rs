@@ -0,0 +1,162 @@
+use panel_core::{crc::crc8, ds18b20::Probes, onewire::OneWire};
+
+/// A device on the fake bus, with the scratchpad it sends back.
+struct Device {
+    rom: u64,
+    scratchpad: [u8; 9],
+    /// Whether it's still taking part since the last reset.
+    active: bool,
+}
+
+impl Device {
+    fn new(family: u8, serial: u64) -> Self {
+        let mut rom = (serial << 8 | family as u64).to_le_bytes();
+        rom[7] = crc8(0, &rom[..7]);
+        Self { rom: u64::from_le_bytes(rom), scratchpad: [0; 9], active: false }
+    }
+
+    /// A probe whose last conversion read `raw` sixteenths of a degree.
+    fn probe(serial: u64, raw: i16) -> Self {
+        let mut device = Self::new(0x28, serial);
+        let [low, high] = raw.to_le_bytes();
+        device.scratchpad = [low, high, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0];
+        device.scratchpad[8] = crc8(0, &device.scratchpad[..8]);
+        device
+    }
+
+    fn rom_bit(&self, bit: usize) -> bool {
+        self.rom & (1 << bit) != 0
+    }
+}
+
+/// Where the devices are in a transaction. In a search, each bit is read, then its complement,
+/// then the chosen bit written.
+#[derive(Clone, Copy)]
+enum State {
+    Idle,
+    RomCommand { byte: u8, bits: usize },
+    Search { bit: usize, reads: usize },
+    MatchRom { bit: usize },
+    FunctionCommand { byte: u8, bits: usize },
+    ReadScratchpad { bit: usize },
+}
+
+/// A 1-Wire bus with some devices on it, which answers time slots the way they would.
+struct FakeBus {
+    devices: Vec<Device>,
+    state: State,
+}
+
+impl FakeBus {
+    fn new(devices: Vec<Device>) -> Self {
+        Self { devices, state: State::Idle }
+    }
+
+    /// The line is pulled low if any active device sends a 0.
+    fn wired_and(&self, bit: impl Fn(&Device) -> bool) -> bool {
+        self.devices.iter().filter(|device| device.active).all(bit)
+    }
+}
+
+impl OneWire for FakeBus {
+    fn reset(&mut self) -> bool {
+        for device in &mut self.devices {
+            device.active = true;
+        }
+        self.state = State::RomCommand { byte: 0, bits: 0 };
+        !self.devices.is_empty()
+    }
+
+    fn write_bit(&mut self, value: bool) {
+        self.state = match self.state {
+            State::RomCommand { byte, bits } => {
+                let byte = byte | (value as u8) << bits;
+                match (bits + 1, byte) {
+                    (8, 0xF0) => State::Search { bit: 0, reads: 0 },
+                    (8, 0x55) => State::MatchRom { bit: 0 },
+                    (8, 0xCC) => State::FunctionCommand { byte: 0, bits: 0 },
+                    (8, _) => State::Idle,
+                    (bits, byte) => State::RomCommand { byte, bits },
+                }
+            },
+            State::Search { bit, reads: 2 } | State::MatchRom { bit } => {
+                for device in &mut self.devices {
+                    device.active &= device.rom_bit(bit) == value;
+                }
+                match (self.state, bit + 1) {
+                    (State::Search { .. }, 64) => State::Idle,
+                    (State::Search { .. }, bit) => State::Search { bit, reads: 0 },
+                    (_, 64) => State::FunctionCommand { byte: 0, bits: 0 },
+                    (_, bit) => State::MatchRom { bit },
+                }
+            },
+            State::FunctionCommand { byte, bits } => {
+                let byte = byte | (value as u8) << bits;
+                match (bits + 1, byte) {
+                    (8, 0xBE) => State::ReadScratchpad { bit: 0 },
+                    (8, _) => State::Idle,
+                    (bits, byte) => State::FunctionCommand { byte, bits },
+                }
+            },
+            state => state,
+        };
+    }
+
+    fn read_bit(&mut self) -> bool {
+        match self.state {
+            State::Search { bit, reads } if reads < 2 => {
+                self.state = State::Search { bit, reads: reads + 1 };
+                self.wired_and(|device| device.rom_bit(bit) != (reads == 1))
+            },
+            State::ReadScratchpad { bit } if bit < 72 => {
+                self.state = State::ReadScratchpad { bit: bit + 1 };
+                self.wired_and(|device| device.scratchpad[bit / 8] & (1 << (bit % 8)) != 0)
+            },
+            _ => true,
+        }
+    }
+}
+
+#[test]
+fn finds_every_probe_on_the_bus() {
+    let mut bus = FakeBus::new(vec![
+        Device::probe(0x1234, 0),
+        Device::new(0x10, 0x5678),
+        Device::probe(0x1235, 0),
+        Device::probe(0xabcdef, 0),
+    ]);
+
+    let probes = Probes::<4>::find(&mut bus);
+    assert_eq!(probes.len(), 3);
+
+    let probes = Probes::<2>::find(&mut bus);
+    assert_eq!(probes.len(), 2);
+
+    assert!(Probes::<4>::find(&mut FakeBus::new(vec![])).is_empty());
+}
+
+#[test]
+fn reads_each_probe() {
+    // 25.0625 and -10.125 degrees.
+    let mut bus = FakeBus::new(vec![Device::probe(1, 0x0191), Device::probe(2, -162)]);
+    let probes = Probes::<4>::find(&mut bus);
+    probes.start_conversion(&mut bus);
+
+    let mut temperatures: Vec<_> =
+        (0..probes.len()).map(|i| probes.temperature(&mut bus, i)).collect();
+    temperatures.sort();
+    assert_eq!(temperatures, [Some(-1012), Some(2506)]);
+    assert_eq!(probes.temperature(&mut bus, 2), None);
+}
+
+#[test]
+fn ignores_corrupted_readings() {
+    let mut bus = FakeBus::new(vec![Device::probe(1, 0x0191)]);
+    let probes = Probes::<4>::find(&mut bus);
+
+    bus.devices[0].scratchpad[0] ^= 1;
+    assert_eq!(probes.temperature(&mut bus, 0), None);
+
+    bus.devices[0].scratchpad = [0; 9];
+    assert_eq!(probes.temperature(&mut bus, 0), None);
+}
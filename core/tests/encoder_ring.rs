@@ -0,0 +1,26 @@
+use panel_core::{encoder_ring, fraction::Fraction};
+
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+
+fn dot<const N: usize>(frame: &[(u8, u8, u8); N]) -> usize {
+    let brightest = frame.iter().max().unwrap();
+    assert_eq!(frame.iter().filter(|&led| led == brightest).count(), 1);
+    frame.iter().position(|led| led == brightest).unwrap()
+}
+
+#[test]
+fn follows_the_dial_round() {
+    let dots: Vec<_> = [0, 1, 2, 3, 23, 24, 25, -1, -2, 48]
+        .iter()
+        .map(|&position| dot(&encoder_ring::render::<12>(position, 24, WHITE, Fraction::ONE)))
+        .collect();
+    assert_eq!(dots, [0, 1, 1, 2, 0, 0, 1, 0, 11, 0]);
+}
+
+#[test]
+fn glows_in_the_mode_color() {
+    let frame = encoder_ring::render::<4>(1, 4, (255, 128, 0), Fraction::from_ratio(1, 2));
+    assert_eq!(frame[1], (127, 64, 0));
+    assert_eq!(frame[0], (15, 8, 0));
+    assert_eq!(frame[3], frame[0]);
+}
@@ -0,0 +1,36 @@
+use panel_core::{
+    fan::{self, FanController, FanCurve},
+    fraction::Fraction,
+};
+
+const CURVE: FanCurve = FanCurve { start_celsius: 40, full_celsius: 60, min_percent: 30 };
+
+#[test]
+fn follows_the_curve() {
+    let mut fan = FanController::new(CURVE);
+
+    assert_eq!(fan.duty(20), Fraction::ZERO);
+    assert_eq!(fan.duty(39), Fraction::ZERO);
+    assert_eq!(fan.duty(40), Fraction::from_ratio(30, 100));
+    assert_eq!(fan.duty(50), Fraction::from_ratio(65, 100));
+    assert_eq!(fan.duty(60), Fraction::ONE);
+    assert_eq!(fan.duty(85), Fraction::ONE);
+}
+
+#[test]
+fn keeps_running_until_it_has_cooled_down() {
+    let mut fan = FanController::new(CURVE);
+
+    assert_eq!(fan.duty(40), Fraction::from_ratio(30, 100));
+    assert_eq!(fan.duty(38), Fraction::from_ratio(30, 100));
+    assert_eq!(fan.duty(37), Fraction::ZERO);
+    assert_eq!(fan.duty(39), Fraction::ZERO);
+}
+
+#[test]
+fn counts_two_pulses_per_revolution() {
+    assert_eq!(fan::rpm(0, 1000), 0);
+    assert_eq!(fan::rpm(40, 1000), 1200);
+    assert_eq!(fan::rpm(20, 500), 1200);
+    assert_eq!(fan::rpm(u32::MAX, 1000), u16::MAX);
+}
@@ -0,0 +1,24 @@
+use panel_core::fraction::Fraction;
+
+#[test]
+fn scales_like_floating_point() {
+    for &(numerator, denominator) in &[(0, 1), (1, 4), (3, 10), (1, 2), (1, 1)] {
+        let fraction = Fraction::from_ratio(numerator, denominator);
+        let float = numerator as f32 / denominator as f32;
+
+        for value in 0..=u8::MAX {
+            assert_eq!(fraction.of_u8(value), (value as f32 * float) as u8);
+        }
+        // 16 bit values lose a little precision, but not enough to see on the lights.
+        for value in 0..=u16::MAX {
+            let expected = (value as f32 * float) as i32;
+            assert!((fraction.of_u16(value) as i32 - expected).abs() <= 2);
+        }
+    }
+
+    assert_eq!(Fraction::from_ratio(3, 2), Fraction::ONE);
+    assert_eq!(Fraction::ONE.of_u16(u16::MAX), u16::MAX);
+    assert_eq!(Fraction::ONE.of_u32(1 << 16), 1 << 16);
+    assert_eq!(Fraction::from_ratio(1, 4).of_u32(3125), 781);
+    assert_eq!(Fraction::from_ratio(1, 2) * Fraction::from_ratio(1, 2), Fraction::from_ratio(1, 4));
+}
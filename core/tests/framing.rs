@@ -0,0 +1,52 @@
+use panel_core::framing::{self, FrameReader, MAX_FRAME_LEN, MAX_PAYLOAD_LEN};
+
+/// Feeds `bytes` to `reader` one at a time, and returns the payloads of the frames they complete.
+fn read(reader: &mut FrameReader, bytes: &[u8]) -> Vec<Vec<u8>> {
+    bytes.iter().filter_map(|&byte| reader.push(byte).map(|payload| payload.to_vec())).collect()
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    framing::encode(payload, &mut [0; MAX_FRAME_LEN]).to_vec()
+}
+
+#[test]
+fn frames_round_trip() {
+    let mut reader = FrameReader::new();
+    let payloads = [
+        vec![1, 2, 3],
+        vec![],
+        vec![0, 0, 5, 0],
+        // Runs of 254 and more bytes without a zero take more than one COBS block.
+        vec![0xff; 254],
+        (0..MAX_PAYLOAD_LEN).map(|i| i as u8).collect(),
+    ];
+
+    for payload in &payloads {
+        let frame = frame(payload);
+        assert_eq!(frame.iter().position(|&byte| byte == 0), Some(frame.len() - 1));
+    }
+
+    let stream: Vec<u8> = payloads.iter().flat_map(|payload| frame(payload)).collect();
+    assert_eq!(read(&mut reader, &stream), payloads);
+    assert_eq!(reader.take_corrupt_frames(), 0);
+}
+
+#[test]
+fn recovers_from_corrupt_and_partial_frames() {
+    let mut reader = FrameReader::new();
+    let mut corrupt = frame(&[0x10, 0x20]);
+    corrupt[2] ^= 0x01;
+    let partial = &frame(&[0x40, 0x50, 0x60])[..3];
+
+    // Garbage and the start of a frame run into the frame after them, which is lost too.
+    let stream =
+        [&corrupt[..], &frame(&[0x30]), &[0x12, 0x34], partial, &frame(&[0x70]), &frame(&[0x80])]
+            .concat();
+    assert_eq!(read(&mut reader, &stream), [vec![0x30], vec![0x80]]);
+    assert_eq!(reader.take_corrupt_frames(), 2);
+
+    // More than fits in a frame is dropped once, at the next zero.
+    let stream = [&[0x01; MAX_FRAME_LEN + 10][..], &[0], &frame(&[0x90])].concat();
+    assert_eq!(read(&mut reader, &stream), [vec![0x90]]);
+    assert_eq!(reader.take_corrupt_frames(), 1);
+}
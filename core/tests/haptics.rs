@@ -0,0 +1,24 @@
+use panel_core::{
+    button::ButtonEvent,
+    haptics::{HapticEffects, HapticEvent, NO_EFFECT},
+    input::InputEvent,
+};
+
+#[test]
+fn plays_each_events_effect() {
+    let mut effects = HapticEffects::default();
+
+    let feel = |effects: &HapticEffects, event: InputEvent| {
+        HapticEvent::for_input(&event).and_then(|event| effects.get(event))
+    };
+    assert_eq!(feel(&effects, InputEvent::Button(ButtonEvent::ShortRelease)), Some(1));
+    assert_eq!(feel(&effects, InputEvent::Dial(-2)), Some(24));
+    assert_eq!(feel(&effects, InputEvent::Button(ButtonEvent::Pressed)), None);
+
+    effects.set(HapticEvent::from_u8(2).unwrap(), 47);
+    assert_eq!(feel(&effects, InputEvent::Button(ButtonEvent::LongPress)), Some(47));
+    effects.set(HapticEvent::Detent, NO_EFFECT);
+    assert_eq!(feel(&effects, InputEvent::Dial(1)), None);
+
+    assert_eq!(HapticEvent::from_u8(3), None);
+}
@@ -0,0 +1,105 @@
+use panel_core::ir::{IrAction, IrBinding, IrBindings, IrCode, IrDecoder, IrProtocol};
+
+/// A mark (the receiver sees the carrier) or a space, in microseconds.
+#[derive(Clone, Copy)]
+enum Pulse {
+    Mark(u32),
+    Space(u32),
+}
+
+/// Feeds `pulses` to `decoder` as the edges which end them, and returns the codes it decoded.
+fn receive(decoder: &mut IrDecoder, pulses: &[Pulse]) -> Vec<IrCode> {
+    pulses
+        .iter()
+        .filter_map(|&pulse| match pulse {
+            Pulse::Mark(us) => decoder.edge(true, us),
+            Pulse::Space(us) => decoder.edge(false, us),
+        })
+        .collect()
+}
+
+fn nec_frame(bytes: [u8; 4]) -> Vec<Pulse> {
+    let mut pulses = vec![Pulse::Space(100_000), Pulse::Mark(9000), Pulse::Space(4500)];
+    for bit in 0..32 {
+        let one = u32::from_le_bytes(bytes) & (1 << bit) != 0;
+        pulses.push(Pulse::Mark(560));
+        pulses.push(Pulse::Space(if one { 1690 } else { 560 }));
+    }
+    pulses.push(Pulse::Mark(560));
+    pulses
+}
+
+fn nec_repeat() -> Vec<Pulse> {
+    vec![Pulse::Space(40_000), Pulse::Mark(9000), Pulse::Space(2250), Pulse::Mark(560)]
+}
+
+fn rc5_frame(address: u8, command: u8, toggle: bool) -> Vec<Pulse> {
+    let mut bits = vec![true, command & 0x40 == 0, toggle];
+    bits.extend((0..5).rev().map(|bit| address & (1 << bit) != 0));
+    bits.extend((0..6).rev().map(|bit| command & (1 << bit) != 0));
+
+    // Each bit is two halves, a space and a mark for a one. Halves at the same level make up one
+    // pulse. The first space runs on from the idle line, and so may the last.
+    let halves = bits.iter().flat_map(|&one| vec![!one, one]);
+    let mut pulses = vec![Pulse::Space(100_000)];
+    for mark in halves.skip(1) {
+        match (pulses.last_mut(), mark) {
+            (Some(Pulse::Mark(us)), true) | (Some(Pulse::Space(us)), false) => *us += 889,
+            (_, true) => pulses.push(Pulse::Mark(889)),
+            (_, false) => pulses.push(Pulse::Space(889)),
+        }
+    }
+    if let Some(Pulse::Space(_)) = pulses.last() {
+        pulses.pop();
+    }
+    pulses
+}
+
+#[test]
+fn decodes_nec() {
+    let mut decoder = IrDecoder::new();
+
+    let code = IrCode { protocol: IrProtocol::Nec, address: 0x04, command: 0x08, repeat: false };
+    assert_eq!(receive(&mut decoder, &nec_frame([0x04, !0x04, 0x08, !0x08])), vec![code]);
+    assert_eq!(receive(&mut decoder, &nec_repeat()), vec![IrCode { repeat: true, ..code }]);
+
+    // Extended NEC has a 16 bit address.
+    let code = IrCode { address: 0x1234, ..code };
+    assert_eq!(receive(&mut decoder, &nec_frame([0x34, 0x12, 0x08, !0x08])), vec![code]);
+
+    // A frame which doesn't check out is dropped.
+    assert!(receive(&mut decoder, &nec_frame([0x04, !0x04, 0x08, 0x08])).is_empty());
+}
+
+#[test]
+fn decodes_rc5() {
+    let mut decoder = IrDecoder::new();
+
+    let code = IrCode { protocol: IrProtocol::Rc5, address: 5, command: 12, repeat: false };
+    assert_eq!(receive(&mut decoder, &rc5_frame(5, 12, false)), vec![code]);
+
+    // A held button repeats the frame as it is, and the next press flips the toggle bit.
+    let repeat = IrCode { repeat: true, ..code };
+    assert_eq!(receive(&mut decoder, &rc5_frame(5, 12, false)), vec![repeat]);
+    assert_eq!(receive(&mut decoder, &rc5_frame(5, 12, true)), vec![code]);
+
+    // Extended RC5 has a seventh command bit.
+    let code = IrCode { address: 31, command: 0x41, ..code };
+    assert_eq!(receive(&mut decoder, &rc5_frame(31, 0x41, false)), vec![code]);
+}
+
+#[test]
+fn bound_codes_trigger_actions() {
+    let mut bindings = IrBindings::default();
+    let binding = IrBinding { protocol: IrProtocol::Nec, address: 0x04, command: 0x08 };
+    bindings.set(IrAction::RecallScene, Some(binding));
+
+    let code = IrCode { protocol: IrProtocol::Nec, address: 0x04, command: 0x08, repeat: false };
+    assert_eq!(bindings.action(&code), Some(IrAction::RecallScene));
+    assert_eq!(bindings.action(&IrCode { repeat: true, ..code }), Some(IrAction::RecallScene));
+    assert_eq!(bindings.action(&IrCode { command: 0x09, ..code }), None);
+    assert_eq!(bindings.action(&IrCode { protocol: IrProtocol::Rc5, ..code }), None);
+
+    bindings.set(IrAction::RecallScene, None);
+    assert_eq!(bindings.action(&code), None);
+}
@@ -0,0 +1,52 @@
+use panel_core::joystick::{Direction, Joystick};
+
+const CENTRE: u16 = 2048;
+
+/// A reading `amount` of the way from the centre to the edge, in each axis.
+fn push(amount_x: f32, amount_y: f32) -> (u16, u16) {
+    let reading = |amount: f32| (CENTRE as f32 + amount * 2047.0) as u16;
+    (reading(amount_x), reading(amount_y))
+}
+
+#[test]
+fn nudges_once_per_push() {
+    let mut joystick = Joystick::new();
+    assert_eq!(joystick.sample(CENTRE, CENTRE), None);
+
+    let (x, y) = push(0.1, 0.2);
+    assert_eq!(joystick.sample(x, y), None);
+    let (x, y) = push(0.0, 0.6);
+    assert_eq!(joystick.sample(x, y), Some(Direction::Up));
+    let (x, y) = push(0.9, 0.0);
+    assert_eq!(joystick.sample(x, y), None);
+
+    // Not far enough back yet.
+    let (x, y) = push(0.3, 0.0);
+    assert_eq!(joystick.sample(x, y), None);
+    let (x, y) = push(0.0, 0.0);
+    assert_eq!(joystick.sample(x, y), None);
+    let (x, y) = push(0.9, 0.0);
+    assert_eq!(joystick.sample(x, y), Some(Direction::Right));
+}
+
+#[test]
+fn quantizes_to_eight_directions() {
+    let cases = [
+        ((0.1, 0.9), Direction::Up),
+        ((0.6, 0.6), Direction::UpRight),
+        ((0.9, -0.3), Direction::Right),
+        ((0.5, -0.6), Direction::DownRight),
+        ((-0.2, -0.9), Direction::Down),
+        ((-0.7, -0.7), Direction::DownLeft),
+        ((-0.9, 0.2), Direction::Left),
+        ((-0.4, 0.8), Direction::UpLeft),
+    ];
+
+    for &((amount_x, amount_y), direction) in &cases {
+        // Centred somewhere other than the middle of the range.
+        let mut joystick = Joystick::new();
+        joystick.sample(CENTRE - 100, CENTRE + 50);
+        let (x, y) = push(amount_x, amount_y);
+        assert_eq!(joystick.sample(x - 100, y + 50), Some(direction), "{:?}", (x, y));
+    }
+}
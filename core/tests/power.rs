@@ -0,0 +1,58 @@
+mod common;
+
+use common::advance_ms;
+use panel_core::power::{PowerManager, PowerState, SuspendPolicy};
+
+#[test]
+fn follows_activity_host_and_commands() {
+    let mut power = PowerManager::new(100, 1000);
+    assert_eq!(power.poll(), None);
+
+    advance_ms(100);
+    assert_eq!(power.poll(), Some(PowerState::Dimmed));
+
+    power.input_activity();
+    assert_eq!(power.poll(), Some(PowerState::Active));
+
+    advance_ms(1000);
+    assert_eq!(power.poll(), Some(PowerState::Sleep));
+
+    // Connecting wakes the panel, and it only dims while the host is there.
+    power.set_host_present(true);
+    assert_eq!(power.poll(), Some(PowerState::Active));
+    advance_ms(2000);
+    assert_eq!(power.poll(), Some(PowerState::Dimmed));
+    assert_eq!(power.poll(), None);
+
+    power.sleep();
+    assert_eq!(power.poll(), Some(PowerState::Sleep));
+    power.wake();
+    assert_eq!(power.poll(), Some(PowerState::Active));
+
+    power.set_host_present(false);
+    advance_ms(1000);
+    assert_eq!(power.poll(), Some(PowerState::Sleep));
+    assert_eq!(power.state(), PowerState::Sleep);
+
+    // Without a cable there's no sleeping host to follow, so the panel only dims.
+    power.set_cable_present(false);
+    assert_eq!(power.poll(), Some(PowerState::Dimmed));
+    advance_ms(2000);
+    assert_eq!(power.poll(), None);
+
+    // A suspended host counts as present, and the panel follows the policy until it resumes.
+    power.set_cable_present(true);
+    power.set_host_suspended(true);
+    power.wake();
+    assert_eq!(power.poll(), Some(PowerState::Active));
+    power.set_suspend_policy(SuspendPolicy::Dim);
+    assert_eq!(power.poll(), Some(PowerState::Dimmed));
+    advance_ms(2000);
+    assert_eq!(power.poll(), None);
+    power.set_suspend_policy(SuspendPolicy::Off);
+    assert_eq!(power.poll(), Some(PowerState::Sleep));
+    power.set_host_suspended(false);
+    assert_eq!(power.poll(), Some(PowerState::Active));
+    assert_eq!(SuspendPolicy::from_u8(1), Some(SuspendPolicy::Dim));
+    assert_eq!(SuspendPolicy::from_u8(3), None);
+}
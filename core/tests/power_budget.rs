@@ -0,0 +1,32 @@
+use panel_core::{fraction::Fraction, power_budget::PowerBudget};
+
+#[test]
+fn holds_the_strip_back_while_over_budget() {
+    let mut budget = PowerBudget::new(2000);
+    assert_eq!(budget.scale(), Fraction::ONE);
+
+    budget.measure(1990);
+    assert_eq!(budget.scale(), Fraction::ONE);
+
+    budget.measure(4000);
+    assert_eq!(budget.scale(), Fraction::from_ratio(1, 2));
+    budget.measure(8000);
+    assert_eq!(budget.scale(), Fraction::from_ratio(1, 8));
+
+    // Close to the budget, the limit stays put.
+    budget.measure(1900);
+    assert_eq!(budget.scale(), Fraction::from_ratio(1, 8));
+}
+
+#[test]
+fn lets_the_strip_brighten_again() {
+    let mut budget = PowerBudget::new(2000);
+    budget.measure(4000);
+
+    budget.measure(1000);
+    assert_eq!(budget.scale(), Fraction::from_ratio(9, 16));
+    for _ in 0..10 {
+        budget.measure(1000);
+    }
+    assert_eq!(budget.scale(), Fraction::ONE);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::advance_ms;
+use panel_core::{
+    fraction::Fraction,
+    pulser::{Pulser, Waveform},
+};
+use std::f32::consts::PI;
+
+/// The floating point version of `Pulser::intensity`, before it was converted to fixed point.
+fn reference_intensity(elapsed_ms: u32, interval_ms: u32) -> f32 {
+    let pattern_ms = (4.0 * PI * interval_ms as f32) as u32;
+    let intervals = (elapsed_ms % pattern_ms) as f32 / interval_ms as f32;
+    let pulse = (intervals.sin() + 1.0) * 0.5;
+    let skip_one = if ((intervals + PI / 2.0) / 2.0).sin() >= 0.0 { 1.0 } else { 0.0 };
+
+    pulse * skip_one
+}
+
+fn as_f32(fraction: Fraction) -> f32 {
+    fraction.of_u16(u16::MAX) as f32 / u16::MAX as f32
+}
+
+#[test]
+fn pulse_pattern_repeats_and_reconfigures() {
+    let mut pulser = Pulser::new(700);
+    assert_eq!(pulser.intensity(), Fraction::from_ratio(1, 2));
+
+    // One pattern is two pulses, the second of which is skipped.
+    let pattern_ms = (4.0 * PI * 700.0) as u32;
+    let mut pattern = Vec::new();
+    for elapsed_ms in 0..pattern_ms {
+        let intensity = pulser.intensity();
+        pattern.push(intensity);
+        advance_ms(1);
+
+        // Well within one step of the LEDs' brightness.
+        assert!((as_f32(intensity) - reference_intensity(elapsed_ms, 700)).abs() < 0.001);
+    }
+
+    assert!(pattern.iter().any(|&intensity| as_f32(intensity) > 0.99));
+    assert!(
+        pattern.iter().filter(|&&intensity| intensity == Fraction::ZERO).count() as u32
+            > pattern_ms / 3
+    );
+
+    for &intensity in &pattern {
+        assert_eq!(pulser.intensity(), intensity);
+        advance_ms(1);
+    }
+
+    // Changing the interval or the waveform carries on from the same intensity.
+    advance_ms(500);
+    let before = pulser.intensity();
+    pulser.reconfigure(1400, Waveform::SkipAlternate);
+    assert!((as_f32(pulser.intensity()) - as_f32(before)).abs() < 0.001);
+
+    while pulser.intensity() != Fraction::ZERO {
+        advance_ms(1);
+    }
+    advance_ms(1000);
+    pulser.reconfigure(1400, Waveform::Sine);
+    assert_eq!(pulser.intensity(), Fraction::ZERO);
+    advance_ms(1);
+    assert!(as_f32(pulser.intensity()) < 0.001);
+
+    advance_ms(2000);
+    let before = pulser.intensity();
+    pulser.reconfigure(700, Waveform::SkipAlternate);
+    assert!((as_f32(pulser.intensity()) - as_f32(before)).abs() < 0.001);
+}
@@ -0,0 +1,52 @@
+use panel_core::schedule::{minute_of_day, Schedule, ScheduleEntry, MAX_ENTRIES};
+
+fn entry(hour: u16, minute: u16, brightness: u16) -> ScheduleEntry {
+    ScheduleEntry { minute_of_day: hour * 60 + minute, brightness, color_temperature: 0 }
+}
+
+#[test]
+fn fires_each_entry_when_its_time_comes() {
+    let mut schedule = Schedule::new();
+    schedule.set(0, Some(entry(7, 0, 1000)));
+    schedule.set(1, Some(entry(19, 0, 200)));
+
+    // The first poll catches up with the entry before now, even if it was yesterday.
+    assert_eq!(schedule.poll(6 * 60), Some(entry(19, 0, 200)));
+    assert_eq!(schedule.poll(6 * 60 + 59), None);
+    assert_eq!(schedule.poll(7 * 60), Some(entry(7, 0, 1000)));
+    assert_eq!(schedule.poll(7 * 60), None);
+    assert_eq!(schedule.poll(18 * 60), None);
+    assert_eq!(schedule.poll(19 * 60 + 5), Some(entry(19, 0, 200)));
+
+    // Past midnight and into the next day.
+    assert_eq!(schedule.poll(23 * 60 + 59), None);
+    assert_eq!(schedule.poll(3), None);
+    assert_eq!(schedule.poll(7 * 60 + 1), Some(entry(7, 0, 1000)));
+
+    // If the clock skips over several, only the latest applies.
+    schedule.set(2, Some(entry(12, 0, 500)));
+    assert_eq!(schedule.poll(20 * 60), Some(entry(19, 0, 200)));
+}
+
+#[test]
+fn keeps_a_fixed_number_of_entries() {
+    let mut schedule = Schedule::new();
+    assert_eq!(schedule.poll(0), None);
+
+    assert!(schedule.set(MAX_ENTRIES - 1, Some(entry(12, 0, 1))));
+    assert!(!schedule.set(MAX_ENTRIES, Some(entry(13, 0, 2))));
+    assert_eq!(schedule.get(MAX_ENTRIES - 1), Some(entry(12, 0, 1)));
+    assert_eq!(schedule.get(MAX_ENTRIES), None);
+
+    assert!(schedule.set(MAX_ENTRIES - 1, None));
+    assert_eq!(schedule.poll(12 * 60), None);
+}
+
+#[test]
+fn finds_the_local_time_of_day() {
+    // 2024-01-01 23:30 UTC.
+    let unix = 1_704_151_800;
+    assert_eq!(minute_of_day(unix, 0), 23 * 60 + 30);
+    assert_eq!(minute_of_day(unix, 60), 30);
+    assert_eq!(minute_of_day(unix, -8 * 60), 15 * 60 + 30);
+}
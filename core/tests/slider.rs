@@ -0,0 +1,35 @@
+use panel_core::slider::Slider;
+
+#[test]
+fn reports_the_first_reading() {
+    let mut slider = Slider::new();
+    assert_eq!(slider.sample(2048), Some(32775));
+}
+
+#[test]
+fn ignores_noise() {
+    let mut slider = Slider::new();
+    slider.sample(2048);
+    for &reading in &[2050, 2046, 2049, 2047, 2051] {
+        assert_eq!(slider.sample(reading), None);
+    }
+}
+
+#[test]
+fn follows_the_slider_to_either_end() {
+    let mut slider = Slider::new();
+    slider.sample(2048);
+
+    // It takes a few readings to follow a move, and reaches the end even if the reading doesn't.
+    let mut position = 0;
+    for _ in 0..100 {
+        position = slider.sample(4090).unwrap_or(position);
+    }
+    assert_eq!(position, u16::MAX);
+
+    for _ in 0..100 {
+        position = slider.sample(3).unwrap_or(position);
+    }
+    assert_eq!(position, 0);
+    assert_eq!(slider.sample(3), None);
+}
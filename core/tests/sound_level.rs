@@ -0,0 +1,40 @@
+use panel_core::{
+    fraction::Fraction,
+    sound_level::{SoundLevel, UNITY_SENSITIVITY},
+};
+
+#[test]
+fn follows_the_level_smoothly() {
+    // The LEDs aren't dimmed while the effect is off.
+    let mut sound = SoundLevel::new();
+    assert_eq!(sound.intensity(), Fraction::ONE);
+
+    sound.configure(true, UNITY_SENSITIVITY, 1);
+    assert_eq!(sound.intensity(), Fraction::ZERO);
+
+    // Each reading halves the distance to it.
+    sound.sample(0xfff);
+    let half = sound.intensity();
+    assert!(half > Fraction::from_ratio(49, 100) && half < Fraction::from_ratio(51, 100));
+    for _ in 0..30 {
+        sound.sample(0xfff);
+    }
+    assert!(sound.intensity() > Fraction::from_ratio(99, 100));
+
+    for _ in 0..30 {
+        sound.sample(0);
+    }
+    assert!(sound.intensity() < Fraction::from_ratio(1, 100));
+}
+
+#[test]
+fn scales_by_the_sensitivity() {
+    let mut sound = SoundLevel::new();
+    sound.configure(true, UNITY_SENSITIVITY * 4, 0);
+
+    sound.sample(0xfff / 8);
+    let intensity = sound.intensity();
+    assert!(intensity > Fraction::from_ratio(49, 100) && intensity < Fraction::from_ratio(51, 100));
+    sound.sample(0xfff / 2);
+    assert_eq!(sound.intensity(), Fraction::ONE);
+}
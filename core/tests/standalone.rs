@@ -0,0 +1,62 @@
+mod common;
+
+use common::advance_ms;
+use panel_core::{button::ButtonEvent, input::InputEvent, standalone::Standalone};
+
+#[test]
+fn takes_over_the_lights_without_a_host() {
+    let mut standalone = Standalone::new(1000, 0x8000);
+
+    // Input is left to the host until it has had its chance to connect.
+    advance_ms(999);
+    assert_eq!(standalone.poll(false), None);
+    assert_eq!(standalone.handle(&InputEvent::Dial(1)), None);
+    advance_ms(1);
+    assert_eq!(standalone.poll(false), Some(true));
+    assert_eq!(standalone.light_brightness(), 0x8000);
+
+    let step = u16::MAX / 32;
+    assert_eq!(standalone.handle(&InputEvent::Dial(2)), Some(0x8000 + 2 * step));
+    assert_eq!(standalone.handle(&InputEvent::Dial(-1)), Some(0x8000 + step));
+    assert_eq!(standalone.handle(&InputEvent::Dial(100)), Some(u16::MAX));
+    assert_eq!(standalone.handle(&InputEvent::Button(ButtonEvent::Pressed)), None);
+    assert_eq!(standalone.handle(&InputEvent::Button(ButtonEvent::ShortRelease)), Some(0));
+
+    // Turning the dial turns the lights back on.
+    assert_eq!(standalone.handle(&InputEvent::Dial(-1)), Some(u16::MAX - step));
+
+    assert_eq!(standalone.poll(true), Some(false));
+    assert!(!standalone.is_active());
+    assert_eq!(standalone.poll(true), None);
+
+    // It starts again, with the lights on, once the host has been gone for long enough.
+    standalone.handle(&InputEvent::Button(ButtonEvent::ShortRelease));
+    advance_ms(500);
+    assert_eq!(standalone.poll(false), None);
+    advance_ms(500);
+    assert_eq!(standalone.poll(false), Some(true));
+    assert_eq!(standalone.light_brightness(), u16::MAX - step);
+
+    // Unplugged, there's no host to wait for.
+    assert_eq!(standalone.poll(true), Some(false));
+    standalone.set_cable_present(false);
+    assert_eq!(standalone.poll(false), Some(true));
+
+    // A host which stops sending heartbeats is gone, even with the port open.
+    standalone.set_cable_present(true);
+    assert_eq!(standalone.poll(true), Some(false));
+    standalone.heartbeat(100);
+    advance_ms(99);
+    assert_eq!(standalone.poll(true), None);
+    standalone.heartbeat(100);
+    advance_ms(99);
+    assert_eq!(standalone.poll(true), None);
+    advance_ms(1);
+    assert_eq!(standalone.poll(true), Some(true));
+
+    // Once they start again the host takes back over, and a timeout of 0 stops the checks.
+    standalone.heartbeat(0);
+    advance_ms(1000);
+    assert_eq!(standalone.poll(true), Some(false));
+    assert_eq!(standalone.poll(true), None);
+}
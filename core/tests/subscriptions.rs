@@ -0,0 +1,27 @@
+use panel_core::subscriptions::{Category, Subscriptions};
+use panel_protocol::Report;
+
+#[test]
+fn only_unsubscribed_reports_are_held_back() {
+    let mut subscriptions = Subscriptions::ALL;
+    for category in Category::ALL.iter().copied() {
+        if category != Category::Dial {
+            subscriptions.set(category, false);
+        }
+    }
+
+    assert!(subscriptions.wants(&Report::DialValue { diff: 3 }));
+    assert!(subscriptions.wants(&Report::DialHomed));
+    assert!(!subscriptions.wants(&Report::Press));
+    assert!(!subscriptions.wants(&Report::AmbientLight { lux: 120 }));
+    assert!(!subscriptions.wants(&Report::CorruptFrames { count: 1 }));
+
+    // Answers to the host's own commands always go out.
+    assert!(subscriptions.wants(&Report::Pong { token: 7 }));
+    assert!(subscriptions.wants(&Report::Ack { command: 1 }));
+
+    subscriptions.set(Category::Button, true);
+    assert!(subscriptions.wants(&Report::LongPress));
+    assert_eq!(Subscriptions::from_bits(subscriptions.bits()), subscriptions);
+    assert_eq!(Category::from_u8(Category::ALL.len() as u8), None);
+}
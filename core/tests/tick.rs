@@ -0,0 +1,22 @@
+mod common;
+
+use common::advance_ms;
+use panel_core::tick::{Duration, Every, Instant};
+
+#[test]
+fn every_runs_once_per_period() {
+    let start = Instant::now();
+    let mut every = Every::new(16);
+    let mut runs = 0;
+
+    for _ in 0..100 {
+        if every.is_due(Instant::now()) {
+            runs += 1;
+        }
+        advance_ms(1);
+    }
+
+    // At 0, 16, 32, 48, 64, 80 and 96 ms.
+    assert_eq!(runs, 7);
+    assert_eq!(start.elapsed(), Duration::from_ms(100));
+}
@@ -0,0 +1,122 @@
+// Replays the transcripts in tests/transcripts/ through the same input handling as the firmware,
+// and checks the reports it would send the host. Each line of a transcript is either an input,
+// applied that many milliseconds after the start:
+//
+//     <ms> button down|up
+//     <ms> dial <count>
+//     <ms> index active|inactive
+//     <ms> protocol <version>
+//     <ms> dial_rate <max reports per second>
+//
+// or a report which the inputs so far should have produced, as formatted by `Debug`:
+//
+//     report <report>
+
+mod common;
+
+use common::{advance_ms, FakePin, FakeQei};
+use panel_core::{
+    button::{Active, Button, Debouncer},
+    counter::Counter,
+    input::{self, DialRateLimit, DEFAULT_PROTOCOL_VERSION},
+};
+use std::{cell::Cell, fs, path::Path};
+
+/// Inputs keep being polled for this long after the last one, so that slow events like a long
+/// press have time to happen.
+const SETTLE_MS: u32 = 1_500;
+
+enum Line {
+    Input { at_ms: u32, name: String, value: String },
+    Report(String),
+}
+
+fn parse(transcript: &str) -> Vec<Line> {
+    transcript
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if let Some(report) = line.strip_prefix("report ") {
+                return Line::Report(report.to_string());
+            }
+
+            let mut words = line.split_whitespace();
+            let mut next = || words.next().unwrap_or_else(|| panic!("bad line: {}", line));
+            let at_ms = next().parse().unwrap_or_else(|_| panic!("bad time: {}", line));
+            Line::Input { at_ms, name: next().to_string(), value: next().to_string() }
+        })
+        .collect()
+}
+
+/// Runs a transcript, and returns the reports which were expected and those which were sent.
+fn replay(transcript: &str) -> (Vec<String>, Vec<String>) {
+    let lines = parse(transcript);
+
+    // The button and index pins are active low, and pulled up when idle.
+    let button_level = Cell::new(true);
+    let index_level = Cell::new(true);
+    let count = Cell::new(0);
+    let mut protocol_version = DEFAULT_PROTOCOL_VERSION;
+
+    let debouncer = Debouncer::new(FakePin(&button_level), Active::Low, 30, 1000);
+    let mut button = Button::new(debouncer, 1000);
+    let mut counter = Counter::new(FakeQei(&count), Some((FakePin(&index_level), Active::Low)));
+    let mut dial_limit = DialRateLimit::new(0);
+
+    let mut inputs: Vec<_> = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Input { at_ms, name, value } => Some((*at_ms, name.as_str(), value.as_str())),
+            Line::Report(_) => None,
+        })
+        .collect();
+    inputs.sort_by_key(|&(at_ms, _, _)| at_ms);
+    let expected = lines.iter().filter_map(|line| match line {
+        Line::Report(report) => Some(report.clone()),
+        Line::Input { .. } => None,
+    });
+
+    let end_ms = inputs.last().map_or(0, |&(at_ms, _, _)| at_ms) + SETTLE_MS;
+    let mut pending = inputs.into_iter().peekable();
+    let mut sent = Vec::new();
+
+    for ms in 0..end_ms {
+        while let Some((_, name, value)) = pending.next_if(|&(at_ms, _, _)| at_ms == ms) {
+            match (name, value) {
+                ("button", "down") => button_level.set(false),
+                ("button", "up") => button_level.set(true),
+                ("index", "active") => index_level.set(false),
+                ("index", "inactive") => index_level.set(true),
+                ("dial", count_value) => count.set(count_value.parse().unwrap()),
+                ("protocol", version) => protocol_version = version.parse().unwrap(),
+                ("dial_rate", rate) => dial_limit.set_max_per_second(rate.parse().unwrap()),
+                _ => panic!("unknown input: {} {}", name, value),
+            }
+        }
+
+        advance_ms(1);
+        input::poll(&mut button, &mut counter, &mut dial_limit, |event| {
+            if let Some(report) = event.report(protocol_version) {
+                sent.push(format!("{:?}", report));
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    (expected.collect(), sent)
+}
+
+#[test]
+fn transcripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/transcripts");
+    let mut paths: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let (expected, sent) = replay(&fs::read_to_string(&path).unwrap());
+        assert_eq!(sent, expected, "{}", path.display());
+    }
+}
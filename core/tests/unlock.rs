@@ -0,0 +1,27 @@
+mod common;
+
+use common::advance_ms;
+use panel_core::unlock::{Unlock, WINDOW};
+
+#[test]
+fn lets_one_command_through_within_the_window() {
+    let mut unlock = Unlock::new(0x1234);
+    assert!(!unlock.take());
+
+    assert!(!unlock.unlock(0x4321));
+    assert!(!unlock.take());
+
+    assert!(unlock.unlock(0x1234));
+    advance_ms(WINDOW.as_ms());
+    assert!(unlock.take());
+    assert!(!unlock.take());
+
+    assert!(unlock.unlock(0x1234));
+    advance_ms(WINDOW.as_ms() + 1);
+    assert!(!unlock.take());
+
+    // A wrong key locks it again.
+    assert!(unlock.unlock(0x1234));
+    assert!(!unlock.unlock(0));
+    assert!(!unlock.take());
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{advance_ms, FakePin};
+use panel_core::{
+    button::{Active, Button, ButtonEvent, Debouncer},
+    power::{PowerManager, PowerState},
+    tick::{Duration, Every, Instant},
+};
+use std::cell::Cell;
+
+#[test]
+fn timing_survives_the_clock_wrapping() {
+    // Get to just before the millisecond clock wraps, after ~49 days.
+    advance_ms(u32::MAX - 499);
+    let before = Instant::now();
+    let mut power = PowerManager::new(1000, 5000);
+
+    let level = Cell::new(true);
+    let debouncer = Debouncer::new(FakePin(&level), Active::Low, 1, 1000);
+    let mut button = Button::new(debouncer, 1000);
+    level.set(false);
+    assert_eq!(button.poll(), Some(ButtonEvent::Pressed));
+
+    // Schedules start from 0 ms, so this one first runs as the clock wraps.
+    let mut every = Every::new(100);
+    let mut runs = 0;
+    for _ in 0..1000 {
+        if every.is_due(Instant::now()) {
+            runs += 1;
+        }
+        advance_ms(1);
+    }
+    assert_eq!(runs, 5);
+
+    // The button was pressed down before the wrap.
+    assert_eq!(button.poll(), None);
+    advance_ms(1);
+    assert_eq!(button.poll(), Some(ButtonEvent::LongPress));
+
+    assert_eq!(before.elapsed(), Duration::from_ms(1001));
+    assert_eq!(Instant::now().duration_since(before), Duration::from_ms(1001));
+
+    assert_eq!(power.poll(), Some(PowerState::Dimmed));
+    power.input_activity();
+    assert_eq!(power.poll(), Some(PowerState::Active));
+}
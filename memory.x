@@ -1,8 +1,12 @@
 MEMORY
 {
   /* NOTE K = KiBi = 1024 bytes */
-  FLASH : ORIGIN = 0x08000000, LENGTH = 128K
-  RAM : ORIGIN = 0x20000000, LENGTH = 20K
+  /* The first 16K of flash are reserved for the bootloader (bootloader/memory.x), and the last 3K
+     for the settings store (src/eeprom.rs) and the panic log (src/panic_log.rs), in that order.
+     The panic log's page also holds the brownout snapshot (src/brownout.rs). */
+  FLASH : ORIGIN = 0x08004000, LENGTH = 109K
+  /* The first word of RAM is the bootload flag, shared with the bootloader. */
+  RAM : ORIGIN = 0x20000004, LENGTH = 20K - 4
 }
 
 /* This is where the call stack will be allocated. */
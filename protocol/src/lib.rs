@@ -0,0 +1,536 @@
+#![no_std]
+
+// The commands the host sends the panel, the reports the panel sends back, and how each is
+// packed into bytes. The firmware, panel-core, the client library and the simulator all build
+// against this crate from the same tree, so the two ends of the link can't disagree on the wire
+// format.
+//
+// Each message is encoded by postcard, from its serde derives: the variant's index as a varint,
+// so its position in the enum, then its fields in order, with integers wider than a byte as
+// varints too. New variants only ever go at the end, so that the existing ones keep their
+// numbers, and new fields only go in new variants. While there are fewer than 128 variants, the
+// first byte of a message says which it is. What each one does is in the README.
+
+use arrayvec::Array;
+pub use arrayvec::{ArrayString, ArrayVec};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The longest a command is on the wire.
+pub const MAX_COMMAND_LEN: usize = 64;
+/// The longest a report is on the wire.
+pub const MAX_REPORT_LEN: usize = 64;
+/// The most bytes of text in one `Report::Debug`, which leaves room for its id and length.
+/// arrayvec only has strings of some lengths, and this is the longest which fits.
+pub const MAX_DEBUG_MSG_LEN: usize = 56;
+/// The most bytes of the image in one `Command::UpdateChunk`.
+pub const UPDATE_CHUNK_LEN: usize = 48;
+/// The most commands `CommandReader::process_bytes()` returns at once.
+pub const MAX_COMMAND_QUEUE_LEN: usize = 8;
+/// The most reports `ReportReader::process_bytes()` returns at once, as many as fit in a USB
+/// packet.
+pub const MAX_REPORT_QUEUE_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The bytes so far are longer than any message, without being a whole one.
+    BufferFull,
+    MalformedMessage,
+    CommandQueueFull,
+    ReportQueueFull,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    Brightness {
+        target: u8,
+        value: u16,
+    },
+    Temperature {
+        target: u8,
+        value: u16,
+    },
+    Led {
+        r: u8,
+        g: u8,
+        b: u8,
+        pulse: bool,
+    },
+    Bootload,
+    SelfTest,
+    SetTime {
+        unix_seconds: u32,
+    },
+    GetDiagnostics,
+    GetReadoutProtection,
+    EnableReadoutProtection {
+        key: u32,
+    },
+    BeginUpdate {
+        length: u32,
+        crc: u32,
+    },
+    UpdateChunk {
+        offset: u32,
+        data: ArrayVec<[u8; UPDATE_CHUNK_LEN]>,
+    },
+    FinishUpdate,
+    Reboot,
+    Sleep,
+    Wake,
+    GetProfile,
+    SetAutoBrightness {
+        min: u16,
+        max: u16,
+    },
+    DisableAutoBrightness,
+    OverrideBrightness {
+        value: u16,
+    },
+    ClearBrightnessOverride,
+    Beep {
+        freq: u16,
+        duration: u16,
+    },
+    SetClickFeedback {
+        enabled: bool,
+    },
+    BindIrCode {
+        protocol: u8,
+        address: u16,
+        command: u8,
+        action: u8,
+    },
+    UnbindIrCode {
+        action: u8,
+    },
+    SetOutput {
+        channel: u8,
+        on: bool,
+    },
+    GetOutputs,
+    SetScheduleEntry {
+        index: u8,
+        hour: u8,
+        minute: u8,
+        brightness: u16,
+        color_temperature: u16,
+    },
+    ClearScheduleEntry {
+        index: u8,
+    },
+    SetUtcOffset {
+        minutes: i16,
+    },
+    SetHapticEffect {
+        event: u8,
+        effect: u8,
+    },
+    GetDoorState,
+    SetSoundReactive {
+        enabled: bool,
+        sensitivity: u16,
+        smoothing: u8,
+    },
+    SetDaylightCurve {
+        sunrise_hour: u8,
+        sunrise_minute: u8,
+        sunset_hour: u8,
+        sunset_minute: u8,
+        night: u16,
+        midday: u16,
+    },
+    DisableDaylightCurve,
+    OverrideColorTemperature {
+        value: u16,
+    },
+    ClearColorTemperatureOverride,
+    Unlock {
+        key: u32,
+    },
+    SetProtocolVersion {
+        version: u8,
+    },
+    SetDefaultLed {
+        r: u8,
+        g: u8,
+        b: u8,
+        pulse: bool,
+    },
+    Hello {
+        version: u8,
+    },
+    GetState {
+        target: u8,
+    },
+    GetFirmwareVersion,
+    GetDeviceId,
+    Ping {
+        token: u32,
+    },
+    GetReportSequence,
+    Heartbeat {
+        timeout_ms: u16,
+    },
+    SetSuspendPolicy {
+        policy: u8,
+    },
+    SetDialRate {
+        max_per_second: u16,
+    },
+    GetStatus,
+    SetStatusOnConnect {
+        enabled: bool,
+    },
+    LedAt {
+        index: u16,
+        r: u8,
+        g: u8,
+        b: u8,
+    },
+    CommitLeds,
+    StreamLeds {
+        enabled: bool,
+    },
+    SaveScene {
+        slot: u8,
+    },
+    RecallScene {
+        slot: u8,
+    },
+    SetConfig {
+        key: u8,
+        value: u16,
+    },
+    GetConfig {
+        key: u8,
+    },
+    GetCapabilities,
+    Subscribe {
+        category: u8,
+    },
+    Unsubscribe {
+        category: u8,
+    },
+    Reset,
+}
+
+impl Command {
+    pub fn as_arrayvec(&self) -> ArrayVec<[u8; MAX_COMMAND_LEN]> {
+        encode(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Report {
+    Press,
+    LongPress,
+    DialValue {
+        diff: i8,
+    },
+    Debug {
+        message: ArrayString<[u8; MAX_DEBUG_MSG_LEN]>,
+    },
+    DialHomed,
+    SelfTest {
+        pwm: bool,
+        strip: bool,
+        counter: bool,
+        button: bool,
+    },
+    CpuLoad {
+        percent: u8,
+    },
+    LowVoltage {
+        count: u16,
+    },
+    VoltageRecovered,
+    DeviceInfo {
+        unique_id: [u8; 12],
+        flash_size_kb: u16,
+        revision: u16,
+    },
+    Diagnostics {
+        clean_boots: u16,
+        watchdog_resets: u16,
+        panics: u16,
+        low_voltage_events: u16,
+    },
+    ReadoutProtection {
+        enabled: bool,
+    },
+    UpdateProgress {
+        written: u32,
+    },
+    UpdateVerified,
+    BootDiagnostics {
+        faults: u16,
+    },
+    Profile {
+        section: u8,
+        max_cycles: u32,
+        mean_cycles: u32,
+        count: u32,
+    },
+    AmbientLight {
+        lux: u16,
+    },
+    SliderValue {
+        channel: u8,
+        value: u16,
+    },
+    IrCode {
+        protocol: u8,
+        address: u16,
+        command: u8,
+        repeat: bool,
+    },
+    Fan {
+        rpm: u16,
+        duty: u16,
+        temperature: i16,
+    },
+    Output {
+        channel: u8,
+        on: bool,
+    },
+    FixtureTemperature {
+        probe: u8,
+        temperature: i16,
+    },
+    LedRail {
+        millivolts: u16,
+        milliamps: u16,
+    },
+    DoorState {
+        open: bool,
+    },
+    ExpanderButton {
+        pin: u8,
+        long: bool,
+    },
+    Nudge {
+        direction: u8,
+    },
+    ColorTemperatureSetpoint {
+        value: u16,
+    },
+    DialTurned {
+        diff: i8,
+        button_held: bool,
+    },
+    ReportsDropped {
+        count: u16,
+    },
+    CorruptFrames {
+        count: u16,
+    },
+    Hello {
+        version: u8,
+    },
+    Ack {
+        command: u8,
+    },
+    Nack {
+        command: u8,
+        reason: u8,
+    },
+    State {
+        target: u8,
+        brightness: u16,
+        color_temperature: u16,
+        r: u8,
+        g: u8,
+        b: u8,
+        pulse: bool,
+    },
+    FirmwareVersion {
+        major: u8,
+        minor: u8,
+        patch: u8,
+        build_timestamp: u32,
+        commit: u32,
+        dirty: bool,
+    },
+    DeviceId {
+        unique_id: [u8; 12],
+    },
+    Pong {
+        token: u32,
+    },
+    ReportSequence {
+        sent: u16,
+    },
+    Status {
+        front_brightness: u16,
+        front_temperature: u16,
+        back_brightness: u16,
+        back_temperature: u16,
+        r: u8,
+        g: u8,
+        b: u8,
+        pulse: bool,
+        button_pressed: bool,
+        uptime_ms: u32,
+    },
+    Config {
+        key: u8,
+        value: u16,
+    },
+    ReceiveOverflow {
+        bytes: u16,
+        commands: u16,
+    },
+    Capabilities {
+        lights: u8,
+        led_count: u16,
+        outputs: u8,
+        sliders: u8,
+        features: u32,
+    },
+}
+
+impl Report {
+    pub fn as_arrayvec(&self) -> ArrayVec<[u8; MAX_REPORT_LEN]> {
+        encode(self)
+    }
+}
+
+/// Puts commands back together from bytes, wherever the reads they come in are split.
+pub struct CommandReader {
+    reader: Reader<[u8; MAX_COMMAND_LEN]>,
+}
+
+impl CommandReader {
+    pub fn new() -> Self {
+        Self { reader: Reader::new() }
+    }
+
+    /// Adds `bytes` to what's left from last time, and returns the commands they complete. An
+    /// error drops everything so far, and the next call starts on a new command.
+    pub fn process_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<ArrayVec<[Command; MAX_COMMAND_QUEUE_LEN]>, Error> {
+        self.reader.process_bytes(bytes, Error::CommandQueueFull)
+    }
+
+    /// Reads the next command from the front of `bytes`, and moves `bytes` on past it. A command
+    /// which is all in `bytes` is read straight out of it, without being copied into the reader
+    /// first. Returns `None` if `bytes` runs out part way through a command, the start of which
+    /// is kept for the next call. An error drops the command so far, and the next call starts on
+    /// a new command from the byte after the one which was malformed.
+    pub fn next_command(&mut self, bytes: &mut &[u8]) -> Result<Option<Command>, Error> {
+        self.reader.next(bytes)
+    }
+}
+
+impl Default for CommandReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The host's side of `CommandReader`.
+pub struct ReportReader {
+    reader: Reader<[u8; MAX_REPORT_LEN]>,
+}
+
+impl ReportReader {
+    pub fn new() -> Self {
+        Self { reader: Reader::new() }
+    }
+
+    /// Adds `bytes` to what's left from last time, and returns the reports they complete. An
+    /// error drops everything so far, and the next call starts on a new report.
+    pub fn process_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<ArrayVec<[Report; MAX_REPORT_QUEUE_LEN]>, Error> {
+        self.reader.process_bytes(bytes, Error::ReportQueueFull)
+    }
+}
+
+impl Default for ReportReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Holds on to the start of a message until the rest of it comes in.
+struct Reader<B: Array<Item = u8>> {
+    buf: ArrayVec<B>,
+}
+
+impl<B: Array<Item = u8>> Reader<B> {
+    fn new() -> Self {
+        Self { buf: ArrayVec::new() }
+    }
+
+    fn process_bytes<T, Q>(
+        &mut self,
+        mut bytes: &[u8],
+        queue_full: Error,
+    ) -> Result<ArrayVec<Q>, Error>
+    where
+        T: DeserializeOwned,
+        Q: Array<Item = T>,
+    {
+        let mut messages = ArrayVec::new();
+        while let Some(message) = self.next(&mut bytes)? {
+            if messages.try_push(message).is_err() {
+                self.buf.clear();
+                return Err(queue_full);
+            }
+        }
+        Ok(messages)
+    }
+
+    fn next<T: DeserializeOwned>(&mut self, bytes: &mut &[u8]) -> Result<Option<T>, Error> {
+        if self.buf.is_empty() {
+            match postcard::take_from_bytes::<T>(bytes) {
+                Ok((message, rest)) => {
+                    *bytes = rest;
+                    return Ok(Some(message));
+                },
+                // Only the start of a message, the rest of which is still to come.
+                Err(postcard::Error::DeserializeUnexpectedEnd) if bytes.len() < B::CAPACITY => {
+                    let _ = self.buf.try_extend_from_slice(bytes);
+                    *bytes = &[];
+                    return Ok(None);
+                },
+                // Which byte made it malformed, or too long, is only found out a byte at a time.
+                Err(_) => {},
+            }
+        }
+
+        // Adding a byte at a time means that whatever the buffer holds once a message is read
+        // is exactly that message.
+        while let Some((&byte, rest)) = bytes.split_first() {
+            *bytes = rest;
+            self.buf.push(byte);
+            match postcard::take_from_bytes::<T>(&self.buf) {
+                Ok((message, _)) => {
+                    self.buf.clear();
+                    return Ok(Some(message));
+                },
+                Err(postcard::Error::DeserializeUnexpectedEnd) if self.buf.is_full() => {
+                    self.buf.clear();
+                    return Err(Error::BufferFull);
+                },
+                Err(postcard::Error::DeserializeUnexpectedEnd) => {},
+                Err(_) => {
+                    self.buf.clear();
+                    return Err(Error::MalformedMessage);
+                },
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn encode<T: Serialize, B: Array<Item = u8>>(message: &T) -> ArrayVec<B> {
+    let mut bytes: ArrayVec<B> = core::iter::repeat_n(0, B::CAPACITY).collect();
+    let len = postcard::to_slice(message, &mut bytes).expect("every message fits").len();
+    bytes.truncate(len);
+    bytes
+}
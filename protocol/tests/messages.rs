@@ -0,0 +1,252 @@
+// Every command and report, with each field at whichever value takes the most bytes on the wire,
+// so that a new variant, or a wider field, which doesn't fit in MAX_COMMAND_LEN or MAX_REPORT_LEN
+// is caught here rather than by encode()'s panic on the panel.
+
+use panel_protocol::{
+    ArrayString, ArrayVec, Command, CommandReader, Error, Report, ReportReader, MAX_COMMAND_LEN,
+    MAX_DEBUG_MSG_LEN, MAX_REPORT_LEN, UPDATE_CHUNK_LEN,
+};
+
+/// One of each command, in the order they're numbered.
+fn commands() -> Vec<Command> {
+    let (target, value) = (u8::MAX, u16::MAX);
+    vec![
+        Command::Brightness { target, value },
+        Command::Temperature { target, value },
+        Command::Led { r: 255, g: 255, b: 255, pulse: true },
+        Command::Bootload,
+        Command::SelfTest,
+        Command::SetTime { unix_seconds: u32::MAX },
+        Command::GetDiagnostics,
+        Command::GetReadoutProtection,
+        Command::EnableReadoutProtection { key: u32::MAX },
+        Command::BeginUpdate { length: u32::MAX, crc: u32::MAX },
+        Command::UpdateChunk { offset: u32::MAX, data: ArrayVec::from([0xFF; UPDATE_CHUNK_LEN]) },
+        Command::FinishUpdate,
+        Command::Reboot,
+        Command::Sleep,
+        Command::Wake,
+        Command::GetProfile,
+        Command::SetAutoBrightness { min: u16::MAX, max: u16::MAX },
+        Command::DisableAutoBrightness,
+        Command::OverrideBrightness { value },
+        Command::ClearBrightnessOverride,
+        Command::Beep { freq: u16::MAX, duration: u16::MAX },
+        Command::SetClickFeedback { enabled: true },
+        Command::BindIrCode {
+            protocol: u8::MAX,
+            address: u16::MAX,
+            command: u8::MAX,
+            action: u8::MAX,
+        },
+        Command::UnbindIrCode { action: u8::MAX },
+        Command::SetOutput { channel: u8::MAX, on: true },
+        Command::GetOutputs,
+        Command::SetScheduleEntry {
+            index: u8::MAX,
+            hour: u8::MAX,
+            minute: u8::MAX,
+            brightness: u16::MAX,
+            color_temperature: u16::MAX,
+        },
+        Command::ClearScheduleEntry { index: u8::MAX },
+        Command::SetUtcOffset { minutes: i16::MIN },
+        Command::SetHapticEffect { event: u8::MAX, effect: u8::MAX },
+        Command::GetDoorState,
+        Command::SetSoundReactive { enabled: true, sensitivity: u16::MAX, smoothing: u8::MAX },
+        Command::SetDaylightCurve {
+            sunrise_hour: u8::MAX,
+            sunrise_minute: u8::MAX,
+            sunset_hour: u8::MAX,
+            sunset_minute: u8::MAX,
+            night: u16::MAX,
+            midday: u16::MAX,
+        },
+        Command::DisableDaylightCurve,
+        Command::OverrideColorTemperature { value },
+        Command::ClearColorTemperatureOverride,
+        Command::Unlock { key: u32::MAX },
+        Command::SetProtocolVersion { version: u8::MAX },
+        Command::SetDefaultLed { r: 255, g: 255, b: 255, pulse: true },
+        Command::Hello { version: u8::MAX },
+        Command::GetState { target },
+        Command::GetFirmwareVersion,
+        Command::GetDeviceId,
+        Command::Ping { token: u32::MAX },
+        Command::GetReportSequence,
+        Command::Heartbeat { timeout_ms: u16::MAX },
+        Command::SetSuspendPolicy { policy: u8::MAX },
+        Command::SetDialRate { max_per_second: u16::MAX },
+        Command::GetStatus,
+        Command::SetStatusOnConnect { enabled: true },
+        Command::LedAt { index: u16::MAX, r: 255, g: 255, b: 255 },
+        Command::CommitLeds,
+        Command::StreamLeds { enabled: true },
+        Command::SaveScene { slot: u8::MAX },
+        Command::RecallScene { slot: u8::MAX },
+        Command::SetConfig { key: u8::MAX, value },
+        Command::GetConfig { key: u8::MAX },
+        Command::GetCapabilities,
+        Command::Subscribe { category: u8::MAX },
+        Command::Unsubscribe { category: u8::MAX },
+        Command::Reset,
+    ]
+}
+
+/// One of each report, in the order they're numbered.
+fn reports() -> Vec<Report> {
+    // The longest string which fits, all in characters which take more than one byte.
+    let mut message = ArrayString::new();
+    while message.try_push('é').is_ok() {}
+    assert_eq!(message.len(), MAX_DEBUG_MSG_LEN);
+
+    vec![
+        Report::Press,
+        Report::LongPress,
+        Report::DialValue { diff: i8::MIN },
+        Report::Debug { message },
+        Report::DialHomed,
+        Report::SelfTest { pwm: true, strip: true, counter: true, button: true },
+        Report::CpuLoad { percent: u8::MAX },
+        Report::LowVoltage { count: u16::MAX },
+        Report::VoltageRecovered,
+        Report::DeviceInfo { unique_id: [0xFF; 12], flash_size_kb: u16::MAX, revision: u16::MAX },
+        Report::Diagnostics {
+            clean_boots: u16::MAX,
+            watchdog_resets: u16::MAX,
+            panics: u16::MAX,
+            low_voltage_events: u16::MAX,
+        },
+        Report::ReadoutProtection { enabled: true },
+        Report::UpdateProgress { written: u32::MAX },
+        Report::UpdateVerified,
+        Report::BootDiagnostics { faults: u16::MAX },
+        Report::Profile {
+            section: u8::MAX,
+            max_cycles: u32::MAX,
+            mean_cycles: u32::MAX,
+            count: u32::MAX,
+        },
+        Report::AmbientLight { lux: u16::MAX },
+        Report::SliderValue { channel: u8::MAX, value: u16::MAX },
+        Report::IrCode { protocol: u8::MAX, address: u16::MAX, command: u8::MAX, repeat: true },
+        Report::Fan { rpm: u16::MAX, duty: u16::MAX, temperature: i16::MIN },
+        Report::Output { channel: u8::MAX, on: true },
+        Report::FixtureTemperature { probe: u8::MAX, temperature: i16::MIN },
+        Report::LedRail { millivolts: u16::MAX, milliamps: u16::MAX },
+        Report::DoorState { open: true },
+        Report::ExpanderButton { pin: u8::MAX, long: true },
+        Report::Nudge { direction: u8::MAX },
+        Report::ColorTemperatureSetpoint { value: u16::MAX },
+        Report::DialTurned { diff: i8::MIN, button_held: true },
+        Report::ReportsDropped { count: u16::MAX },
+        Report::CorruptFrames { count: u16::MAX },
+        Report::Hello { version: u8::MAX },
+        Report::Ack { command: u8::MAX },
+        Report::Nack { command: u8::MAX, reason: u8::MAX },
+        Report::State {
+            target: u8::MAX,
+            brightness: u16::MAX,
+            color_temperature: u16::MAX,
+            r: 255,
+            g: 255,
+            b: 255,
+            pulse: true,
+        },
+        Report::FirmwareVersion {
+            major: u8::MAX,
+            minor: u8::MAX,
+            patch: u8::MAX,
+            build_timestamp: u32::MAX,
+            commit: u32::MAX,
+            dirty: true,
+        },
+        Report::DeviceId { unique_id: [0xFF; 12] },
+        Report::Pong { token: u32::MAX },
+        Report::ReportSequence { sent: u16::MAX },
+        Report::Status {
+            front_brightness: u16::MAX,
+            front_temperature: u16::MAX,
+            back_brightness: u16::MAX,
+            back_temperature: u16::MAX,
+            r: 255,
+            g: 255,
+            b: 255,
+            pulse: true,
+            button_pressed: true,
+            uptime_ms: u32::MAX,
+        },
+        Report::Config { key: u8::MAX, value: u16::MAX },
+        Report::ReceiveOverflow { bytes: u16::MAX, commands: u16::MAX },
+        Report::Capabilities {
+            lights: u8::MAX,
+            led_count: u16::MAX,
+            outputs: u8::MAX,
+            sliders: u8::MAX,
+            features: u32::MAX,
+        },
+    ]
+}
+
+#[test]
+fn lists_every_command() {
+    // While there are fewer than 128 commands, the first byte is the command's number.
+    for (i, command) in commands().iter().enumerate() {
+        assert_eq!(command.as_arrayvec()[0] as usize, i, "{:?}", command);
+    }
+    let mut bytes = &[commands().len() as u8][..];
+    assert_eq!(CommandReader::new().next_command(&mut bytes), Err(Error::MalformedMessage));
+}
+
+#[test]
+fn lists_every_report() {
+    for (i, report) in reports().iter().enumerate() {
+        assert_eq!(report.as_arrayvec()[0] as usize, i, "{:?}", report);
+    }
+    let bytes = [reports().len() as u8];
+    assert_eq!(ReportReader::new().process_bytes(&bytes).err(), Some(Error::MalformedMessage));
+}
+
+#[test]
+fn the_longest_messages_fit() {
+    let longest_command = commands().iter().map(|command| command.as_arrayvec().len()).max();
+    let longest_report = reports().iter().map(|report| report.as_arrayvec().len()).max();
+    assert!(longest_command.unwrap() <= MAX_COMMAND_LEN);
+    assert!(longest_report.unwrap() <= MAX_REPORT_LEN);
+}
+
+#[test]
+fn commands_round_trip() {
+    for command in commands() {
+        let bytes = command.as_arrayvec();
+
+        // All at once, which is read straight out of the bytes.
+        let mut reader = CommandReader::new();
+        let mut chunk = &bytes[..];
+        assert_eq!(reader.next_command(&mut chunk), Ok(Some(command.clone())));
+        assert!(chunk.is_empty());
+
+        // A byte at a time, which goes through the reader's buffer.
+        let mut parsed = Vec::new();
+        for byte in bytes.chunks(1) {
+            parsed.extend(reader.process_bytes(byte).unwrap());
+        }
+        assert_eq!(parsed, [command]);
+    }
+}
+
+#[test]
+fn reports_round_trip() {
+    for report in reports() {
+        let bytes = report.as_arrayvec();
+
+        let mut reader = ReportReader::new();
+        assert_eq!(reader.process_bytes(&bytes).unwrap().as_slice(), std::slice::from_ref(&report));
+
+        let mut parsed = Vec::new();
+        for byte in bytes.chunks(1) {
+            parsed.extend(reader.process_bytes(byte).unwrap());
+        }
+        assert_eq!(parsed, [report]);
+    }
+}
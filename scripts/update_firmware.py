@@ -0,0 +1,37 @@
+#!/usr/bin/env python3
+# Uploads an application image to a panel which is running the bootloader, see
+# bootloader/src/main.rs for the protocol.
+#
+# Usage: update_firmware.py <serial port> <image.bin>
+
+import struct
+import sys
+import zlib
+
+import serial  # pip install pyserial
+
+
+def main():
+    port, path = sys.argv[1], sys.argv[2]
+
+    with open(path, "rb") as f:
+        image = f.read()
+
+    # The bootloader programs flash 16 bits at a time.
+    if len(image) % 2:
+        image += b"\xff"
+
+    header = b"PNLU" + struct.pack("<II", len(image), zlib.crc32(image) & 0xFFFFFFFF)
+
+    with serial.Serial(port, timeout=30) as s:
+        s.write(header + image)
+        result = s.read(1)
+
+    if result != b"K":
+        sys.exit("update failed")
+
+    print("update succeeded, the panel is restarting")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,258 @@
+// A virtual panel for developing host applications without the hardware. It runs the same input
+// handling as the firmware, from panel-core, against fake pins driven from the terminal, and
+// talks the panel protocol over a pseudo terminal. The lights only exist as log lines.
+//
+// Type these to operate the panel:
+//
+//     button down|up
+//     dial <detents>
+//     index active|inactive
+
+mod pty;
+
+use core::convert::Infallible;
+use embedded_hal::digital::{ErrorType, InputPin};
+use panel_core::{
+    button::{Active, Button, Debouncer},
+    command_parser::{CommandParser, Parsed},
+    counter::{Counter, QuadratureCounter},
+    framing::{self, MAX_FRAME_LEN, SEQUENCE_LEN, SEQUENCE_PROTOCOL_VERSION},
+    input::{self, DialRateLimit, InputEvent},
+    power::PowerManager,
+    tick,
+};
+use panel_protocol::{Command, Report, MAX_COMMAND_LEN};
+use pty::Pty;
+use std::{
+    cell::Cell,
+    io::{self, BufRead},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+// The same as the firmware's defaults, see src/settings.rs and src/main.rs.
+const DEBOUNCE_TIME_MS: u16 = 30;
+const LONG_PRESS_TIMEOUT_MS: u32 = 1000;
+const DIM_AFTER_MS: u32 = 60_000;
+const SLEEP_AFTER_MS: u32 = 15 * 60_000;
+const DIAL_REPORTS_PER_SECOND: u16 = 50;
+
+/// The encoder counts four times per detent.
+const COUNTS_PER_DETENT: i16 = 4;
+
+/// An input pin at whatever level the terminal has set.
+struct SimulatedPin<'a>(&'a Cell<bool>);
+
+impl ErrorType for SimulatedPin<'_> {
+    type Error = Infallible;
+}
+
+impl InputPin for SimulatedPin<'_> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.get())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.0.get())
+    }
+}
+
+/// An encoder counter at whatever count the terminal has turned it to.
+struct SimulatedQei<'a>(&'a Cell<u16>);
+
+impl QuadratureCounter for SimulatedQei<'_> {
+    fn count(&self) -> u16 {
+        self.0.get()
+    }
+}
+
+/// The pseudo terminal the host talks to the panel over, and what it has asked for, which
+/// decides whether reports go in frames.
+struct Host {
+    pty: Pty,
+    parser: CommandParser,
+    /// Reports sent, wrapping, which also numbers the next one, the same as the firmware's.
+    reports_sent: u16,
+}
+
+impl Host {
+    fn send(&mut self, report: &Report) -> io::Result<()> {
+        println!("report: {:?}", report);
+        let report = report.as_arrayvec();
+        let sequence = self.reports_sent.to_be_bytes();
+        self.reports_sent = self.reports_sent.wrapping_add(1);
+        if !self.parser.framed() {
+            return self.pty.write(&report);
+        }
+
+        let mut payload = Vec::with_capacity(SEQUENCE_LEN + report.len());
+        if self.parser.version() >= SEQUENCE_PROTOCOL_VERSION {
+            payload.extend_from_slice(&sequence);
+        }
+        payload.extend_from_slice(&report);
+        let mut frame = [0; MAX_FRAME_LEN];
+        self.pty.write(framing::encode(&payload, &mut frame))
+    }
+}
+
+/// Reads the lines typed into the terminal on their own thread, so that the simulation doesn't
+/// have to wait for them.
+fn spawn_terminal_reader() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+fn main() -> io::Result<()> {
+    let pty = Pty::open()?;
+    println!("simulated panel at {}", pty.path());
+    let mut host = Host { pty, parser: CommandParser::new(), reports_sent: 0 };
+
+    // The button and index pins are active low, and pulled up when idle.
+    let button_level = Cell::new(true);
+    let index_level = Cell::new(true);
+    let count = Cell::new(0u16);
+
+    let debouncer =
+        Debouncer::new(SimulatedPin(&button_level), Active::Low, DEBOUNCE_TIME_MS, 1000);
+    let mut button = Button::new(debouncer, LONG_PRESS_TIMEOUT_MS);
+    let mut counter =
+        Counter::new(SimulatedQei(&count), Some((SimulatedPin(&index_level), Active::Low)));
+    let mut dial_limit = DialRateLimit::new(DIAL_REPORTS_PER_SECOND);
+    let mut power = PowerManager::new(DIM_AFTER_MS, SLEEP_AFTER_MS);
+
+    let terminal = spawn_terminal_reader();
+    let mut read_buf = [0u8; MAX_COMMAND_LEN];
+    let mut last_tick = Instant::now();
+
+    loop {
+        thread::sleep(Duration::from_millis(1));
+
+        // One tick per millisecond of real time, as the SysTick interrupt would.
+        let elapsed_ms = last_tick.elapsed().as_millis() as u32;
+        tick::advance(elapsed_ms);
+        last_tick += Duration::from_millis(elapsed_ms as u64);
+
+        while let Ok(line) = terminal.try_recv() {
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("button"), Some("down")) => button_level.set(false),
+                (Some("button"), Some("up")) => button_level.set(true),
+                (Some("index"), Some("active")) => index_level.set(false),
+                (Some("index"), Some("inactive")) => index_level.set(true),
+                (Some("dial"), Some(detents)) => match detents.parse::<i16>() {
+                    Ok(detents) => {
+                        let counts = detents.saturating_mul(COUNTS_PER_DETENT);
+                        count.set(count.get().wrapping_add(counts as u16));
+                    },
+                    Err(_) => println!("not a number of detents: {}", detents),
+                },
+                _ => println!("unknown input: {}", line),
+            }
+        }
+
+        let mut events = Vec::new();
+        if let Err(e) = input::poll(&mut button, &mut counter, &mut dial_limit, |event| {
+            events.push(event);
+            Ok(())
+        }) {
+            println!("dial error: {:?}", e);
+        }
+
+        for event in events {
+            if let InputEvent::Button(_) | InputEvent::Dial(_) = event {
+                power.input_activity();
+            }
+            if let Some(report) = event.report(host.parser.version()) {
+                host.send(&report)?;
+            }
+        }
+
+        let received = host.pty.read(&mut read_buf)?;
+        if received > 0 {
+            // A pseudo terminal can't tell when the host closes it, so it counts as connected
+            // from the first command on.
+            power.set_host_present(true);
+        }
+
+        // The parser stops after each command, so that the bytes after one which switches to
+        // frames are read as frames.
+        let mut bytes = &read_buf[..received];
+        while !bytes.is_empty() {
+            // Applied once the parser is done with them, as applying them sends reports.
+            let mut commands = Vec::new();
+            match host.parser.push(&mut bytes, |command| commands.push(command)) {
+                Ok(Parsed::LedFrame(colors)) => {
+                    println!("led strip: frame of {} leds", colors.len() / 3);
+                },
+                Ok(Parsed::Nothing) => {},
+                Err(e) => println!("bad command: {:?}", e),
+            }
+            for command in commands {
+                apply(&mut host, &mut power, &mut dial_limit, command)?;
+            }
+        }
+
+        if let Some(state) = power.poll() {
+            println!("power state: {:?}", state);
+        }
+    }
+}
+
+/// Applies a command from the host, as far as there's anything to simulate.
+fn apply(
+    host: &mut Host,
+    power: &mut PowerManager,
+    dial_limit: &mut DialRateLimit,
+    command: Command,
+) -> io::Result<()> {
+    match command {
+        Command::Brightness { target, value } => {
+            println!("light {}: brightness {}", target, value);
+        },
+        Command::Temperature { target, value } => {
+            println!("light {}: color temperature {}", target, value);
+        },
+        Command::Led { r, g, b, pulse } => {
+            println!("led strip: ({}, {}, {}){}", r, g, b, if pulse { ", pulsing" } else { "" });
+        },
+        Command::Ping { token } => host.send(&Report::Pong { token })?,
+        Command::Sleep => power.sleep(),
+        Command::Wake => power.wake(),
+        Command::SetDialRate { max_per_second } => dial_limit.set_max_per_second(max_per_second),
+        // Acknowledgements aren't simulated, so it doesn't offer frames, which hosts take to
+        // mean them too. A host can still ask for frames with `Command::SetProtocolVersion`,
+        // which the parser follows.
+        Command::Hello { .. } => {
+            host.send(&Report::Hello { version: input::BUTTON_HELD_PROTOCOL_VERSION })?;
+        },
+        Command::SetProtocolVersion { version } => {
+            println!("protocol version: {}", version);
+        },
+        Command::SelfTest => {
+            let report = Report::SelfTest { pwm: true, strip: true, counter: true, button: true };
+            host.send(&report)?;
+        },
+        Command::GetDiagnostics => {
+            let report = Report::Diagnostics {
+                clean_boots: 0,
+                watchdog_resets: 0,
+                panics: 0,
+                low_voltage_events: 0,
+            };
+            host.send(&report)?;
+        },
+        command => println!("not simulated: {:?}", command),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::{
+    ffi::CStr,
+    fs::File,
+    io::{self, Read, Write},
+    mem::MaybeUninit,
+    os::unix::io::{AsRawFd, FromRawFd},
+    ptr,
+};
+
+/// A pseudo terminal, which host applications open like the serial port of a real panel.
+pub struct Pty {
+    master: File,
+    /// Kept open so that reading the master doesn't fail while no host has the port open.
+    _slave: File,
+    path: String,
+}
+
+impl Pty {
+    pub fn open() -> io::Result<Self> {
+        let (mut master_fd, mut slave_fd) = (0, 0);
+        let result = unsafe {
+            libc::openpty(
+                &mut master_fd,
+                &mut slave_fd,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let master = unsafe { File::from_raw_fd(master_fd) };
+        let slave = unsafe { File::from_raw_fd(slave_fd) };
+
+        // Pass bytes through untouched, like a USB serial port does.
+        unsafe {
+            let mut termios = MaybeUninit::uninit();
+            if libc::tcgetattr(slave_fd, termios.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut termios = termios.assume_init();
+            libc::cfmakeraw(&mut termios);
+            if libc::tcsetattr(slave_fd, libc::TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        // The simulation polls for commands along with everything else, rather than waiting.
+        unsafe {
+            let flags = libc::fcntl(master_fd, libc::F_GETFL);
+            if flags < 0 || libc::fcntl(master_fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        let name = unsafe { libc::ttyname(slave.as_raw_fd()) };
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let path = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+
+        Ok(Self { master, _slave: slave, path })
+    }
+
+    /// The path for host applications to open.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Reads whatever the host has sent, without waiting.
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.master.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            result => result,
+        }
+    }
+
+    /// Sends bytes to the host. Like the firmware, this drops them if the host isn't reading.
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self.master.write_all(bytes) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use embedded_hal::i2c::I2c;
+
+// Driver for a BH1750 ambient light sensor, with its ADDR pin tied low. It shares the I2C bus
+// with the status display, so the bus is passed in for each operation.
+// https://www.mouser.com/datasheet/2/348/bh1750fvi-e-186247.pdf
+
+const ADDRESS: u8 = 0x23;
+const POWER_ON: u8 = 0x01;
+/// Measures continuously at a resolution of 1 lux, taking 120 ms per measurement.
+const CONTINUOUS_HIGH_RESOLUTION: u8 = 0x10;
+
+pub struct AmbientLight(());
+
+impl AmbientLight {
+    /// Starts the sensor measuring. Fails if there's no sensor on the bus.
+    pub fn new<I: I2c>(i2c: &mut I) -> Result<Self, I::Error> {
+        i2c.write(ADDRESS, &[POWER_ON])?;
+        i2c.write(ADDRESS, &[CONTINUOUS_HIGH_RESOLUTION])?;
+
+        Ok(Self(()))
+    }
+
+    /// The latest measurement, in lux.
+    pub fn lux<I: I2c>(&mut self, i2c: &mut I) -> Result<u16, I::Error> {
+        let mut raw = [0u8; 2];
+        i2c.read(ADDRESS, &mut raw)?;
+
+        // The sensor counts 1.2 per lux.
+        Ok((u16::from_be_bytes(raw) as u32 * 5 / 6) as u16)
+    }
+}
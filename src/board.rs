@@ -0,0 +1,269 @@
+use crate::drv2605::Actuator;
+#[cfg(feature = "stm32f1")]
+use crate::platform::hal::gpio::{
+    gpioa::{PA2, PA3, PA6, PA7},
+    gpiob::{PB12, PB13},
+    Alternate, Input, Output, PullUp, PushPull, Pxx,
+};
+use panel_core::{button::Active, fan::FanCurve};
+use panel_protocol::MAX_COMMAND_LEN;
+
+// Everything which differs between hardware revisions lives here, selected with exactly one
+// of the `board-*` Cargo features. Each revision is described by one block below: a `Board`,
+// the types of its pins, and a `take_pins!` table saying which pin does what. The timers for the
+// lights and the dial, and their pins, are fixed by the timer remapping options on this chip, so
+// they're the same for every revision and set up in platform/stm32f1/init.rs. The pins are named
+// for the STM32F1's GPIO banks, so they're only there with the `stm32f1` feature.
+//
+// That leaves no timer for a third and fourth light of the panel's own, which would need another
+// eight PWM channels. TIM3 and TIM4 drive the front and back lights and TIM2 reads the dial.
+// Moving the buzzer and the fan off TIM1 wouldn't free enough of it: channel 4 is on A11, USB's
+// D-, so it has three channels for the four of one light, and its complementary outputs, B13 to
+// B15, only mirror them. Light targets from 2 on are DMX fixtures instead, see dmx.rs.
+
+#[cfg(all(feature = "board-v1", feature = "board-v2"))]
+compile_error!("only one of the board-v1 and board-v2 features can be enabled");
+
+#[cfg(not(any(feature = "board-v1", feature = "board-v2")))]
+compile_error!("one of the board-v1 or board-v2 features must be enabled");
+
+/// The parts of a board revision which aren't pins.
+pub struct Board {
+    /// The number of LEDs on the strip.
+    pub led_count: usize,
+    /// The level of the encoder's index (Z) channel at its index position, if it has one.
+    pub encoder_index: Option<Active>,
+    /// The level of the button pin while the button is pressed.
+    pub button_active: Active,
+    /// The overhead lights' PWM frequency. Their timers count at 48 MHz, so this also sets how
+    /// many steps of duty cycle there are: 48,000 at 1 kHz. Below about 733 Hz the timers need a
+    /// prescaler, and the steps get coarser again.
+    pub light_pwm_hz: u32,
+    /// The number of analog sliders, on A4 and then A5.
+    pub slider_count: usize,
+    /// Whether an analog joystick's X and Y axes are wired to A4 and A5, in place of the
+    /// sliders, see panel_core::joystick.
+    pub joystick: bool,
+    /// Whether a microphone's envelope detector is wired to A5, for the sound reactive LED
+    /// effect, see panel_core::sound_level. It takes the second slider's pin.
+    pub microphone: bool,
+    /// How fast the fan runs, on boards which cool their lights with one, see
+    /// platform::stm32f1::fan.
+    pub fan: Option<FanCurve>,
+    /// The level of A2 while the door is closed, for a hall effect or reed switch on a door, see
+    /// panel_core::door. Only boards without an encoder index channel, which uses the same pin,
+    /// can have one.
+    pub door_sensor: Option<Active>,
+    /// Whether USB's VBUS is wired to A15, through a divider, to tell when there's no cable.
+    pub vbus_sense: bool,
+    /// The shunt of the current monitor on the LED strip's rail, if one is fitted, see
+    /// power_monitor.rs.
+    pub led_rail_shunt_milliohms: u16,
+    /// The most current the strip's rail should draw. It's only enforced with a monitor fitted.
+    pub led_rail_budget_ma: Option<u16>,
+    /// The motor on the haptic driver, if one is fitted, see drv2605.rs.
+    pub haptic_actuator: Actuator,
+    /// The number of LEDs on the ring around the dial, with the `encoder-ring` feature.
+    pub encoder_ring_len: usize,
+    /// Detents for a whole turn of the dial, which the encoder ring goes round once in.
+    pub detents_per_turn: u16,
+    /// The most bytes read from the host at a time. Longer commands take several reads.
+    pub usb_rx_len: usize,
+    /// The sizes of the queues in queues.rs. Each holds one element less than its size.
+    pub command_queue_len: usize,
+    pub report_queue_len: usize,
+}
+
+/// The GPIO pins which aren't driven by a timer, in the modes they're used in. Take them from
+/// the GPIO banks with `take_pins!(gpioa, gpiob)`.
+#[cfg(feature = "stm32f1")]
+pub struct Pins {
+    pub status_led: StatusLedPin,
+    pub button: ButtonPin,
+    pub encoder_index: EncoderIndexPin,
+    pub strip_data: StripDataPin,
+    /// The output of an IR receiver, if one is fitted, which is low while it sees a remote.
+    pub ir_receiver: IrReceiverPin,
+    /// The fan's open collector tachometer output, if there's a fan.
+    pub fan_tach: FanTachPin,
+    /// Outputs the host can switch, see outputs.rs. They start off.
+    pub outputs: [OutputPin; OUTPUT_COUNT],
+    #[cfg(feature = "encoder-ring")]
+    pub ring_data: RingDataPin,
+}
+
+/// Outputs are on B14 and B15, except with the `encoder-ring` feature, where the ring's data
+/// line takes B15.
+#[cfg(not(feature = "encoder-ring"))]
+pub const OUTPUT_COUNT: usize = 2;
+#[cfg(feature = "encoder-ring")]
+pub const OUTPUT_COUNT: usize = 1;
+
+/// SPI2's MOSI, the only pin it has, which sends the encoder ring's frames. It's only taken with
+/// the `encoder-ring` feature.
+#[cfg(feature = "stm32f1")]
+pub type RingDataPin = crate::platform::hal::gpio::gpiob::PB15<Alternate<PushPull>>;
+
+/// The outputs' pins, which are the same on every board, for `take_pins!`.
+#[cfg(all(feature = "stm32f1", not(feature = "encoder-ring")))]
+#[macro_export]
+macro_rules! take_outputs {
+    ($gpiob:ident) => {
+        [
+            $gpiob
+                .pb14
+                .into_push_pull_output_with_state(
+                    &mut $gpiob.crh,
+                    $crate::platform::hal::gpio::State::Low,
+                )
+                .downgrade(),
+            $gpiob
+                .pb15
+                .into_push_pull_output_with_state(
+                    &mut $gpiob.crh,
+                    $crate::platform::hal::gpio::State::Low,
+                )
+                .downgrade(),
+        ]
+    };
+}
+
+#[cfg(all(feature = "stm32f1", feature = "encoder-ring"))]
+#[macro_export]
+macro_rules! take_outputs {
+    ($gpiob:ident) => {
+        [$gpiob
+            .pb14
+            .into_push_pull_output_with_state(
+                &mut $gpiob.crh,
+                $crate::platform::hal::gpio::State::Low,
+            )
+            .downgrade()]
+    };
+}
+
+/// The original panel: two LEDs under the dial and an encoder without an index channel.
+#[cfg(feature = "board-v1")]
+mod variant {
+    use super::*;
+
+    pub const BOARD: Board = Board {
+        led_count: 2,
+        encoder_index: None,
+        button_active: Active::Low,
+        light_pwm_hz: 1_000,
+        slider_count: 0,
+        joystick: false,
+        microphone: false,
+        fan: None,
+        door_sensor: None,
+        vbus_sense: false,
+        led_rail_shunt_milliohms: 10,
+        led_rail_budget_ma: None,
+        haptic_actuator: Actuator::Erm,
+        encoder_ring_len: 12,
+        detents_per_turn: 24,
+        usb_rx_len: MAX_COMMAND_LEN,
+        command_queue_len: 8,
+        report_queue_len: 16,
+    };
+
+    #[cfg(feature = "stm32f1")]
+    pub use pins::*;
+
+    #[cfg(feature = "stm32f1")]
+    mod pins {
+        use super::*;
+
+        pub type StatusLedPin = PB12<Output<PushPull>>;
+        pub type ButtonPin = PA3<Input<PullUp>>;
+        /// Unconnected unless there's a door sensor, but pulled up so that it doesn't float.
+        pub type EncoderIndexPin = PA2<Input<PullUp>>;
+        pub type StripDataPin = PA7<Alternate<PushPull>>;
+        pub type IrReceiverPin = PB13<Input<PullUp>>;
+        pub type FanTachPin = PA6<Input<PullUp>>;
+        /// B14 and B15, see `OUTPUT_COUNT`.
+        pub type OutputPin = Pxx<Output<PushPull>>;
+
+        #[macro_export]
+        macro_rules! take_pins {
+            ($gpioa:ident, $gpiob:ident) => {
+                $crate::board::Pins {
+                    status_led: $gpiob.pb12.into_push_pull_output(&mut $gpiob.crh),
+                    button: $gpioa.pa3.into_pull_up_input(&mut $gpioa.crl),
+                    encoder_index: $gpioa.pa2.into_pull_up_input(&mut $gpioa.crl),
+                    strip_data: $gpioa.pa7.into_alternate_push_pull(&mut $gpioa.crl),
+                    ir_receiver: $gpiob.pb13.into_pull_up_input(&mut $gpiob.crh),
+                    fan_tach: $gpioa.pa6.into_pull_up_input(&mut $gpioa.crl),
+                    outputs: $crate::take_outputs!($gpiob),
+                    #[cfg(feature = "encoder-ring")]
+                    ring_data: $gpiob.pb15.into_alternate_push_pull(&mut $gpiob.crh),
+                }
+            };
+        }
+    }
+}
+
+/// The second revision: a longer LED ring, and an encoder with an open collector index (Z)
+/// channel on A2.
+#[cfg(feature = "board-v2")]
+mod variant {
+    use super::*;
+
+    pub const BOARD: Board = Board {
+        led_count: 8,
+        encoder_index: Some(Active::Low),
+        button_active: Active::Low,
+        light_pwm_hz: 1_000,
+        slider_count: 0,
+        joystick: false,
+        microphone: false,
+        fan: None,
+        door_sensor: None,
+        vbus_sense: false,
+        led_rail_shunt_milliohms: 10,
+        led_rail_budget_ma: None,
+        haptic_actuator: Actuator::Erm,
+        encoder_ring_len: 12,
+        detents_per_turn: 24,
+        usb_rx_len: MAX_COMMAND_LEN,
+        command_queue_len: 8,
+        report_queue_len: 16,
+    };
+
+    #[cfg(feature = "stm32f1")]
+    pub use pins::*;
+
+    #[cfg(feature = "stm32f1")]
+    mod pins {
+        use super::*;
+
+        pub type StatusLedPin = PB12<Output<PushPull>>;
+        pub type ButtonPin = PA3<Input<PullUp>>;
+        pub type EncoderIndexPin = PA2<Input<PullUp>>;
+        pub type StripDataPin = PA7<Alternate<PushPull>>;
+        pub type IrReceiverPin = PB13<Input<PullUp>>;
+        pub type FanTachPin = PA6<Input<PullUp>>;
+        /// B14 and B15, see `OUTPUT_COUNT`.
+        pub type OutputPin = Pxx<Output<PushPull>>;
+
+        #[macro_export]
+        macro_rules! take_pins {
+            ($gpioa:ident, $gpiob:ident) => {
+                $crate::board::Pins {
+                    status_led: $gpiob.pb12.into_push_pull_output(&mut $gpiob.crh),
+                    button: $gpioa.pa3.into_pull_up_input(&mut $gpioa.crl),
+                    encoder_index: $gpioa.pa2.into_pull_up_input(&mut $gpioa.crl),
+                    strip_data: $gpioa.pa7.into_alternate_push_pull(&mut $gpioa.crl),
+                    ir_receiver: $gpiob.pb13.into_pull_up_input(&mut $gpiob.crh),
+                    fan_tach: $gpioa.pa6.into_pull_up_input(&mut $gpioa.crl),
+                    outputs: $crate::take_outputs!($gpiob),
+                    #[cfg(feature = "encoder-ring")]
+                    ring_data: $gpiob.pb15.into_alternate_push_pull(&mut $gpiob.crh),
+                }
+            };
+        }
+    }
+}
+
+pub use variant::*;
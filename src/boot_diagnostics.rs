@@ -0,0 +1,37 @@
+// The checks run at boot, so that dead peripherals are reported before anyone notices them.
+
+/// A subsystem which failed its check at boot, as a bit in `Report::BootDiagnostics`.
+#[derive(Debug, Clone, Copy)]
+#[repr(u16)]
+pub enum Fault {
+    /// The system clock isn't running from the HSE crystal at the expected frequency.
+    Clock = 1 << 0,
+    /// The SPI bus driving the LED strip didn't accept a frame.
+    Spi = 1 << 1,
+    /// The dial's timer isn't counting in encoder mode.
+    Qei = 1 << 2,
+    /// The button reads as pressed, so either it's held down or its pull-up isn't working.
+    Button = 1 << 3,
+    /// One of the lights' PWM timers isn't running.
+    Pwm = 1 << 4,
+}
+
+/// The faults found at boot. No bits set means every check passed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BootDiagnostics {
+    faults: u16,
+}
+
+impl BootDiagnostics {
+    /// Records the result of one check.
+    pub fn check(&mut self, fault: Fault, passed: bool) {
+        if !passed {
+            warn!("boot check failed: {}", defmt::Debug2Format(&fault));
+            self.faults |= fault as u16;
+        }
+    }
+
+    pub fn faults(&self) -> u16 {
+        self.faults
+    }
+}
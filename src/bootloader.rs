@@ -0,0 +1,33 @@
+use core::ptr;
+use cortex_m::peripheral::SCB;
+
+// These have to match the bootloader, see bootloader/src/main.rs.
+const BOOTLOAD_FLAG_ADDRESS: u32 = 0x2000_0000;
+#[cfg(not(feature = "uart-transport"))]
+const BOOTLOAD_MAGIC: u32 = 0xB007_10AD;
+#[cfg(feature = "uart-transport")]
+const SYSTEM_BOOTLOAD_MAGIC: u32 = 0x5157_B007;
+
+/// Resets into the bootloader, which stays resident and waits for a new application image over
+/// USB instead of starting the application again.
+#[cfg(not(feature = "uart-transport"))]
+pub fn reboot_into_bootloader() -> ! {
+    warn!("rebooting into the bootloader");
+    reboot_with_flag(BOOTLOAD_MAGIC)
+}
+
+/// The host is on USART1 rather than USB, so it couldn't reach the resident bootloader. This
+/// resets into the chip's own bootloader instead, which takes an image over USART1 from
+/// `make flash` without BOOT0 being held high.
+#[cfg(feature = "uart-transport")]
+pub fn reboot_into_bootloader() -> ! {
+    warn!("rebooting into the system bootloader");
+    reboot_with_flag(SYSTEM_BOOTLOAD_MAGIC)
+}
+
+fn reboot_with_flag(flag: u32) -> ! {
+    // The flag lives in the first word of RAM, which neither the application nor the bootloader
+    // initializes, so it survives the reset.
+    unsafe { ptr::write_volatile(BOOTLOAD_FLAG_ADDRESS as *mut u32, flag) };
+    SCB::sys_reset();
+}
@@ -0,0 +1,88 @@
+use crate::{
+    platform::flash::{self, RawFlash},
+    rgb_led::LedSettings,
+    settings::Scene,
+};
+use core::convert::TryInto;
+
+// What the lights and the LED strip were set to when the supply last dropped, so that a panel
+// which browns out comes back on as it was rather than at its defaults. Erasing a page takes
+// far too long while the supply is failing, so the snapshot is programmed into a slot which was
+// erased in advance, which only takes a few half words. The slot is the second half of the panic
+// log's page, see panic_log.rs, which only uses the start of it. It's read and erased at boot,
+// and erased again once the supply recovers without a reset, ready for the next drop.
+
+/// The last page of flash, which the panic log starts at.
+const PAGE_ADDRESS: u32 = flash::FLASH_END - flash::PAGE_SIZE;
+const SLOT_ADDRESS: u32 = PAGE_ADDRESS + flash::PAGE_SIZE / 2;
+/// Marks a snapshot, ahead of its six half words.
+const MAGIC: u16 = 0xB20B;
+const SNAPSHOT_LEN: usize = 14;
+
+/// Saves `scene` into the slot, unless it already holds a snapshot from a drop the supply hasn't
+/// recovered from since, or a task is part way through writing flash. It's quick enough for the
+/// PVD interrupt, which this is meant for.
+pub fn save(scene: &Scene) {
+    if !is_erased() {
+        return;
+    }
+    let mut flash = match RawFlash::try_steal() {
+        Some(flash) => flash,
+        None => return,
+    };
+
+    let (r, g, b) = scene.led.color;
+    let values = [
+        scene.front.0,
+        scene.front.1,
+        scene.back.0,
+        scene.back.1,
+        u16::from_be_bytes([r, g]),
+        u16::from_be_bytes([b, scene.led.pulse as u8]),
+    ];
+    // The magic goes last, so that a snapshot cut short by the reset, or by a write which didn't
+    // program, isn't taken as one.
+    let programmed = values
+        .iter()
+        .enumerate()
+        .all(|(i, &value)| flash.program_half_word(SLOT_ADDRESS + 2 + i as u32 * 2, value).is_ok());
+    if programmed {
+        let _ = flash.program_half_word(SLOT_ADDRESS, MAGIC);
+    }
+}
+
+/// Returns the snapshot saved before the last reset, if the panel browned out, and erases it.
+/// A panic erases the page before writing its log, so there's never a snapshot and a panic log
+/// at once, and erasing either doesn't lose the other.
+pub fn take() -> Option<Scene> {
+    let bytes = flash::read(SLOT_ADDRESS, SNAPSHOT_LEN);
+    let half_word = |i: usize| u16::from_le_bytes(bytes[i * 2..i * 2 + 2].try_into().unwrap());
+
+    let scene = if half_word(0) == MAGIC {
+        let [r, g] = half_word(5).to_be_bytes();
+        let [b, pulse] = half_word(6).to_be_bytes();
+        warn!("found a snapshot from a brownout before the last reset");
+        Some(Scene {
+            front: (half_word(1), half_word(2)),
+            back: (half_word(3), half_word(4)),
+            led: LedSettings { color: (r, g, b), pulse: pulse != 0 },
+        })
+    } else {
+        None
+    };
+
+    clear();
+    scene
+}
+
+/// Erases the slot, if it isn't already, ready for the next drop. This takes as long as erasing
+/// a page, too long for an interrupt.
+pub fn clear() {
+    if !is_erased() {
+        unsafe { RawFlash::steal() }.erase_page(PAGE_ADDRESS);
+    }
+}
+
+fn is_erased() -> bool {
+    flash::read(SLOT_ADDRESS, SNAPSHOT_LEN).iter().all(|&byte| byte == 0xFF)
+}
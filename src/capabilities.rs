@@ -0,0 +1,47 @@
+// What this panel has, so that one host application can drive every board revision. It's sent
+// in answer to `Command::GetCapabilities`: the number of lights, LEDs, outputs and sliders, and a
+// bit for each optional part, whether the board has it, it's built in with a Cargo feature, or it
+// was found at boot.
+
+/// An optional part of the panel, as a bit in `Report::Capabilities`.
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    /// The dial's encoder has an index channel, so the panel sends `Report::DialHomed`.
+    EncoderIndex = 1 << 0,
+    DoorSensor = 1 << 1,
+    VbusSense = 1 << 2,
+    Joystick = 1 << 3,
+    Microphone = 1 << 4,
+    Fan = 1 << 5,
+    EncoderRing = 1 << 6,
+    DmxOutput = 1 << 7,
+    HidInput = 1 << 8,
+    AmbientLight = 1 << 9,
+    Display = 1 << 10,
+    Haptics = 1 << 11,
+    ExternalRtc = 1 << 12,
+    LedRailMonitor = 1 << 13,
+    Expander = 1 << 14,
+    FixtureProbes = 1 << 15,
+}
+
+/// The optional parts the panel has. No bits set means it's a bare panel.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    features: u32,
+}
+
+impl Capabilities {
+    pub fn set(&mut self, feature: Feature, present: bool) {
+        if present {
+            self.features |= feature as u32;
+        } else {
+            self.features &= !(feature as u32);
+        }
+    }
+
+    pub fn features(&self) -> u32 {
+        self.features
+    }
+}
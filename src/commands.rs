@@ -0,0 +1,345 @@
+use crate::{
+    board,
+    capabilities::Capabilities,
+    ds3231::Ds3231,
+    eeprom::Eeprom,
+    lights::Lights,
+    platform::{
+        buzzer::Buzzer,
+        device_info,
+        flash::{self, RawFlash},
+        wall_clock::WallClock,
+    },
+    profiler::{self, Section},
+    queues::{self, CommandQueue, ReportQueue},
+    resources::{
+        self, AuxOutputs, BackLight, Door, EncoderButton, Expander, FrontLight, I2cBus,
+        EXPANDER_OUTPUTS,
+    },
+    rgb_led::{LedSettings, Rgb},
+    serial::Report,
+    settings::{self, BootCounts, ConfigKey, Scene, Settings, SCENE_SLOTS},
+    version::{FirmwareVersion, FIRMWARE_VERSION},
+};
+use cortex_m::peripheral::SCB;
+use panel_core::{
+    commands::{self, Ctx, Light, Panel, Setting},
+    input::DialRateLimit,
+    power::PowerManager,
+    pulser::Pulser,
+    sound_level::SoundLevel,
+    subscriptions::Category,
+};
+use rtic::Mutex;
+
+// The panel's side of panel_core::commands, over the resources the apply_commands task shares
+// with the rest of the firmware.
+
+/// The tasks the commands asked for, which apply_commands spawns once they've all been applied.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FollowUp {
+    /// Reset, into the bootloader if true. The first command to ask wins.
+    pub reset: Option<bool>,
+    pub self_test: bool,
+    pub send_status: bool,
+}
+
+/// The resources the commands change. Those shared with the tasks at priority 2 are locked for
+/// each change, the rest belong to this priority.
+pub struct Hardware<'a, F, B, L, X, P, S, R, W, E> {
+    pub lights: Lights<F, B>,
+    pub led_settings: L,
+    pub led_pixels: X,
+    pub pulser: P,
+    pub sound_level: S,
+    pub reports: R,
+    pub power: W,
+    pub low_voltage_events: E,
+    pub wall_clock: &'a mut WallClock,
+    pub external_rtc: &'a mut Option<Ds3231>,
+    pub i2c: &'a mut I2cBus,
+    pub eeprom: &'a mut Eeprom,
+    pub buzzer: &'a mut Buzzer,
+    pub outputs: &'a mut AuxOutputs,
+    pub expander: &'a mut Expander,
+    pub door: &'a mut Option<Door>,
+    pub dial_limit: &'a mut DialRateLimit,
+    pub settings: &'a mut Settings,
+    pub encoder_button: &'a mut EncoderButton,
+    pub capabilities: &'a Capabilities,
+    pub follow_up: FollowUp,
+}
+
+/// Applies every queued command, see panel_core::commands. The tasks they asked for are left in
+/// the panel's `follow_up`.
+pub fn run(ctx: &mut Ctx<impl Panel>, mut commands: impl Mutex<T = CommandQueue>) {
+    commands::apply_all(ctx, || commands.lock(|commands| commands.dequeue()));
+    queues::wake_host_task();
+}
+
+impl<F, B, L, X, P, S, R, W, E> Panel for Hardware<'_, F, B, L, X, P, S, R, W, E>
+where
+    F: Mutex<T = FrontLight>,
+    B: Mutex<T = BackLight>,
+    L: Mutex<T = LedSettings>,
+    X: Mutex<T = resources::Pixels>,
+    P: Mutex<T = Pulser>,
+    S: Mutex<T = SoundLevel>,
+    R: Mutex<T = ReportQueue>,
+    W: Mutex<T = PowerManager>,
+    E: Mutex<T = u16>,
+{
+    fn set_brightness(&mut self, light: Light, brightness: u16) {
+        match light {
+            Light::Front => self.lights.front.lock(|light| light.set_brightness(brightness)),
+            Light::Back => self.lights.back.lock(|light| light.set_brightness(brightness)),
+        }
+    }
+
+    fn set_color_temperature(&mut self, light: Light, color_temperature: u16) {
+        match light {
+            Light::Front => {
+                self.lights.front.lock(|light| light.set_color_temperature(color_temperature));
+            },
+            Light::Back => {
+                self.lights.back.lock(|light| light.set_color_temperature(color_temperature));
+            },
+        }
+    }
+
+    fn levels(&mut self, light: Light) -> (u16, u16) {
+        self.lights.levels()[light as usize]
+    }
+
+    fn set_led(&mut self, color: (u8, u8, u8), pulse: bool) {
+        self.led_settings.lock(|settings| *settings = LedSettings { color, pulse });
+        self.led_pixels.lock(|pixels| pixels.clear());
+    }
+
+    fn led(&mut self) -> ((u8, u8, u8), bool) {
+        let LedSettings { color, pulse } = self.led_settings.lock(|settings| *settings);
+        (color, pulse)
+    }
+
+    fn set_pixel(&mut self, index: u16, (r, g, b): (u8, u8, u8)) -> bool {
+        self.led_pixels.lock(|pixels| pixels.set(index as usize, Rgb::new(r, g, b)))
+    }
+
+    fn commit_pixels(&mut self) {
+        self.led_pixels.lock(|pixels| pixels.commit());
+    }
+
+    // Without a microphone, the effect would only ever turn the strip off.
+    fn configure_sound_level(&mut self, enabled: bool, sensitivity: u16, smoothing: u8) {
+        let enabled = enabled && board::BOARD.microphone;
+        self.sound_level.lock(|sound| sound.configure(enabled, sensitivity, smoothing));
+    }
+
+    fn save_scene(&mut self, slot: u8) -> bool {
+        if slot as usize >= SCENE_SLOTS {
+            return false;
+        }
+        let [front, back] = self.lights.levels();
+        let scene = Scene { front, back, led: self.led_settings.lock(|settings| *settings) };
+        settings::save_scene(self.eeprom, slot as usize, &scene);
+        true
+    }
+
+    fn recall_scene(&mut self, slot: u8) -> bool {
+        if slot as usize >= SCENE_SLOTS {
+            return false;
+        }
+        let Scene { front, back, led } = match settings::load_scene(self.eeprom, slot as usize) {
+            Some(scene) => scene,
+            None => return false,
+        };
+        self.lights.front.lock(|light| {
+            light.set_brightness(front.0);
+            light.set_color_temperature(front.1);
+        });
+        self.lights.back.lock(|light| {
+            light.set_brightness(back.0);
+            light.set_color_temperature(back.1);
+        });
+        self.led_settings.lock(|settings| *settings = led);
+        self.led_pixels.lock(|pixels| pixels.clear());
+        true
+    }
+
+    fn save(&mut self, setting: Setting) {
+        let eeprom = &mut *self.eeprom;
+        match setting {
+            Setting::ScheduleEntry(index, entry) => {
+                settings::save_schedule_entry(eeprom, index, entry);
+            },
+            Setting::UtcOffset(minutes) => settings::save_utc_offset(eeprom, minutes),
+            Setting::SuspendPolicy(policy) => settings::save_suspend_policy(eeprom, policy),
+            Setting::IrBinding(action, binding) => {
+                settings::save_ir_binding(eeprom, action, binding);
+            },
+            Setting::HapticEffect(event, effect) => {
+                settings::save_haptic_effect(eeprom, event, effect);
+            },
+            Setting::StatusOnConnect(enabled) => {
+                settings::save_status_on_connect(eeprom, enabled);
+            },
+            Setting::DefaultLed { color, pulse } => {
+                settings::save_default_led(eeprom, LedSettings { color, pulse });
+            },
+        }
+    }
+
+    fn power<T>(&mut self, f: impl FnOnce(&mut PowerManager) -> T) -> T {
+        self.power.lock(f)
+    }
+
+    fn set_time(&mut self, unix_seconds: u32) -> bool {
+        self.wall_clock.set(unix_seconds);
+        match self.external_rtc {
+            Some(rtc) => rtc.set(self.i2c, unix_seconds).is_ok(),
+            None => true,
+        }
+    }
+
+    fn beep(&mut self, freq: u16, duration_ms: u16) {
+        self.buzzer.beep(freq as u32, duration_ms as u32);
+    }
+
+    fn set_dial_rate(&mut self, max_per_second: u16) {
+        self.dial_limit.set_max_per_second(max_per_second);
+    }
+
+    fn set_config(&mut self, key: u8, value: u16) -> bool {
+        let key = match ConfigKey::from_u8(key) {
+            Some(key) if self.settings.set(key, value) => key,
+            _ => return false,
+        };
+        let expander_buttons = self.expander.buttons.iter_mut();
+        match key {
+            ConfigKey::DebounceTimeMs => {
+                self.encoder_button.set_debounce_time(value);
+                expander_buttons.for_each(|button| button.set_debounce_time(value));
+                if let Some(door) = self.door.as_mut() {
+                    door.set_debounce_time(value);
+                }
+            },
+            ConfigKey::LongPressTimeoutMs => {
+                self.encoder_button.set_long_press_timeout(value as u32);
+                expander_buttons.for_each(|button| button.set_long_press_timeout(value as u32));
+            },
+            ConfigKey::PulseIntervalMs => {
+                self.pulser.lock(|pulser| pulser.set_interval(value as u32));
+            },
+            ConfigKey::DialReportsPerSecond => self.dial_limit.set_max_per_second(value),
+        }
+        self.settings.save(self.eeprom);
+        true
+    }
+
+    fn config(&mut self, key: u8) -> Option<u16> {
+        ConfigKey::from_u8(key).map(|key| self.settings.get(key))
+    }
+
+    // The expander's outputs are numbered after the panel's own.
+    fn set_output(&mut self, channel: u8, on: bool) -> bool {
+        match channel.checked_sub(board::OUTPUT_COUNT as u8) {
+            Some(_) if self.expander.chip.is_none() => false,
+            Some(expander_channel) => self.expander.outputs.set(expander_channel, on),
+            None => self.outputs.set(channel, on),
+        }
+    }
+
+    fn output(&mut self, channel: u8) -> Option<bool> {
+        match channel.checked_sub(board::OUTPUT_COUNT as u8) {
+            Some(_) if self.expander.chip.is_none() => None,
+            Some(expander_channel) => self.expander.outputs.get(expander_channel),
+            None => self.outputs.get(channel),
+        }
+    }
+
+    fn door_open(&mut self) -> Option<bool> {
+        self.door.as_ref().map(|door| door.is_open())
+    }
+
+    fn enable_readout_protection(&mut self) {
+        // Nothing else may touch flash while the option bytes are rewritten.
+        cortex_m::interrupt::free(|_| {
+            unsafe { RawFlash::steal() }.enable_readout_protection();
+        });
+        // The option bytes are only loaded at reset.
+        SCB::sys_reset();
+    }
+
+    fn diagnostics(&mut self) -> Report {
+        let boots = BootCounts::load(self.eeprom);
+        Report::Diagnostics {
+            clean_boots: boots.clean_boots,
+            watchdog_resets: boots.watchdog_resets,
+            panics: boots.panics,
+            low_voltage_events: self.low_voltage_events.lock(|count| *count),
+        }
+    }
+
+    fn device_id(&mut self) -> Report {
+        Report::DeviceId { unique_id: device_info::unique_id() }
+    }
+
+    fn firmware_version(&mut self) -> Report {
+        let FirmwareVersion { major, minor, patch, build_timestamp, commit, dirty } =
+            FIRMWARE_VERSION;
+        Report::FirmwareVersion { major, minor, patch, build_timestamp, commit, dirty }
+    }
+
+    fn readout_protection(&mut self) -> Report {
+        Report::ReadoutProtection { enabled: flash::readout_protection_enabled() }
+    }
+
+    fn capabilities(&mut self) -> Report {
+        let dmx_targets = if cfg!(feature = "dmx-output") { panel_core::dmx::TARGETS } else { 0 };
+        let expander_outputs = if self.expander.chip.is_some() { EXPANDER_OUTPUTS } else { 0 };
+        Report::Capabilities {
+            lights: (Light::ALL.len() + dmx_targets) as u8,
+            led_count: board::BOARD.led_count as u16,
+            outputs: (board::OUTPUT_COUNT + expander_outputs) as u8,
+            sliders: board::BOARD.slider_count as u8,
+            features: self.capabilities.features(),
+        }
+    }
+
+    fn send_profile(&mut self) -> bool {
+        if !cfg!(feature = "profiler") {
+            return false;
+        }
+
+        for &section in &Section::ALL {
+            let stats = profiler::take(section);
+            self.send(Report::Profile {
+                section: section as u8,
+                max_cycles: stats.max_cycles,
+                mean_cycles: stats.mean_cycles,
+                count: stats.count,
+            });
+        }
+        true
+    }
+
+    fn reset(&mut self, into_bootloader: bool) {
+        self.follow_up.reset.get_or_insert(into_bootloader);
+    }
+
+    fn start_self_test(&mut self) {
+        self.follow_up.self_test = true;
+    }
+
+    fn send_status(&mut self) {
+        self.follow_up.send_status = true;
+    }
+
+    fn subscribe(&mut self, category: Category, subscribed: bool) {
+        queues::subscribe(category, subscribed);
+    }
+
+    fn send(&mut self, report: Report) {
+        let _ = self.reports.lock(|reports| queues::send_report(reports, report));
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{
+    board,
+    drv2605::Drv2605,
+    lights::Lights,
+    platform::{
+        buzzer::Buzzer,
+        clock_security,
+        hal::{gpio::ExtiPin, watchdog::IndependentWatchdog},
+    },
+    profiler::{self, Section},
+    queues::{self, InputQueue, ReportQueue},
+    resources::{
+        AnalogInputs, BackLight, Door, EncoderButton, EncoderCounter, Expander, FrontLight, I2cBus,
+        IrReceiver, Protocol, VbusPin,
+    },
+    serial::Report,
+    SYSCLK_HZ,
+};
+use cortex_m::peripheral::DWT;
+use embedded_hal_02::{
+    adc::OneShot,
+    digital::v2::{InputPin, OutputPin, ToggleableOutputPin},
+    watchdog::Watchdog,
+};
+use panel_core::{
+    button::ButtonEvent,
+    haptics::{HapticEffects, HapticEvent},
+    input::{self, DialRateLimit, InputEvent},
+    ir::{IrAction, IrBindings, IrCode},
+    joystick::Joystick,
+    power::{PowerManager, PowerState},
+    slider::Slider,
+    sound_level::SoundLevel,
+    standalone::Standalone,
+    tick::{Every, Instant},
+};
+use rtic::Mutex;
+
+// The panel's controls: the dial and its button, the door sensor, the IR remote, the buttons on
+// the GPIO expander, the sliders, the joystick and the microphone. Each is sampled on its own
+// schedule, and what it does is reported to the host, or in standalone mode sets the lights.
+
+/// The button and rotary encoder are sampled on every tick.
+pub const INPUT_POLL_PERIOD_MS: u32 = 1;
+
+/// How often the sliders are sampled, on boards which have them.
+pub const SLIDER_PERIOD_MS: u32 = 10;
+
+/// How often the joystick is sampled, on boards which have one.
+pub const JOYSTICK_PERIOD_MS: u32 = 10;
+
+/// How often the microphone's envelope is sampled, on boards which have one.
+pub const MICROPHONE_PERIOD_MS: u32 = 5;
+
+/// How often the GPIO expander's pins are read and written, if there is one. Its buttons are
+/// debounced and timed the same way as the dial's.
+pub const EXPANDER_POLL_PERIOD_MS: u32 = 5;
+
+/// The short click played on input, when the host has turned click feedback on.
+const CLICK_HZ: u32 = 4000;
+const CLICK_MS: u32 = 5;
+
+/// The status LED blinks at this period once the HSE crystal has failed.
+const CLOCK_FAULT_BLINK_PERIOD_MS: u32 = 250;
+
+/// What `InputPoll::run()` keeps from one tick to the next.
+pub struct InputPollState {
+    clock_fault_blink: Every,
+    was_connected: bool,
+}
+
+impl InputPollState {
+    pub const fn new() -> Self {
+        Self { clock_fault_blink: Every::new(CLOCK_FAULT_BLINK_PERIOD_MS), was_connected: false }
+    }
+}
+
+impl Default for InputPollState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The tasks `InputPoll::run()` found there's something for.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FollowUp {
+    pub handle_input: bool,
+    pub apply_power_state: bool,
+    pub start_standalone: bool,
+    pub send_status: bool,
+}
+
+/// What's sampled on every tick: the dial, its button and the door, and whether a host or a
+/// cable is there.
+pub struct InputPoll<'a, R, P, H> {
+    pub input_events: &'a mut InputQueue,
+    pub reports: R,
+    pub counter: &'a mut EncoderCounter,
+    pub dial_limit: &'a mut DialRateLimit,
+    pub encoder_button: &'a mut EncoderButton,
+    pub door: &'a mut Option<Door>,
+    pub led: &'a mut board::StatusLedPin,
+    pub vbus: &'a VbusPin,
+    pub watchdog: &'a mut IndependentWatchdog,
+    pub power: P,
+    pub protocol: H,
+    pub standalone: &'a mut Standalone,
+    pub buzzer: &'a mut Buzzer,
+    /// Whether a host which connects is sent `Report::Status` straight away.
+    pub status_on_connect: bool,
+    pub protocol_version: &'a mut u8,
+}
+
+impl<R, P, H> InputPoll<'_, R, P, H>
+where
+    R: Mutex<T = ReportQueue>,
+    P: Mutex<T = PowerManager>,
+    H: Mutex<T = Protocol>,
+{
+    pub fn run(mut self, state: &mut InputPollState) -> FollowUp {
+        let mut follow_up = FollowUp::default();
+
+        self.watchdog.feed();
+
+        // USB is down along with the crystal, so the LED is the only way left to show the fault.
+        // The schedule is polled regardless, so that it doesn't have to catch up once it matters.
+        // Writing to a GPIO pin can't fail on this chip, hence the ignored result.
+        let blink_due = state.clock_fault_blink.is_due(Instant::now());
+        if clock_security::has_failed() && blink_due {
+            let _ = self.led.toggle();
+        }
+
+        let queued_before = self.input_events.len();
+
+        let (encoder_button, counter, dial_limit) =
+            (&mut *self.encoder_button, &mut *self.counter, &mut *self.dial_limit);
+        let input_events = &mut *self.input_events;
+        let polled = profiler::measure(Section::InputPoll, || {
+            input::poll(encoder_button, counter, dial_limit, |event| input_events.enqueue(event))
+        });
+        if let Err(e) = polled {
+            self.reports.lock(|reports| queues::report_error(reports, e.into()));
+        }
+
+        self.buzzer.poll();
+
+        if let Some(open) = self.door.as_mut().and_then(|door| door.poll()) {
+            let report = Report::DoorState { open };
+            let _ = self.reports.lock(|reports| queues::send_report(reports, report));
+        }
+
+        let input_activity = self.input_events.len() > queued_before;
+        follow_up.handle_input = input_activity;
+
+        // Without VBUS sensing, the cable counts as always plugged in.
+        let cable_present = !board::BOARD.vbus_sense || self.vbus.is_high().unwrap_or(true);
+        self.standalone.set_cable_present(cable_present);
+
+        follow_up.apply_power_state = self.power.lock(|power| {
+            power.set_cable_present(cable_present);
+            if input_activity {
+                power.input_activity();
+            }
+            power.poll().is_some()
+        });
+
+        // The USB peripheral stays up in standalone mode, so a host can still enumerate the
+        // panel and take over at any time. A suspended host is asleep rather than gone, and
+        // carries on where it left off when it resumes.
+        let (host_connected, host_suspended) =
+            self.protocol.lock(|protocol| (protocol.is_connected(), protocol.is_suspended()));
+        if host_suspended {
+            return follow_up;
+        }
+        follow_up.send_status = host_connected && !state.was_connected && self.status_on_connect;
+        state.was_connected = host_connected;
+        // The next host to connect may be an older one.
+        if !host_connected {
+            *self.protocol_version = input::DEFAULT_PROTOCOL_VERSION;
+        }
+        follow_up.start_standalone = self.standalone.poll(host_connected) == Some(true);
+        follow_up
+    }
+}
+
+/// Turns input events into reports for the host, and feedback on the status LED, the buzzer
+/// and the haptic driver. In standalone mode they also control the lights.
+pub struct HandleInput<'a, R, F, B, H> {
+    pub input_events: &'a mut InputQueue,
+    pub reports: R,
+    pub counter: &'a mut EncoderCounter,
+    pub led: &'a mut board::StatusLedPin,
+    pub standalone: &'a mut Standalone,
+    pub lights: Lights<F, B>,
+    pub buzzer: &'a mut Buzzer,
+    pub click_feedback: bool,
+    pub i2c: &'a mut I2cBus,
+    pub haptics: &'a mut Option<Drv2605>,
+    pub haptic_effects: &'a HapticEffects,
+    pub protocol_version: u8,
+    /// Only used with the hid-input feature, which reports the events there too.
+    pub protocol: H,
+}
+
+impl<R, F, B, H> HandleInput<'_, R, F, B, H>
+where
+    R: Mutex<T = ReportQueue>,
+    F: Mutex<T = FrontLight>,
+    B: Mutex<T = BackLight>,
+    H: Mutex<T = Protocol>,
+{
+    pub fn run(mut self) {
+        while let Some(event) = self.input_events.dequeue() {
+            #[cfg(feature = "hid-input")]
+            self.protocol.lock(|protocol| protocol.transport_mut().push_input(&event));
+
+            match event {
+                InputEvent::Button(ButtonEvent::Pressed) => {
+                    let _ = self.led.set_low();
+                },
+                InputEvent::Button(ButtonEvent::ShortRelease) => {
+                    let _ = self.led.set_high();
+                },
+                InputEvent::Button(ButtonEvent::LongPress) => {
+                    let _ = self.led.set_high();
+                },
+                _ => {},
+            }
+
+            if let InputEvent::Button(ButtonEvent::ShortRelease)
+            | InputEvent::Button(ButtonEvent::LongPress)
+            | InputEvent::Dial(_) = event
+            {
+                if self.click_feedback {
+                    self.buzzer.beep(CLICK_HZ, CLICK_MS);
+                }
+            }
+
+            if let Some(haptics) = self.haptics.as_mut() {
+                let haptic_effects = self.haptic_effects;
+                let effect = HapticEvent::for_input(&event).and_then(|e| haptic_effects.get(e));
+                if let Some(effect) = effect {
+                    if haptics.play(self.i2c, effect).is_err() {
+                        warn!("haptic driver didn't respond");
+                    }
+                }
+            }
+
+            if let Some(brightness) = self.standalone.handle(&event) {
+                self.lights.set_brightness(brightness);
+            }
+
+            let report = match event.report(self.protocol_version) {
+                Some(report) => report,
+                None => continue,
+            };
+
+            // Movement while the button was held is dropped, as it would be reported as though
+            // the button wasn't.
+            match self.reports.lock(|reports| queues::send_report(reports, report)) {
+                Err(Report::DialValue { diff }) => self.counter.defer(diff),
+                Err(Report::DialTurned { diff, button_held: false }) => self.counter.defer(diff),
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Times a pulse from the IR receiver, see panel_core::ir, and returns the code it completes,
+/// if any. `last_edge` is the cycle count at the last edge.
+pub fn ir_edge(
+    IrReceiver { pin, decoder }: &mut IrReceiver,
+    last_edge: &mut u32,
+) -> Option<IrCode> {
+    let now = DWT::cycle_count();
+    let duration_us = now.wrapping_sub(*last_edge) / (SYSCLK_HZ / 1_000_000);
+    *last_edge = now;
+
+    pin.clear_interrupt_pending_bit();
+    let high = pin.is_high().unwrap_or(true);
+
+    decoder.edge(high, duration_us)
+}
+
+/// Performs the action bound to a remote button, once per press, or passes its code on to the
+/// host. Returns true if the standalone scene should be recalled.
+pub fn handle_ir(
+    code: IrCode,
+    ir_bindings: &IrBindings,
+    mut reports: impl Mutex<T = ReportQueue>,
+    mut power: impl Mutex<T = PowerManager>,
+) -> bool {
+    match ir_bindings.action(&code) {
+        Some(_) if code.repeat => {},
+        Some(IrAction::ToggleLights) => power.lock(|power| match power.state() {
+            PowerState::Sleep => power.wake(),
+            PowerState::Active | PowerState::Dimmed => power.sleep(),
+        }),
+        Some(IrAction::RecallScene) => {
+            power.lock(|power| power.input_activity());
+            return true;
+        },
+        None => {
+            let _ = reports.lock(|reports| queues::send_report(reports, code.report()));
+        },
+    }
+    false
+}
+
+/// Reports each slider to the host when it has moved, see panel_core::slider. The ADC has no
+/// embedded-hal 1.0 trait, so this uses the HAL's 0.2 one directly.
+pub fn sample_sliders(
+    AnalogInputs { adc, pins: (a4, a5) }: &mut AnalogInputs,
+    sliders: &mut [Slider],
+    mut reports: impl Mutex<T = ReportQueue>,
+    mut power: impl Mutex<T = PowerManager>,
+) {
+    let sliders = sliders.iter_mut().take(board::BOARD.slider_count);
+    for (channel, slider) in sliders.enumerate() {
+        // A conversion only ever takes a few microseconds.
+        let reading: Result<u16, _> = match channel {
+            0 => nb::block!(adc.read(a4)),
+            _ => nb::block!(adc.read(a5)),
+        };
+        let value = match reading.ok().and_then(|reading| slider.sample(reading)) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        power.lock(|power| power.input_activity());
+        let report = Report::SliderValue { channel: channel as u8, value };
+        let _ = reports.lock(|reports| queues::send_report(reports, report));
+    }
+}
+
+/// Reads and writes the GPIO expander's pins, see mcp23017.rs, and reports presses of the
+/// buttons on it to the host. Like the dial's button, they count as input for power saving.
+pub fn poll_expander(
+    i2c: &mut I2cBus,
+    Expander { chip, buttons, .. }: &mut Expander,
+    mut reports: impl Mutex<T = ReportQueue>,
+    mut power: impl Mutex<T = PowerManager>,
+) {
+    let expander = match chip {
+        Some(expander) => expander,
+        None => return,
+    };
+
+    if expander.sync(i2c).is_err() {
+        warn!("GPIO expander didn't respond");
+        return;
+    }
+
+    for (pin, button) in buttons.iter_mut().enumerate() {
+        let event = match button.poll() {
+            Some(event) => event,
+            None => continue,
+        };
+        power.lock(|power| power.input_activity());
+
+        let long = match event {
+            ButtonEvent::ShortRelease => false,
+            ButtonEvent::LongPress => true,
+            ButtonEvent::Pressed | ButtonEvent::LongRelease => continue,
+        };
+        let report = Report::ExpanderButton { pin: pin as u8, long };
+        let _ = reports.lock(|reports| queues::send_report(reports, report));
+    }
+}
+
+/// Sends the host a nudge each time the joystick is pushed, see panel_core::joystick. Its axes
+/// are on the sliders' pins.
+pub fn sample_joystick(
+    AnalogInputs { adc, pins: (a4, a5) }: &mut AnalogInputs,
+    joystick: &mut Joystick,
+    mut reports: impl Mutex<T = ReportQueue>,
+    mut power: impl Mutex<T = PowerManager>,
+) {
+    let x: Result<u16, _> = nb::block!(adc.read(a4));
+    let y: Result<u16, _> = nb::block!(adc.read(a5));
+    let direction = match (x, y) {
+        (Ok(x), Ok(y)) => joystick.sample(x, y),
+        _ => None,
+    };
+
+    if let Some(direction) = direction {
+        power.lock(|power| power.input_activity());
+        let report = Report::Nudge { direction: direction as u8 };
+        let _ = reports.lock(|reports| queues::send_report(reports, report));
+    }
+}
+
+/// Follows the microphone's envelope for the sound reactive LED effect, see
+/// panel_core::sound_level. The second slider's pin is the microphone's on boards with one.
+pub fn sample_microphone(
+    AnalogInputs { adc, pins: (_, a5) }: &mut AnalogInputs,
+    mut sound_level: impl Mutex<T = SoundLevel>,
+) {
+    let reading: Result<u16, _> = nb::block!(adc.read(a5));
+    if let Ok(reading) = reading {
+        sound_level.lock(|sound_level| sound_level.sample(reading));
+    }
+}
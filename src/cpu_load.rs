@@ -0,0 +1,23 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::{interrupt, peripheral::DWT};
+
+/// Cycles spent asleep in `idle` since the last call to `take_busy_percent()`.
+static SLEEP_CYCLES: AtomicU32 = AtomicU32::new(0);
+
+/// Sleeps until the next interrupt, counting the cycles spent asleep. Interrupts are masked until
+/// the count is taken, so that the time spent handling the wakeup counts as busy.
+pub fn sleep() {
+    interrupt::free(|_| {
+        let start = DWT::cycle_count();
+        cortex_m::asm::wfi();
+        let slept = DWT::cycle_count().wrapping_sub(start);
+        SLEEP_CYCLES.fetch_add(slept, Ordering::Relaxed);
+    });
+}
+
+/// Returns the share of the last `period_cycles` which was spent awake, in percent.
+pub fn take_busy_percent(period_cycles: u32) -> u8 {
+    let slept = SLEEP_CYCLES.swap(0, Ordering::Relaxed).min(period_cycles);
+
+    (100 - slept as u64 * 100 / period_cycles as u64) as u8
+}
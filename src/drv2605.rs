@@ -0,0 +1,59 @@
+use embedded_hal::i2c::I2c;
+
+// Driver for a DRV2605 or DRV2605L haptic driver, which plays effects from its built-in waveform
+// library on an ERM or LRA motor, see panel_core::haptics. Effects are started over I2C from the
+// internal trigger, and play out without any further help. It shares the I2C bus with the other
+// devices on it.
+// https://www.ti.com/lit/ds/symlink/drv2605.pdf
+
+const ADDRESS: u8 = 0x5a;
+const STATUS: u8 = 0x00;
+const MODE: u8 = 0x01;
+const LIBRARY_SELECTION: u8 = 0x03;
+const WAVEFORM_SEQUENCE: u8 = 0x04;
+const GO: u8 = 0x0c;
+const FEEDBACK_CONTROL: u8 = 0x1a;
+
+/// Out of standby, triggered by writing to `GO`.
+const MODE_INTERNAL_TRIGGER: u8 = 0x00;
+/// Selects an LRA rather than an ERM, leaving the other fields at their defaults.
+const FEEDBACK_CONTROL_LRA: u8 = 0x80 | 0x36;
+
+/// The kind of motor the driver is wired to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Actuator {
+    /// An eccentric rotating mass.
+    Erm,
+    /// A linear resonant actuator.
+    Lra,
+}
+
+pub struct Drv2605;
+
+impl Drv2605 {
+    /// Sets the driver up for `actuator`. Fails if there's no driver on the bus.
+    pub fn new<I: I2c>(i2c: &mut I, actuator: Actuator) -> Result<Self, I::Error> {
+        let mut status = [0u8];
+        i2c.write_read(ADDRESS, &[STATUS], &mut status)?;
+
+        // Library A is tuned for the most common ERMs, and library 6 is the only one for LRAs.
+        let library = match actuator {
+            Actuator::Erm => 1,
+            Actuator::Lra => {
+                i2c.write(ADDRESS, &[FEEDBACK_CONTROL, FEEDBACK_CONTROL_LRA])?;
+                6
+            },
+        };
+        i2c.write(ADDRESS, &[LIBRARY_SELECTION, library])?;
+        i2c.write(ADDRESS, &[MODE, MODE_INTERNAL_TRIGGER])?;
+
+        Ok(Self)
+    }
+
+    /// Starts playing `effect`, cutting short whatever was playing.
+    pub fn play<I: I2c>(&mut self, i2c: &mut I, effect: u8) -> Result<(), I::Error> {
+        // The sequence ends at the first empty slot.
+        i2c.write(ADDRESS, &[WAVEFORM_SEQUENCE, effect, 0])?;
+        i2c.write(ADDRESS, &[GO, 1])
+    }
+}
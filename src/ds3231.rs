@@ -0,0 +1,90 @@
+use embedded_hal::i2c::I2c;
+use panel_core::calendar::DateTime;
+
+// Driver for a DS3231 real-time clock, which keeps time from its own temperature compensated
+// crystal and battery, so that the panel knows the time after days without the host or power.
+// It keeps the calendar date and time of day in BCD, in 24 hour mode here, from 2000 to 2199. It
+// shares the I2C bus with the other devices on it.
+// https://www.analog.com/media/en/technical-documentation/data-sheets/DS3231.pdf
+
+const ADDRESS: u8 = 0x68;
+const SECONDS: u8 = 0x00;
+const STATUS: u8 = 0x0f;
+/// Set whenever the oscillator has stopped, including when the chip first gets power, after
+/// which the time is wrong until it's set again.
+const OSCILLATOR_STOPPED: u8 = 0x80;
+/// In the month register, set once the year has gone past 2099.
+const CENTURY: u8 = 0x80;
+
+pub struct Ds3231;
+
+impl Ds3231 {
+    /// Fails if there's no DS3231 on the bus.
+    pub fn new<I: I2c>(i2c: &mut I) -> Result<Self, I::Error> {
+        let mut status = [0u8];
+        i2c.write_read(ADDRESS, &[STATUS], &mut status)?;
+
+        Ok(Self)
+    }
+
+    /// Seconds since the Unix epoch, or `None` if the clock stopped since it was last set, as it
+    /// does when its battery runs out.
+    pub fn now<I: I2c>(&mut self, i2c: &mut I) -> Result<Option<u32>, I::Error> {
+        // The time registers are latched at the start of a read, so they're read together.
+        let mut registers = [0u8; STATUS as usize + 1];
+        i2c.write_read(ADDRESS, &[SECONDS], &mut registers)?;
+
+        if registers[STATUS as usize] & OSCILLATOR_STOPPED != 0 {
+            return Ok(None);
+        }
+
+        // Register 3 is the day of the week, which the date already says.
+        let [second, minute, hour, day, month, year] =
+            [registers[0], registers[1], registers[2], registers[4], registers[5], registers[6]];
+        let century = if month & CENTURY != 0 { 100 } else { 0 };
+        let time = DateTime {
+            year: 2000 + century + from_bcd(year) as u16,
+            month: from_bcd(month & !CENTURY),
+            day: from_bcd(day),
+            hour: from_bcd(hour & 0x3f),
+            minute: from_bcd(minute),
+            second: from_bcd(second),
+        };
+
+        Ok(Some(time.to_unix()))
+    }
+
+    /// Sets the clock, and clears the flag which says it had stopped.
+    pub fn set<I: I2c>(&mut self, i2c: &mut I, unix_seconds: u32) -> Result<(), I::Error> {
+        let time = DateTime::from_unix(unix_seconds);
+        let century = if time.year >= 2100 { CENTURY } else { 0 };
+        // 1 to 7 from Sunday. The epoch was a Thursday.
+        let weekday = ((unix_seconds / 86_400 + 4) % 7 + 1) as u8;
+
+        i2c.write(
+            ADDRESS,
+            &[
+                SECONDS,
+                to_bcd(time.second),
+                to_bcd(time.minute),
+                to_bcd(time.hour),
+                weekday,
+                to_bcd(time.day),
+                to_bcd(time.month) | century,
+                to_bcd((time.year % 100) as u8),
+            ],
+        )?;
+
+        let mut status = [0u8];
+        i2c.write_read(ADDRESS, &[STATUS], &mut status)?;
+        i2c.write(ADDRESS, &[STATUS, status[0] & !OSCILLATOR_STOPPED])
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
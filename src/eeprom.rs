@@ -0,0 +1,18 @@
+// Settings and counters are kept as 16 bit values under 16 bit keys, in one of two backends
+// with the same interface. By default they're in the chip's own flash (internal.rs). Panels whose
+// settings change very often can keep them in an external 24Cxx EEPROM on I2C instead
+// (external.rs), which wears far more slowly, by building with the `external-eeprom` feature.
+
+/// How often changed settings are written out, when they're kept in an external EEPROM. Each
+/// period writes one, which is about how long the EEPROM takes to program it.
+pub const EEPROM_FLUSH_PERIOD_MS: u32 = 10;
+
+#[cfg(not(feature = "external-eeprom"))]
+mod internal;
+#[cfg(not(feature = "external-eeprom"))]
+pub use internal::Eeprom;
+
+#[cfg(feature = "external-eeprom")]
+mod external;
+#[cfg(feature = "external-eeprom")]
+pub use external::Eeprom;
@@ -0,0 +1,82 @@
+use embedded_hal::i2c::I2c;
+
+// Settings in an external 24C02 or larger EEPROM, with its address pins tied low, on the same I2C
+// bus as the sensor and display. Each key has a 4 byte record at 4 times its number: the value
+// followed by its complement, little endian, so that erased memory or a write cut short by a
+// reset read as no value.
+//
+// The bus is only available to tasks which hold the `i2c` resource, so `write()` only updates a
+// copy of the values in RAM, and `flush()` writes them out from a task which does. The EEPROM
+// takes up to 5 ms to program a record, during which it doesn't acknowledge its address, so each
+// call writes one record and the next retries until the EEPROM is ready again.
+// https://ww1.microchip.com/downloads/en/DeviceDoc/doc0180.pdf
+
+const ADDRESS: u8 = 0x50;
+const RECORD_SIZE: usize = 4;
+/// As many records as fit in the 256 bytes of a 24C02, which is plenty for settings.rs.
+const KEY_COUNT: usize = 256 / RECORD_SIZE;
+
+pub struct Eeprom {
+    values: [Option<u16>; KEY_COUNT],
+    /// A bit for each key whose value hasn't been written to the EEPROM yet.
+    dirty: u64,
+}
+
+impl Eeprom {
+    /// Values are kept in RAM until `flush()` writes them out.
+    pub const BUFFERED: bool = true;
+
+    /// Reads every record. If there's no EEPROM on the bus, every key starts without a value, and
+    /// writes are retried until one appears.
+    pub fn open<I: I2c>(i2c: &mut I) -> Self {
+        let mut values = [None; KEY_COUNT];
+
+        let mut bytes = [0u8; KEY_COUNT * RECORD_SIZE];
+        if i2c.write_read(ADDRESS, &[0], &mut bytes).is_ok() {
+            for (value, record) in values.iter_mut().zip(bytes.chunks_exact(RECORD_SIZE)) {
+                let stored = u16::from_le_bytes([record[0], record[1]]);
+                let complement = u16::from_le_bytes([record[2], record[3]]);
+                *value = if stored == !complement { Some(stored) } else { None };
+            }
+        } else {
+            warn!("no settings EEPROM");
+        }
+
+        Self { values, dirty: 0 }
+    }
+
+    /// Returns the most recently written value for `key`.
+    pub fn read(&self, key: u16) -> Option<u16> {
+        self.values.get(key as usize).copied().flatten()
+    }
+
+    /// Writes a new value for `key`, which must be less than 64. It's saved by the next `flush()`.
+    pub fn write(&mut self, key: u16, value: u16) {
+        assert!((key as usize) < KEY_COUNT);
+
+        if self.read(key) == Some(value) {
+            return;
+        }
+
+        self.values[key as usize] = Some(value);
+        self.dirty |= 1 << key;
+    }
+
+    /// Writes the lowest key which has changed to the EEPROM, leaving it to a later call if the
+    /// EEPROM is still busy with the one before.
+    pub fn flush<I: I2c>(&mut self, i2c: &mut I) {
+        if self.dirty == 0 {
+            return;
+        }
+
+        let key = self.dirty.trailing_zeros() as usize;
+        let value = self.values[key].unwrap_or(0xFFFF);
+        let [low, high] = value.to_le_bytes();
+        let [complement_low, complement_high] = (!value).to_le_bytes();
+        let record = [(key * RECORD_SIZE) as u8, low, high, complement_low, complement_high];
+
+        if i2c.write(ADDRESS, &record).is_ok() {
+            self.dirty &= !(1 << key);
+        }
+    }
+}
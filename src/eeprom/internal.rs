@@ -0,0 +1,209 @@
+use crate::platform::flash::{self, RawFlash, PAGE_SIZE};
+use core::convert::TryInto;
+use embedded_hal::i2c::I2c;
+
+// EEPROM emulation in two pages of flash, along the lines of ST's AN2594. Each page starts with
+// a status half word, followed by (value, key) records which are appended on every write. The
+// most recent record for a key holds its current value. When the active page fills up, the
+// current value of every key is copied to the other page, which becomes active, so that the
+// number of erase cycles is spread across both pages.
+
+/// The two pages just before the panic log page, which memory.x keeps out of the application image.
+const PAGE_ADDRESSES: [u32; 2] =
+    [flash::FLASH_END - 3 * PAGE_SIZE, flash::FLASH_END - 2 * PAGE_SIZE];
+
+const RECORD_SIZE: u32 = 4;
+const HEADER_SIZE: u32 = 4;
+
+/// A key which is never written, as it's indistinguishable from erased flash.
+const EMPTY_KEY: u16 = 0xFFFF;
+
+/// How many records a write tries before giving up, each after the one before wouldn't program.
+const WRITE_ATTEMPTS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PageStatus {
+    Erased,
+    /// A page transfer to this page is in progress.
+    Receiving,
+    /// This page holds the current values.
+    Valid,
+    Corrupt,
+}
+
+impl PageStatus {
+    fn read(page: u32) -> Self {
+        match read_half_word(page) {
+            0xFFFF => PageStatus::Erased,
+            0xEEEE => PageStatus::Receiving,
+            0x0000 => PageStatus::Valid,
+            _ => PageStatus::Corrupt,
+        }
+    }
+
+    fn as_u16(self) -> u16 {
+        match self {
+            PageStatus::Erased => 0xFFFF,
+            PageStatus::Receiving => 0xEEEE,
+            PageStatus::Valid => 0x0000,
+            PageStatus::Corrupt => unreachable!(),
+        }
+    }
+}
+
+pub struct Eeprom {
+    /// The address of the page holding the current values.
+    active_page: u32,
+    /// The address at which the next record will be written.
+    next_record: u32,
+}
+
+impl Eeprom {
+    /// Values are programmed as they're written, so there's nothing for `flush()` to do.
+    pub const BUFFERED: bool = false;
+
+    /// Finds the active page, recovering from a page transfer which was interrupted by a reset,
+    /// and formats both pages if neither holds valid data. The bus is only used by the external
+    /// backend.
+    pub fn open<I: I2c>(_i2c: &mut I) -> Self {
+        let [page_a, page_b] = PAGE_ADDRESSES;
+        let mut flash = unsafe { RawFlash::steal() };
+
+        let active_page = match (PageStatus::read(page_a), PageStatus::read(page_b)) {
+            // If a transfer was interrupted before it finished, the old page is still intact.
+            (PageStatus::Valid, other) => {
+                if other != PageStatus::Erased {
+                    flash.erase_page(page_b);
+                }
+                page_a
+            },
+            (other, PageStatus::Valid) => {
+                if other != PageStatus::Erased {
+                    flash.erase_page(page_a);
+                }
+                page_b
+            },
+            // A transfer finished, but the new page wasn't yet marked as valid.
+            (PageStatus::Receiving, PageStatus::Erased) => {
+                let _ = flash.program_half_word(page_a, PageStatus::Valid.as_u16());
+                page_a
+            },
+            (PageStatus::Erased, PageStatus::Receiving) => {
+                let _ = flash.program_half_word(page_b, PageStatus::Valid.as_u16());
+                page_b
+            },
+            _ => {
+                warn!("formatting settings pages");
+                flash.erase_page(page_a);
+                flash.erase_page(page_b);
+                let _ = flash.program_half_word(page_a, PageStatus::Valid.as_u16());
+                page_a
+            },
+        };
+
+        // A reset part way through a write can leave a record with its value but no key. That
+        // can't be programmed again, so the next record is the first one which is fully erased.
+        let next_record = (HEADER_SIZE..PAGE_SIZE)
+            .step_by(RECORD_SIZE as usize)
+            .map(|offset| active_page + offset)
+            .find(|&address| {
+                read_half_word(address) == 0xFFFF && read_half_word(address + 2) == 0xFFFF
+            })
+            .unwrap_or(active_page + PAGE_SIZE);
+
+        Self { active_page, next_record }
+    }
+
+    /// Returns the most recently written value for `key`.
+    pub fn read(&self, key: u16) -> Option<u16> {
+        find_in_page(self.active_page, self.next_record, key)
+    }
+
+    /// Writes a new value for `key`, which must not be `0xFFFF`.
+    pub fn write(&mut self, key: u16, value: u16) {
+        assert!(key != EMPTY_KEY);
+
+        if self.read(key) == Some(value) {
+            return;
+        }
+
+        let mut flash = unsafe { RawFlash::steal() };
+
+        // A record which doesn't program is left behind, and the value goes in the next one.
+        for _ in 0..WRITE_ATTEMPTS {
+            if self.next_record >= self.active_page + PAGE_SIZE {
+                self.transfer(&mut flash, key);
+            }
+
+            let record = self.next_record;
+            self.next_record += RECORD_SIZE;
+            match flash
+                .program_half_word(record, value)
+                .and_then(|()| flash.program_half_word(record + 2, key))
+            {
+                Ok(()) => return,
+                Err(_e) => warn!("settings record didn't program: {}", defmt::Debug2Format(&_e)),
+            }
+        }
+    }
+
+    pub fn flush<I: I2c>(&mut self, _i2c: &mut I) {}
+
+    /// Copies the current value of every key except `skip_key` into the other page, and makes
+    /// it the active page.
+    fn transfer(&mut self, flash: &mut RawFlash, skip_key: u16) {
+        debug!("compacting settings page");
+
+        let old_page = self.active_page;
+        let old_end = self.next_record;
+        let new_page =
+            if old_page == PAGE_ADDRESSES[0] { PAGE_ADDRESSES[1] } else { PAGE_ADDRESSES[0] };
+
+        let _ = flash.program_half_word(new_page, PageStatus::Receiving.as_u16());
+        let mut next_record = new_page + HEADER_SIZE;
+
+        // Walk backwards so that the first record seen for each key is its current value.
+        let mut address = old_end;
+        while address > old_page + HEADER_SIZE {
+            address -= RECORD_SIZE;
+            let key = read_half_word(address + 2);
+
+            // Records left without a key by a reset or a failed write aren't carried over.
+            if key == skip_key
+                || key == EMPTY_KEY
+                || find_in_page(new_page, next_record, key).is_some()
+            {
+                continue;
+            }
+
+            let value = read_half_word(address);
+            let _ = flash
+                .program_half_word(next_record, value)
+                .and_then(|()| flash.program_half_word(next_record + 2, key));
+            next_record += RECORD_SIZE;
+        }
+
+        flash.erase_page(old_page);
+        let _ = flash.program_half_word(new_page, PageStatus::Valid.as_u16());
+
+        self.active_page = new_page;
+        self.next_record = next_record;
+    }
+}
+
+/// Finds the last record for `key` in the page starting at `page`, before the address `end`.
+fn find_in_page(page: u32, end: u32, key: u16) -> Option<u16> {
+    let mut address = end;
+    while address > page + HEADER_SIZE {
+        address -= RECORD_SIZE;
+        if read_half_word(address + 2) == key {
+            return Some(read_half_word(address));
+        }
+    }
+
+    None
+}
+
+fn read_half_word(address: u32) -> u16 {
+    u16::from_le_bytes(flash::read(address, 2).try_into().unwrap())
+}
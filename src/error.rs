@@ -0,0 +1,39 @@
+use crate::serial;
+use panel_core::counter;
+
+/// Errors which can happen while the firmware is running. None of them should stop it: they're
+/// logged, reported to the host if possible, and then the firmware carries on.
+#[derive(Debug)]
+pub enum Error {
+    Serial(serial::Error),
+    Counter(counter::Error),
+}
+
+impl From<serial::Error> for Error {
+    fn from(e: serial::Error) -> Error {
+        Error::Serial(e)
+    }
+}
+
+impl From<counter::Error> for Error {
+    fn from(e: counter::Error) -> Error {
+        Error::Counter(e)
+    }
+}
+
+impl Error {
+    /// A short description, for the log and debug reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Error::Serial(serial::Error::Serial(_)) => "serial error",
+            Error::Serial(serial::Error::UsbError(_)) => "USB error",
+            Error::Serial(serial::Error::BufferFull) => "command buffer full",
+            Error::Serial(serial::Error::MalformedMessage) => "malformed command",
+            Error::Serial(serial::Error::CommandQueueFull) => "command queue full",
+            Error::Serial(serial::Error::ReportQueueFull) => "report queue full",
+            Error::Serial(serial::Error::WriteTimeout) => "report write timed out",
+            Error::Counter(counter::Error::Overflow) => "dial counter overflow",
+            Error::Counter(counter::Error::CountJump) => "dial count jumped",
+        }
+    }
+}
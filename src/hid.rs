@@ -0,0 +1,317 @@
+use crate::serial::{Error, Transport};
+use panel_core::{button::ButtonEvent, input::InputEvent};
+use usb_device::{
+    class_prelude::{
+        ControlIn, ControlOut, DescriptorWriter, EndpointAddress, EndpointIn, EndpointOut,
+        InterfaceNumber, UsbBus, UsbBusAllocator, UsbClass, UsbError,
+    },
+    control::{Recipient, Request, RequestType},
+    device::{UsbDevice, UsbDeviceState},
+};
+
+// The protocol over a vendor defined USB HID interface, for hosts where a CDC serial port needs
+// a driver or permissions and HID doesn't. The same bytes as over serial are carried in 64 byte
+// reports, in both directions: the first byte is how many of the rest are protocol bytes, and
+// any after those are padding. Hosts read and write them with hidapi or similar, on the
+// interrupt endpoints, and the panel counts as connected once the host has configured it.
+//
+// With the hid-input feature, the panel is instead a composite device: USB serial for the
+// protocol as usual, and a standard HID interface next to it which reports the button and dial.
+// https://www.usb.org/sites/default/files/hid1_11.pdf
+
+#[cfg(all(feature = "hid-transport", feature = "uart-transport"))]
+compile_error!("hid-transport and uart-transport are both the transport to the host");
+#[cfg(all(feature = "hid-input", any(feature = "hid-transport", feature = "uart-transport")))]
+compile_error!("hid-input goes alongside USB serial, not the other transports");
+
+/// The size of every report, and of the interrupt endpoints that carry them.
+const REPORT_LEN: usize = 64;
+
+/// The most protocol bytes in one report, and so the most read from the host at a time.
+pub const PAYLOAD_LEN: usize = REPORT_LEN - 1;
+
+/// How often the host polls the endpoints, in milliseconds.
+const POLL_INTERVAL_MS: u8 = 1;
+
+const USB_CLASS_HID: u8 = 0x03;
+const HID_DESCRIPTOR_TYPE: u8 = 0x21;
+const REPORT_DESCRIPTOR_TYPE: u8 = 0x22;
+const SET_IDLE: u8 = 0x0A;
+
+/// One 64 byte input report and one 64 byte output report, with a vendor defined usage so that
+/// the host doesn't take the panel for a keyboard or mouse.
+#[rustfmt::skip]
+const REPORT_DESCRIPTOR: &[u8] = &[
+    0x06, 0x00, 0xFF, // Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x01, // Usage (0x01)
+    0xA1, 0x01, // Collection (Application)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xFF, 0x00, //   Logical Maximum (255)
+    0x75, 0x08, //   Report Size (8)
+    0x95, REPORT_LEN as u8, //   Report Count (64)
+    0x09, 0x01, //   Usage (0x01)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x95, REPORT_LEN as u8, //   Report Count (64)
+    0x09, 0x01, //   Usage (0x01)
+    0x91, 0x02, //   Output (Data, Variable, Absolute)
+    0xC0, // End Collection
+];
+
+/// A button and a dial, as a multi-axis controller, for hosts which only want the input. Each
+/// report is two bytes: whether the button is down in the lowest bit, then how far the dial has
+/// turned since the last one.
+#[rustfmt::skip]
+const INPUT_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x08, // Usage (Multi-axis Controller)
+    0xA1, 0x01, // Collection (Application)
+    0x05, 0x09, //   Usage Page (Button)
+    0x19, 0x01, //   Usage Minimum (1)
+    0x29, 0x01, //   Usage Maximum (1)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x75, 0x07, //   Report Size (7)
+    0x81, 0x03, //   Input (Constant), the rest of the byte
+    0x05, 0x01, //   Usage Page (Generic Desktop)
+    0x09, 0x37, //   Usage (Dial)
+    0x15, 0x81, //   Logical Minimum (-127)
+    0x25, 0x7F, //   Logical Maximum (127)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x06, //   Input (Data, Variable, Relative)
+    0xC0, // End Collection
+];
+
+const INPUT_REPORT_LEN: usize = 2;
+
+/// What both kinds of HID interface have in common: their descriptors, and answering the host's
+/// requests for them.
+struct Interface {
+    number: InterfaceNumber,
+    report_descriptor: &'static [u8],
+}
+
+impl Interface {
+    /// Writes the interface and HID descriptors, which the endpoints' go after.
+    fn write_descriptors(&self, writer: &mut DescriptorWriter) -> usb_device::Result<()> {
+        writer.interface(self.number, USB_CLASS_HID, 0, 0)?;
+
+        // HID 1.11, not localized, with the one report descriptor.
+        let [len_low, len_high] = (self.report_descriptor.len() as u16).to_le_bytes();
+        writer.write(
+            HID_DESCRIPTOR_TYPE,
+            &[0x11, 0x01, 0x00, 0x01, REPORT_DESCRIPTOR_TYPE, len_low, len_high],
+        )
+    }
+
+    fn is_for_interface(&self, recipient: Recipient, index: u16) -> bool {
+        recipient == Recipient::Interface && index == u8::from(self.number) as u16
+    }
+
+    fn control_in<B: UsbBus>(&self, xfer: ControlIn<B>) {
+        let request = *xfer.request();
+        let for_interface = self.is_for_interface(request.recipient, request.index);
+        if for_interface
+            && request.request_type == RequestType::Standard
+            && request.request == Request::GET_DESCRIPTOR
+            && (request.value >> 8) as u8 == REPORT_DESCRIPTOR_TYPE
+        {
+            let _ = xfer.accept_with_static(self.report_descriptor);
+        }
+    }
+
+    /// Reports only go out when there's something to say, so there's no idle rate to keep to.
+    fn control_out<B: UsbBus>(&self, xfer: ControlOut<B>) {
+        let request = *xfer.request();
+        let for_interface = self.is_for_interface(request.recipient, request.index);
+        if for_interface
+            && request.request_type == RequestType::Class
+            && request.request == SET_IDLE
+        {
+            let _ = xfer.accept();
+        }
+    }
+}
+
+/// The vendor defined interface which carries the protocol, with no report IDs.
+pub struct VendorHid<'a, B: UsbBus> {
+    interface: Interface,
+    in_endpoint: EndpointIn<'a, B>,
+    out_endpoint: EndpointOut<'a, B>,
+}
+
+impl<'a, B: UsbBus> VendorHid<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            interface: Interface {
+                number: alloc.interface(),
+                report_descriptor: REPORT_DESCRIPTOR,
+            },
+            in_endpoint: alloc.interrupt(REPORT_LEN as u16, POLL_INTERVAL_MS),
+            out_endpoint: alloc.interrupt(REPORT_LEN as u16, POLL_INTERVAL_MS),
+        }
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for VendorHid<'_, B> {
+    fn get_configuration_descriptors(
+        &self,
+        writer: &mut DescriptorWriter,
+    ) -> usb_device::Result<()> {
+        self.interface.write_descriptors(writer)?;
+        writer.endpoint(&self.in_endpoint)?;
+        writer.endpoint(&self.out_endpoint)
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        self.interface.control_in(xfer);
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        self.interface.control_out(xfer);
+    }
+}
+
+/// The button and the dial as a standard HID device, next to USB serial, for host software which
+/// only wants the input and not the protocol.
+pub struct InputHid<'a, B: UsbBus> {
+    interface: Interface,
+    in_endpoint: EndpointIn<'a, B>,
+    pressed: bool,
+    /// How far the dial has turned since the last report which went out.
+    unsent_dial: i16,
+    /// Whether there's anything the host hasn't been sent yet.
+    changed: bool,
+}
+
+impl<'a, B: UsbBus> InputHid<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            interface: Interface {
+                number: alloc.interface(),
+                report_descriptor: INPUT_REPORT_DESCRIPTOR,
+            },
+            in_endpoint: alloc.interrupt(INPUT_REPORT_LEN as u16, POLL_INTERVAL_MS),
+            pressed: false,
+            unsent_dial: 0,
+            changed: false,
+        }
+    }
+
+    pub fn push(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Button(ButtonEvent::Pressed) => self.pressed = true,
+            InputEvent::Button(ButtonEvent::ShortRelease)
+            | InputEvent::Button(ButtonEvent::LongRelease) => self.pressed = false,
+            // The button's bit says whether it was held, as `button_held` does in the protocol.
+            InputEvent::Dial(diff) | InputEvent::HeldDial(diff) => {
+                self.unsent_dial = self.unsent_dial.saturating_add(diff as i16);
+            },
+            _ => return,
+        }
+
+        self.changed = true;
+        self.flush();
+    }
+
+    /// Sends whatever has changed, unless the host hasn't read the last report yet, in which case
+    /// it's tried again on the next call.
+    pub fn flush(&mut self) {
+        if !self.changed {
+            return;
+        }
+
+        let dial = self.unsent_dial.clamp(-127, 127);
+        if self.in_endpoint.write(&[self.pressed as u8, dial as i8 as u8]).is_ok() {
+            self.unsent_dial -= dial;
+            self.changed = self.unsent_dial != 0;
+        }
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for InputHid<'_, B> {
+    fn get_configuration_descriptors(
+        &self,
+        writer: &mut DescriptorWriter,
+    ) -> usb_device::Result<()> {
+        self.interface.write_descriptors(writer)?;
+        writer.endpoint(&self.in_endpoint)
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        self.interface.control_in(xfer);
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        self.interface.control_out(xfer);
+    }
+
+    fn endpoint_in_complete(&mut self, address: EndpointAddress) {
+        if address == self.in_endpoint.address() {
+            self.flush();
+        }
+    }
+}
+
+/// USB HID, as the transport for the protocol.
+pub struct UsbHid<'a, B: UsbBus> {
+    usb_device: UsbDevice<'a, B>,
+    hid: VendorHid<'a, B>,
+}
+
+impl<'a, B: UsbBus> UsbHid<'a, B> {
+    pub fn new(usb_device: UsbDevice<'a, B>, hid: VendorHid<'a, B>) -> Self {
+        Self { usb_device, hid }
+    }
+}
+
+impl<B: UsbBus> Transport for UsbHid<'_, B> {
+    /// Reads the protocol bytes of the next report from the host, so `buf` should have room for
+    /// `PAYLOAD_LEN` of them. Any more are dropped.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.usb_device.poll(&mut [&mut self.hid]);
+
+        let mut report = [0u8; REPORT_LEN];
+        let len = match self.hid.out_endpoint.read(&mut report) {
+            Ok(len) => len,
+            Err(UsbError::WouldBlock) => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let payload = &report[1..len.max(1)];
+        let count = (report[0] as usize).min(payload.len()).min(buf.len());
+        buf[..count].copy_from_slice(&payload[..count]);
+        Ok(count)
+    }
+
+    /// Sends up to a report's worth of `bytes` in one report.
+    fn write(&mut self, bytes: &[u8]) -> nb::Result<usize, Error> {
+        let len = bytes.len().min(PAYLOAD_LEN);
+        let mut report = [0u8; REPORT_LEN];
+        report[0] = len as u8;
+        report[1..1 + len].copy_from_slice(&bytes[..len]);
+
+        match self.hid.in_endpoint.write(&report) {
+            Ok(_) => Ok(len),
+            Err(UsbError::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(e) => Err(nb::Error::Other(e.into())),
+        }
+    }
+
+    fn packet_len(&self) -> usize {
+        PAYLOAD_LEN
+    }
+
+    /// HID has nothing like DTR, so the host counts as connected once it has configured the
+    /// device.
+    fn is_connected(&self) -> bool {
+        self.usb_device.state() == UsbDeviceState::Configured
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.usb_device.state() == UsbDeviceState::Suspend
+    }
+}
@@ -0,0 +1,55 @@
+#![no_std]
+
+// Everything the firmware does lives in this library, including bringing up the peripherals, see
+// platform::init, so that main.rs only has to wire them into the RTIC tasks. The modules which
+// work on the tasks' resources are only there for the platforms with an RTIC app, see
+// platform/mod.rs.
+
+// The log macros, which all of the other modules use.
+#[macro_use]
+extern crate panel_core;
+
+pub mod ambient_light;
+pub mod board;
+pub mod boot_diagnostics;
+pub mod bootloader;
+pub mod brownout;
+pub mod capabilities;
+#[cfg(feature = "stm32f1")]
+pub mod commands;
+#[cfg(feature = "stm32f1")]
+pub mod controls;
+pub mod cpu_load;
+pub mod drv2605;
+pub mod ds3231;
+pub mod eeprom;
+pub mod error;
+pub mod hid;
+#[cfg(feature = "stm32f1")]
+pub mod lights;
+mod log;
+pub mod mcp23017;
+pub mod outputs;
+pub mod overhead_light;
+pub mod panic_log;
+pub mod platform;
+pub mod power_monitor;
+pub mod profiler;
+pub mod queues;
+#[cfg(feature = "stm32f1")]
+pub mod resources;
+pub mod rgb_led;
+#[cfg(feature = "stm32f1")]
+pub mod self_test;
+pub mod serial;
+pub mod settings;
+pub mod ssd1306;
+#[cfg(feature = "stm32f1")]
+pub mod telemetry;
+pub mod usb;
+pub mod version;
+
+pub const SYSCLK_HZ: u32 = 48_000_000;
+
+/// SysTick fires once per millisecond, driving the scheduler in `systick()` in main.rs.
+pub const TICK_HZ: u32 = 1_000;
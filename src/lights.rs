@@ -0,0 +1,388 @@
+use crate::{
+    ambient_light::AmbientLight,
+    board, bootloader, brownout,
+    ds3231::Ds3231,
+    eeprom::Eeprom,
+    platform::{dmx::DmxPort, pvd, wall_clock::WallClock},
+    profiler::{self, Section},
+    queues::{self, ReportQueue},
+    resources::{BackLight, EncoderCounter, FrontLight, I2cBus, Pixels, Ring, Strip},
+    rgb_led::{LedSettings, Rgb},
+    serial::Report,
+    settings::{self, Scene},
+};
+use cortex_m::peripheral::SCB;
+use panel_core::{
+    auto_brightness::AutoBrightness,
+    daylight::Daylight,
+    dmx::Universe,
+    encoder_ring,
+    fraction::Fraction,
+    power::PowerManager,
+    power_budget::PowerBudget,
+    pulser::Pulser,
+    schedule::{self, Schedule},
+    sound_level::SoundLevel,
+    standalone::Standalone,
+    tick::{Every, Instant},
+};
+use rtic::Mutex;
+
+// What the tasks do to the front and back lights and the LED strip, besides what the host's
+// commands do, see commands.rs: the strip's frames, the power state and the supply voltage,
+// standalone mode, the daily schedule, the daylight curve and auto-brightness.
+
+/// While the supply voltage is low, the lights and LED strip are dimmed to this fraction of their
+/// brightness to reduce the load on it.
+pub const LOW_VOLTAGE_BRIGHTNESS: Fraction = Fraction::from_ratio(1, 4);
+
+/// The scene the panel sets when it starts controlling its lights itself.
+pub const STANDALONE_BRIGHTNESS: u16 = u16::MAX / 2;
+pub const STANDALONE_TEMPERATURE: u16 = u16::MAX / 2;
+pub const STANDALONE_LED_COLOR: (u8, u8, u8) = (255, 140, 40);
+
+/// The LED strip is refreshed at 60 Hz, from its own timer.
+pub const LED_FRAME_HZ: u32 = 60;
+
+/// How often the ambient light is measured, adjusting the lights if auto-brightness is on.
+pub const AMBIENT_LIGHT_PERIOD_MS: u32 = 1000;
+
+/// How often the encoder ring is brought up to date, with the encoder-ring feature. Frames are
+/// only sent when they change.
+pub const ENCODER_RING_PERIOD_MS: u32 = 20;
+
+/// How often the DMX fixtures are sent their levels, with the dmx-output feature. Fixtures hold
+/// the last frame they got, so this only limits how quickly they follow the host.
+pub const DMX_FRAME_PERIOD_MS: u32 = 25;
+
+/// How often the wall clock is checked against the daily schedule.
+pub const SCHEDULE_PERIOD_MS: u32 = 1000;
+
+/// How often the lights take a step along the daylight curve, if the host has set one.
+pub const DAYLIGHT_PERIOD_MS: u32 = 1000;
+
+/// How often the wall clock is set from the DS3231, on boards which have one, as the internal
+/// RTC drifts by seconds a day.
+pub const EXTERNAL_RTC_SYNC_PERIOD_MS: u32 = 60 * 60_000;
+
+/// The front and back lights, for the tasks which set both the same. Each is locked on its own,
+/// for as short a time as possible.
+pub struct Lights<F, B> {
+    pub front: F,
+    pub back: B,
+}
+
+impl<F, B> Lights<F, B>
+where
+    F: Mutex<T = FrontLight>,
+    B: Mutex<T = BackLight>,
+{
+    pub fn set_brightness(&mut self, brightness: u16) {
+        self.front.lock(|light| light.set_brightness(brightness));
+        self.back.lock(|light| light.set_brightness(brightness));
+    }
+
+    pub fn set_color_temperature(&mut self, color_temperature: u16) {
+        self.front.lock(|light| light.set_color_temperature(color_temperature));
+        self.back.lock(|light| light.set_color_temperature(color_temperature));
+    }
+
+    pub fn set_brightness_scale(&mut self, scale: Fraction) {
+        self.front.lock(|light| light.set_brightness_scale(scale));
+        self.back.lock(|light| light.set_brightness_scale(scale));
+    }
+
+    /// The brightness and color temperature of each light, front first.
+    pub fn levels(&mut self) -> [(u16, u16); 2] {
+        [
+            self.front.lock(|light| (light.brightness(), light.color_temperature())),
+            self.back.lock(|light| (light.brightness(), light.color_temperature())),
+        ]
+    }
+}
+
+/// Everything which goes into a frame of the LED strip.
+pub struct LedFrame<'a> {
+    pub strip: &'a mut Strip,
+    pub pixels: &'a Pixels,
+    /// The color the self-test is showing, in place of the strip's own while it's set.
+    pub strip_test: Option<Rgb>,
+    pub pulser: &'a mut Pulser,
+    pub settings: &'a LedSettings,
+    pub sound_level: &'a SoundLevel,
+    pub low_voltage: bool,
+    pub power: &'a PowerManager,
+    pub power_budget: &'a Option<PowerBudget>,
+}
+
+impl LedFrame<'_> {
+    /// Sends the strip its next frame, unless it's the same as `last_frame`. The LEDs latch their
+    /// color, so a static frame only needs to be sent once.
+    pub fn send(self, last_frame: &mut Option<[Rgb; board::BOARD.led_count]>) {
+        let mut intensity =
+            if self.settings.pulse { self.pulser.intensity() } else { Fraction::ONE };
+        intensity *= self.sound_level.intensity();
+        intensity *= self.power.state().strip_brightness();
+        if self.low_voltage {
+            intensity *= LOW_VOLTAGE_BRIGHTNESS;
+        }
+        if let Some(budget) = self.power_budget {
+            intensity *= budget.scale();
+        }
+        let frame = match (self.strip_test, self.pixels.frame(intensity)) {
+            (Some(test), _) => [test; board::BOARD.led_count],
+            (None, Some(frame)) => frame,
+            (None, None) => [self.settings.frame(intensity); board::BOARD.led_count],
+        };
+
+        if *last_frame != Some(frame) {
+            let strip = self.strip;
+            profiler::measure(Section::LedWrite, || strip.set_colors(&frame));
+            *last_frame = Some(frame);
+        }
+    }
+}
+
+/// Dims everything while the supply voltage is low, as panels on long USB runs can brown out
+/// under full LED load, and tells the host. Returns true once the voltage has recovered, when
+/// the count of low-voltage events should be saved.
+pub fn voltage_change(
+    reports: &mut ReportQueue,
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    led_settings: &LedSettings,
+    low_voltage: &mut bool,
+    low_voltage_events: &mut u16,
+    power: &PowerManager,
+) -> bool {
+    let is_low = pvd::is_low();
+    if is_low == *low_voltage {
+        return false;
+    }
+    *low_voltage = is_low;
+
+    let mut scale = power.state().light_brightness();
+    if is_low {
+        scale *= LOW_VOLTAGE_BRIGHTNESS;
+    }
+    lights.set_brightness_scale(scale);
+
+    // Writing flash can take milliseconds if a page needs erasing, which is too long for an
+    // interrupt, and a bad idea while the supply is failing. So the event is only counted
+    // here, and saved once the voltage recovers. The lights and the strip are the exception,
+    // as they go into a slot which was erased in advance, in case the panel resets before then.
+    let report = if is_low {
+        warn!("supply voltage is low");
+        let [front, back] = lights.levels();
+        brownout::save(&Scene { front, back, led: *led_settings });
+        *low_voltage_events = low_voltage_events.saturating_add(1);
+        Report::LowVoltage { count: *low_voltage_events }
+    } else {
+        info!("supply voltage recovered");
+        Report::VoltageRecovered
+    };
+    let _ = queues::send_report(reports, report);
+    !is_low
+}
+
+/// Saves the count of low-voltage events, once the supply has recovered from the latest, and
+/// erases the snapshot of the lights ready for the next. An event the panel resets during,
+/// before recovering, goes uncounted.
+pub fn save_low_voltage_events(eeprom: &mut Eeprom, mut low_voltage_events: impl Mutex<T = u16>) {
+    // The voltage dropped again before this ran, and it's spawned again when it recovers.
+    if pvd::is_low() {
+        return;
+    }
+
+    let count = low_voltage_events.lock(|count| *count);
+    settings::save_low_voltage_count(eeprom, count);
+    brownout::clear();
+}
+
+/// Turns the lights down or off as the panel dims or goes to sleep, see panel_core::power. The
+/// LED strip follows the power state on its own. DMX fixtures have their own supply, so they
+/// aren't dimmed for a low voltage.
+pub fn apply_power_state(
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    dmx: &mut Universe,
+    mut low_voltage: impl Mutex<T = bool>,
+    mut power: impl Mutex<T = PowerManager>,
+) {
+    let mut scale = power.lock(|power| power.state()).light_brightness();
+    dmx.set_brightness_scale(scale);
+    if low_voltage.lock(|low_voltage| *low_voltage) {
+        scale *= LOW_VOLTAGE_BRIGHTNESS;
+    }
+    lights.set_brightness_scale(scale);
+}
+
+/// Sets the default scene for standalone mode, or when it's recalled from a remote. Once a host
+/// connects it sets its own.
+pub fn start_standalone(
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    mut led_settings: impl Mutex<T = LedSettings>,
+    mut led_pixels: impl Mutex<T = Pixels>,
+    standalone: &Standalone,
+) {
+    lights.set_brightness(standalone.light_brightness());
+    lights.set_color_temperature(STANDALONE_TEMPERATURE);
+    led_settings.lock(|settings| {
+        *settings = LedSettings { color: STANDALONE_LED_COLOR, pulse: false };
+    });
+    led_pixels.lock(|pixels| pixels.clear());
+}
+
+/// Turns the lights and the LED strip off, then resets the panel, into the bootloader if asked
+/// to. The strip would otherwise keep showing its last frame through the reset, and for as
+/// long as the bootloader runs.
+pub fn reset(
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    mut led_strip: impl Mutex<T = Strip>,
+    into_bootloader: bool,
+) {
+    lights.set_brightness(0);
+    // Held through the reset, so that the frame timer can't light the strip again.
+    led_strip.lock(|led_strip| {
+        led_strip.set_all(Rgb::new(0, 0, 0));
+        if into_bootloader {
+            bootloader::reboot_into_bootloader();
+        }
+        SCB::sys_reset()
+    })
+}
+
+/// Sets the lights as the daily schedule says, see panel_core::schedule, whether or not the
+/// host is connected. Until the wall clock has been set, there's no schedule to follow.
+pub fn run_schedule(
+    wall_clock: &mut WallClock,
+    external_rtc: &mut Option<Ds3231>,
+    i2c: &mut I2cBus,
+    schedule: &mut Schedule,
+    utc_offset_minutes: i16,
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    external_rtc_sync: &mut Every,
+) {
+    if let Some(rtc) = external_rtc {
+        if external_rtc_sync.is_due(Instant::now()) {
+            match rtc.now(i2c) {
+                Ok(Some(now)) => wall_clock.set(now),
+                Ok(None) => {},
+                Err(_) => warn!("DS3231 didn't respond"),
+            }
+        }
+    }
+
+    let now = match wall_clock.now() {
+        Some(now) => now,
+        None => return,
+    };
+    if let Some(entry) = schedule.poll(schedule::minute_of_day(now, utc_offset_minutes)) {
+        lights.set_brightness(entry.brightness);
+        lights.set_color_temperature(entry.color_temperature);
+    }
+}
+
+/// Moves the lights' color temperature along the daylight curve, see panel_core::daylight, and
+/// tells the host whenever where it's heading changes.
+pub fn track_daylight(
+    wall_clock: &WallClock,
+    utc_offset_minutes: i16,
+    daylight: &mut Daylight,
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    mut reports: impl Mutex<T = ReportQueue>,
+) {
+    if let Some(now) = wall_clock.now() {
+        daylight.set_time(schedule::minute_of_day(now, utc_offset_minutes));
+    }
+
+    let current = lights.front.lock(|light| light.color_temperature());
+    if let Some(temperature) = daylight.poll(current) {
+        lights.set_color_temperature(temperature);
+    }
+
+    if let Some(value) = daylight.setpoint_change() {
+        let report = Report::ColorTemperatureSetpoint { value };
+        let _ = reports.lock(|reports| queues::send_report(reports, report));
+    }
+}
+
+/// Reports the ambient light to the host, and adjusts the lights to it if auto-brightness is
+/// on, see panel_core::auto_brightness. The daylight curve follows it too, until the wall
+/// clock is set.
+pub fn measure_ambient_light(
+    sensor: &mut Option<AmbientLight>,
+    i2c: &mut I2cBus,
+    auto_brightness: &mut AutoBrightness,
+    daylight: &mut Daylight,
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    mut reports: impl Mutex<T = ReportQueue>,
+) {
+    let sensor = match sensor {
+        Some(sensor) => sensor,
+        None => return,
+    };
+
+    let lux = match sensor.lux(i2c) {
+        Ok(lux) => lux,
+        Err(_) => {
+            warn!("ambient light sensor didn't respond");
+            return;
+        },
+    };
+
+    auto_brightness.measure(lux);
+    daylight.measure(lux);
+    if let Some(brightness) = auto_brightness.poll() {
+        lights.set_brightness(brightness);
+    }
+
+    let _ = reports.lock(|reports| queues::send_report(reports, Report::AmbientLight { lux }));
+}
+
+/// Shows the dial's position on the ring around it, see panel_core::encoder_ring, in the color
+/// of the panel's mode: the standalone scene's while the panel controls the lights itself, and
+/// the host's LED color otherwise. It dims and sleeps along with the strip. Frames are only
+/// sent when they differ from `displayed`. There's only a ring with the encoder-ring feature.
+pub fn update_encoder_ring(
+    ring: &mut Option<Ring>,
+    counter: &EncoderCounter,
+    standalone: &Standalone,
+    mut led_settings: impl Mutex<T = LedSettings>,
+    mut power: impl Mutex<T = PowerManager>,
+    mut low_voltage: impl Mutex<T = bool>,
+    displayed: &mut Option<[Rgb; board::BOARD.encoder_ring_len]>,
+) {
+    let ring = match ring {
+        Some(ring) => ring,
+        None => return,
+    };
+
+    let color = if standalone.is_active() {
+        STANDALONE_LED_COLOR
+    } else {
+        led_settings.lock(|settings| settings.color)
+    };
+    let mut intensity = power.lock(|power| power.state().strip_brightness());
+    if low_voltage.lock(|low_voltage| *low_voltage) {
+        intensity *= LOW_VOLTAGE_BRIGHTNESS;
+    }
+
+    let position = counter.position();
+    let frame = encoder_ring::render(position, board::BOARD.detents_per_turn, color, intensity)
+        .map(|(r, g, b)| Rgb::new(r, g, b));
+    if *displayed != Some(frame) {
+        ring.set_colors(&frame);
+        *displayed = Some(frame);
+    }
+}
+
+/// Sends the DMX fixtures their levels, see panel_core::dmx. The break is held inside the
+/// lock, which at about 0.1 ms is short of what anything at priority 2 notices. There's only a
+/// port with the dmx-output feature.
+pub fn send_dmx_frame(dmx: &Universe, mut dmx_port: impl Mutex<T = Option<DmxPort>>) {
+    let frame = dmx.frame();
+    dmx_port.lock(|port| {
+        if let Some(port) = port {
+            port.send(frame);
+        }
+    });
+}
@@ -0,0 +1,9 @@
+// The log macros are exported by panel-core. With the "logging" feature they go out over RTT,
+// for developers with a debug probe attached.
+
+#[cfg(feature = "logging")]
+use defmt_rtt as _; // global logger
+
+// Each line is stamped with the time since boot.
+#[cfg(feature = "logging")]
+defmt::timestamp!("{=u32:ms}", panel_core::tick::uptime().as_ms());
@@ -1,216 +1,1003 @@
 #![no_main]
 #![no_std]
+// The code #[rtic::app] generates for RTIC 0.5 predates these lints.
+#![allow(static_mut_refs, non_local_definitions, unexpected_cfgs)]
+
+use heapless::spsc::Queue;
+use panel_core::{
+    auto_brightness::AutoBrightness,
+    commands::{Ctx, UNLOCK_KEY},
+    daylight::Daylight,
+    dmx::Universe,
+    fraction::Fraction,
+    haptics::HapticEffects,
+    input::{self, DialRateLimit},
+    ir::{IrBindings, IrCode},
+    joystick::Joystick,
+    power::PowerManager,
+    power_budget::PowerBudget,
+    pulser::Pulser,
+    schedule::Schedule,
+    slider::Slider,
+    sound_level::SoundLevel,
+    standalone::Standalone,
+    tick::{self, Every, Instant},
+    unlock::Unlock,
+};
+use panel_firmware::{
+    ambient_light::AmbientLight,
+    board,
+    capabilities::Capabilities,
+    commands::{self, Hardware},
+    controls::{
+        self, HandleInput, InputPoll, InputPollState, EXPANDER_POLL_PERIOD_MS,
+        INPUT_POLL_PERIOD_MS, JOYSTICK_PERIOD_MS, MICROPHONE_PERIOD_MS, SLIDER_PERIOD_MS,
+    },
+    cpu_load,
+    drv2605::Drv2605,
+    ds3231::Ds3231,
+    eeprom::{Eeprom, EEPROM_FLUSH_PERIOD_MS},
+    lights::{
+        self, LedFrame, Lights, AMBIENT_LIGHT_PERIOD_MS, DAYLIGHT_PERIOD_MS, DMX_FRAME_PERIOD_MS,
+        ENCODER_RING_PERIOD_MS, EXTERNAL_RTC_SYNC_PERIOD_MS, SCHEDULE_PERIOD_MS,
+    },
+    platform::{
+        self,
+        buzzer::Buzzer,
+        dmx::DmxPort,
+        hal::{gpio::ExtiPin, pac::TIM1, timer::CountDownTimer, watchdog::IndependentWatchdog},
+        pvd,
+        wall_clock::WallClock,
+    },
+    power_monitor::PowerMonitor,
+    profiler::{self, Section},
+    queues::{CommandQueue, InputQueue, ReportQueue},
+    resources::{
+        AnalogInputs, AuxOutputs, BackLight, Door, EncoderButton, EncoderCounter, Expander, Fan,
+        FixtureProbes, FrontLight, I2cBus, IrReceiver, Pixels, Protocol, Ring, StatusDisplay,
+        Strip, VbusPin,
+    },
+    rgb_led::{LedPixels, LedSettings, Rgb},
+    self_test::SelfTest,
+    settings::Settings,
+    telemetry::{
+        self, CPU_LOAD_PERIOD_MS, DISPLAY_PERIOD_MS, FAN_PERIOD_MS, FIXTURE_PROBE_PERIOD_MS,
+        LED_RAIL_PERIOD_MS, LED_RAIL_REPORT_PERIOD_MS,
+    },
+    usb::{self, BootReport},
+};
+use rtic::Exclusive;
+
+// The RTIC app: the resources the tasks share, and which task does what when. The peripherals are
+// brought up by platform::init, and the tasks' work is done by the library.
+
+#[rtic::app(device = panel_firmware::platform::hal::pac, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        protocol: Protocol,
+        #[init(Queue::new())]
+        commands: CommandQueue,
+        #[init(Queue::new())]
+        reports: ReportQueue,
+        #[init(Queue::new())]
+        input_events: InputQueue,
+        front_light: FrontLight,
+        back_light: BackLight,
+        led_strip: Strip,
+        #[init(LedPixels::new())]
+        led_pixels: Pixels,
+        /// The color the self-test is showing on the strip, which the frame timer shows in place
+        /// of the strip's own while it's set.
+        #[init(None)]
+        strip_test: Option<Rgb>,
+        /// `None` without the encoder-ring feature.
+        encoder_ring: Option<Ring>,
+        led_timer: CountDownTimer<TIM1>,
+        buzzer: Buzzer,
+        #[init(false)]
+        click_feedback: bool,
+        /// Whether a host which connects is sent `Report::Status` straight away.
+        status_on_connect: bool,
+        /// The optional parts found at boot, for `Command::GetCapabilities`.
+        capabilities: Capabilities,
+        /// Which version of the protocol the connected host speaks, see panel_core::input.
+        #[init(input::DEFAULT_PROTOCOL_VERSION)]
+        protocol_version: u8,
+        pulser: Pulser,
+        led_settings: LedSettings,
+        counter: EncoderCounter,
+        dial_limit: DialRateLimit,
+        /// The timings which can be changed with `Command::SetConfig`.
+        settings: Settings,
+        encoder_button: EncoderButton,
+        /// `None` if there's no door sensor on the board.
+        door: Option<Door>,
+        led: board::StatusLedPin,
+        /// High while a USB cable is plugged in, on boards which sense VBUS.
+        vbus: VbusPin,
+        watchdog: IndependentWatchdog,
+        eeprom: Eeprom,
+        wall_clock: WallClock,
+        /// `None` if there's no DS3231 on the board.
+        external_rtc: Option<Ds3231>,
+        schedule: Schedule,
+        /// Added to UTC to get the local time the schedule follows.
+        utc_offset_minutes: i16,
+        #[init(false)]
+        low_voltage: bool,
+        /// Low-voltage events so far, which are only saved once the supply has recovered.
+        low_voltage_events: u16,
+        power: PowerManager,
+        standalone: Standalone,
+        /// Shared by everything on I2C2, see platform::init.
+        i2c: I2cBus,
+        /// `None` if there's no sensor on the board.
+        ambient_light: Option<AmbientLight>,
+        display: StatusDisplay,
+        /// `None` if there's no haptic driver on the board.
+        haptics: Option<Drv2605>,
+        haptic_effects: HapticEffects,
+        /// `None` if there's no current monitor on the LED strip's rail.
+        power_monitor: Option<PowerMonitor>,
+        /// `None` unless the board has a budget for the rail, and a monitor to enforce it.
+        power_budget: Option<PowerBudget>,
+        #[init(AutoBrightness::new())]
+        auto_brightness: AutoBrightness,
+        #[init(Daylight::new())]
+        daylight: Daylight,
+        analog: AnalogInputs,
+        #[init([Slider::new(), Slider::new()])]
+        sliders: [Slider; 2],
+        #[init(Joystick::new())]
+        joystick: Joystick,
+        #[init(SoundLevel::new())]
+        sound_level: SoundLevel,
+        ir_receiver: IrReceiver,
+        ir_bindings: IrBindings,
+        fan: Fan,
+        fan_tach: board::FanTachPin,
+        /// Counted since the last time the fan was adjusted.
+        #[init(0)]
+        tach_pulses: u32,
+        outputs: AuxOutputs,
+        expander: Expander,
+        /// Light targets beyond the front and back lights. They're only sent anywhere with the
+        /// dmx-output feature.
+        #[init(Universe::new())]
+        dmx: Universe,
+        /// `None` without the dmx-output feature.
+        dmx_port: Option<DmxPort>,
+        fixture_probes: FixtureProbes,
+        boot_report: BootReport,
+    }
 
-use panic_halt as _; // panic handler
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        let setup = platform::init::bring_up(cx.core, cx.device);
+
+        init::LateResources {
+            protocol: setup.protocol,
+            front_light: setup.front_light,
+            back_light: setup.back_light,
+            led_strip: setup.led_strip,
+            encoder_ring: setup.encoder_ring,
+            dmx_port: setup.dmx_port,
+            led_timer: setup.led_timer,
+            buzzer: setup.buzzer,
+            pulser: setup.pulser,
+            led_settings: setup.led_settings,
+            counter: setup.counter,
+            dial_limit: setup.dial_limit,
+            settings: setup.settings,
+            encoder_button: setup.encoder_button,
+            status_on_connect: setup.status_on_connect,
+            capabilities: setup.capabilities,
+            door: setup.door,
+            led: setup.led,
+            vbus: setup.vbus,
+            watchdog: setup.watchdog,
+            eeprom: setup.eeprom,
+            wall_clock: setup.wall_clock,
+            external_rtc: setup.external_rtc,
+            schedule: setup.schedule,
+            utc_offset_minutes: setup.utc_offset_minutes,
+            low_voltage_events: setup.low_voltage_events,
+            power: setup.power,
+            standalone: setup.standalone,
+            i2c: setup.i2c,
+            ambient_light: setup.ambient_light,
+            display: setup.display,
+            haptics: setup.haptics,
+            haptic_effects: setup.haptic_effects,
+            power_monitor: setup.power_monitor,
+            power_budget: setup.power_budget,
+            analog: setup.analog,
+            ir_receiver: setup.ir_receiver,
+            ir_bindings: setup.ir_bindings,
+            fan: setup.fan,
+            fan_tach: setup.fan_tach,
+            outputs: setup.outputs,
+            expander: setup.expander,
+            fixture_probes: setup.fixture_probes,
+            boot_report: setup.boot_report,
+        }
+    }
 
-use stm32f1xx_hal as hal;
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        // Everything happens in interrupts or scheduled tasks, so sleep until the next one.
+        loop {
+            cpu_load::sleep();
+        }
+    }
 
-use crate::{
-    button::{Active, Button, ButtonEvent, Debouncer},
-    counter::Counter,
-    overhead_light::OverheadLight,
-    rgb_led::{LedStrip, Pulser, Rgb},
-    serial::{Command, Report, SerialProtocol},
-};
-use cortex_m::asm::delay;
-use cortex_m_rt::entry;
-use embedded_hal::digital::v2::OutputPin;
-use hal::{
-    pac,
-    prelude::*,
-    qei::QeiOptions,
-    spi::{Mode as SpiMode, NoMiso, NoSck, Phase, Polarity, Spi, Spi1NoRemap},
-    time::MonoTimer,
-    timer::{Tim2NoRemap, Tim3PartialRemap, Timer},
-    usb::{Peripheral, UsbBus},
-};
-use usb_device::device::{UsbDeviceBuilder, UsbVidPid};
-use usbd_serial::{SerialPort, USB_CLASS_CDC};
-
-mod button;
-mod counter;
-mod overhead_light;
-mod rgb_led;
-mod serial;
-
-#[entry]
-fn main() -> ! {
-    let mut cp =
-        cortex_m::peripheral::Peripherals::take().expect("failed to get cortex_m peripherals");
-    let dp = pac::Peripherals::take().expect("failed to get stm32 peripherals");
-
-    // Take ownership over the raw flash and rcc devices and convert them into the corresponding
-    // HAL structs.
-    // RCC = Reset and Clock Control
-    let mut flash = dp.FLASH.constrain();
-    let mut rcc = dp.RCC.constrain();
-
-    // The various system clocks need to be configured to particular values
-    // to work with USB - we'll set them up here.
-    let clocks = rcc
-        .cfgr
-        .use_hse(8.mhz()) // Use the High Speed External 8MHz crystal
-        .sysclk(48.mhz()) // The main system clock will be 48MHz
-        .pclk1(24.mhz())  // Use 24MHz for the APB1 (Advanced Peripheral Bus 1)
-        .freeze(&mut flash.acr);
-
-    assert!(clocks.usbclk_valid());
-
-    // Needed in order for MonoTimer to work properly
-    cp.DCB.enable_trace();
-
-    // Prepare the alternate function I/O registers
-    let mut afio = dp.AFIO.constrain(&mut rcc.apb2);
-
-    // Grab the GPIO banks we'll use.
-    let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
-    let mut gpiob = dp.GPIOB.split(&mut rcc.apb2);
-
-    // Set up the LED (B12).
-    let mut led = gpiob.pb12.into_push_pull_output(&mut gpiob.crh);
-
-    // Set up USB communications
-    let usb_pin_d_minus = gpioa.pa11;
-
-    // Pull the USB D+ pin low to indicate to the USB host that this device
-    // is resetting (sends a RESET condition on the USB bus).
-    let mut usb_pin_d_plus = gpioa.pa12.into_push_pull_output(&mut gpioa.crh);
-    usb_pin_d_plus.set_low().unwrap();
-    delay(clocks.sysclk().0 / 100);
-
-    let usb_pin_d_plus = usb_pin_d_plus.into_floating_input(&mut gpioa.crh);
-
-    let usb = Peripheral { usb: dp.USB, pin_dm: usb_pin_d_minus, pin_dp: usb_pin_d_plus };
-
-    let usb_bus = UsbBus::new(usb);
-    let serial = SerialPort::new(&usb_bus);
-
-    let usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
-        .manufacturer("tonari")
-        .product("tonari dashboard controller")
-        .serial_number("tonari-dashboard-controller-v1")
-        .device_class(USB_CLASS_CDC)
-        .build();
-
-    let mut protocol = SerialProtocol::new(usb_dev, serial);
-
-    // Disable JTAG so that we can use the pin PB4 for the timer
-    let (_pa15, _pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
-
-    // SPI Setup (for WS8212b RGB LEDs)
-    let mosi_pin = gpioa.pa7.into_alternate_push_pull(&mut gpioa.crl);
-    let spi_pins = (NoSck, NoMiso, mosi_pin);
-    let spi_mode = SpiMode { polarity: Polarity::IdleLow, phase: Phase::CaptureOnFirstTransition };
-
-    let spi = Spi::<_, Spi1NoRemap, _, u8>::spi1(
-        dp.SPI1,
-        spi_pins,
-        &mut afio.mapr,
-        spi_mode,
-        2250.khz(), // https://os.mbed.com/teams/ST/wiki/SPI-output-clock-frequency
-        clocks,
-        &mut rcc.apb2,
-    );
-
-    let mut led_strip = LedStrip::new(spi);
-
-    let timer = MonoTimer::new(cp.DWT, cp.DCB, clocks);
-    let mut pulser = Pulser::new(700, &timer);
-
-    // PWM Setup
-    // https://docs.rs/stm32f1xx-hal/0.6.1/stm32f1xx_hal/timer/index.html
-    let timer3_pwm_pins = (
-        pb4.into_alternate_push_pull(&mut gpiob.crl),
-        gpiob.pb5.into_alternate_push_pull(&mut gpiob.crl),
-        gpiob.pb0.into_alternate_push_pull(&mut gpiob.crl),
-        gpiob.pb1.into_alternate_push_pull(&mut gpiob.crl),
-    );
-    let timer4_pwm_pins = (
-        gpiob.pb6.into_alternate_push_pull(&mut gpiob.crl),
-        gpiob.pb7.into_alternate_push_pull(&mut gpiob.crl),
-        gpiob.pb8.into_alternate_push_pull(&mut gpiob.crh),
-        gpiob.pb9.into_alternate_push_pull(&mut gpiob.crh),
-    );
-    let (pwm1, pwm2, pwm3, pwm4) = Timer::tim3(dp.TIM3, &clocks, &mut rcc.apb1)
-        .pwm::<Tim3PartialRemap, _, _, _>(timer3_pwm_pins, &mut afio.mapr, 1.khz())
-        .split();
-    let (pwm5, pwm6, pwm7, pwm8) = Timer::tim4(dp.TIM4, &clocks, &mut rcc.apb1)
-        .pwm(timer4_pwm_pins, &mut afio.mapr, 1.khz())
-        .split();
-
-    // The overhead light closer to the screen.
-    let mut front_light = OverheadLight::new(pwm1, pwm2, pwm3, pwm4);
-
-    // The overhead light farther away from the screen.
-    let mut back_light = OverheadLight::new(pwm5, pwm6, pwm7, pwm8);
-
-    // Connect a rotary encoder to pins A0 and A1.
-    let rotary_encoder_pins = (gpioa.pa0, gpioa.pa1);
-    // Tim2NoRemap relates to how you can "remap" pins used on timer 2 for certain peripherals.
-    // https://docs.rs/stm32f1xx-hal/0.6.1/stm32f1xx_hal/timer/index.html
-    let rotary_encoder = Timer::tim2(dp.TIM2, &clocks, &mut rcc.apb1).qei::<Tim2NoRemap, _>(
-        rotary_encoder_pins,
-        &mut afio.mapr,
-        QeiOptions::default(),
-    );
-    let mut counter = Counter::new(rotary_encoder);
-
-    let button_pin = gpioa.pa3.into_pull_up_input(&mut gpioa.crl);
-    let debounced_encoder_pin = Debouncer::new(button_pin, Active::Low, 30, 3000);
-    let mut encoder_button = Button::new(debounced_encoder_pin, 1000, timer);
-
-    let mut led_color = (0u8, 30u8, 255u8);
-    let mut led_pulse = false;
-
-    loop {
-        match encoder_button.poll() {
-            Some(ButtonEvent::Pressed) => {
-                led.set_low().unwrap();
+    #[task(
+        binds = USB_HP_CAN_TX,
+        priority = 2,
+        resources = [
+            protocol,
+            commands,
+            reports,
+            boot_report,
+            power,
+            led_pixels,
+        ],
+        spawn = [apply_commands]
+    )]
+    fn usb_tx(cx: usb_tx::Context) {
+        let commands_received = profiler::measure(Section::UsbPoll, || {
+            usb::service(
+                cx.resources.protocol,
+                cx.resources.commands,
+                cx.resources.reports,
+                cx.resources.boot_report,
+            )
+        });
+
+        let spawn = cx.spawn;
+        usb::after_service(
+            cx.resources.protocol,
+            cx.resources.power,
+            cx.resources.led_pixels,
+            commands_received,
+            || {
+                let _ = spawn.apply_commands();
             },
-            Some(ButtonEvent::ShortRelease) => {
-                protocol.report(Report::Press).unwrap();
-                led.set_high().unwrap();
+        );
+    }
+
+    #[task(
+        binds = USB_LP_CAN_RX0,
+        priority = 2,
+        resources = [
+            protocol,
+            commands,
+            reports,
+            boot_report,
+            power,
+            led_pixels,
+        ],
+        spawn = [apply_commands]
+    )]
+    fn usb_rx0(cx: usb_rx0::Context) {
+        let commands_received = profiler::measure(Section::UsbPoll, || {
+            usb::service(
+                cx.resources.protocol,
+                cx.resources.commands,
+                cx.resources.reports,
+                cx.resources.boot_report,
+            )
+        });
+
+        let spawn = cx.spawn;
+        usb::after_service(
+            cx.resources.protocol,
+            cx.resources.power,
+            cx.resources.led_pixels,
+            commands_received,
+            || {
+                let _ = spawn.apply_commands();
             },
-            Some(ButtonEvent::LongPress) => {
-                protocol.report(Report::LongPress).unwrap();
-                led.set_high().unwrap();
+        );
+    }
+
+    // Fires for each byte received, and when queues::send_report has a report to send, with the
+    // uart-transport feature, or whenever the UART is ready for the next slot of a DMX frame,
+    // with dmx-output. RTIC binds every hardware task whatever the features, so the USB tasks
+    // above are bound in a UART build and this one in a USB build, but only the interrupts of
+    // the peripheral which was set up ever fire.
+    #[task(
+        binds = USART1,
+        priority = 2,
+        resources = [
+            protocol,
+            commands,
+            reports,
+            boot_report,
+            power,
+            led_pixels,
+            dmx_port,
+        ],
+        spawn = [apply_commands]
+    )]
+    fn usart1(cx: usart1::Context) {
+        if let Some(dmx_port) = cx.resources.dmx_port {
+            dmx_port.on_ready();
+            return;
+        }
+
+        let commands_received = profiler::measure(Section::UsbPoll, || {
+            usb::service(
+                cx.resources.protocol,
+                cx.resources.commands,
+                cx.resources.reports,
+                cx.resources.boot_report,
+            )
+        });
+
+        let spawn = cx.spawn;
+        usb::after_service(
+            cx.resources.protocol,
+            cx.resources.power,
+            cx.resources.led_pixels,
+            commands_received,
+            || {
+                let _ = spawn.apply_commands();
             },
-            Some(ButtonEvent::LongRelease) => {},
-            _ => {},
-        }
-
-        if let Some(diff) = counter.poll() {
-            if !encoder_button.is_pressed() {
-                protocol.report(Report::DialValue { diff }).unwrap();
-            }
-        }
-
-        // TODO(bschwind) - Report any poll errors back to the USB host if possible.
-        for command in protocol.poll().unwrap() {
-            match command {
-                Command::Brightness { target, value } => match target {
-                    0 => front_light.set_brightness(value),
-                    1 => back_light.set_brightness(value),
-                    _ => {},
-                },
-                Command::Temperature { target, value } => match target {
-                    0 => front_light.set_color_temperature(value),
-                    1 => back_light.set_color_temperature(value),
-                    _ => {},
-                },
-                Command::Led { r, g, b, pulse } => {
-                    led_color = (r, g, b);
-                    led_pulse = pulse;
-                },
-                _ => {},
-            }
-        }
-
-        let intensity = if led_pulse { pulser.intensity() } else { 1.0 };
-        led_strip.set_all(Rgb::new(
-            (led_color.0 as f32 * intensity) as u8,
-            (led_color.1 as f32 * intensity) as u8,
-            (led_color.2 as f32 * intensity) as u8,
-        ));
-    }
-}
+        );
+    }
+
+    // Applies the host's commands, see commands.rs, then starts the tasks they asked for.
+    #[task(
+        resources = [
+            commands,
+            reports,
+            front_light,
+            back_light,
+            led_settings,
+            wall_clock,
+            external_rtc,
+            i2c,
+            schedule,
+            utc_offset_minutes,
+            eeprom,
+            power,
+            auto_brightness,
+            daylight,
+            buzzer,
+            click_feedback,
+            protocol_version,
+            haptic_effects,
+            ir_bindings,
+            outputs,
+            expander,
+            door,
+            sound_level,
+            dmx,
+            standalone,
+            dial_limit,
+            status_on_connect,
+            led_pixels,
+            settings,
+            encoder_button,
+            pulser,
+            capabilities,
+            low_voltage_events,
+        ],
+        spawn = [self_test, reset, send_status]
+    )]
+    fn apply_commands(cx: apply_commands::Context) {
+        static mut DESTRUCTIVE_COMMANDS: Unlock = Unlock::new(UNLOCK_KEY);
+
+        let panel = Hardware {
+            lights: Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            led_settings: cx.resources.led_settings,
+            led_pixels: cx.resources.led_pixels,
+            pulser: cx.resources.pulser,
+            sound_level: cx.resources.sound_level,
+            reports: cx.resources.reports,
+            power: cx.resources.power,
+            low_voltage_events: cx.resources.low_voltage_events,
+            wall_clock: cx.resources.wall_clock,
+            external_rtc: cx.resources.external_rtc,
+            i2c: cx.resources.i2c,
+            eeprom: cx.resources.eeprom,
+            buzzer: cx.resources.buzzer,
+            outputs: cx.resources.outputs,
+            expander: cx.resources.expander,
+            door: cx.resources.door,
+            dial_limit: cx.resources.dial_limit,
+            settings: cx.resources.settings,
+            encoder_button: cx.resources.encoder_button,
+            capabilities: cx.resources.capabilities,
+            follow_up: commands::FollowUp::default(),
+        };
+        let mut ctx = Ctx {
+            panel,
+            unlock: DESTRUCTIVE_COMMANDS,
+            schedule: cx.resources.schedule,
+            utc_offset_minutes: cx.resources.utc_offset_minutes,
+            auto_brightness: cx.resources.auto_brightness,
+            daylight: cx.resources.daylight,
+            ir_bindings: cx.resources.ir_bindings,
+            haptic_effects: cx.resources.haptic_effects,
+            standalone: cx.resources.standalone,
+            dmx: cx.resources.dmx,
+            click_feedback: cx.resources.click_feedback,
+            status_on_connect: cx.resources.status_on_connect,
+            protocol_version: cx.resources.protocol_version,
+        };
+        commands::run(&mut ctx, cx.resources.commands);
+
+        let follow_up = ctx.panel.follow_up;
+
+        if let Some(into_bootloader) = follow_up.reset {
+            let _ = cx.spawn.reset(into_bootloader);
+        }
+        if follow_up.self_test {
+            let _ = cx.spawn.self_test();
+        }
+        if follow_up.send_status {
+            let _ = cx.spawn.send_status();
+        }
+    }
+
+    // Advances the millisecond clock, and spawns each periodic task when it's due. This has the
+    // highest priority so that the clock never misses a tick, and does as little as possible.
+    #[task(
+        binds = SysTick,
+        priority = 3,
+        spawn = [
+            input_poll,
+            report_cpu_load,
+            measure_ambient_light,
+            track_daylight,
+            sample_sliders,
+            control_fan,
+            read_fixture_probes,
+            update_display,
+            flush_eeprom,
+            monitor_led_rail,
+            run_schedule,
+            update_encoder_ring,
+            poll_expander,
+            sample_joystick,
+            sample_microphone,
+            send_dmx_frame,
+        ]
+    )]
+    fn systick(cx: systick::Context) {
+        static mut INPUT_POLL: Every = Every::new(INPUT_POLL_PERIOD_MS);
+        static mut CPU_LOAD: Every = Every::new(CPU_LOAD_PERIOD_MS);
+        static mut AMBIENT_LIGHT: Every = Every::new(AMBIENT_LIGHT_PERIOD_MS);
+        static mut ENCODER_RING: Every = Every::new(ENCODER_RING_PERIOD_MS);
+        static mut SLIDERS: Every = Every::new(SLIDER_PERIOD_MS);
+        static mut EXPANDER: Every = Every::new(EXPANDER_POLL_PERIOD_MS);
+        static mut JOYSTICK: Every = Every::new(JOYSTICK_PERIOD_MS);
+        static mut MICROPHONE: Every = Every::new(MICROPHONE_PERIOD_MS);
+        static mut FAN: Every = Every::new(FAN_PERIOD_MS);
+        static mut FIXTURE_PROBES: Every = Every::new(FIXTURE_PROBE_PERIOD_MS);
+        static mut DISPLAY: Every = Every::new(DISPLAY_PERIOD_MS);
+        static mut LED_RAIL: Every = Every::new(LED_RAIL_PERIOD_MS);
+        static mut SCHEDULE: Every = Every::new(SCHEDULE_PERIOD_MS);
+        static mut DAYLIGHT: Every = Every::new(DAYLIGHT_PERIOD_MS);
+        static mut EEPROM_FLUSH: Every = Every::new(EEPROM_FLUSH_PERIOD_MS);
+        static mut DMX_FRAME: Every = Every::new(DMX_FRAME_PERIOD_MS);
+
+        tick::increment();
+        let now = Instant::now();
+
+        // If a task is still running from its last period, there's no point queueing it again.
+        if INPUT_POLL.is_due(now) {
+            let _ = cx.spawn.input_poll();
+        }
+
+        if CPU_LOAD.is_due(now) {
+            let _ = cx.spawn.report_cpu_load();
+        }
+
+        if AMBIENT_LIGHT.is_due(now) {
+            let _ = cx.spawn.measure_ambient_light();
+        }
+
+        if ENCODER_RING.is_due(now) && cfg!(feature = "encoder-ring") {
+            let _ = cx.spawn.update_encoder_ring();
+        }
+
+        if SLIDERS.is_due(now) && board::BOARD.slider_count != 0 {
+            let _ = cx.spawn.sample_sliders();
+        }
+
+        if EXPANDER.is_due(now) {
+            let _ = cx.spawn.poll_expander();
+        }
+
+        if JOYSTICK.is_due(now) && board::BOARD.joystick {
+            let _ = cx.spawn.sample_joystick();
+        }
+
+        if MICROPHONE.is_due(now) && board::BOARD.microphone {
+            let _ = cx.spawn.sample_microphone();
+        }
+
+        if FAN.is_due(now) && board::BOARD.fan.is_some() {
+            let _ = cx.spawn.control_fan();
+        }
+
+        if FIXTURE_PROBES.is_due(now) {
+            let _ = cx.spawn.read_fixture_probes();
+        }
+
+        if DISPLAY.is_due(now) {
+            let _ = cx.spawn.update_display();
+        }
+
+        if LED_RAIL.is_due(now) {
+            let _ = cx.spawn.monitor_led_rail();
+        }
+
+        if SCHEDULE.is_due(now) {
+            let _ = cx.spawn.run_schedule();
+        }
+
+        if DAYLIGHT.is_due(now) {
+            let _ = cx.spawn.track_daylight();
+        }
+
+        if EEPROM_FLUSH.is_due(now) && Eeprom::BUFFERED {
+            let _ = cx.spawn.flush_eeprom();
+        }
+
+        if DMX_FRAME.is_due(now) && cfg!(feature = "dmx-output") {
+            let _ = cx.spawn.send_dmx_frame();
+        }
+    }
+
+    #[task(
+        resources = [
+            input_events,
+            reports,
+            counter,
+            dial_limit,
+            encoder_button,
+            status_on_connect,
+            door,
+            led,
+            vbus,
+            watchdog,
+            power,
+            protocol,
+            standalone,
+            buzzer,
+            protocol_version,
+        ],
+        spawn = [handle_input, apply_power_state, start_standalone, send_status]
+    )]
+    fn input_poll(cx: input_poll::Context) {
+        static mut STATE: InputPollState = InputPollState::new();
+
+        let follow_up = InputPoll {
+            input_events: cx.resources.input_events,
+            reports: cx.resources.reports,
+            counter: cx.resources.counter,
+            dial_limit: cx.resources.dial_limit,
+            encoder_button: cx.resources.encoder_button,
+            door: cx.resources.door,
+            led: cx.resources.led,
+            vbus: cx.resources.vbus,
+            watchdog: cx.resources.watchdog,
+            power: cx.resources.power,
+            protocol: cx.resources.protocol,
+            standalone: cx.resources.standalone,
+            buzzer: cx.resources.buzzer,
+            status_on_connect: *cx.resources.status_on_connect,
+            protocol_version: cx.resources.protocol_version,
+        }
+        .run(STATE);
+
+        if follow_up.handle_input {
+            let _ = cx.spawn.handle_input();
+        }
+        if follow_up.apply_power_state {
+            let _ = cx.spawn.apply_power_state();
+        }
+        if follow_up.send_status {
+            let _ = cx.spawn.send_status();
+        }
+        if follow_up.start_standalone {
+            let _ = cx.spawn.start_standalone();
+        }
+    }
+
+    #[task(
+        resources = [
+            input_events,
+            reports,
+            counter,
+            led,
+            standalone,
+            front_light,
+            back_light,
+            buzzer,
+            click_feedback,
+            i2c,
+            haptics,
+            haptic_effects,
+            protocol_version,
+            protocol,
+        ]
+    )]
+    fn handle_input(cx: handle_input::Context) {
+        HandleInput {
+            input_events: cx.resources.input_events,
+            reports: cx.resources.reports,
+            counter: cx.resources.counter,
+            led: cx.resources.led,
+            standalone: cx.resources.standalone,
+            lights: Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            buzzer: cx.resources.buzzer,
+            click_feedback: *cx.resources.click_feedback,
+            i2c: cx.resources.i2c,
+            haptics: cx.resources.haptics,
+            haptic_effects: cx.resources.haptic_effects,
+            protocol_version: *cx.resources.protocol_version,
+            protocol: cx.resources.protocol,
+        }
+        .run();
+    }
+
+    #[task(
+        binds = TIM1_UP,
+        priority = 2,
+        resources = [
+            led_timer,
+            led_strip,
+            led_pixels,
+            strip_test,
+            pulser,
+            led_settings,
+            sound_level,
+            low_voltage,
+            power,
+            power_budget,
+        ]
+    )]
+    fn led_frame(cx: led_frame::Context) {
+        static mut LAST_FRAME: Option<[Rgb; board::BOARD.led_count]> = None;
+
+        cx.resources.led_timer.clear_update_interrupt_flag();
+
+        LedFrame {
+            strip: cx.resources.led_strip,
+            pixels: cx.resources.led_pixels,
+            strip_test: *cx.resources.strip_test,
+            pulser: cx.resources.pulser,
+            settings: cx.resources.led_settings,
+            sound_level: cx.resources.sound_level,
+            low_voltage: *cx.resources.low_voltage,
+            power: cx.resources.power,
+            power_budget: cx.resources.power_budget,
+        }
+        .send(LAST_FRAME);
+    }
+
+    // Fires whenever the supply crosses the PVD threshold, see lights::voltage_change.
+    #[task(
+        binds = PVD,
+        priority = 2,
+        resources = [
+            reports,
+            front_light,
+            back_light,
+            led_settings,
+            low_voltage,
+            low_voltage_events,
+            power,
+        ],
+        spawn = [save_low_voltage_events]
+    )]
+    fn voltage_change(cx: voltage_change::Context) {
+        pvd::clear_pending();
+
+        let recovered = lights::voltage_change(
+            cx.resources.reports,
+            Lights {
+                front: Exclusive(cx.resources.front_light),
+                back: Exclusive(cx.resources.back_light),
+            },
+            cx.resources.led_settings,
+            cx.resources.low_voltage,
+            cx.resources.low_voltage_events,
+            cx.resources.power,
+        );
+        if recovered {
+            let _ = cx.spawn.save_low_voltage_events();
+        }
+    }
+
+    #[task(resources = [eeprom, low_voltage_events])]
+    fn save_low_voltage_events(cx: save_low_voltage_events::Context) {
+        lights::save_low_voltage_events(cx.resources.eeprom, cx.resources.low_voltage_events);
+    }
+
+    #[task(resources = [front_light, back_light, dmx, low_voltage, power])]
+    fn apply_power_state(cx: apply_power_state::Context) {
+        lights::apply_power_state(
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            cx.resources.dmx,
+            cx.resources.low_voltage,
+            cx.resources.power,
+        );
+    }
+
+    #[task(resources = [front_light, back_light, led_settings, encoder_button, reports])]
+    fn send_status(cx: send_status::Context) {
+        telemetry::send_status(
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            cx.resources.led_settings,
+            cx.resources.encoder_button,
+            cx.resources.reports,
+        );
+    }
+
+    #[task(resources = [front_light, back_light, led_settings, led_pixels, standalone])]
+    fn start_standalone(cx: start_standalone::Context) {
+        lights::start_standalone(
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            cx.resources.led_settings,
+            cx.resources.led_pixels,
+            cx.resources.standalone,
+        );
+    }
+
+    // Times the pulses from the IR receiver, see controls::ir_edge. This runs above the tasks
+    // which might take a while, so that edges are timed to within a few microseconds.
+    #[task(
+        binds = EXTI15_10,
+        priority = 2,
+        resources = [ir_receiver],
+        spawn = [handle_ir]
+    )]
+    fn ir_edge(cx: ir_edge::Context) {
+        static mut LAST_EDGE: u32 = 0;
+
+        let code = controls::ir_edge(cx.resources.ir_receiver, LAST_EDGE);
+        if let Some(code) = code {
+            let _ = cx.spawn.handle_ir(code);
+        }
+    }
+
+    #[task(capacity = 4, resources = [ir_bindings, reports, power], spawn = [start_standalone])]
+    fn handle_ir(cx: handle_ir::Context, code: IrCode) {
+        let recall_scene = controls::handle_ir(
+            code,
+            cx.resources.ir_bindings,
+            cx.resources.reports,
+            cx.resources.power,
+        );
+        if recall_scene {
+            let _ = cx.spawn.start_standalone();
+        }
+    }
+
+    #[task(binds = EXTI9_5, priority = 2, resources = [fan_tach, tach_pulses])]
+    fn count_tach_pulse(cx: count_tach_pulse::Context) {
+        cx.resources.fan_tach.clear_interrupt_pending_bit();
+        *cx.resources.tach_pulses += 1;
+    }
+
+    #[task(resources = [fan, analog, tach_pulses, reports])]
+    fn control_fan(cx: control_fan::Context) {
+        // The duty cycle over the period the pulses were counted in.
+        static mut LAST_DUTY: Fraction = Fraction::ZERO;
+
+        telemetry::control_fan(
+            cx.resources.fan,
+            cx.resources.analog,
+            cx.resources.tach_pulses,
+            cx.resources.reports,
+            LAST_DUTY,
+        );
+    }
+
+    #[task(resources = [fixture_probes, reports])]
+    fn read_fixture_probes(cx: read_fixture_probes::Context) {
+        telemetry::read_fixture_probes(cx.resources.fixture_probes, cx.resources.reports);
+    }
+
+    #[task(
+        resources = [
+            i2c,
+            ambient_light,
+            auto_brightness,
+            daylight,
+            front_light,
+            back_light,
+            reports,
+        ]
+    )]
+    fn measure_ambient_light(cx: measure_ambient_light::Context) {
+        lights::measure_ambient_light(
+            cx.resources.ambient_light,
+            cx.resources.i2c,
+            cx.resources.auto_brightness,
+            cx.resources.daylight,
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            cx.resources.reports,
+        );
+    }
+
+    #[task(resources = [i2c, display, protocol, front_light, back_light, counter])]
+    fn update_display(cx: update_display::Context) {
+        telemetry::update_display(
+            cx.resources.display,
+            cx.resources.i2c,
+            cx.resources.protocol,
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            cx.resources.counter,
+        );
+    }
+
+    #[task(resources = [encoder_ring, counter, standalone, led_settings, power, low_voltage])]
+    fn update_encoder_ring(cx: update_encoder_ring::Context) {
+        static mut DISPLAYED: Option<[Rgb; board::BOARD.encoder_ring_len]> = None;
+
+        lights::update_encoder_ring(
+            cx.resources.encoder_ring,
+            cx.resources.counter,
+            cx.resources.standalone,
+            cx.resources.led_settings,
+            cx.resources.power,
+            cx.resources.low_voltage,
+            DISPLAYED,
+        );
+    }
+
+    #[task(resources = [i2c, power_monitor, power_budget, reports])]
+    fn monitor_led_rail(cx: monitor_led_rail::Context) {
+        static mut REPORT: Every = Every::new(LED_RAIL_REPORT_PERIOD_MS);
+
+        telemetry::monitor_led_rail(
+            cx.resources.i2c,
+            cx.resources.power_monitor,
+            cx.resources.power_budget,
+            cx.resources.reports,
+            REPORT,
+        );
+    }
+
+    #[task(
+        resources = [
+            wall_clock,
+            external_rtc,
+            i2c,
+            schedule,
+            utc_offset_minutes,
+            front_light,
+            back_light,
+        ]
+    )]
+    fn run_schedule(cx: run_schedule::Context) {
+        static mut EXTERNAL_RTC_SYNC: Every = Every::new(EXTERNAL_RTC_SYNC_PERIOD_MS);
+
+        lights::run_schedule(
+            cx.resources.wall_clock,
+            cx.resources.external_rtc,
+            cx.resources.i2c,
+            cx.resources.schedule,
+            *cx.resources.utc_offset_minutes,
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            EXTERNAL_RTC_SYNC,
+        );
+    }
+
+    #[task(
+        resources = [wall_clock, utc_offset_minutes, daylight, front_light, back_light, reports]
+    )]
+    fn track_daylight(cx: track_daylight::Context) {
+        lights::track_daylight(
+            cx.resources.wall_clock,
+            *cx.resources.utc_offset_minutes,
+            cx.resources.daylight,
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            cx.resources.reports,
+        );
+    }
+
+    // Writes out a setting which has changed, see eeprom/external.rs. Only one is written each
+    // time, so that the other tasks at this priority don't wait long.
+    #[task(resources = [i2c, eeprom])]
+    fn flush_eeprom(cx: flush_eeprom::Context) {
+        let i2c = cx.resources.i2c;
+        let eeprom = cx.resources.eeprom;
+
+        eeprom.flush(i2c);
+    }
+
+    #[task(resources = [analog, sliders, reports, power])]
+    fn sample_sliders(cx: sample_sliders::Context) {
+        controls::sample_sliders(
+            cx.resources.analog,
+            cx.resources.sliders,
+            cx.resources.reports,
+            cx.resources.power,
+        );
+    }
+
+    #[task(resources = [i2c, expander, reports, power])]
+    fn poll_expander(cx: poll_expander::Context) {
+        controls::poll_expander(
+            cx.resources.i2c,
+            cx.resources.expander,
+            cx.resources.reports,
+            cx.resources.power,
+        );
+    }
+
+    #[task(resources = [analog, joystick, reports, power])]
+    fn sample_joystick(cx: sample_joystick::Context) {
+        controls::sample_joystick(
+            cx.resources.analog,
+            cx.resources.joystick,
+            cx.resources.reports,
+            cx.resources.power,
+        );
+    }
+
+    #[task(resources = [analog, sound_level])]
+    fn sample_microphone(cx: sample_microphone::Context) {
+        controls::sample_microphone(cx.resources.analog, cx.resources.sound_level);
+    }
+
+    #[task(resources = [dmx, dmx_port])]
+    fn send_dmx_frame(cx: send_dmx_frame::Context) {
+        lights::send_dmx_frame(cx.resources.dmx, cx.resources.dmx_port);
+    }
+
+    #[task(resources = [reports])]
+    fn report_cpu_load(cx: report_cpu_load::Context) {
+        telemetry::report_cpu_load(cx.resources.reports);
+    }
+
+    #[task(
+        resources = [
+            reports,
+            front_light,
+            back_light,
+            led_strip,
+            strip_test,
+            counter,
+            encoder_button,
+            watchdog,
+        ]
+    )]
+    fn self_test(cx: self_test::Context) {
+        SelfTest {
+            reports: cx.resources.reports,
+            front_light: cx.resources.front_light,
+            back_light: cx.resources.back_light,
+            led_strip: cx.resources.led_strip,
+            strip_test: cx.resources.strip_test,
+            counter: cx.resources.counter,
+            encoder_button: cx.resources.encoder_button,
+            watchdog: cx.resources.watchdog,
+        }
+        .run();
+    }
+
+    #[task(resources = [front_light, back_light, led_strip])]
+    fn reset(cx: reset::Context, into_bootloader: bool) {
+        lights::reset(
+            Lights { front: cx.resources.front_light, back: cx.resources.back_light },
+            cx.resources.led_strip,
+            into_bootloader,
+        );
+    }
+
+    // Interrupts which are otherwise unused, for RTIC to dispatch software tasks from.
+    // CAN shares its RAM with USB, so these will never fire on their own.
+    extern "C" {
+        fn CAN_RX1();
+        fn CAN_SCE();
+    }
+};
@@ -0,0 +1,96 @@
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicU16, Ordering},
+};
+use embedded_hal::{
+    digital::{ErrorType, InputPin, OutputPin},
+    i2c::I2c,
+};
+
+// Driver for an MCP23017 GPIO expander, with its address pins tied low, which adds 16 pins on the
+// I2C bus it shares with the other devices on it. Port A's pins are numbered 0 to 7 and port B's
+// 8 to 15.
+//
+// The bus is only available to tasks which hold the `i2c` resource, so pins can't talk to the
+// expander themselves. Instead `sync()` reads every input at once, and writes every output, and
+// the `ExpanderPin`s in between read and set a copy of them. That way they're ordinary
+// embedded-hal pins, which can e.g. be debounced like the MCU's own.
+// https://ww1.microchip.com/downloads/en/DeviceDoc/20001952C.pdf
+
+const ADDRESS: u8 = 0x20;
+// Port B's registers follow port A's, so each pair is written and read in one go.
+const IODIRA: u8 = 0x00;
+const GPPUA: u8 = 0x0c;
+const GPIOA: u8 = 0x12;
+const OLATA: u8 = 0x14;
+
+/// The levels last read from the pins, which start out high as if pulled up.
+static INPUTS: AtomicU16 = AtomicU16::new(0xffff);
+/// The levels the outputs are to be set to at the next `sync()`.
+static OUTPUTS: AtomicU16 = AtomicU16::new(0);
+
+pub struct Mcp23017;
+
+impl Mcp23017 {
+    /// Makes the pins in `output_mask` outputs, starting low, and the rest inputs with pull-ups.
+    /// Fails if there's no expander on the bus.
+    pub fn new<I: I2c>(i2c: &mut I, output_mask: u16) -> Result<Self, I::Error> {
+        let [outputs_a, outputs_b] = OUTPUTS.load(Ordering::Relaxed).to_le_bytes();
+        let [inputs_a, inputs_b] = (!output_mask).to_le_bytes();
+
+        i2c.write(ADDRESS, &[OLATA, outputs_a, outputs_b])?;
+        i2c.write(ADDRESS, &[GPPUA, inputs_a, inputs_b])?;
+        i2c.write(ADDRESS, &[IODIRA, inputs_a, inputs_b])?;
+
+        Ok(Self)
+    }
+
+    /// Reads every pin, and writes the outputs which have been set since the last call.
+    pub fn sync<I: I2c>(&mut self, i2c: &mut I) -> Result<(), I::Error> {
+        let mut inputs = [0u8; 2];
+        i2c.write_read(ADDRESS, &[GPIOA], &mut inputs)?;
+        INPUTS.store(u16::from_le_bytes(inputs), Ordering::Relaxed);
+
+        let [outputs_a, outputs_b] = OUTPUTS.load(Ordering::Relaxed).to_le_bytes();
+        i2c.write(ADDRESS, &[OLATA, outputs_a, outputs_b])
+    }
+}
+
+/// A pin of the expander, as of the last `Mcp23017::sync()`.
+pub struct ExpanderPin {
+    mask: u16,
+}
+
+impl ExpanderPin {
+    /// Pin `pin`, from 0 to 15. Pins can be made before the expander is set up, but read as
+    /// high and do nothing until it is.
+    pub fn new(pin: u8) -> Self {
+        Self { mask: 1 << pin }
+    }
+}
+
+impl ErrorType for ExpanderPin {
+    type Error = Infallible;
+}
+
+impl InputPin for ExpanderPin {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(INPUTS.load(Ordering::Relaxed) & self.mask != 0)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+impl OutputPin for ExpanderPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        OUTPUTS.fetch_and(!self.mask, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        OUTPUTS.fetch_or(self.mask, Ordering::Relaxed);
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use embedded_hal::digital::{OutputPin, PinState};
+
+/// Outputs on spare pins which the host can switch, e.g. to drive relays for other devices in
+/// the room. Every output is off at power on and after a reset: the pins float until they're set
+/// up, and should be pulled down on the board to stay off meanwhile.
+pub struct Outputs<P: OutputPin, const N: usize> {
+    pins: [P; N],
+    states: [bool; N],
+}
+
+impl<P: OutputPin, const N: usize> Outputs<P, N> {
+    /// Takes over `pins`, switching them all off.
+    pub fn new(mut pins: [P; N]) -> Self {
+        for pin in &mut pins {
+            let _ = pin.set_low();
+        }
+
+        Self { pins, states: [false; N] }
+    }
+
+    /// Switches `channel` on or off. Returns false, doing nothing, if there's no such channel.
+    pub fn set(&mut self, channel: u8, on: bool) -> bool {
+        let pin = match self.pins.get_mut(channel as usize) {
+            Some(pin) => pin,
+            None => return false,
+        };
+
+        // Writing to a GPIO pin can't fail on this chip.
+        let _ = pin.set_state(PinState::from(on));
+        self.states[channel as usize] = on;
+        true
+    }
+
+    /// Whether `channel` is on, or `None` if there's no such channel.
+    pub fn get(&self, channel: u8) -> Option<bool> {
+        self.states.get(channel as usize).copied()
+    }
+
+    /// Whether each channel is on, in order.
+    pub fn states(&self) -> impl Iterator<Item = (u8, bool)> + '_ {
+        self.states.iter().enumerate().map(|(channel, &on)| (channel as u8, on))
+    }
+}
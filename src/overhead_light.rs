@@ -1,70 +1,134 @@
-use embedded_hal::PwmPin;
+use embedded_hal::pwm::SetDutyCycle;
+use panel_core::fraction::Fraction;
+
+/// How many steps `OverheadLight::self_test_step()` takes.
+pub const SELF_TEST_STEPS: usize = 8;
 
 pub struct OverheadLight<P1, P2, P3, P4>
 where
-    P1: PwmPin<Duty = u16>,
-    P1: PwmPin<Duty = u16>,
-    P3: PwmPin<Duty = u16>,
-    P4: PwmPin<Duty = u16>,
+    P1: SetDutyCycle,
+    P2: SetDutyCycle,
+    P3: SetDutyCycle,
+    P4: SetDutyCycle,
 {
     brightness_c1: P1,
     brightness_c2: P2,
     color_c1: P3,
     color_c2: P4,
+    /// The brightness most recently requested, before scaling.
+    brightness: u16,
+    brightness_scale: Fraction,
+    color_temperature: u16,
 }
 
 impl<P1, P2, P3, P4> OverheadLight<P1, P2, P3, P4>
 where
-    P1: PwmPin<Duty = u16>,
-    P2: PwmPin<Duty = u16>,
-    P3: PwmPin<Duty = u16>,
-    P4: PwmPin<Duty = u16>,
+    P1: SetDutyCycle,
+    P2: SetDutyCycle,
+    P3: SetDutyCycle,
+    P4: SetDutyCycle,
 {
-    pub fn new(
-        mut brightness_c1: P1,
-        mut brightness_c2: P2,
-        mut color_c1: P3,
-        mut color_c2: P4,
-    ) -> Self {
-        brightness_c1.enable();
-        brightness_c2.enable();
-        color_c1.enable();
-        color_c2.enable();
-
-        // Set maximum brightness
-        brightness_c1.set_duty(0);
-        brightness_c2.set_duty(0);
-
-        // Set white color temperature
-        color_c1.set_duty(0);
-        color_c2.set_duty(0);
-
-        OverheadLight { brightness_c1, brightness_c2, color_c1, color_c2 }
+    /// Starts at full brightness and a white color temperature.
+    pub fn new(brightness_c1: P1, brightness_c2: P2, color_c1: P3, color_c2: P4) -> Self {
+        let mut light = OverheadLight {
+            brightness_c1,
+            brightness_c2,
+            color_c1,
+            color_c2,
+            brightness: u16::MAX,
+            brightness_scale: Fraction::ONE,
+            color_temperature: u16::MAX,
+        };
+
+        light.apply_brightness();
+        light.apply_color_temperature();
+        light
     }
 
     /// Sets the brightness of both channels.
     /// 0 = Off
     /// u16::MAX = Full brightness
     pub fn set_brightness(&mut self, brightness: u16) {
+        trace!("brightness: {}", brightness);
+
+        self.brightness = brightness;
+        self.apply_brightness();
+    }
+
+    /// The brightness most recently set, before scaling.
+    pub fn brightness(&self) -> u16 {
+        self.brightness
+    }
+
+    /// Scales every brightness set from now on, and the current one, by `scale`.
+    pub fn set_brightness_scale(&mut self, scale: Fraction) {
+        self.brightness_scale = scale;
+        self.apply_brightness();
+    }
+
+    fn apply_brightness(&mut self) {
+        let brightness = self.brightness_scale.of_u16(self.brightness);
+
         // Invert the value because our transistor circuit inverts the PWM signal.
         let brightness = u16::MAX - brightness;
 
-        let adjusted = ((brightness as f32 / u16::MAX as f32)
-            * self.brightness_c1.get_max_duty() as f32) as u16;
-        self.brightness_c1.set_duty(adjusted);
-        self.brightness_c2.set_duty(adjusted);
+        let adjusted = scale_to_duty(brightness, self.brightness_c1.max_duty_cycle());
+        let _ = self.brightness_c1.set_duty_cycle(adjusted);
+        let _ = self.brightness_c2.set_duty_cycle(adjusted);
     }
 
     /// Sets the color temperature of both channels.
     /// 0 = Full yellow
     /// u16::MAX = Full white
     pub fn set_color_temperature(&mut self, color: u16) {
+        trace!("color temperature: {}", color);
+
+        self.color_temperature = color;
+        self.apply_color_temperature();
+    }
+
+    pub fn color_temperature(&self) -> u16 {
+        self.color_temperature
+    }
+
+    fn apply_color_temperature(&mut self) {
         // Invert the value because our transistor circuit inverts the PWM signal.
-        let color = u16::MAX - color;
+        let color = u16::MAX - self.color_temperature;
 
-        let adjusted =
-            ((color as f32 / u16::MAX as f32) * self.color_c1.get_max_duty() as f32) as u16;
-        self.color_c1.set_duty(adjusted);
-        self.color_c2.set_duty(adjusted);
+        let adjusted = scale_to_duty(color, self.color_c1.max_duty_cycle());
+        let _ = self.color_c1.set_duty_cycle(adjusted);
+        let _ = self.color_c2.set_duty_cycle(adjusted);
     }
+
+    /// Takes step `step` of `SELF_TEST_STEPS`, which turn each channel fully off and then on in
+    /// turn, and returns whether the duty cycle was accepted. The caller holds each step for
+    /// long enough that the change can be seen, and calls `end_self_test()` after the last.
+    /// There's no feedback from the lights themselves, so whoever watches the test has to check
+    /// those.
+    pub fn self_test_step(&mut self, step: usize) -> bool {
+        let on = step % 2 == 1;
+        match step / 2 {
+            0 => set_test_duty(&mut self.brightness_c1, on),
+            1 => set_test_duty(&mut self.brightness_c2, on),
+            2 => set_test_duty(&mut self.color_c1, on),
+            3 => set_test_duty(&mut self.color_c2, on),
+            _ => true,
+        }
+    }
+
+    /// Puts the light back to what it was set to before the self-test.
+    pub fn end_self_test(&mut self) {
+        self.apply_brightness();
+        self.apply_color_temperature();
+    }
+}
+
+/// Maps `value` from 0 to u16::MAX onto a duty cycle from 0 to `max_duty`.
+fn scale_to_duty(value: u16, max_duty: u16) -> u16 {
+    (value as u32 * max_duty as u32 / u16::MAX as u32) as u16
+}
+
+fn set_test_duty<P: SetDutyCycle>(channel: &mut P, on: bool) -> bool {
+    let duty = if on { channel.max_duty_cycle() } else { 0 };
+    channel.set_duty_cycle(duty).is_ok()
 }
@@ -0,0 +1,69 @@
+use crate::platform::flash::{self, RawFlash};
+use core::{convert::TryInto, fmt::Write, panic::PanicInfo};
+use cortex_m::peripheral::SCB;
+use panel_protocol::ArrayString;
+
+/// The last page of flash, which memory.x keeps out of the application image. Its second half
+/// holds the brownout snapshot, see brownout.rs.
+const PANIC_PAGE_ADDRESS: u32 = flash::FLASH_END - flash::PAGE_SIZE;
+const PANIC_MAGIC: u32 = 0xDEAD_C0DE;
+const MAX_PANIC_MESSAGE_LEN: usize = 128;
+
+pub type PanicMessage = ArrayString<[u8; MAX_PANIC_MESSAGE_LEN]>;
+
+/// Returns the message saved by a panic before the last reset, if any, and erases it.
+pub fn take() -> Option<PanicMessage> {
+    let header = flash::read(PANIC_PAGE_ADDRESS, 8);
+    if u32::from_le_bytes(header[0..4].try_into().unwrap()) != PANIC_MAGIC {
+        return None;
+    }
+
+    let len = u16::from_le_bytes(header[4..6].try_into().unwrap()) as usize;
+    let bytes = flash::read(PANIC_PAGE_ADDRESS + 8, len.min(MAX_PANIC_MESSAGE_LEN));
+
+    let mut writer = TruncatingWriter(PanicMessage::new());
+    let _ = writer.write_str(core::str::from_utf8(bytes).unwrap_or("unreadable panic message"));
+
+    warn!("found a panic log from before the last reset");
+    unsafe { RawFlash::steal() }.erase_page(PANIC_PAGE_ADDRESS);
+
+    Some(writer.0)
+}
+
+/// Writes a string into a fixed buffer, silently dropping whatever doesn't fit.
+struct TruncatingWriter(PanicMessage);
+
+impl Write for TruncatingWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if self.0.try_push(c).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Saves the panic message and location to flash, then resets the panel. The message is
+/// reported to the host after the next boot.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    error!("{}", defmt::Display2Format(info));
+
+    let mut writer = TruncatingWriter(PanicMessage::new());
+    let _ = write!(writer, "{}", info);
+    let message = writer.0;
+
+    // Interrupts are disabled, so nothing else can be using flash.
+    let mut flash = unsafe { RawFlash::steal() };
+    flash.erase_page(PANIC_PAGE_ADDRESS);
+    // There's nothing to be done about a write which doesn't program.
+    let _ = flash.program(PANIC_PAGE_ADDRESS, &PANIC_MAGIC.to_le_bytes());
+    let _ = flash.program(PANIC_PAGE_ADDRESS + 4, &(message.len() as u16).to_le_bytes());
+    let _ = flash.program(PANIC_PAGE_ADDRESS + 8, message.as_bytes());
+    drop(flash);
+
+    SCB::sys_reset();
+}
@@ -0,0 +1,27 @@
+// Everything specific to one family of STM32 chips is kept behind this module, so that the rest
+// of the firmware only depends on embedded-hal and usb-device traits. Exactly one platform
+// feature has to be enabled. The STM32F1 is the only complete port. The STM32F4 is a skeleton,
+// which provides the items the library needs but not yet an `init` module or those main.rs uses,
+// so only the library builds for it. A new port needs to provide the same items as `stm32f1`,
+// including `init`. An STM32F0 port needs more than that, as the Cortex-M0 has neither the DWT
+// cycle counter, which cpu_load.rs and profiler.rs use, nor the atomic read-modify-write
+// instructions used throughout.
+
+#[cfg(not(any(feature = "stm32f1", feature = "stm32f4")))]
+compile_error!("no platform feature is enabled, e.g. stm32f1");
+
+#[cfg(all(feature = "stm32f1", feature = "stm32f4"))]
+compile_error!("only one of the stm32f1 and stm32f4 features can be enabled");
+
+#[cfg(feature = "stm32f4")]
+mod registers;
+
+#[cfg(feature = "stm32f1")]
+mod stm32f1;
+#[cfg(feature = "stm32f4")]
+mod stm32f4;
+
+#[cfg(feature = "stm32f1")]
+pub use stm32f1::*;
+#[cfg(feature = "stm32f4")]
+pub use stm32f4::*;
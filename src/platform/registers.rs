@@ -0,0 +1,25 @@
+use core::ptr;
+
+// Plain volatile access to memory mapped registers, for the STM32F4, which doesn't have a HAL and
+// PAC in the firmware to do it yet.
+
+/// # Safety
+/// `address` must be the address of a 32-bit register which can be read.
+pub unsafe fn read(address: u32) -> u32 {
+    ptr::read_volatile(address as *const u32)
+}
+
+/// # Safety
+/// `address` must be the address of a 32-bit register, and writing `value` to it mustn't break
+/// whatever else is using the peripheral.
+pub unsafe fn write(address: u32, value: u32) {
+    ptr::write_volatile(address as *mut u32, value)
+}
+
+/// Reads the register at `address`, and writes back what `f` makes of it.
+///
+/// # Safety
+/// As for `write()`, and nothing else may write the register in between.
+pub unsafe fn modify(address: u32, f: impl FnOnce(u32) -> u32) {
+    write(address, f(read(address)))
+}
@@ -0,0 +1,27 @@
+pub mod boot_checks;
+pub mod buzzer;
+pub mod clock_security;
+pub mod device_info;
+pub mod dmx;
+pub mod eh1;
+pub mod fan;
+pub mod flash;
+pub mod init;
+pub mod onewire;
+pub mod pvd;
+pub mod reset_reason;
+pub mod uart;
+pub mod wall_clock;
+
+pub use stm32f1xx_hal as hal;
+
+pub type UsbBusType = hal::usb::UsbBus<hal::usb::Peripheral>;
+
+/// The errors from the UART, see `serial::Error::Serial`.
+pub type SerialError = hal::serial::Error;
+
+/// The interrupt of the task which talks to the host.
+#[cfg(not(feature = "uart-transport"))]
+pub const HOST_INTERRUPT: hal::pac::Interrupt = hal::pac::Interrupt::USB_LP_CAN_RX0;
+#[cfg(feature = "uart-transport")]
+pub const HOST_INTERRUPT: hal::pac::Interrupt = hal::pac::Interrupt::USART1;
@@ -0,0 +1,29 @@
+use super::hal::pac::{RCC, TIM2, TIM3, TIM4};
+
+// Register level checks for src/boot_diagnostics.rs, for peripherals whose HAL types don't say
+// whether they're actually running.
+
+/// True if the HSE crystal is running and the system clock comes from the PLL it drives.
+pub fn hse_running() -> bool {
+    let rcc = unsafe { &*RCC::ptr() };
+    // SWS reads 0b10 when the PLL is the system clock.
+    rcc.cr.read().hserdy().bit_is_set() && rcc.cfgr.read().sws().bits() == 0b10
+}
+
+/// True if TIM2 is counting in encoder mode, on both inputs.
+pub fn qei_running() -> bool {
+    let tim = unsafe { &*TIM2::ptr() };
+    tim.cr1.read().cen().bit_is_set() && tim.smcr.read().sms().bits() == 0b011
+}
+
+/// True if TIM3 and TIM4 are counting, with all four of their outputs enabled.
+pub fn pwm_running() -> bool {
+    let tim3 = unsafe { &*TIM3::ptr() };
+    let tim4 = unsafe { &*TIM4::ptr() };
+    let outputs_enabled = |ccer: u32| ccer & 0x1111 == 0x1111;
+
+    tim3.cr1.read().cen().bit_is_set()
+        && tim4.cr1.read().cen().bit_is_set()
+        && outputs_enabled(tim3.ccer.read().bits())
+        && outputs_enabled(tim4.ccer.read().bits())
+}
@@ -0,0 +1,102 @@
+use super::hal::{
+    gpio::{gpioa::PA8, Alternate, PushPull},
+    pac::TIM1,
+};
+use panel_core::tick::{Duration, Instant};
+
+// The piezo buzzer is driven by channel 1 of TIM1, on A8. Every other timer is taken by the lights
+// and the dial, and TIM1 also paces the LED strip, see `led_frame` in main.rs. Its repetition
+// counter keeps the update interrupt close to the frame rate whatever the buzzer plays: the
+// timer runs at the buzzer's frequency, and only updates every so many periods. While the buzzer
+// is silent, the timer runs as fast as the repetition counter allows, so that its other channels
+// can drive PWM outputs such as the fan, see fan.rs. Changing the period keeps their duty cycles.
+
+// Bits of TIM1_CCMR1, for PWM mode 1 with preload on channel 1.
+const OC1M_MASK: u32 = 0b111 << 4;
+const OC1M_PWM1: u32 = 0b110 << 4;
+const OC1PE: u32 = 1 << 3;
+
+/// Piezo buzzers are inaudible, or close to it, above this.
+const MAX_HZ: u32 = 20_000;
+
+/// The repetition counter is 8 bits wide.
+const MAX_REPETITIONS: u32 = 256;
+
+pub struct Buzzer {
+    _pin: PA8<Alternate<PushPull>>,
+    /// The frequency TIM1 counts at.
+    timer_hz: u32,
+    /// The rate of the update interrupt, which TIM1 has to keep to.
+    frame_hz: u32,
+    /// When the current beep started, and how long it lasts.
+    beep: Option<(Instant, Duration)>,
+}
+
+impl Buzzer {
+    /// Takes over channel 1 of TIM1, which must already be counting at `frame_hz`, silent.
+    pub fn new(pin: PA8<Alternate<PushPull>>, timer_hz: u32, frame_hz: u32) -> Self {
+        let tim = unsafe { &*TIM1::ptr() };
+
+        tim.ccr1.write(|w| unsafe { w.bits(0) });
+        tim.ccmr1_output()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !OC1M_MASK) | OC1M_PWM1 | OC1PE) });
+        tim.ccer.modify(|_, w| w.cc1e().set_bit());
+        // TIM1 is an advanced timer, whose outputs also need the main output enable.
+        tim.bdtr.modify(|_, w| w.moe().set_bit());
+
+        let mut buzzer = Self { _pin: pin, timer_hz, frame_hz, beep: None };
+        buzzer.stop();
+        buzzer
+    }
+
+    /// Sounds a square wave at `freq_hz` for `duration_ms`, cutting short any beep in progress.
+    /// `poll()` has to be called regularly to end it.
+    pub fn beep(&mut self, freq_hz: u32, duration_ms: u32) {
+        self.start(freq_hz);
+        self.beep = Some((Instant::now(), Duration::from_ms(duration_ms)));
+    }
+
+    /// Ends the current beep once it has lasted long enough.
+    pub fn poll(&mut self) {
+        if let Some((start, duration)) = self.beep {
+            if start.elapsed() >= duration {
+                self.stop();
+            }
+        }
+    }
+
+    fn start(&mut self, freq_hz: u32) {
+        let freq_hz = freq_hz.max(self.frame_hz).min(MAX_HZ);
+        let repetitions = (freq_hz / self.frame_hz).min(MAX_REPETITIONS);
+        self.set_period(freq_hz, repetitions, true);
+    }
+
+    pub fn stop(&mut self) {
+        self.beep = None;
+        self.set_period(self.frame_hz * MAX_REPETITIONS, MAX_REPETITIONS, false);
+    }
+
+    fn set_period(&mut self, freq_hz: u32, repetitions: u32, sounding: bool) {
+        let tim = unsafe { &*TIM1::ptr() };
+
+        let ticks = self.timer_hz / freq_hz;
+        let prescaler = (ticks - 1) / (1 << 16);
+        let reload = ticks / (prescaler + 1) - 1;
+        let duty = if sounding { reload.div_ceil(2) } else { 0 };
+
+        let old_period = tim.arr.read().bits() + 1;
+        let rescale =
+            |compare: u32| (compare as u64 * (reload + 1) as u64 / old_period as u64) as u32;
+
+        tim.psc.write(|w| unsafe { w.bits(prescaler) });
+        tim.arr.write(|w| unsafe { w.bits(reload) });
+        tim.rcr.write(|w| unsafe { w.bits(repetitions - 1) });
+        tim.ccr1.write(|w| unsafe { w.bits(duty) });
+        tim.ccr2.modify(|r, w| unsafe { w.bits(rescale(r.bits())) });
+        tim.ccr3.modify(|r, w| unsafe { w.bits(rescale(r.bits())) });
+        tim.ccr4.modify(|r, w| unsafe { w.bits(rescale(r.bits())) });
+        // Load the new period straight away. This also raises an update, so the LED strip gets
+        // an extra frame.
+        tim.egr.write(|w| w.ug().set_bit());
+    }
+}
@@ -0,0 +1,76 @@
+use super::hal::pac::{self, RCC, USB};
+use core::sync::atomic::{AtomicBool, Ordering};
+use cortex_m::{
+    asm::delay,
+    peripheral::{NVIC, SYST},
+};
+use cortex_m_rt::exception;
+
+// With the clock security system enabled, a failure of the HSE crystal switches the system clock
+// over to the HSI and raises an NMI. USB can't run without the 48 MHz clock derived from the
+// crystal, so the handler shuts it down, while the lights and inputs carry on at the slower clock.
+// A crystal which doesn't start at boot is handled the same way, but with the PLL run from the
+// HSI, so nothing is slower.
+
+const HSI_HZ: u32 = 8_000_000;
+
+/// How long the crystal has to start at boot.
+const HSE_STARTUP_MS: u32 = 100;
+
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Starts the HSE, and returns whether it's running within `HSE_STARTUP_MS`. Call this before
+/// the clocks are frozen, as the HAL waits for the HSE forever.
+pub fn start_hse() -> bool {
+    let rcc = unsafe { &*RCC::ptr() };
+    rcc.cr.modify(|_, w| w.hseon().set_bit());
+
+    // The chip is still running from the HSI.
+    for _ in 0..HSE_STARTUP_MS {
+        if rcc.cr.read().hserdy().bit_is_set() {
+            return true;
+        }
+        delay(HSI_HZ / 1000);
+    }
+
+    rcc.cr.modify(|_, w| w.hseon().clear_bit());
+    false
+}
+
+/// Enables the clock security system. Call this once the system clock is running from the HSE.
+pub fn enable() {
+    let rcc = unsafe { &*RCC::ptr() };
+    rcc.cr.modify(|_, w| w.csson().set_bit());
+}
+
+/// True once the HSE has failed, or if it didn't start at boot, and USB is shut down. After a
+/// failure, anything derived from `SYSCLK_HZ` other than the millisecond tick, such as busy-wait
+/// delays and the PWM frequency, runs six times slower.
+pub fn has_failed() -> bool {
+    FAILED.load(Ordering::Relaxed)
+}
+
+/// Shuts USB down for want of its clock. Call this once USB has been set up, if the HSE didn't
+/// start at boot.
+pub fn shut_down_usb() {
+    FAILED.store(true, Ordering::Relaxed);
+
+    NVIC::mask(pac::Interrupt::USB_HP_CAN_TX);
+    NVIC::mask(pac::Interrupt::USB_LP_CAN_RX0);
+    let usb = unsafe { &*USB::ptr() };
+    usb.cntr.write(|w| w.fres().set_bit().pdwn().set_bit());
+}
+
+#[exception]
+fn NonMaskableInt() {
+    let rcc = unsafe { &*RCC::ptr() };
+    if rcc.cir.read().cssf().bit_is_clear() {
+        return;
+    }
+    rcc.cir.modify(|_, w| w.cssc().set_bit());
+
+    // Keep the millisecond tick running at the right rate.
+    unsafe { (*SYST::PTR).rvr.write(HSI_HZ / crate::TICK_HZ - 1) };
+
+    shut_down_usb();
+}
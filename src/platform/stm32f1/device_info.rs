@@ -0,0 +1,37 @@
+use super::hal::pac::DBGMCU;
+
+/// The 96-bit unique ID, factory programmed into the system memory.
+const UNIQUE_ID_ADDRESS: usize = 0x1FFF_F7E8;
+
+/// The size of the main flash memory in KiB.
+const FLASH_SIZE_ADDRESS: usize = 0x1FFF_F7E0;
+
+/// The electronic signature of this particular chip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceInfo {
+    pub unique_id: [u8; 12],
+    pub flash_size_kb: u16,
+    /// The silicon revision. On the STM32F1 this reads as 0 unless a debug probe is attached.
+    pub revision: u16,
+}
+
+impl DeviceInfo {
+    pub fn read(dbgmcu: &DBGMCU) -> Self {
+        let unique_id = unique_id();
+        let flash_size_kb = unsafe { core::ptr::read_volatile(FLASH_SIZE_ADDRESS as *const u16) };
+        let revision = dbgmcu.idcode.read().rev_id().bits();
+
+        Self { unique_id, flash_size_kb, revision }
+    }
+}
+
+/// The 96-bit unique ID on its own, which unlike the revision can be read at any time.
+pub fn unique_id() -> [u8; 12] {
+    let mut unique_id = [0u8; 12];
+    for (i, byte) in unique_id.iter_mut().enumerate() {
+        // Safe because the signature is always mapped and read-only.
+        *byte = unsafe { core::ptr::read_volatile((UNIQUE_ID_ADDRESS + i) as *const u8) };
+    }
+
+    unique_id
+}
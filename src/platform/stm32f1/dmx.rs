@@ -0,0 +1,86 @@
+use super::hal::{
+    pac::{GPIOA, USART1},
+    serial::Tx,
+};
+use cortex_m::peripheral::DWT;
+use embedded_hal_02::serial::Write;
+use panel_core::dmx::FRAME_LEN;
+
+// DMX512 out of USART1 on A9, at 250 kbaud with two stop bits, into an RS-485 transceiver such as
+// a MAX485 with its driver always enabled. Each frame starts with a break of at least 88 us and a
+// mark after it of at least 8 us. The UART's own break is only a character long, so the pin is
+// switched to a plain output and held low instead, timed with the cycle counter, which init.rs
+// enables. The slots are then sent one at a time from the UART's interrupt as it empties.
+// https://www.analog.com/media/en/technical-documentation/data-sheets/MAX1487-MAX491.pdf
+
+#[cfg(all(feature = "dmx-output", feature = "uart-transport"))]
+compile_error!("dmx-output and uart-transport both need USART1");
+
+pub const BAUD: u32 = 250_000;
+
+const BREAK_US: u32 = 100;
+const MARK_AFTER_BREAK_US: u32 = 12;
+
+// The A9 bits of GPIOA_CRH, as a 50 MHz push pull output and as the UART's alternate function.
+const CRH_PA9_MASK: u32 = 0xf << 4;
+const CRH_PA9_OUTPUT: u32 = 0b0011 << 4;
+const CRH_PA9_ALTERNATE: u32 = 0b1011 << 4;
+
+pub struct DmxPort {
+    tx: Tx<USART1>,
+    frame: [u8; FRAME_LEN],
+    /// The next slot to send, or `FRAME_LEN` once they've all been written to the UART.
+    next: usize,
+    cycles_per_us: u32,
+}
+
+impl DmxPort {
+    /// Takes the transmitter of USART1, set up at `BAUD` with two stop bits.
+    pub fn new(tx: Tx<USART1>, sysclk_hz: u32) -> Self {
+        // The pin only drives its output register low while it's held for the break.
+        let gpioa = unsafe { &*GPIOA::ptr() };
+        gpioa.bsrr.write(|w| w.br9().set_bit());
+
+        Self { tx, frame: [0; FRAME_LEN], next: FRAME_LEN, cycles_per_us: sysclk_hz / 1_000_000 }
+    }
+
+    /// Sends the break, then starts sending `frame` from the interrupt. Returns false, without
+    /// sending anything, if the last frame is still going out.
+    pub fn send(&mut self, frame: [u8; FRAME_LEN]) -> bool {
+        let usart = unsafe { &*USART1::ptr() };
+        if self.next < FRAME_LEN || usart.sr.read().tc().bit_is_clear() {
+            return false;
+        }
+
+        self.set_pin_mode(CRH_PA9_OUTPUT);
+        self.delay_us(BREAK_US);
+        self.set_pin_mode(CRH_PA9_ALTERNATE);
+        self.delay_us(MARK_AFTER_BREAK_US);
+
+        self.frame = frame;
+        self.next = 0;
+        self.tx.listen();
+        true
+    }
+
+    /// Writes the next slot, from the UART's interrupt once it has room for it.
+    pub fn on_ready(&mut self) {
+        match self.frame.get(self.next) {
+            Some(&slot) => {
+                let _ = self.tx.write(slot);
+                self.next += 1;
+            },
+            None => self.tx.unlisten(),
+        }
+    }
+
+    fn set_pin_mode(&mut self, mode: u32) {
+        let gpioa = unsafe { &*GPIOA::ptr() };
+        gpioa.crh.modify(|r, w| unsafe { w.bits((r.bits() & !CRH_PA9_MASK) | mode) });
+    }
+
+    fn delay_us(&self, us: u32) {
+        let start = DWT::cycle_count();
+        while DWT::cycle_count().wrapping_sub(start) < us * self.cycles_per_us {}
+    }
+}
@@ -0,0 +1,149 @@
+use core::convert::Infallible;
+use eh02::blocking::i2c::{Read as I2cRead, Write as I2cWrite, WriteRead as I2cWriteRead};
+use embedded_hal::{digital, i2c, pwm, spi};
+use embedded_hal_02 as eh02;
+use nb::block;
+use panel_core::counter::QuadratureCounter;
+
+// stm32f1xx-hal only implements the embedded-hal 0.2 traits, while the drivers are written
+// against embedded-hal 1.0. This wrapper adapts the HAL's types to the traits the drivers need.
+
+/// An embedded-hal 0.2 type, seen through the equivalent embedded-hal 1.0 trait.
+pub struct Eh1<T>(pub T);
+
+impl<T: eh02::PwmPin<Duty = u16>> Eh1<T> {
+    /// Wraps a PWM channel, enabling it first as embedded-hal 1.0 has no way to.
+    pub fn enabled_pwm(mut channel: T) -> Self {
+        channel.enable();
+        Self(channel)
+    }
+}
+
+// GPIO pins on this chip can't fail, whichever way they're used.
+impl<T> digital::ErrorType for Eh1<T> {
+    type Error = Infallible;
+}
+
+impl<T: eh02::digital::v2::InputPin<Error = Infallible>> digital::InputPin for Eh1<T> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        self.0.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        self.0.is_low()
+    }
+}
+
+impl<T: eh02::digital::v2::OutputPin<Error = Infallible>> digital::OutputPin for Eh1<T> {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.set_high()
+    }
+}
+
+impl<T: eh02::PwmPin<Duty = u16>> pwm::ErrorType for Eh1<T> {
+    type Error = Infallible;
+}
+
+impl<T: eh02::PwmPin<Duty = u16>> pwm::SetDutyCycle for Eh1<T> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.0.get_max_duty()
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        self.0.set_duty(duty);
+        Ok(())
+    }
+}
+
+impl<T: eh02::spi::FullDuplex<u8>> spi::ErrorType for Eh1<T> {
+    type Error = spi::ErrorKind;
+}
+
+impl<T: eh02::spi::FullDuplex<u8>> spi::SpiBus for Eh1<T> {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), spi::ErrorKind> {
+        for word in words {
+            *word = self.transfer_word(0)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), spi::ErrorKind> {
+        for &word in words {
+            self.transfer_word(word)?;
+        }
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), spi::ErrorKind> {
+        for i in 0..read.len().max(write.len()) {
+            let word = self.transfer_word(write.get(i).copied().unwrap_or(0))?;
+            if let Some(read_word) = read.get_mut(i) {
+                *read_word = word;
+            }
+        }
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), spi::ErrorKind> {
+        for word in words {
+            *word = self.transfer_word(*word)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), spi::ErrorKind> {
+        // Every word is read back before the next one is sent, so nothing is left in flight.
+        Ok(())
+    }
+}
+
+impl<T: eh02::spi::FullDuplex<u8>> Eh1<T> {
+    fn transfer_word(&mut self, word: u8) -> Result<u8, spi::ErrorKind> {
+        block!(self.0.send(word)).map_err(|_| spi::ErrorKind::Other)?;
+        block!(self.0.read()).map_err(|_| spi::ErrorKind::Other)
+    }
+}
+
+impl<T: I2cRead + I2cWrite + I2cWriteRead> i2c::ErrorType for Eh1<T> {
+    type Error = i2c::ErrorKind;
+}
+
+impl<T: I2cRead + I2cWrite + I2cWriteRead> i2c::I2c for Eh1<T> {
+    /// embedded-hal 0.2 has no transactions, so each operation after the first starts with a
+    /// stop and a new start rather than a repeated start. Use `write_read` where that matters.
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), i2c::ErrorKind> {
+        for operation in operations {
+            let ok = match operation {
+                i2c::Operation::Read(buffer) => self.0.read(address, buffer).is_ok(),
+                i2c::Operation::Write(bytes) => self.0.write(address, bytes).is_ok(),
+            };
+            if !ok {
+                return Err(i2c::ErrorKind::Other);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), i2c::ErrorKind> {
+        self.0.write_read(address, write, read).map_err(|_| i2c::ErrorKind::Other)
+    }
+}
+
+impl<T: eh02::Qei<Count = u16>> QuadratureCounter for Eh1<T> {
+    fn count(&self) -> u16 {
+        self.0.count()
+    }
+}
@@ -0,0 +1,40 @@
+use super::hal::{
+    gpio::{gpioa::PA9, Alternate, PushPull},
+    pac::TIM1,
+};
+use panel_core::fraction::Fraction;
+
+// The PWM input of a 4-wire fan is driven by channel 2 of TIM1, on A9, at the period the buzzer
+// leaves the timer at, see buzzer.rs. The fan's tachometer is counted from its pin interrupt in
+// main.rs.
+
+// Bits of TIM1_CCMR1, for PWM mode 1 with preload on channel 2.
+const OC2M_MASK: u32 = 0b111 << 12;
+const OC2M_PWM1: u32 = 0b110 << 12;
+const OC2PE: u32 = 1 << 11;
+
+pub struct FanPwm {
+    _pin: PA9<Alternate<PushPull>>,
+}
+
+impl FanPwm {
+    /// Takes over channel 2 of TIM1, with the fan stopped. Set up the buzzer first, which
+    /// enables the timer's outputs.
+    pub fn new(pin: PA9<Alternate<PushPull>>) -> Self {
+        let tim = unsafe { &*TIM1::ptr() };
+
+        tim.ccr2.write(|w| unsafe { w.bits(0) });
+        tim.ccmr1_output()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !OC2M_MASK) | OC2M_PWM1 | OC2PE) });
+        tim.ccer.modify(|_, w| w.cc2e().set_bit());
+
+        Self { _pin: pin }
+    }
+
+    pub fn set_duty(&mut self, duty: Fraction) {
+        let tim = unsafe { &*TIM1::ptr() };
+
+        let period = tim.arr.read().bits() + 1;
+        tim.ccr2.write(|w| unsafe { w.bits(duty.of_u32(period)) });
+    }
+}
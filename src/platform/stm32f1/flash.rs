@@ -0,0 +1,149 @@
+use super::hal::pac::{flash::RegisterBlock, FLASH};
+use core::ptr;
+
+/// Flash on the STM32F103 is erased in 1 KiB pages.
+pub const PAGE_SIZE: u32 = 1024;
+
+/// One past the last address of flash.
+pub const FLASH_END: u32 = 0x0802_0000;
+
+const FLASH_KEY1: u32 = 0x4567_0123;
+const FLASH_KEY2: u32 = 0xCDEF_89AB;
+
+/// The option byte which sets the readout protection level, paired with its complement.
+const OPTION_BYTE_RDP_ADDRESS: u32 = 0x1FFF_F800;
+
+/// Writing anything but this to the RDP option byte enables readout protection.
+const RDP_UNPROTECTED: u16 = 0xA5;
+
+/// Why flash wasn't programmed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// PGERR: the half word wasn't erased.
+    Programming,
+    /// WRPRTERR: the page is write protected.
+    WriteProtected,
+}
+
+/// Direct access to the flash program/erase controller, for writing the pages which memory.x
+/// keeps out of the application image.
+///
+/// The HAL takes ownership of the FLASH peripheral at boot, but only uses it to configure wait
+/// states, so it's safe to program flash this way as long as only one `RawFlash` is in use at a
+/// time.
+pub struct RawFlash {
+    regs: &'static RegisterBlock,
+}
+
+impl RawFlash {
+    /// # Safety
+    /// The caller must ensure nothing else is programming or erasing flash concurrently.
+    pub unsafe fn steal() -> Self {
+        let regs = &*FLASH::ptr();
+
+        if regs.cr.read().lock().bit_is_set() {
+            regs.keyr.write(|w| w.bits(FLASH_KEY1));
+            regs.keyr.write(|w| w.bits(FLASH_KEY2));
+        }
+
+        Self { regs }
+    }
+
+    /// Like `steal()`, for an interrupt which may have preempted a task part way through
+    /// writing flash. Flash is unlocked for as long as a `RawFlash` is in use, so this returns
+    /// `None` while it is.
+    pub fn try_steal() -> Option<Self> {
+        let regs = unsafe { &*FLASH::ptr() };
+        if regs.cr.read().lock().bit_is_clear() {
+            return None;
+        }
+        Some(unsafe { Self::steal() })
+    }
+
+    pub fn erase_page(&mut self, address: u32) {
+        debug!("erasing flash page at {:#x}", address);
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.per().set_bit());
+        self.regs.ar.write(|w| unsafe { w.bits(address) });
+        self.regs.cr.modify(|_, w| w.strt().set_bit());
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.per().clear_bit());
+    }
+
+    /// Flash can only be programmed 16 bits at a time, and only from the erased state.
+    pub fn program_half_word(&mut self, address: u32, value: u16) -> Result<(), Error> {
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.pg().set_bit());
+        unsafe { ptr::write_volatile(address as *mut u16, value) };
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.pg().clear_bit());
+
+        let sr = self.regs.sr.read();
+        let result = if sr.pgerr().bit_is_set() {
+            Err(Error::Programming)
+        } else if sr.wrprterr().bit_is_set() {
+            Err(Error::WriteProtected)
+        } else {
+            Ok(())
+        };
+        // The flags are cleared by writing ones to them.
+        self.regs.sr.write(|w| w.pgerr().set_bit().wrprterr().set_bit());
+        result
+    }
+
+    /// Programs `bytes` starting at `address`, padding an odd trailing byte with 0xFF.
+    pub fn program(&mut self, address: u32, bytes: &[u8]) -> Result<(), Error> {
+        for (i, chunk) in bytes.chunks(2).enumerate() {
+            let low = chunk[0] as u16;
+            let high = chunk.get(1).copied().unwrap_or(0xFF) as u16;
+            self.program_half_word(address + i as u32 * 2, low | (high << 8))?;
+        }
+        Ok(())
+    }
+
+    /// Enables readout protection, so that flash can no longer be read over SWD or by the ROM
+    /// bootloader. It takes effect after the next reset. Erasing the option bytes also resets the
+    /// user option bytes and write protection to their defaults, which the firmware relies on
+    /// anyway. Turning protection off again mass erases the flash, so only a debug probe can.
+    pub fn enable_readout_protection(&mut self) {
+        warn!("enabling readout protection");
+
+        self.regs.optkeyr.write(|w| unsafe { w.bits(FLASH_KEY1) });
+        self.regs.optkeyr.write(|w| unsafe { w.bits(FLASH_KEY2) });
+
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.opter().set_bit());
+        self.regs.cr.modify(|_, w| w.strt().set_bit());
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.opter().clear_bit());
+
+        // The erased value would already enable protection, but it's clearer to say so.
+        self.regs.cr.modify(|_, w| w.optpg().set_bit());
+        unsafe {
+            ptr::write_volatile(OPTION_BYTE_RDP_ADDRESS as *mut u16, !RDP_UNPROTECTED & 0xFF)
+        };
+        self.wait_until_ready();
+        self.regs.cr.modify(|_, w| w.optpg().clear_bit().optwre().clear_bit());
+    }
+
+    fn wait_until_ready(&self) {
+        while self.regs.sr.read().bsy().bit_is_set() {}
+    }
+}
+
+impl Drop for RawFlash {
+    fn drop(&mut self) {
+        self.regs.cr.modify(|_, w| w.lock().set_bit());
+    }
+}
+
+/// Reads `len` bytes of flash starting at `address`.
+pub fn read(address: u32, len: usize) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(address as *const u8, len) }
+}
+
+/// True if the option bytes loaded at the last reset enable readout protection.
+pub fn readout_protection_enabled() -> bool {
+    let regs = unsafe { &*FLASH::ptr() };
+    regs.obr.read().rdprt().bit_is_set()
+}
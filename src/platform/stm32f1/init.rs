@@ -0,0 +1,689 @@
+#[cfg(feature = "uart-transport")]
+use super::hal::serial::Event as SerialEvent;
+#[cfg(feature = "dmx-output")]
+use super::hal::serial::StopBits;
+#[cfg(any(feature = "uart-transport", feature = "dmx-output"))]
+use super::hal::serial::{Config as SerialConfig, Serial};
+#[cfg(feature = "encoder-ring")]
+use super::hal::spi::Spi2NoRemap;
+#[cfg(not(feature = "uart-transport"))]
+use super::hal::usb::{Peripheral, UsbBus};
+use super::hal::{
+    adc::Adc,
+    gpio::{Edge, ExtiPin},
+    i2c::{BlockingI2c, DutyCycle, Mode as I2cMode},
+    pac::{self, TIM1},
+    prelude::*,
+    qei::QeiOptions,
+    rtc::Rtc,
+    spi::{Mode as SpiMode, NoMiso, NoSck, Phase, Polarity, Spi, Spi1NoRemap},
+    timer::{CountDownTimer, Event, Tim2NoRemap, Tim3PartialRemap, Timer},
+    watchdog::IndependentWatchdog,
+};
+#[cfg(feature = "hid-input")]
+use crate::hid::InputHid;
+#[cfg(feature = "hid-transport")]
+use crate::hid::{UsbHid, VendorHid};
+#[cfg(not(any(feature = "uart-transport", feature = "dmx-output")))]
+use crate::platform::fan::FanPwm;
+#[cfg(feature = "uart-transport")]
+use crate::platform::uart::Uart;
+#[cfg(not(feature = "uart-transport"))]
+use crate::platform::UsbBusType;
+#[cfg(not(any(feature = "uart-transport", feature = "hid-transport")))]
+use crate::serial::UsbSerial;
+use crate::{
+    ambient_light::AmbientLight,
+    board,
+    boot_diagnostics::{BootDiagnostics, Fault},
+    brownout,
+    capabilities::{Capabilities, Feature},
+    controls::{EXPANDER_POLL_PERIOD_MS, INPUT_POLL_PERIOD_MS},
+    drv2605::Drv2605,
+    ds3231::Ds3231,
+    eeprom::Eeprom,
+    lights::{LED_FRAME_HZ, STANDALONE_BRIGHTNESS},
+    mcp23017::{ExpanderPin, Mcp23017},
+    outputs::Outputs,
+    overhead_light::OverheadLight,
+    panic_log,
+    platform::{
+        boot_checks, buzzer::Buzzer, clock_security, device_info::DeviceInfo, dmx::DmxPort,
+        eh1::Eh1, onewire::OneWirePin, pvd, reset_reason::ResetReason, wall_clock::WallClock,
+    },
+    power_monitor::PowerMonitor,
+    resources::{
+        AnalogInputs, AuxOutputs, BackLight, Door, EncoderButton, EncoderCounter, Expander, Fan,
+        FixtureProbes, FrontLight, I2cBus, IrReceiver, Protocol, Ring, StatusDisplay, Strip,
+        VbusPin, EXPANDER_OUTPUT_MASK,
+    },
+    rgb_led::{LedSettings, LedStrip, Rgb},
+    serial::SerialProtocol,
+    settings::{self, BootCounts, Scene, Settings},
+    ssd1306::Ssd1306,
+    usb::BootReport,
+    SYSCLK_HZ, TICK_HZ,
+};
+use cortex_m::{asm::delay, peripheral::syst::SystClkSource};
+use embedded_hal_02::digital::v2::{InputPin, OutputPin};
+use panel_core::{
+    button::{Active, Button, Debouncer},
+    counter::Counter,
+    display::Frame,
+    door::DoorSensor,
+    ds18b20::Probes,
+    fan::FanController,
+    haptics::HapticEffects,
+    input::DialRateLimit,
+    ir::{IrBindings, IrDecoder},
+    power::PowerManager,
+    power_budget::PowerBudget,
+    pulser::Pulser,
+    schedule::Schedule,
+    standalone::Standalone,
+};
+#[cfg(not(feature = "uart-transport"))]
+use usb_device::{
+    bus::UsbBusAllocator,
+    device::{UsbDeviceBuilder, UsbVidPid},
+};
+#[cfg(not(any(feature = "uart-transport", feature = "hid-transport")))]
+use usbd_serial::SerialPort;
+#[cfg(not(any(
+    feature = "uart-transport",
+    feature = "hid-transport",
+    feature = "hid-input"
+)))]
+use usbd_serial::USB_CLASS_CDC;
+
+// Brings up the clocks and every peripheral at boot, and looks for the optional parts, for
+// main.rs to hand to the RTIC tasks as their resources.
+
+/// The watchdog resets the panel if input polling stops running for this long.
+const WATCHDOG_TIMEOUT_MS: u32 = 1_000;
+
+/// The UART's speed, when the protocol goes over it rather than USB.
+#[cfg(feature = "uart-transport")]
+const UART_BAUD: u32 = 115_200;
+
+/// The LED strip dims after the panel has been left alone for this long.
+const DIM_AFTER_MS: u32 = 60_000;
+
+/// Everything turns off after this long without input, unless the host is connected.
+const SLEEP_AFTER_MS: u32 = 15 * 60_000;
+
+/// If no host has opened the serial port for this long, the panel starts to control its lights
+/// itself, see panel_core::standalone.
+const STANDALONE_AFTER_MS: u32 = 10_000;
+
+/// The resources `bring_up()` sets up, each of which main.rs passes on to RTIC under its own
+/// name. The rest start out the same on every boot, and are set up in main.rs.
+pub struct Setup {
+    pub protocol: Protocol,
+    pub front_light: FrontLight,
+    pub back_light: BackLight,
+    pub led_strip: Strip,
+    pub encoder_ring: Option<Ring>,
+    pub led_timer: CountDownTimer<TIM1>,
+    pub buzzer: Buzzer,
+    pub status_on_connect: bool,
+    pub capabilities: Capabilities,
+    pub pulser: Pulser,
+    pub led_settings: LedSettings,
+    pub counter: EncoderCounter,
+    pub dial_limit: DialRateLimit,
+    pub settings: Settings,
+    pub encoder_button: EncoderButton,
+    pub door: Option<Door>,
+    pub led: board::StatusLedPin,
+    pub vbus: VbusPin,
+    pub watchdog: IndependentWatchdog,
+    pub eeprom: Eeprom,
+    pub wall_clock: WallClock,
+    pub external_rtc: Option<Ds3231>,
+    pub schedule: Schedule,
+    pub utc_offset_minutes: i16,
+    pub low_voltage_events: u16,
+    pub power: PowerManager,
+    pub standalone: Standalone,
+    pub i2c: I2cBus,
+    pub ambient_light: Option<AmbientLight>,
+    pub display: StatusDisplay,
+    pub haptics: Option<Drv2605>,
+    pub haptic_effects: HapticEffects,
+    pub power_monitor: Option<PowerMonitor>,
+    pub power_budget: Option<PowerBudget>,
+    pub analog: AnalogInputs,
+    pub ir_receiver: IrReceiver,
+    pub ir_bindings: IrBindings,
+    pub fan: Fan,
+    pub fan_tach: board::FanTachPin,
+    pub outputs: AuxOutputs,
+    pub expander: Expander,
+    pub dmx_port: Option<DmxPort>,
+    pub fixture_probes: FixtureProbes,
+    pub boot_report: BootReport,
+}
+
+/// Sets up the panel from reset, with interrupts still masked. Parts which aren't found are
+/// logged, and left out of the capabilities reported to the host.
+pub fn bring_up(cp: cortex_m::Peripherals, dp: pac::Peripherals) -> Setup {
+    // This has to be read before the RCC is constrained below.
+    let reset_reason = ResetReason::read_and_clear(&dp.RCC);
+    info!("booting, {}", reset_reason.as_str());
+
+    let device_info = DeviceInfo::read(&dp.DBGMCU);
+    info!("flash {} KiB, revision {:#x}", device_info.flash_size_kb, device_info.revision);
+
+    pvd::enable(&dp.RCC, &dp.PWR, &dp.EXTI);
+
+    // Take ownership over the raw flash and rcc devices and convert them into the corresponding
+    // HAL structs.
+    // RCC = Reset and Clock Control
+    let mut flash = dp.FLASH.constrain();
+    let mut rcc = dp.RCC.constrain();
+
+    // The various system clocks need to be configured to particular values
+    // to work with USB - we'll set them up here. Without the crystal, the PLL runs from
+    // the HSI instead, which isn't accurate enough for USB, so USB is shut down below.
+    let hse_running = clock_security::start_hse();
+    let cfgr = rcc
+        .cfgr
+        .sysclk(SYSCLK_HZ.hz()) // The main system clock will be 48MHz
+        .pclk1(24.mhz()); // Use 24MHz for the APB1 (Advanced Peripheral Bus 1)
+                          // Use the High Speed External 8MHz crystal
+    let cfgr = if hse_running { cfgr.use_hse(8.mhz()) } else { cfgr };
+    let clocks = cfgr.freeze(&mut flash.acr);
+
+    let usb_clock_valid = hse_running && clocks.usbclk_valid();
+    if !usb_clock_valid {
+        warn!("crystal didn't start, running without USB");
+    }
+
+    // Checks of each subsystem as it's set up, reported to the host once it connects.
+    let mut diagnostics = BootDiagnostics::default();
+    diagnostics.check(Fault::Clock, boot_checks::hse_running() && clocks.sysclk().0 == SYSCLK_HZ);
+    if hse_running {
+        clock_security::enable();
+    }
+
+    // Prepare the alternate function I/O registers
+    let mut afio = dp.AFIO.constrain(&mut rcc.apb2);
+
+    // Grab the GPIO banks we'll use.
+    let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
+    let mut gpiob = dp.GPIOB.split(&mut rcc.apb2);
+
+    // The RTC lives in the backup domain, which keeps it running through resets.
+    let mut pwr = dp.PWR;
+    let mut backup_domain = rcc.bkp.constrain(dp.BKP, &mut rcc.apb1, &mut pwr);
+    let rtc = Rtc::rtc(dp.RTC, &mut backup_domain);
+    let mut wall_clock = WallClock::new(rtc, backup_domain);
+
+    // The ambient light sensor, the status display, the haptic driver, the LED rail's current
+    // monitor, the DS3231, the GPIO expander and the settings EEPROM, on boards which have
+    // them, are on I2C2 (B10 and B11). All can run the bus at 400 kHz, which keeps display
+    // updates short.
+    let i2c_pins = (
+        gpiob.pb10.into_alternate_open_drain(&mut gpiob.crh),
+        gpiob.pb11.into_alternate_open_drain(&mut gpiob.crh),
+    );
+    let i2c = BlockingI2c::i2c2(
+        dp.I2C2,
+        i2c_pins,
+        I2cMode::Fast { frequency: 400_000.hz(), duty_cycle: DutyCycle::Ratio2to1 },
+        clocks,
+        &mut rcc.apb1,
+        1000,
+        10,
+        1000,
+        1000,
+    );
+    let mut i2c = Eh1(i2c);
+
+    let mut eeprom = Eeprom::open(&mut i2c);
+    let settings = Settings::load(&eeprom);
+    let ir_bindings = settings::load_ir_bindings(&eeprom);
+    let schedule = settings::load_schedule(&eeprom);
+    let utc_offset_minutes = settings::load_utc_offset(&eeprom);
+    let haptic_effects = settings::load_haptic_effects(&eeprom);
+    // A panel which browned out comes back as it was, see brownout.rs.
+    let brownout_snapshot = brownout::take();
+    let led_settings = match brownout_snapshot {
+        Some(scene) => scene.led,
+        None => settings::load_default_led(&eeprom),
+    };
+    let suspend_policy = settings::load_suspend_policy(&eeprom);
+    let status_on_connect = settings::load_status_on_connect(&eeprom);
+    let low_voltage_events = settings::low_voltage_count(&eeprom);
+
+    // Which pin does what depends on the board, see board.rs.
+    let pins = crate::take_pins!(gpioa, gpiob);
+    let mut led = pins.status_led;
+    let outputs = Outputs::new(pins.outputs.map(Eh1));
+
+    // If the firmware panicked before the last reset, blink the LED rapidly to show it.
+    let panic_message = panic_log::take();
+    if panic_message.is_some() {
+        for _ in 0..5 {
+            let _ = led.set_low();
+            delay(clocks.sysclk().0 / 20);
+            let _ = led.set_high();
+            delay(clocks.sysclk().0 / 20);
+        }
+    }
+
+    BootCounts::record_boot(&mut eeprom, reset_reason, panic_message.is_some());
+
+    #[cfg(not(feature = "uart-transport"))]
+    let usb_bus = {
+        // Set up USB communications
+        let usb_pin_d_minus = gpioa.pa11;
+
+        // Pull the USB D+ pin low to indicate to the USB host that this device
+        // is resetting (sends a RESET condition on the USB bus).
+        let mut usb_pin_d_plus = gpioa.pa12.into_push_pull_output(&mut gpioa.crh);
+        let _ = usb_pin_d_plus.set_low();
+        delay(clocks.sysclk().0 / 100);
+
+        let usb_pin_d_plus = usb_pin_d_plus.into_floating_input(&mut gpioa.crh);
+
+        let usb = Peripheral { usb: dp.USB, pin_dm: usb_pin_d_minus, pin_dp: usb_pin_d_plus };
+
+        // The USB bus allocator has to outlive the device and class which borrow it.
+        let usb_bus: &'static _ =
+            cortex_m::singleton!(: UsbBusAllocator<UsbBusType> = UsbBus::new(usb)).unwrap();
+        usb_bus
+    };
+
+    #[cfg(not(any(feature = "uart-transport", feature = "hid-transport")))]
+    let protocol = {
+        let serial = SerialPort::new(usb_bus);
+        #[cfg(feature = "hid-input")]
+        let input = Some(InputHid::new(usb_bus));
+        #[cfg(not(feature = "hid-input"))]
+        let input = None;
+
+        let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
+            .manufacturer("tonari")
+            .product("tonari dashboard controller")
+            .serial_number("tonari-dashboard-controller-v1");
+        // With a HID interface alongside, the device is a composite one and each function
+        // says what it is in its own interface association descriptor.
+        #[cfg(feature = "hid-input")]
+        let usb_dev = usb_dev.device_class(0xef).device_sub_class(0x02).device_protocol(0x01);
+        #[cfg(not(feature = "hid-input"))]
+        let usb_dev = usb_dev.device_class(USB_CLASS_CDC);
+
+        SerialProtocol::new(UsbSerial::new(usb_dev.build(), serial, input))
+    };
+
+    // A vendor defined HID interface instead, under the shared ID for those, see hid.rs.
+    #[cfg(feature = "hid-transport")]
+    let protocol = {
+        let hid = VendorHid::new(usb_bus);
+
+        let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x05df))
+            .manufacturer("tonari")
+            .product("tonari dashboard controller")
+            .serial_number("tonari-dashboard-controller-v1")
+            .build();
+
+        SerialProtocol::new(UsbHid::new(usb_dev, hid))
+    };
+
+    #[cfg(not(feature = "uart-transport"))]
+    if !usb_clock_valid {
+        clock_security::shut_down_usb();
+    }
+
+    // Panels built into another device talk to it over USART1 instead, see uart.rs.
+    #[cfg(feature = "uart-transport")]
+    let protocol = {
+        let uart_pins = (gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh), gpioa.pa10);
+        let mut uart = Serial::usart1(
+            dp.USART1,
+            uart_pins,
+            &mut afio.mapr,
+            SerialConfig::default().baudrate(UART_BAUD.bps()),
+            clocks,
+            &mut rcc.apb2,
+        );
+        uart.listen(SerialEvent::Rxne);
+        let (tx, rx) = uart.split();
+
+        SerialProtocol::new(Uart::new(tx, rx))
+    };
+
+    // DMX fixtures are driven from USART1 as well, through a transceiver, see dmx.rs.
+    #[cfg(feature = "dmx-output")]
+    let dmx_port = {
+        let dmx_pins = (gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh), gpioa.pa10);
+        let baud = super::dmx::BAUD;
+        let config = SerialConfig::default().baudrate(baud.bps()).stopbits(StopBits::STOP2);
+        let serial =
+            Serial::usart1(dp.USART1, dmx_pins, &mut afio.mapr, config, clocks, &mut rcc.apb2);
+        let (tx, _rx) = serial.split();
+
+        Some(DmxPort::new(tx, SYSCLK_HZ))
+    };
+    #[cfg(not(feature = "dmx-output"))]
+    let dmx_port = None;
+
+    // Disable JTAG so that we can use the pin PB4 for the timer
+    let (pa15, pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+    let vbus = pa15.into_pull_down_input(&mut gpioa.crh);
+
+    // SPI Setup (for WS8212b RGB LEDs)
+    let spi_pins = (NoSck, NoMiso, pins.strip_data);
+    let spi_mode = SpiMode { polarity: Polarity::IdleLow, phase: Phase::CaptureOnFirstTransition };
+
+    let spi = Spi::<_, Spi1NoRemap, _, u8>::spi1(
+        dp.SPI1,
+        spi_pins,
+        &mut afio.mapr,
+        spi_mode,
+        2250.khz(), // https://os.mbed.com/teams/ST/wiki/SPI-output-clock-frequency
+        clocks,
+        &mut rcc.apb2,
+    );
+
+    let mut led_strip = LedStrip::new(Eh1(spi));
+    diagnostics.check(Fault::Spi, led_strip.try_set_all(Rgb::new(0, 0, 0)).is_ok());
+
+    // The LED ring around the dial, with the encoder-ring feature, is driven the same way
+    // from SPI2, on B15.
+    #[cfg(feature = "encoder-ring")]
+    let encoder_ring = {
+        let spi = Spi::<_, Spi2NoRemap, _, u8>::spi2(
+            dp.SPI2,
+            (NoSck, NoMiso, pins.ring_data),
+            spi_mode,
+            2250.khz(),
+            clocks,
+            &mut rcc.apb1,
+        );
+        let mut encoder_ring = LedStrip::new(Eh1(spi));
+        encoder_ring.set_all(Rgb::new(0, 0, 0));
+        Some(encoder_ring)
+    };
+    #[cfg(not(feature = "encoder-ring"))]
+    let encoder_ring = None;
+
+    // The strip is refreshed from a timer interrupt, at the same priority as USB so that
+    // heavy traffic can't hold up the animation.
+    let mut led_timer =
+        Timer::tim1(dp.TIM1, &clocks, &mut rcc.apb2).start_count_down(LED_FRAME_HZ.hz());
+    led_timer.listen(Event::Update);
+
+    // The piezo buzzer shares TIM1 with the LED strip, see buzzer.rs.
+    let buzzer_pin = gpioa.pa8.into_alternate_push_pull(&mut gpioa.crh);
+    let buzzer = Buzzer::new(buzzer_pin, clocks.pclk2_tim().0, LED_FRAME_HZ);
+
+    // So does the fan, on boards which have one, unless A9 is the UART's or DMX's instead.
+    #[cfg(not(any(feature = "uart-transport", feature = "dmx-output")))]
+    let fan_pwm = Some(FanPwm::new(gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh)));
+    #[cfg(any(feature = "uart-transport", feature = "dmx-output"))]
+    let fan_pwm = None;
+    let fan_controller = board::BOARD.fan.filter(|_| fan_pwm.is_some()).map(FanController::new);
+    let mut fan_tach = pins.fan_tach;
+    if fan_controller.is_some() {
+        fan_tach.make_interrupt_source(&mut afio);
+        fan_tach.trigger_on_edge(&dp.EXTI, Edge::FALLING);
+        fan_tach.enable_interrupt(&dp.EXTI);
+    }
+
+    let pulser = Pulser::new(settings.pulse_interval_ms as u32);
+
+    // PWM Setup
+    // https://docs.rs/stm32f1xx-hal/0.6.1/stm32f1xx_hal/timer/index.html
+    let timer3_pwm_pins = (
+        pb4.into_alternate_push_pull(&mut gpiob.crl),
+        gpiob.pb5.into_alternate_push_pull(&mut gpiob.crl),
+        gpiob.pb0.into_alternate_push_pull(&mut gpiob.crl),
+        gpiob.pb1.into_alternate_push_pull(&mut gpiob.crl),
+    );
+    let timer4_pwm_pins = (
+        gpiob.pb6.into_alternate_push_pull(&mut gpiob.crl),
+        gpiob.pb7.into_alternate_push_pull(&mut gpiob.crl),
+        gpiob.pb8.into_alternate_push_pull(&mut gpiob.crh),
+        gpiob.pb9.into_alternate_push_pull(&mut gpiob.crh),
+    );
+    let light_pwm_hz = board::BOARD.light_pwm_hz.hz();
+    let (pwm1, pwm2, pwm3, pwm4) = Timer::tim3(dp.TIM3, &clocks, &mut rcc.apb1)
+        .pwm::<Tim3PartialRemap, _, _, _>(timer3_pwm_pins, &mut afio.mapr, light_pwm_hz)
+        .split();
+    let (pwm5, pwm6, pwm7, pwm8) = Timer::tim4(dp.TIM4, &clocks, &mut rcc.apb1)
+        .pwm(timer4_pwm_pins, &mut afio.mapr, light_pwm_hz)
+        .split();
+
+    // The overhead light closer to the screen.
+    let mut front_light = OverheadLight::new(
+        Eh1::enabled_pwm(pwm1),
+        Eh1::enabled_pwm(pwm2),
+        Eh1::enabled_pwm(pwm3),
+        Eh1::enabled_pwm(pwm4),
+    );
+
+    // The overhead light farther away from the screen.
+    let mut back_light = OverheadLight::new(
+        Eh1::enabled_pwm(pwm5),
+        Eh1::enabled_pwm(pwm6),
+        Eh1::enabled_pwm(pwm7),
+        Eh1::enabled_pwm(pwm8),
+    );
+    if let Some(Scene { front, back, .. }) = brownout_snapshot {
+        front_light.set_brightness(front.0);
+        front_light.set_color_temperature(front.1);
+        back_light.set_brightness(back.0);
+        back_light.set_color_temperature(back.1);
+    }
+    diagnostics.check(Fault::Pwm, boot_checks::pwm_running());
+
+    let ambient_light = AmbientLight::new(&mut i2c).ok();
+    if ambient_light.is_none() {
+        info!("no ambient light sensor");
+    }
+    let display = Ssd1306::new(&mut i2c).ok();
+    if display.is_none() {
+        info!("no status display");
+    }
+    let haptics = Drv2605::new(&mut i2c, board::BOARD.haptic_actuator).ok();
+    if haptics.is_none() {
+        info!("no haptic driver");
+    }
+    let power_monitor = PowerMonitor::new(&mut i2c, board::BOARD.led_rail_shunt_milliohms).ok();
+    if power_monitor.is_none() {
+        info!("no LED rail current monitor");
+    }
+    let power_budget =
+        board::BOARD.led_rail_budget_ma.filter(|_| power_monitor.is_some()).map(PowerBudget::new);
+    // The DS3231 keeps time through power loss better than the internal RTC, so it's trusted
+    // over it whenever it has the time. It's only looked for with the external-rtc feature.
+    let mut external_rtc =
+        if cfg!(feature = "external-rtc") { Ds3231::new(&mut i2c).ok() } else { None };
+    match external_rtc.as_mut().map(|rtc| rtc.now(&mut i2c)) {
+        Some(Ok(Some(now))) => wall_clock.set(now),
+        Some(_) => warn!("DS3231 hasn't been set"),
+        None => info!("no DS3231"),
+    }
+
+    // AnalogInputs sliders, on boards which have them, are on A4 and A5. Unused, the pins are left
+    // as analog inputs, which draw the least current.
+    let slider_pins =
+        (gpioa.pa4.into_analog(&mut gpioa.crl), gpioa.pa5.into_analog(&mut gpioa.crl));
+    let adc = Adc::adc1(dp.ADC1, &mut rcc.apb2, clocks);
+
+    // The IR receiver interrupts on every edge, so that its pulses can be timed.
+    let mut ir_receiver = pins.ir_receiver;
+    ir_receiver.make_interrupt_source(&mut afio);
+    ir_receiver.trigger_on_edge(&dp.EXTI, Edge::RISING_FALLING);
+    ir_receiver.enable_interrupt(&dp.EXTI);
+
+    // Connect a rotary encoder to pins A0 and A1.
+    let rotary_encoder_pins = (gpioa.pa0, gpioa.pa1);
+    // Tim2NoRemap relates to how you can "remap" pins used on timer 2 for certain peripherals.
+    // https://docs.rs/stm32f1xx-hal/0.6.1/stm32f1xx_hal/timer/index.html
+    let rotary_encoder = Timer::tim2(dp.TIM2, &clocks, &mut rcc.apb1).qei::<Tim2NoRemap, _>(
+        rotary_encoder_pins,
+        &mut afio.mapr,
+        QeiOptions::default(),
+    );
+    diagnostics.check(Fault::Qei, boot_checks::qei_running());
+    // The door sensor, on boards which have one, takes the index channel's pin.
+    let (encoder_index, door_pin) = match board::BOARD.encoder_index {
+        Some(active) => (Some((Eh1(pins.encoder_index), active)), None),
+        None => (None, Some(pins.encoder_index)),
+    };
+    let counter = Counter::new(Eh1(rotary_encoder), encoder_index);
+
+    // The button should read as released, once the pin has had a moment to settle.
+    let button_pin = pins.button;
+    delay(1000);
+    let released = match board::BOARD.button_active {
+        Active::Low => button_pin.is_high(),
+        Active::High => button_pin.is_low(),
+    };
+    diagnostics.check(Fault::Button, released.unwrap_or(false));
+    let debounced_encoder_pin = Debouncer::new(
+        Eh1(button_pin),
+        board::BOARD.button_active,
+        settings.debounce_time_ms,
+        (TICK_HZ / INPUT_POLL_PERIOD_MS) as u16,
+    );
+    let encoder_button = Button::new(debounced_encoder_pin, settings.long_press_timeout_ms as u32);
+
+    let expander = Mcp23017::new(&mut i2c, EXPANDER_OUTPUT_MASK).ok();
+    if expander.is_none() {
+        info!("no GPIO expander");
+    }
+    let expander_buttons = [0, 1, 2, 3, 4, 5, 6, 7].map(|pin| {
+        let debounced_pin = Debouncer::new(
+            ExpanderPin::new(pin),
+            Active::Low,
+            settings.debounce_time_ms,
+            (TICK_HZ / EXPANDER_POLL_PERIOD_MS) as u16,
+        );
+        Button::new(debounced_pin, settings.long_press_timeout_ms as u32)
+    });
+    let expander_outputs = Outputs::new([8, 9, 10, 11, 12, 13, 14, 15].map(ExpanderPin::new));
+    let door = match (door_pin, board::BOARD.door_sensor) {
+        (Some(pin), Some(active)) => Some(DoorSensor::new(Debouncer::new(
+            Eh1(pin),
+            active,
+            settings.debounce_time_ms,
+            (TICK_HZ / INPUT_POLL_PERIOD_MS) as u16,
+        ))),
+        _ => None,
+    };
+
+    let mut watchdog = IndependentWatchdog::new(dp.IWDG);
+    watchdog.start(WATCHDOG_TIMEOUT_MS.ms());
+
+    // The cycle counter measures how long `idle` sleeps for. The core clock is normally gated
+    // during sleep, which would stop the counter too.
+    let mut dcb = cp.DCB;
+    let mut dwt = cp.DWT;
+    dcb.enable_trace();
+    dwt.enable_cycle_counter();
+    dp.DBGMCU.cr.modify(|_, w| w.dbg_sleep().set_bit());
+
+    // Temperature probes on the light fixtures, on boards which have them, share a 1-Wire bus
+    // on B3. Its timing needs the cycle counter.
+    let mut fixture_bus = OneWirePin::new(pb3.into_open_drain_output(&mut gpiob.crl), SYSCLK_HZ);
+    let fixture_probes = Probes::find(&mut fixture_bus);
+    if fixture_probes.is_empty() {
+        info!("no fixture temperature probes");
+    } else {
+        info!("{} fixture temperature probes", fixture_probes.len());
+        fixture_probes.start_conversion(&mut fixture_bus);
+    }
+
+    let mut systick = cp.SYST;
+    systick.set_clock_source(SystClkSource::Core);
+    systick.set_reload(SYSCLK_HZ / TICK_HZ - 1);
+    systick.clear_current();
+    systick.enable_counter();
+    systick.enable_interrupt();
+    // Dial movement is summed into a limited number of reports a second, unless the host
+    // sets another rate, so that a fast spin can't flood it.
+    let dial_limit = DialRateLimit::new(settings.dial_reports_per_second);
+    let mut power = PowerManager::new(DIM_AFTER_MS, SLEEP_AFTER_MS);
+    power.set_suspend_policy(suspend_policy);
+    let standalone = Standalone::new(STANDALONE_AFTER_MS, STANDALONE_BRIGHTNESS);
+
+    let mut capabilities = Capabilities::default();
+    capabilities.set(Feature::EncoderIndex, board::BOARD.encoder_index.is_some());
+    capabilities.set(Feature::DoorSensor, door.is_some());
+    capabilities.set(Feature::VbusSense, board::BOARD.vbus_sense);
+    capabilities.set(Feature::Joystick, board::BOARD.joystick);
+    capabilities.set(Feature::Microphone, board::BOARD.microphone);
+    capabilities.set(Feature::Fan, fan_controller.is_some());
+    capabilities.set(Feature::EncoderRing, cfg!(feature = "encoder-ring"));
+    capabilities.set(Feature::DmxOutput, cfg!(feature = "dmx-output"));
+    let hid_input = cfg!(all(
+        feature = "hid-input",
+        not(any(feature = "uart-transport", feature = "hid-transport"))
+    ));
+    capabilities.set(Feature::HidInput, hid_input);
+    capabilities.set(Feature::AmbientLight, ambient_light.is_some());
+    capabilities.set(Feature::Display, display.is_some());
+    capabilities.set(Feature::Haptics, haptics.is_some());
+    capabilities.set(Feature::ExternalRtc, external_rtc.is_some());
+    capabilities.set(Feature::LedRailMonitor, power_monitor.is_some());
+    capabilities.set(Feature::Expander, expander.is_some());
+    capabilities.set(Feature::FixtureProbes, !fixture_probes.is_empty());
+    info!("init complete");
+
+    Setup {
+        protocol,
+        front_light,
+        back_light,
+        led_strip,
+        encoder_ring,
+        led_timer,
+        buzzer,
+        status_on_connect,
+        capabilities,
+        pulser,
+        led_settings,
+        counter,
+        dial_limit,
+        settings,
+        encoder_button,
+        door,
+        led,
+        vbus,
+        watchdog,
+        eeprom,
+        wall_clock,
+        external_rtc,
+        schedule,
+        utc_offset_minutes,
+        low_voltage_events,
+        power,
+        standalone,
+        i2c,
+        ambient_light,
+        display: StatusDisplay { driver: display, shown: Frame::blank() },
+        haptics,
+        haptic_effects,
+        power_monitor,
+        power_budget,
+        analog: AnalogInputs { adc, pins: slider_pins },
+        ir_receiver: IrReceiver { pin: ir_receiver, decoder: IrDecoder::new() },
+        ir_bindings,
+        fan: Fan { controller: fan_controller, pwm: fan_pwm },
+        fan_tach,
+        outputs,
+        expander: Expander { chip: expander, buttons: expander_buttons, outputs: expander_outputs },
+        dmx_port,
+        fixture_probes: FixtureProbes { bus: fixture_bus, probes: fixture_probes },
+        boot_report: BootReport {
+            reset_reason: Some(reset_reason),
+            device_info: Some(device_info),
+            diagnostics: Some(diagnostics),
+            panic_message,
+        },
+    }
+}
@@ -0,0 +1,70 @@
+use super::hal::gpio::{gpiob::PB3, OpenDrain, Output};
+use cortex_m::{interrupt, peripheral::DWT};
+use embedded_hal_02::digital::v2::{InputPin, OutputPin};
+use panel_core::onewire::OneWire;
+
+// 1-Wire on B3, bit-banged with the pin in open drain mode and an external 4.7k pull-up. Each slot
+// is timed with the cycle counter, which init.rs enables, with interrupts disabled: a slot which
+// runs long reads as the other bit. The longest, 70 us, is short of what anything else notices.
+// https://www.analog.com/en/resources/technical-articles/1wire-communication-through-software.html
+
+pub struct OneWirePin {
+    pin: PB3<Output<OpenDrain>>,
+    cycles_per_us: u32,
+}
+
+impl OneWirePin {
+    pub fn new(mut pin: PB3<Output<OpenDrain>>, sysclk_hz: u32) -> Self {
+        let _ = pin.set_high();
+        Self { pin, cycles_per_us: sysclk_hz / 1_000_000 }
+    }
+
+    fn delay_us(&self, us: u32) {
+        let start = DWT::cycle_count();
+        while DWT::cycle_count().wrapping_sub(start) < us * self.cycles_per_us {}
+    }
+
+    /// Pulls the line low for `low_us`, then releases it and returns its level `sample_us` later,
+    /// padding the slot out to `slot_us`.
+    fn slot(&mut self, low_us: u32, sample_us: u32, slot_us: u32) -> bool {
+        interrupt::free(|_| {
+            let _ = self.pin.set_low();
+            self.delay_us(low_us);
+            let _ = self.pin.set_high();
+            self.delay_us(sample_us);
+            let high = self.pin.is_high().unwrap_or(false);
+            self.delay_us(slot_us - low_us - sample_us);
+            high
+        })
+    }
+}
+
+impl OneWire for OneWirePin {
+    fn reset(&mut self) -> bool {
+        // Nothing can answer if there's no pull-up, or something holds the line low.
+        if self.pin.is_low().unwrap_or(true) {
+            return false;
+        }
+
+        // Devices answer by holding the line low for 60-240 us, starting 15-60 us after the reset
+        // pulse. The pulse itself can run long.
+        let _ = self.pin.set_low();
+        self.delay_us(480);
+        let present = !self.slot(0, 70, 70);
+        self.delay_us(410);
+
+        present
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        if bit {
+            self.slot(6, 0, 70);
+        } else {
+            self.slot(60, 0, 70);
+        }
+    }
+
+    fn read_bit(&mut self) -> bool {
+        self.slot(6, 9, 70)
+    }
+}
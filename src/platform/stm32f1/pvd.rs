@@ -0,0 +1,29 @@
+use super::hal::pac::{EXTI, PWR, RCC};
+
+// The programmable voltage detector watches VDD, and raises the PVD interrupt through EXTI line
+// 16 whenever the supply crosses the threshold in either direction.
+
+/// PLS setting for a 2.9 V threshold, the highest available, to give as much warning as possible.
+const THRESHOLD_2V9: u8 = 0b111;
+
+/// Enables the detector and its interrupt. Call this before the RCC is constrained.
+pub fn enable(rcc: &RCC, pwr: &PWR, exti: &EXTI) {
+    rcc.apb1enr.modify(|_, w| w.pwren().set_bit());
+    pwr.cr.modify(|_, w| unsafe { w.pls().bits(THRESHOLD_2V9) }.pvde().set_bit());
+
+    exti.rtsr.modify(|_, w| w.tr16().set_bit());
+    exti.ftsr.modify(|_, w| w.tr16().set_bit());
+    exti.imr.modify(|_, w| w.mr16().set_bit());
+}
+
+/// Returns true while VDD is below the threshold.
+pub fn is_low() -> bool {
+    let pwr = unsafe { &*PWR::ptr() };
+    pwr.csr.read().pvdo().bit_is_set()
+}
+
+/// Acknowledges the PVD interrupt, which has to be done in its handler.
+pub fn clear_pending() {
+    let exti = unsafe { &*EXTI::ptr() };
+    exti.pr.write(|w| w.pr16().set_bit());
+}
@@ -0,0 +1,56 @@
+use super::hal::pac::RCC;
+
+/// The cause of the most recent reset, as latched by the RCC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum ResetReason {
+    PowerOn,
+    Pin,
+    Software,
+    IndependentWatchdog,
+    WindowWatchdog,
+    LowPower,
+    Unknown,
+}
+
+impl ResetReason {
+    /// Reads the reset flags from the RCC control/status register and clears them, so the next
+    /// boot reports only its own cause. Call this before the RCC is constrained.
+    pub fn read_and_clear(rcc: &RCC) -> Self {
+        let csr = rcc.csr.read();
+
+        // Several flags can be set at once (a pin reset is also asserted by every other reset
+        // source), so check the most specific causes first.
+        let reason = if csr.lpwrrstf().bit_is_set() {
+            ResetReason::LowPower
+        } else if csr.iwdgrstf().bit_is_set() {
+            ResetReason::IndependentWatchdog
+        } else if csr.wwdgrstf().bit_is_set() {
+            ResetReason::WindowWatchdog
+        } else if csr.sftrstf().bit_is_set() {
+            ResetReason::Software
+        } else if csr.porrstf().bit_is_set() {
+            ResetReason::PowerOn
+        } else if csr.pinrstf().bit_is_set() {
+            ResetReason::Pin
+        } else {
+            ResetReason::Unknown
+        };
+
+        rcc.csr.modify(|_, w| w.rmvf().set_bit());
+
+        reason
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResetReason::PowerOn => "reset: power on",
+            ResetReason::Pin => "reset: reset pin",
+            ResetReason::Software => "reset: software",
+            ResetReason::IndependentWatchdog => "reset: watchdog",
+            ResetReason::WindowWatchdog => "reset: window watchdog",
+            ResetReason::LowPower => "reset: low power",
+            ResetReason::Unknown => "reset: unknown",
+        }
+    }
+}
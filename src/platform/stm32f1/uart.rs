@@ -0,0 +1,58 @@
+use super::hal::{
+    pac::USART1,
+    serial::{Rx, Tx},
+};
+use crate::serial::{Error, Transport};
+use embedded_hal_02::serial::{Read, Write};
+use nb::block;
+
+// The protocol over USART1 (A9 TX, A10 RX), for panels built into another device which talks to
+// them over a plain UART instead of USB. The caller enables the receive interrupt, whose task
+// reads each byte as it arrives, as the UART only holds one.
+
+pub struct Uart {
+    tx: Tx<USART1>,
+    rx: Rx<USART1>,
+    connected: bool,
+}
+
+impl Uart {
+    pub fn new(tx: Tx<USART1>, rx: Rx<USART1>) -> Self {
+        Self { tx, rx, connected: false }
+    }
+}
+
+impl Transport for Uart {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut count = 0;
+        while count < buf.len() {
+            match self.rx.read() {
+                Ok(byte) => {
+                    buf[count] = byte;
+                    count += 1;
+                },
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(e)) => return Err(e.into()),
+            }
+        }
+
+        self.connected |= count > 0;
+        Ok(count)
+    }
+
+    /// Nothing interrupts once the UART is ready for the next byte, so this waits for it rather
+    /// than leaving the rest of the report queued. That's never longer than a byte takes to send.
+    fn write(&mut self, bytes: &[u8]) -> nb::Result<usize, Error> {
+        for &byte in bytes {
+            let _ = block!(self.tx.write(byte));
+        }
+
+        Ok(bytes.len())
+    }
+
+    /// There's no way to tell whether anything is listening, so the host counts as connected
+    /// from the first byte it sends.
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
@@ -0,0 +1,37 @@
+use super::hal::{backup_domain::BackupDomain, rtc::Rtc};
+
+// Wall-clock time from the RTC, which runs from the 32.768 kHz crystal in the backup domain and so
+// keeps counting through resets, and through power loss with a battery on VBAT. The host sets it
+// with `Command::SetTime`, after which the panel can follow schedules while the host is away.
+
+/// A backup register which records that the RTC has been set, as it counts from zero otherwise.
+const TIME_SET_REGISTER: usize = 0;
+const TIME_SET_MAGIC: u16 = 0x7153;
+
+pub struct WallClock {
+    rtc: Rtc,
+    backup_domain: BackupDomain,
+}
+
+impl WallClock {
+    pub fn new(rtc: Rtc, backup_domain: BackupDomain) -> Self {
+        Self { rtc, backup_domain }
+    }
+
+    /// Seconds since the Unix epoch, or `None` if the time hasn't been set since the backup
+    /// domain last lost power.
+    pub fn now(&self) -> Option<u32> {
+        if self.backup_domain.read_data_register_low(TIME_SET_REGISTER) != TIME_SET_MAGIC {
+            return None;
+        }
+
+        Some(self.rtc.current_time())
+    }
+
+    pub fn set(&mut self, unix_seconds: u32) {
+        info!("wall clock set to {}", unix_seconds);
+
+        self.rtc.set_time(unix_seconds);
+        self.backup_domain.write_data_register_low(TIME_SET_REGISTER, TIME_SET_MAGIC);
+    }
+}
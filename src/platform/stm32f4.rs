@@ -0,0 +1,34 @@
+pub mod clock_security;
+pub mod device_info;
+pub mod flash;
+pub mod reset_reason;
+
+use cortex_m::interrupt::Nr;
+
+// A skeleton of a port to the STM32F411. It has the items the library uses, written against the
+// registers directly as there's no HAL for it in the firmware yet. The drivers only init.rs uses,
+// and the RTIC app itself, are still to be written, so the binary can't be built for it.
+
+/// The errors from the UART, see `serial::Error::Serial`. There's no UART driver yet.
+#[derive(Debug)]
+pub enum SerialError {}
+
+/// The interrupts the library uses, numbered and named as in the vector table.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    USART1 = 37,
+    OTG_FS = 67,
+}
+
+unsafe impl Nr for Interrupt {
+    fn nr(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// The interrupt of the task which talks to the host.
+#[cfg(not(feature = "uart-transport"))]
+pub const HOST_INTERRUPT: Interrupt = Interrupt::OTG_FS;
+#[cfg(feature = "uart-transport")]
+pub const HOST_INTERRUPT: Interrupt = Interrupt::USART1;
@@ -0,0 +1,47 @@
+use super::Interrupt;
+use crate::platform::registers;
+use core::sync::atomic::{AtomicBool, Ordering};
+use cortex_m::peripheral::{NVIC, SYST};
+use cortex_m_rt::exception;
+
+// As on the STM32F1, a failure of the HSE switches the system clock over to the HSI and raises an
+// NMI, and USB, which can't run without the crystal, is shut down.
+
+const HSI_HZ: u32 = 16_000_000;
+
+const RCC_CR: u32 = 0x4002_3800;
+const RCC_CIR: u32 = 0x4002_380C;
+const CSSON: u32 = 1 << 19;
+const CSSF: u32 = 1 << 7;
+const CSSC: u32 = 1 << 23;
+
+/// OTG_FS_GCCFG, and the bit which powers up the USB transceiver.
+const OTG_FS_GCCFG: u32 = 0x5000_0038;
+const PWRDWN: u32 = 1 << 16;
+
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Enables the clock security system. Call this once the system clock is running from the HSE.
+pub fn enable() {
+    unsafe { registers::modify(RCC_CR, |cr| cr | CSSON) };
+}
+
+/// True once the HSE has failed.
+pub fn has_failed() -> bool {
+    FAILED.load(Ordering::Relaxed)
+}
+
+#[exception]
+fn NonMaskableInt() {
+    if unsafe { registers::read(RCC_CIR) } & CSSF == 0 {
+        return;
+    }
+    unsafe { registers::modify(RCC_CIR, |cir| cir | CSSC) };
+    FAILED.store(true, Ordering::Relaxed);
+
+    // Keep the millisecond tick running at the right rate.
+    unsafe { (*SYST::PTR).rvr.write(HSI_HZ / crate::TICK_HZ - 1) };
+
+    NVIC::mask(Interrupt::OTG_FS);
+    unsafe { registers::modify(OTG_FS_GCCFG, |gccfg| gccfg & !PWRDWN) };
+}
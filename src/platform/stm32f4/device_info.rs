@@ -0,0 +1,39 @@
+use crate::platform::registers;
+
+/// The 96-bit unique ID, factory programmed into the system memory.
+const UNIQUE_ID_ADDRESS: usize = 0x1FFF_7A10;
+
+/// The size of the main flash memory in KiB.
+const FLASH_SIZE_ADDRESS: usize = 0x1FFF_7A22;
+
+/// DBGMCU_IDCODE, with the silicon revision in its top half.
+const DBGMCU_IDCODE: u32 = 0xE004_2000;
+
+/// The electronic signature of this particular chip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceInfo {
+    pub unique_id: [u8; 12],
+    pub flash_size_kb: u16,
+    pub revision: u16,
+}
+
+impl DeviceInfo {
+    pub fn read() -> Self {
+        let unique_id = unique_id();
+        let flash_size_kb = unsafe { core::ptr::read_volatile(FLASH_SIZE_ADDRESS as *const u16) };
+        let revision = (unsafe { registers::read(DBGMCU_IDCODE) } >> 16) as u16;
+
+        Self { unique_id, flash_size_kb, revision }
+    }
+}
+
+/// The 96-bit unique ID on its own.
+pub fn unique_id() -> [u8; 12] {
+    let mut unique_id = [0u8; 12];
+    for (i, byte) in unique_id.iter_mut().enumerate() {
+        // Safe because the signature is always mapped and read-only.
+        *byte = unsafe { core::ptr::read_volatile((UNIQUE_ID_ADDRESS + i) as *const u8) };
+    }
+
+    unique_id
+}
@@ -0,0 +1,172 @@
+use crate::platform::registers;
+use core::ptr;
+
+// Flash on the STM32F4 is erased in sectors rather than pages, of 16 KiB, 64 KiB and 128 KiB.
+// Only the first four sectors, of 16 KiB each, are small enough to stand in for pages, so they're
+// all the firmware erases, and `FLASH_END` is where they end rather than where flash does. A
+// memory.x for this chip has to keep the bootloader in sector 0, the settings and the panic log
+// in sectors 1 to 3, and the firmware slots after them.
+
+/// The size of the sectors which stand in for pages.
+pub const PAGE_SIZE: u32 = 16 * 1024;
+
+/// One past the last of the sectors which stand in for pages.
+pub const FLASH_END: u32 = 0x0801_0000;
+
+const FLASH_START: u32 = 0x0800_0000;
+
+const FLASH_KEYR: u32 = 0x4002_3C04;
+const FLASH_OPTKEYR: u32 = 0x4002_3C08;
+const FLASH_SR: u32 = 0x4002_3C0C;
+const FLASH_CR: u32 = 0x4002_3C10;
+const FLASH_OPTCR: u32 = 0x4002_3C14;
+
+const FLASH_KEY1: u32 = 0x4567_0123;
+const FLASH_KEY2: u32 = 0xCDEF_89AB;
+const OPT_KEY1: u32 = 0x0819_2A3B;
+const OPT_KEY2: u32 = 0x4C5D_6E7F;
+
+const CR_PG: u32 = 1 << 0;
+const CR_SER: u32 = 1 << 1;
+const CR_SNB_SHIFT: u32 = 3;
+const CR_SNB: u32 = 0b1111 << CR_SNB_SHIFT;
+/// Programs 16 bits at a time, which works at any supply voltage.
+const CR_PSIZE_X16: u32 = 0b01 << 8;
+const CR_PSIZE: u32 = 0b11 << 8;
+const CR_STRT: u32 = 1 << 16;
+const CR_LOCK: u32 = 1 << 31;
+const SR_WRPERR: u32 = 1 << 4;
+const SR_PGAERR: u32 = 1 << 5;
+const SR_PGPERR: u32 = 1 << 6;
+const SR_PGSERR: u32 = 1 << 7;
+const SR_BSY: u32 = 1 << 16;
+const OPTCR_OPTSTRT: u32 = 1 << 1;
+const OPTCR_RDP_SHIFT: u32 = 8;
+const OPTCR_RDP: u32 = 0xFF << OPTCR_RDP_SHIFT;
+
+/// The RDP option byte for level 0. 0xCC is level 2, which can never be undone, and anything
+/// else is level 1.
+const RDP_UNPROTECTED: u32 = 0xAA;
+
+/// Why flash wasn't programmed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// PGAERR, PGPERR or PGSERR: the write didn't match the programming size or sequence.
+    Programming,
+    /// WRPERR: the sector is write protected.
+    WriteProtected,
+}
+
+/// Direct access to the flash program/erase controller, as on the STM32F1.
+pub struct RawFlash {
+    _private: (),
+}
+
+impl RawFlash {
+    /// # Safety
+    /// The caller must ensure nothing else is programming or erasing flash concurrently.
+    pub unsafe fn steal() -> Self {
+        if registers::read(FLASH_CR) & CR_LOCK != 0 {
+            registers::write(FLASH_KEYR, FLASH_KEY1);
+            registers::write(FLASH_KEYR, FLASH_KEY2);
+        }
+
+        Self { _private: () }
+    }
+
+    /// Like `steal()`, but returns `None` while another `RawFlash` is in use.
+    pub fn try_steal() -> Option<Self> {
+        if unsafe { registers::read(FLASH_CR) } & CR_LOCK == 0 {
+            return None;
+        }
+        Some(unsafe { Self::steal() })
+    }
+
+    /// Erases the sector starting at `address`, which has to be one of the first four.
+    pub fn erase_page(&mut self, address: u32) {
+        debug!("erasing flash sector at {:#x}", address);
+        debug_assert!(address < FLASH_END);
+        let sector = (address - FLASH_START) / PAGE_SIZE;
+
+        self.wait_until_ready();
+        unsafe {
+            registers::modify(FLASH_CR, |cr| cr & !CR_SNB | CR_SER | sector << CR_SNB_SHIFT);
+            registers::modify(FLASH_CR, |cr| cr | CR_STRT);
+        }
+        self.wait_until_ready();
+        unsafe { registers::modify(FLASH_CR, |cr| cr & !CR_SER) };
+    }
+
+    /// Programs 16 bits, which have to be erased.
+    pub fn program_half_word(&mut self, address: u32, value: u16) -> Result<(), Error> {
+        self.wait_until_ready();
+        unsafe {
+            registers::modify(FLASH_CR, |cr| cr & !CR_PSIZE | CR_PSIZE_X16 | CR_PG);
+            ptr::write_volatile(address as *mut u16, value);
+        }
+        self.wait_until_ready();
+        unsafe { registers::modify(FLASH_CR, |cr| cr & !CR_PG) };
+
+        let errors = SR_WRPERR | SR_PGAERR | SR_PGPERR | SR_PGSERR;
+        let sr = unsafe { registers::read(FLASH_SR) } & errors;
+        // The flags are cleared by writing ones to them.
+        unsafe { registers::write(FLASH_SR, sr) };
+        if sr & SR_WRPERR != 0 {
+            Err(Error::WriteProtected)
+        } else if sr != 0 {
+            Err(Error::Programming)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Programs `bytes` starting at `address`, padding an odd trailing byte with 0xFF.
+    pub fn program(&mut self, address: u32, bytes: &[u8]) -> Result<(), Error> {
+        for (i, chunk) in bytes.chunks(2).enumerate() {
+            let low = chunk[0] as u16;
+            let high = chunk.get(1).copied().unwrap_or(0xFF) as u16;
+            self.program_half_word(address + i as u32 * 2, low | (high << 8))?;
+        }
+        Ok(())
+    }
+
+    /// Enables level 1 readout protection, which takes effect after the next reset, as on the
+    /// STM32F1. The other option bytes are left as they are.
+    pub fn enable_readout_protection(&mut self) {
+        warn!("enabling readout protection");
+
+        unsafe {
+            registers::write(FLASH_OPTKEYR, OPT_KEY1);
+            registers::write(FLASH_OPTKEYR, OPT_KEY2);
+        }
+
+        self.wait_until_ready();
+        let rdp = (!RDP_UNPROTECTED & 0xFF) << OPTCR_RDP_SHIFT;
+        unsafe {
+            registers::modify(FLASH_OPTCR, |optcr| optcr & !OPTCR_RDP | rdp);
+            registers::modify(FLASH_OPTCR, |optcr| optcr | OPTCR_OPTSTRT);
+        }
+        self.wait_until_ready();
+    }
+
+    fn wait_until_ready(&self) {
+        while unsafe { registers::read(FLASH_SR) } & SR_BSY != 0 {}
+    }
+}
+
+impl Drop for RawFlash {
+    fn drop(&mut self) {
+        unsafe { registers::modify(FLASH_CR, |cr| cr | CR_LOCK) };
+    }
+}
+
+/// Reads `len` bytes of flash starting at `address`.
+pub fn read(address: u32, len: usize) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(address as *const u8, len) }
+}
+
+/// True if the option bytes loaded at the last reset enable readout protection.
+pub fn readout_protection_enabled() -> bool {
+    let optcr = unsafe { registers::read(FLASH_OPTCR) };
+    (optcr & OPTCR_RDP) >> OPTCR_RDP_SHIFT != RDP_UNPROTECTED
+}
@@ -0,0 +1,67 @@
+use crate::platform::registers;
+
+/// RCC_CSR, which latches the cause of a reset.
+const RCC_CSR: u32 = 0x4002_3874;
+
+const RMVF: u32 = 1 << 24;
+const BORRSTF: u32 = 1 << 25;
+const PINRSTF: u32 = 1 << 26;
+const PORRSTF: u32 = 1 << 27;
+const SFTRSTF: u32 = 1 << 28;
+const IWDGRSTF: u32 = 1 << 29;
+const WWDGRSTF: u32 = 1 << 30;
+const LPWRRSTF: u32 = 1 << 31;
+
+/// The cause of the most recent reset, as latched by the RCC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum ResetReason {
+    PowerOn,
+    Pin,
+    Software,
+    IndependentWatchdog,
+    WindowWatchdog,
+    LowPower,
+    Unknown,
+}
+
+impl ResetReason {
+    /// Reads the reset flags and clears them, so the next boot reports only its own cause.
+    pub fn read_and_clear() -> Self {
+        let csr = unsafe { registers::read(RCC_CSR) };
+
+        // As on the STM32F1, check the most specific causes first. A brownout counts as the
+        // supply coming back on.
+        let reason = if csr & LPWRRSTF != 0 {
+            ResetReason::LowPower
+        } else if csr & IWDGRSTF != 0 {
+            ResetReason::IndependentWatchdog
+        } else if csr & WWDGRSTF != 0 {
+            ResetReason::WindowWatchdog
+        } else if csr & SFTRSTF != 0 {
+            ResetReason::Software
+        } else if csr & (PORRSTF | BORRSTF) != 0 {
+            ResetReason::PowerOn
+        } else if csr & PINRSTF != 0 {
+            ResetReason::Pin
+        } else {
+            ResetReason::Unknown
+        };
+
+        unsafe { registers::modify(RCC_CSR, |csr| csr | RMVF) };
+
+        reason
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResetReason::PowerOn => "reset: power on",
+            ResetReason::Pin => "reset: reset pin",
+            ResetReason::Software => "reset: software",
+            ResetReason::IndependentWatchdog => "reset: watchdog",
+            ResetReason::WindowWatchdog => "reset: window watchdog",
+            ResetReason::LowPower => "reset: low power",
+            ResetReason::Unknown => "reset: unknown",
+        }
+    }
+}
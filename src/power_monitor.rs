@@ -0,0 +1,74 @@
+use embedded_hal::i2c::I2c;
+
+// Driver for an INA219 or INA226 current monitor on the LED strip's 5V rail, with its address
+// pins tied low. Both power up measuring the shunt and bus voltages continuously, which is all
+// that's needed: the current is worked out from the shunt voltage, rather than setting up their
+// calibration registers. It shares the I2C bus with the other devices on it.
+// https://www.ti.com/lit/ds/symlink/ina219.pdf
+// https://www.ti.com/lit/ds/symlink/ina226.pdf
+
+const ADDRESS: u8 = 0x40;
+const CONFIGURATION: u8 = 0x00;
+const SHUNT_VOLTAGE: u8 = 0x01;
+const BUS_VOLTAGE: u8 = 0x02;
+/// Only the INA226 has this, which reads "TI".
+const MANUFACTURER_ID: u8 = 0xfe;
+const TEXAS_INSTRUMENTS: u16 = 0x5449;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chip {
+    Ina219,
+    Ina226,
+}
+
+/// A measurement of the rail.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RailReading {
+    pub millivolts: u16,
+    /// Negative current, which would only flow back through a miswired shunt, reads as 0.
+    pub milliamps: u16,
+}
+
+pub struct PowerMonitor {
+    chip: Chip,
+    shunt_milliohms: u16,
+}
+
+impl PowerMonitor {
+    /// Finds out which chip is fitted. Fails if there's no monitor on the bus.
+    pub fn new<I: I2c>(i2c: &mut I, shunt_milliohms: u16) -> Result<Self, I::Error> {
+        read_register(i2c, CONFIGURATION)?;
+        let chip = match read_register(i2c, MANUFACTURER_ID) {
+            Ok(TEXAS_INSTRUMENTS) => Chip::Ina226,
+            _ => Chip::Ina219,
+        };
+
+        Ok(Self { chip, shunt_milliohms })
+    }
+
+    pub fn read<I: I2c>(&mut self, i2c: &mut I) -> Result<RailReading, I::Error> {
+        let shunt = read_register(i2c, SHUNT_VOLTAGE)? as i16 as i32;
+        let bus = read_register(i2c, BUS_VOLTAGE)? as u32;
+
+        let (shunt_microvolts, millivolts) = match self.chip {
+            // 10 uV per count, and 4 mV per count above the three status bits.
+            Chip::Ina219 => (shunt * 10, (bus >> 3) * 4),
+            // 2.5 uV and 1.25 mV per count.
+            Chip::Ina226 => (shunt * 5 / 2, bus * 5 / 4),
+        };
+        let milliamps = shunt_microvolts.max(0) as u32 / self.shunt_milliohms.max(1) as u32;
+
+        Ok(RailReading {
+            millivolts: millivolts.min(u16::MAX as u32) as u16,
+            milliamps: milliamps.min(u16::MAX as u32) as u16,
+        })
+    }
+}
+
+/// Registers are 16 bits, most significant byte first.
+fn read_register<I: I2c>(i2c: &mut I, register: u8) -> Result<u16, I::Error> {
+    let mut raw = [0u8; 2];
+    i2c.write_read(ADDRESS, &[register], &mut raw)?;
+
+    Ok(u16::from_be_bytes(raw))
+}
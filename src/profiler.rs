@@ -0,0 +1,85 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::DWT;
+
+// Measures how long named sections of the firmware take, in cycles of the DWT cycle counter, to
+// find out what causes latency spikes. Each section should only be measured from one task, but
+// its statistics can be taken from any. Without the profiler feature, nothing is measured.
+
+/// The sections which are measured. The number is what's reported to the host.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum Section {
+    /// Servicing the USB device, in either of the USB interrupts.
+    UsbPoll = 0,
+    /// Sending a frame to the LED strip.
+    LedWrite = 1,
+    /// Sampling the button and the dial.
+    InputPoll = 2,
+    /// Parsing the bytes from one read from the host into commands, which is part of `UsbPoll`.
+    CommandParse = 3,
+}
+
+impl Section {
+    pub const ALL: [Section; 4] =
+        [Section::UsbPoll, Section::LedWrite, Section::InputPoll, Section::CommandParse];
+}
+
+/// How long a section took, since the statistics were last taken.
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub max_cycles: u32,
+    pub mean_cycles: u32,
+    pub count: u32,
+}
+
+struct Counters {
+    max_cycles: AtomicU32,
+    /// Saturates rather than wraps, after about 90 seconds spent in the section at 48 MHz.
+    total_cycles: AtomicU32,
+    count: AtomicU32,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            max_cycles: AtomicU32::new(0),
+            total_cycles: AtomicU32::new(0),
+            count: AtomicU32::new(0),
+        }
+    }
+}
+
+static COUNTERS: [Counters; Section::ALL.len()] =
+    [Counters::new(), Counters::new(), Counters::new(), Counters::new()];
+
+/// Runs `f`, counting the cycles it takes towards `section`. This includes any time spent in
+/// higher priority tasks which preempt it.
+pub fn measure<R>(section: Section, f: impl FnOnce() -> R) -> R {
+    if !cfg!(feature = "profiler") {
+        return f();
+    }
+
+    let start = DWT::cycle_count();
+    let result = f();
+    let cycles = DWT::cycle_count().wrapping_sub(start);
+
+    let counters = &COUNTERS[section as usize];
+    counters.max_cycles.fetch_max(cycles, Ordering::Relaxed);
+    let _ = counters.total_cycles.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+        Some(total.saturating_add(cycles))
+    });
+    counters.count.fetch_add(1, Ordering::Relaxed);
+
+    result
+}
+
+/// Returns the statistics for `section` and starts them over.
+pub fn take(section: Section) -> Stats {
+    let counters = &COUNTERS[section as usize];
+    let max_cycles = counters.max_cycles.swap(0, Ordering::Relaxed);
+    let total_cycles = counters.total_cycles.swap(0, Ordering::Relaxed);
+    let count = counters.count.swap(0, Ordering::Relaxed);
+
+    let mean_cycles = total_cycles.checked_div(count).unwrap_or(0);
+    Stats { max_cycles, mean_cycles, count }
+}
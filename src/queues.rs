@@ -0,0 +1,103 @@
+use crate::{
+    board::BOARD,
+    error::Error,
+    platform::HOST_INTERRUPT,
+    serial::{self, Command, Report},
+};
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use heapless::spsc::Queue;
+use panel_core::{
+    input::InputEvent,
+    subscriptions::{Category, Subscriptions},
+};
+
+// Bounded queues between the tasks, so that sampling the inputs, talking to the host and
+// applying its commands each happen in their own task. Every queue has a policy for when it's
+// full, rather than assuming that everything gets handled in one go. Their sizes depend on the
+// board, see board.rs. A queue holds one element less than its size.
+
+/// Commands from the host, waiting to be applied. When full, new commands are dropped and the
+/// host is told, as it's sending faster than the panel can keep up.
+pub type CommandQueue = Queue<Command, { BOARD.command_queue_len }>;
+
+/// Reports waiting to be sent to the host. When full, new reports are dropped, except for dial
+/// movement, which is put back into the counter until there's room. The host is told how many
+/// were dropped once there is, see usb.rs.
+pub type ReportQueue = Queue<Report, { BOARD.report_queue_len }>;
+
+/// Reports dropped since the host was last told about them.
+static DROPPED_REPORTS: AtomicU32 = AtomicU32::new(0);
+
+/// The categories of reports the host is subscribed to, as `Subscriptions::bits`. Reports it
+/// isn't subscribed to are never queued.
+static SUBSCRIPTIONS: AtomicU8 = AtomicU8::new(Subscriptions::ALL.bits());
+
+/// Events from sampling the inputs, waiting to be reported. When full, button events are
+/// dropped, and dial movement is put back into the counter.
+pub type InputQueue = Queue<InputEvent, 8>;
+
+/// Queues a report and wakes up the task which talks to the host to send it. Gives the report
+/// back if the queue is full. Reports the host isn't subscribed to are dropped as if they'd been
+/// sent.
+pub fn send_report(reports: &mut ReportQueue, report: Report) -> Result<(), Report> {
+    if !subscriptions().wants(&report) {
+        return Ok(());
+    }
+
+    let result = reports.enqueue(report);
+    rtic::pend(HOST_INTERRUPT);
+
+    if let Err(report) = &result {
+        warn!("report queue full");
+        if !matches!(report, Report::DialValue { .. } | Report::DialTurned { .. }) {
+            DROPPED_REPORTS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    result
+}
+
+/// Wakes up the task which talks to the host, so that it reads any commands it's holding on to
+/// once there's room for them in the queue.
+pub fn wake_host_task() {
+    rtic::pend(HOST_INTERRUPT);
+}
+
+pub fn subscriptions() -> Subscriptions {
+    Subscriptions::from_bits(SUBSCRIPTIONS.load(Ordering::Relaxed))
+}
+
+/// Subscribes the host to `category` or unsubscribes it, see `Command::Subscribe`. The bit is
+/// set or cleared in one atomic step, so a change made by another task at the same time isn't
+/// lost.
+pub fn subscribe(category: Category, subscribed: bool) {
+    let bit = 1 << category as u8;
+    if subscribed {
+        SUBSCRIPTIONS.fetch_or(bit, Ordering::Relaxed);
+    } else {
+        SUBSCRIPTIONS.fetch_and(!bit, Ordering::Relaxed);
+    }
+}
+
+/// Subscribes the next host to everything, as hosts which don't know about subscriptions expect.
+pub fn subscribe_all() {
+    SUBSCRIPTIONS.store(Subscriptions::ALL.bits(), Ordering::Relaxed);
+}
+
+/// Returns how many reports have been dropped since the last call.
+pub fn take_dropped_reports() -> u32 {
+    DROPPED_REPORTS.swap(0, Ordering::Relaxed)
+}
+
+/// Queues a debug message, split across as many reports as it needs.
+pub fn send_debug(reports: &mut ReportQueue, message: &str) {
+    for report in serial::debug_reports(message) {
+        let _ = send_report(reports, report);
+    }
+}
+
+/// Logs an error and tells the host about it, as far as there's room to.
+pub fn report_error(reports: &mut ReportQueue, error: Error) {
+    error!("{}", error.as_str());
+    send_debug(reports, error.as_str());
+}
@@ -0,0 +1,127 @@
+#[cfg(feature = "hid-transport")]
+use crate::hid::{self, UsbHid};
+#[cfg(feature = "uart-transport")]
+use crate::platform::uart::Uart;
+#[cfg(not(feature = "uart-transport"))]
+use crate::platform::UsbBusType;
+#[cfg(not(any(feature = "uart-transport", feature = "hid-transport")))]
+use crate::serial::UsbSerial;
+use crate::{
+    board,
+    mcp23017::{ExpanderPin, Mcp23017},
+    outputs::Outputs,
+    overhead_light::OverheadLight,
+    platform::{
+        eh1::Eh1,
+        fan::FanPwm,
+        hal::{
+            adc::Adc,
+            gpio::{
+                gpioa::{PA0, PA1, PA15, PA4, PA5},
+                gpiob::{PB10, PB11},
+                Alternate, Analog, Floating, Input, OpenDrain, PullDown,
+            },
+            i2c::BlockingI2c,
+            pac::{ADC1, I2C2, SPI1, SPI2, TIM2, TIM3, TIM4},
+            pwm::{PwmChannel, C1, C2, C3, C4},
+            qei::Qei,
+            spi::{NoMiso, NoSck, Spi, Spi1NoRemap, Spi2NoRemap},
+            timer::Tim2NoRemap,
+        },
+        onewire::OneWirePin,
+    },
+    rgb_led::{LedPixels, LedStrip},
+    serial::SerialProtocol,
+    ssd1306::Ssd1306,
+    telemetry::MAX_FIXTURE_PROBES,
+};
+use panel_core::{
+    button::Button, counter::Counter, display::Frame, door::DoorSensor, ds18b20::Probes,
+    fan::FanController, ir::IrDecoder,
+};
+
+// The types of the hardware the RTIC tasks share, which platform::init brings up and the rest of
+// the library works on. Parts which are only ever used together are grouped into one resource.
+
+/// Buttons on port A of the GPIO expander, and outputs on port B, see mcp23017.rs.
+pub const EXPANDER_BUTTONS: usize = 8;
+pub const EXPANDER_OUTPUTS: usize = 8;
+pub const EXPANDER_OUTPUT_MASK: u16 = 0xff00;
+
+pub type Light<TIM> = OverheadLight<
+    Eh1<PwmChannel<TIM, C1>>,
+    Eh1<PwmChannel<TIM, C2>>,
+    Eh1<PwmChannel<TIM, C3>>,
+    Eh1<PwmChannel<TIM, C4>>,
+>;
+pub type FrontLight = Light<TIM3>;
+pub type BackLight = Light<TIM4>;
+pub type EncoderButton = Button<Eh1<board::ButtonPin>>;
+pub type EncoderCounter = Counter<
+    Eh1<Qei<TIM2, Tim2NoRemap, (PA0<Input<Floating>>, PA1<Input<Floating>>)>>,
+    Eh1<board::EncoderIndexPin>,
+>;
+pub type Strip = LedStrip<
+    Eh1<Spi<SPI1, Spi1NoRemap, (NoSck, NoMiso, board::StripDataPin), u8>>,
+    { board::BOARD.led_count },
+>;
+pub type Pixels = LedPixels<{ board::BOARD.led_count }>;
+pub type Door = DoorSensor<Eh1<board::EncoderIndexPin>>;
+pub type I2cBus = Eh1<BlockingI2c<I2C2, (PB10<Alternate<OpenDrain>>, PB11<Alternate<OpenDrain>>)>>;
+/// Only set up with the encoder-ring feature.
+pub type Ring = LedStrip<
+    Eh1<Spi<SPI2, Spi2NoRemap, (NoSck, NoMiso, board::RingDataPin), u8>>,
+    { board::BOARD.encoder_ring_len },
+>;
+/// The sliders, or the joystick's axes, or the microphone on the second.
+pub type SliderPins = (PA4<Analog>, PA5<Analog>);
+/// High while a USB cable is plugged in, on boards which sense VBUS.
+pub type VbusPin = PA15<Input<PullDown>>;
+pub type ExpanderButton = Button<ExpanderPin>;
+pub type ExpanderOutputs = Outputs<ExpanderPin, EXPANDER_OUTPUTS>;
+pub type AuxOutputs = Outputs<Eh1<board::OutputPin>, { board::OUTPUT_COUNT }>;
+#[cfg(not(any(feature = "uart-transport", feature = "hid-transport")))]
+pub type Protocol = SerialProtocol<UsbSerial<'static, UsbBusType>, { board::BOARD.usb_rx_len }>;
+#[cfg(feature = "hid-transport")]
+pub type Protocol = SerialProtocol<UsbHid<'static, UsbBusType>, { hid::PAYLOAD_LEN }>;
+#[cfg(feature = "uart-transport")]
+pub type Protocol = SerialProtocol<Uart, { board::BOARD.usb_rx_len }>;
+
+/// The ADC, and the pins shared by the sliders, the joystick and the microphone.
+pub struct AnalogInputs {
+    pub adc: Adc<ADC1>,
+    pub pins: SliderPins,
+}
+
+/// Both `None` if there's no fan on the board, or A9 is used by the UART or DMX.
+pub struct Fan {
+    pub controller: Option<FanController>,
+    pub pwm: Option<FanPwm>,
+}
+
+/// Temperature probes on the light fixtures, and the 1-Wire bus they share. Empty if there are
+/// no probes on the bus.
+pub struct FixtureProbes {
+    pub bus: OneWirePin,
+    pub probes: Probes<MAX_FIXTURE_PROBES>,
+}
+
+pub struct IrReceiver {
+    pub pin: board::IrReceiverPin,
+    pub decoder: IrDecoder,
+}
+
+/// The GPIO expander, `None` if there's no expander on the board, and the buttons and outputs on
+/// its pins.
+pub struct Expander {
+    pub chip: Option<Mcp23017>,
+    pub buttons: [ExpanderButton; EXPANDER_BUTTONS],
+    pub outputs: ExpanderOutputs,
+}
+
+pub struct StatusDisplay {
+    /// `None` if there's no display on the board.
+    pub driver: Option<Ssd1306>,
+    /// What the display shows, so that only the pages which change are sent to it.
+    pub shown: Frame,
+}
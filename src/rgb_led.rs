@@ -1,17 +1,34 @@
-use embedded_hal::spi::FullDuplex;
-use nb::block;
-use stm32f1xx_hal::time::{Instant, MonoTimer};
+use embedded_hal::spi::SpiBus;
+use panel_core::{
+    fraction::Fraction,
+    tick::{Duration, Instant},
+};
 
 // Reference implementation:
 // https://github.com/smart-leds-rs/ws2812-spi-rs/blob/fac281eb57b5f72c48e368682645e3b0bd5b4b83/src/lib.rs
-
-const LED_COUNT: usize = 2;
-const PI: f32 = 3.1415927410e+00;
-
-pub struct LedStrip<F: FullDuplex<u8>> {
+//
+// The strip latches a frame once its data line has been low for more than 50 us. Every encoded
+// bit ends low, and the line stays there between frames, so a frame that starts long enough after
+// the last one needs nothing more. One that doesn't is held back by sending zeros first.
+// https://cdn-shop.adafruit.com/datasheets/WS2812B.pdf
+
+/// Zeros to send for the reset. At the 2.25 MHz SPI clock each byte takes 3.6 us, so this holds
+/// the line low for about 71 us.
+const RESET_BYTES: usize = 20;
+
+/// The tick only counts whole milliseconds, so one tick might have been a few microseconds.
+/// After two, at least a full millisecond has passed.
+const SETTLED: Duration = Duration::from_ms(2);
+
+/// A strip of `N` WS2812B LEDs.
+pub struct LedStrip<F: SpiBus<u8>, const N: usize> {
     spi_bus: F,
+    /// When the last byte of the last frame went out, or `None` if the line might not have been
+    /// low since then.
+    last_frame: Option<Instant>,
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub struct Rgb {
     r: u8,
     g: u8,
@@ -19,115 +36,150 @@ pub struct Rgb {
 }
 
 impl Rgb {
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
         Self { r, g, b }
     }
-}
 
-impl<F: FullDuplex<u8>> LedStrip<F> {
-    pub fn new(spi_bus: F) -> Self {
-        Self { spi_bus }
+    pub fn scaled(self, intensity: Fraction) -> Self {
+        Self::new(intensity.of_u8(self.r), intensity.of_u8(self.g), intensity.of_u8(self.b))
     }
+}
 
-    pub fn set_all(&mut self, rgb: Rgb) {
-        self.flush();
-
-        for _led in 0..LED_COUNT {
-            self.write_byte(rgb.g);
-            self.write_byte(rgb.r);
-            self.write_byte(rgb.b);
-        }
+/// The LED strip color and effect most recently requested by the host.
+#[derive(Clone, Copy)]
+pub struct LedSettings {
+    pub color: (u8, u8, u8),
+    pub pulse: bool,
+}
 
-        self.flush();
+impl LedSettings {
+    /// The color to show, dimmed by `intensity`.
+    pub fn frame(&self, intensity: Fraction) -> Rgb {
+        let (r, g, b) = self.color;
+        Rgb::new(r, g, b).scaled(intensity)
     }
+}
 
-    #[allow(unused)]
-    pub fn set_colors(&mut self, rgb_data: &[Rgb; LED_COUNT]) {
-        self.flush();
-
-        for led in rgb_data {
-            self.write_byte(led.g);
-            self.write_byte(led.r);
-            self.write_byte(led.b);
-        }
+/// A color for each of `N` LEDs, set one at a time by the host with `Command::LedAt`. Once
+/// they're committed the strip shows them instead of the single color of `LedSettings`.
+pub struct LedPixels<const N: usize> {
+    /// What the host has set, including any since the last commit.
+    next: [Rgb; N],
+    /// What the strip shows, if the host has committed any.
+    committed: Option<[Rgb; N]>,
+}
 
-        self.flush();
+impl<const N: usize> LedPixels<N> {
+    pub const fn new() -> Self {
+        Self { next: [Rgb::new(0, 0, 0); N], committed: None }
     }
 
-    fn write_byte(&mut self, data: u8) {
-        let mut data = data;
-        let patterns = [0b1000_1000, 0b1000_1110, 0b11101000, 0b11101110];
+    /// Returns false if `index` is past the end of the strip.
+    pub fn set(&mut self, index: usize, rgb: Rgb) -> bool {
+        self.next.get_mut(index).map(|led| *led = rgb).is_some()
+    }
 
-        for _ in 0..4 {
-            let bits = (data & 0b1100_0000) >> 6;
-            let _ = block!({
-                let _ = self.spi_bus.send(patterns[bits as usize]);
-                self.spi_bus.read()
-            });
+    /// Shows everything set so far, all at once.
+    pub fn commit(&mut self) {
+        self.committed = Some(self.next);
+    }
 
-            data <<= 2;
+    /// Shows a frame streamed by the host straight away, from `colors` with three bytes to an
+    /// LED, in the order red, green, blue. LEDs past the end of it stay as they were.
+    pub fn show(&mut self, colors: &[u8]) {
+        for (led, rgb) in self.next.iter_mut().zip(colors.chunks_exact(3)) {
+            *led = Rgb::new(rgb[0], rgb[1], rgb[2]);
         }
+        self.commit();
     }
 
-    fn flush(&mut self) {
-        for _ in 0..20 {
-            let _ = block!({
-                let _ = self.spi_bus.send(0).map_err(|_| ());
-                self.spi_bus.read()
-            });
+    /// Goes back to the single color.
+    pub fn clear(&mut self) {
+        self.committed = None;
+    }
+
+    /// The colors to show, dimmed by `intensity`, if the host has committed any.
+    pub fn frame(&self, intensity: Fraction) -> Option<[Rgb; N]> {
+        let mut frame = self.committed?;
+        for led in &mut frame {
+            *led = led.scaled(intensity);
         }
+        Some(frame)
     }
 }
 
-/// U64Instant::elapsed() tries to correct the u32 overflow of the underlying Instant. It is
-/// supposed to be accurate as long as the function is called frequently enough i.e. at least
-/// once per 1 minute 29 seconds.
-struct U64Instant {
-    elapsed: u64,
-    last_elapsed_u32: u32,
-    instant: Instant,
+impl<const N: usize> Default for LedPixels<N> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl From<Instant> for U64Instant {
-    fn from(instant: Instant) -> Self {
-        let elapsed = instant.elapsed();
+impl<F: SpiBus<u8>, const N: usize> LedStrip<F, N> {
+    pub fn new(spi_bus: F) -> Self {
+        Self { spi_bus, last_frame: None }
+    }
 
-        Self { elapsed: elapsed as u64, last_elapsed_u32: elapsed, instant }
+    pub fn set_all(&mut self, rgb: Rgb) {
+        let _ = self.try_set_all(rgb);
     }
-}
 
-impl U64Instant {
-    fn elapsed(&mut self) -> u64 {
-        let elapsed_u32 = self.instant.elapsed();
-        let mut diff = elapsed_u32 as i64 - self.last_elapsed_u32 as i64;
-        if diff < 0 {
-            diff += u32::MAX as i64 + 1;
+    /// Like `set_all()`, but returns any error from the SPI bus. The strip can't talk back, so
+    /// this can't tell whether the LEDs are actually connected.
+    pub fn try_set_all(&mut self, rgb: Rgb) -> Result<(), F::Error> {
+        self.reset()?;
+
+        for _led in 0..N {
+            self.write_byte(rgb.g)?;
+            self.write_byte(rgb.r)?;
+            self.write_byte(rgb.b)?;
         }
 
-        self.last_elapsed_u32 = elapsed_u32;
-        self.elapsed += diff as u64;
-        self.elapsed
+        self.finish()
     }
-}
 
-pub struct Pulser {
-    instant: U64Instant,
-    interval_ticks: f32,
-}
+    pub fn set_colors(&mut self, rgb_data: &[Rgb; N]) {
+        let _ = self.reset();
+
+        for led in rgb_data {
+            let _ = self.write_byte(led.g);
+            let _ = self.write_byte(led.r);
+            let _ = self.write_byte(led.b);
+        }
+
+        let _ = self.finish();
+    }
+
+    fn write_byte(&mut self, data: u8) -> Result<(), F::Error> {
+        let mut data = data;
+        let patterns = [0b1000_1000, 0b1000_1110, 0b11101000, 0b11101110];
+        let mut encoded = [0u8; 4];
 
-impl Pulser {
-    pub fn new(interval_ms: u32, timer: &MonoTimer) -> Self {
-        let instant = timer.now().into();
-        let interval_ticks = timer.frequency().0 as f32 * (interval_ms as f32 / 1000.0);
+        for byte in &mut encoded {
+            let bits = (data & 0b1100_0000) >> 6;
+            *byte = patterns[bits as usize];
 
-        Self { instant, interval_ticks }
+            data <<= 2;
+        }
+
+        self.spi_bus.write(&encoded)
     }
 
-    pub fn intensity(&mut self) -> f32 {
-        let intervals = self.instant.elapsed() as f32 / self.interval_ticks;
-        let pulse = (libm::sinf(intervals) + 1.0) * 0.5;
-        let skip_one = if libm::sinf((intervals + PI / 2.0) / 2.0) >= 0.0 { 1.0 } else { 0.0 };
+    /// Makes sure the strip has latched the last frame before the next one starts.
+    fn reset(&mut self) -> Result<(), F::Error> {
+        // Until the frame is finished, a failed write could leave the line anywhere.
+        let settled = self.last_frame.take().is_some_and(|at| at.elapsed() >= SETTLED);
+        if settled {
+            return Ok(());
+        }
+
+        self.spi_bus.write(&[0u8; RESET_BYTES])
+    }
 
-        pulse * skip_one
+    /// Waits for the frame to be clocked out, so that the gap after it is timed from its last
+    /// bit rather than from when it went into the SPI peripheral.
+    fn finish(&mut self) -> Result<(), F::Error> {
+        self.spi_bus.flush()?;
+        self.last_frame = Some(Instant::now());
+        Ok(())
     }
 }
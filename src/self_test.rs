@@ -0,0 +1,96 @@
+use crate::{
+    overhead_light::SELF_TEST_STEPS,
+    platform::hal::watchdog::IndependentWatchdog,
+    queues::{self, ReportQueue},
+    resources::{BackLight, EncoderButton, EncoderCounter, FrontLight, Strip},
+    rgb_led::Rgb,
+    serial::Report,
+    SYSCLK_HZ,
+};
+use cortex_m::asm::delay;
+use embedded_hal_02::watchdog::Watchdog;
+use rtic::Mutex;
+
+// The end-of-line manufacturing test, see Command::SelfTest, which reports which parts passed.
+// The dial has to be left alone and the button released while it runs.
+
+/// How long each step of the self-test is held, so that it can be checked by eye or camera.
+pub const SELF_TEST_STEP_MS: u32 = 50;
+
+/// What the self-test exercises or checks.
+pub struct SelfTest<'a, R, F, B, S, T> {
+    pub reports: R,
+    pub front_light: F,
+    pub back_light: B,
+    pub led_strip: S,
+    /// The color the frame timer shows on the strip in place of its own, see lights.rs.
+    pub strip_test: T,
+    pub counter: &'a mut EncoderCounter,
+    pub encoder_button: &'a EncoderButton,
+    pub watchdog: &'a mut IndependentWatchdog,
+}
+
+impl<R, F, B, S, T> SelfTest<'_, R, F, B, S, T>
+where
+    R: Mutex<T = ReportQueue>,
+    F: Mutex<T = FrontLight>,
+    B: Mutex<T = BackLight>,
+    S: Mutex<T = Strip>,
+    T: Mutex<T = Option<Rgb>>,
+{
+    pub fn run(mut self) {
+        info!("running self-test");
+
+        let counter = self.counter;
+        let watchdog = self.watchdog;
+
+        let start_position = counter.position();
+        let mut hold = || {
+            delay(SYSCLK_HZ / 1000 * SELF_TEST_STEP_MS);
+            watchdog.feed();
+        };
+
+        // Each step only locks what it sets for as long as it takes to set it, and is held with
+        // nothing locked, so that the USB interrupt and the frame timer keep running.
+        let mut pwm = true;
+        for step in 0..SELF_TEST_STEPS {
+            pwm &= self.front_light.lock(|light| light.self_test_step(step));
+            hold();
+        }
+        self.front_light.lock(|light| light.end_self_test());
+        for step in 0..SELF_TEST_STEPS {
+            pwm &= self.back_light.lock(|light| light.self_test_step(step));
+            hold();
+        }
+        self.back_light.lock(|light| light.end_self_test());
+
+        // The frame timer keeps showing each test color until the next, and then goes back to
+        // the strip's own colors.
+        let mut strip = true;
+        for &(r, g, b) in &[(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)] {
+            let color = Rgb::new(r, g, b);
+            self.strip_test.lock(|test| *test = Some(color));
+            strip &= self.led_strip.lock(|led_strip| led_strip.try_set_all(color).is_ok());
+            hold();
+        }
+        self.strip_test.lock(|test| *test = None);
+
+        // Any movement while the dial is untouched means noise on the encoder inputs.
+        let counter = match counter.poll() {
+            Ok(diff) => {
+                if let Some(diff) = diff {
+                    counter.defer(diff);
+                }
+                counter.position() == start_position
+            },
+            Err(_) => false,
+        };
+
+        // The pull-up holds the pin high while the button is released.
+        let button = !self.encoder_button.is_pressed();
+
+        info!("self-test: pwm {}, strip {}, counter {}, button {}", pwm, strip, counter, button);
+        let report = Report::SelfTest { pwm, strip, counter, button };
+        let _ = self.reports.lock(|reports| queues::send_report(reports, report));
+    }
+}
@@ -1,28 +1,39 @@
-use stm32f1xx_hal as hal;
-
-use hal::{
-    serial::{self},
-    usb::{Peripheral, UsbBus},
+use crate::{
+    hid::InputHid,
+    platform::{clock_security, SerialError},
+    profiler::{self, Section},
+};
+use core::fmt::Write;
+use heapless::{String, Vec};
+use panel_core::{
+    ack::ACK_PROTOCOL_VERSION,
+    command_parser::{CommandParser, Commands, Parsed},
+    framing::{self, MAX_FRAME_LEN, MAX_PAYLOAD_LEN, SEQUENCE_PROTOCOL_VERSION},
+    input::InputEvent,
+    tick::{Duration, Instant},
+};
+use panel_protocol::{ArrayString, MAX_COMMAND_QUEUE_LEN};
+pub use panel_protocol::{Command, Report};
+use usb_device::{
+    bus::UsbBus,
+    device::{UsbDevice, UsbDeviceState},
+    UsbError,
 };
-use panel_protocol::{ArrayString, ArrayVec, MAX_COMMAND_LEN, MAX_COMMAND_QUEUE_LEN};
-pub use panel_protocol::{Command, CommandReader, Report};
-use usb_device::{device::UsbDevice, UsbError};
 use usbd_serial::SerialPort;
 
-type Stm32F1UsbDevice = stm32f1xx_hal::usb::UsbBus<stm32f1xx_hal::usb::Peripheral>;
-
 #[derive(Debug)]
 pub enum Error {
-    Serial(hal::serial::Error),
+    Serial(SerialError),
     UsbError(UsbError),
     BufferFull,
     MalformedMessage,
     CommandQueueFull,
     ReportQueueFull,
+    WriteTimeout,
 }
 
-impl From<serial::Error> for Error {
-    fn from(e: serial::Error) -> Error {
+impl From<SerialError> for Error {
+    fn from(e: SerialError) -> Error {
         Error::Serial(e)
     }
 }
@@ -44,61 +55,496 @@ impl From<panel_protocol::Error> for Error {
     }
 }
 
-pub struct SerialProtocol<'a> {
-    protocol: CommandReader,
-    usb_device: UsbDevice<'a, UsbBus<Peripheral>>,
-    usb_serial_device: SerialPort<'a, UsbBus<Peripheral>>,
-    read_buf: [u8; MAX_COMMAND_LEN],
+/// Baud rates which pick a mode other than the binary protocol, see `LinkMode`.
+const ASCII_DEBUG_BAUD: u32 = 9600;
+const BOOTLOADER_TOUCH_BAUD: u32 = 1200;
+
+/// The longest line a report is written out as in `LinkMode::AsciiDebug`. Longer ones are cut
+/// off.
+const ASCII_LINE_LEN: usize = 128;
+
+/// The size of a full speed USB bulk packet. Reports are packed into writes of up to this many
+/// bytes, so that a burst of them doesn't take a USB transaction each.
+const PACKET_LEN: usize = 64;
+
+/// The size of the buffer the bytes of commands wait in when there isn't room for them in the
+/// command queue yet, which lets a burst of them wait until there is.
+const RX_BUFFER_LEN: usize = 256;
+
+/// How long to wait for the host to take the rest of a report it has started reading, before
+/// giving up on it. Reports are written from the USB interrupt, so nothing else at its priority
+/// runs in the meantime.
+const WRITE_TIMEOUT: Duration = Duration::from_ms(20);
+
+/// What the host wants from the link. USB serial has no real baud rate, so the one the host opens
+/// the port at picks the mode instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkMode {
+    /// The binary protocol, at 115200 or any rate other than the ones below.
+    Binary,
+    /// At 9600, reports are written out as a line of text each, for a serial terminal, and
+    /// anything typed is ignored.
+    AsciiDebug,
+    /// Opening the port at 1200 and closing it again asks for the bootloader, the way Arduino
+    /// boards do. It's taken as `Command::Bootload`, so it needs the same unlock within two
+    /// seconds before it. Arduino style upload tools only do the touch, and never send the
+    /// unlock, so on their own they can't reboot the panel, which is on purpose: a port opened
+    /// at the wrong rate by mistake mustn't be able to.
+    BootloaderTouch,
+}
+
+impl LinkMode {
+    pub fn from_baud(baud: u32) -> Self {
+        match baud {
+            ASCII_DEBUG_BAUD => LinkMode::AsciiDebug,
+            BOOTLOADER_TOUCH_BAUD => LinkMode::BootloaderTouch,
+            _ => LinkMode::Binary,
+        }
+    }
 }
 
-impl<'a> SerialProtocol<'a> {
-    pub fn new(
-        usb_device: usb_device::device::UsbDevice<'a, Stm32F1UsbDevice>,
-        usb_serial_device: usbd_serial::SerialPort<'a, Stm32F1UsbDevice>,
-    ) -> Self {
+/// A link to the host which the protocol's bytes are carried over.
+pub trait Transport {
+    /// Reads whatever has arrived since the last call into `buf`, returning how many bytes it
+    /// took.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+
+    /// Writes as much of `bytes` as the link takes right away, returning how many bytes that was,
+    /// or `WouldBlock` if it didn't take any.
+    fn write(&mut self, bytes: &[u8]) -> nb::Result<usize, Error>;
+
+    /// True while there's a host at the other end.
+    fn is_connected(&self) -> bool;
+
+    /// True if bytes which aren't read yet stay with the link, which holds the host back once it
+    /// has no more room for them. Otherwise they're lost unless they're read straight away.
+    fn keeps_unread(&self) -> bool {
+        false
+    }
+
+    /// True while the host has put the link to sleep, and will be back where it left off once
+    /// it resumes. Only USB can be suspended.
+    fn is_suspended(&self) -> bool {
+        false
+    }
+
+    /// Links which can't be told otherwise always carry the binary protocol.
+    fn mode(&self) -> LinkMode {
+        LinkMode::Binary
+    }
+
+    /// Whether the host has asked for the bootloader with `LinkMode::BootloaderTouch` since the
+    /// last call.
+    fn take_bootloader_touch(&mut self) -> bool {
+        false
+    }
+
+    /// The most bytes one write goes out in, up to `PACKET_LEN`, which reports are packed into.
+    fn packet_len(&self) -> usize {
+        PACKET_LEN
+    }
+}
+
+/// Talks to the host over `T`, reading up to `RX_LEN` bytes at a time. Commands longer than
+/// that are put together over several reads.
+pub struct SerialProtocol<T: Transport, const RX_LEN: usize> {
+    /// Everything the host has asked for about the link itself, such as the protocol version
+    /// and whether it sends frames, is followed by the parser.
+    parser: CommandParser,
+    transport: T,
+    read_buf: [u8; RX_LEN],
+    /// Bytes read from the host but not parsed yet, as there was no room for their commands.
+    /// They're parsed before the next read, which otherwise is parsed where it was read into.
+    rx_buffer: Vec<u8, RX_BUFFER_LEN>,
+    /// Bytes which didn't fit in `rx_buffer`, and commands which didn't fit in the command queue,
+    /// since `take_overflow()` was last called.
+    dropped_bytes: u32,
+    dropped_commands: u32,
+    /// Commands which didn't parse since `take_malformed()` was last called.
+    malformed_commands: u32,
+    /// Reports sent since the host connected, wrapping, which also numbers the next one.
+    reports_sent: u16,
+    /// The latest LED frame from the host, which replaces any not yet taken.
+    led_frame: Option<Vec<u8, MAX_PAYLOAD_LEN>>,
+}
+
+impl<T: Transport, const RX_LEN: usize> SerialProtocol<T, RX_LEN> {
+    pub fn new(transport: T) -> Self {
         Self {
-            protocol: CommandReader::new(),
-            usb_device,
-            usb_serial_device,
-            read_buf: [0u8; MAX_COMMAND_LEN],
+            parser: CommandParser::new(),
+            transport,
+            read_buf: [0u8; RX_LEN],
+            rx_buffer: Vec::new(),
+            dropped_bytes: 0,
+            dropped_commands: 0,
+            malformed_commands: 0,
+            reports_sent: 0,
+            led_frame: None,
         }
     }
 
-    /// Check to see if a new command from host is available
-    pub fn poll(&mut self) -> Result<ArrayVec<[Command; MAX_COMMAND_QUEUE_LEN]>, Error> {
-        self.usb_device.poll(&mut [&mut self.usb_serial_device]);
+    /// Reads whatever the host has sent since the last call, and returns up to `max_commands` of
+    /// the commands it completes. The bytes of any more wait for the next call. A command or
+    /// frame split across reads is held on to until the rest arrives, see
+    /// tests/command_reader.rs and tests/command_parser.rs in panel-core.
+    pub fn poll(&mut self, max_commands: usize) -> Result<Commands, Error> {
+        // The next host starts off without frames, and counting reports from 0. A suspended one
+        // is the same host, once it resumes.
+        if !self.transport.is_connected() && !self.transport.is_suspended() {
+            self.parser.reset();
+            self.reports_sent = 0;
+            self.rx_buffer.clear();
+        }
 
-        match self.usb_serial_device.read(&mut self.read_buf[..]) {
-            Ok(count) if count > 0 => {
-                let commands = self.protocol.process_bytes(&self.read_buf[..count])?;
-                Ok(commands)
+        // Links which keep what isn't read can hold on to what doesn't fit in the buffer yet.
+        let room = self.rx_buffer.capacity() - self.rx_buffer.len();
+        let read_len = if self.transport.keeps_unread() { room.min(RX_LEN) } else { RX_LEN };
+        let count = match self.transport.read(&mut self.read_buf[..read_len]) {
+            Ok(count) => count,
+            Err(e) => {
+                warn!("read from host failed: {}", defmt::Debug2Format(&e));
+                return Err(e);
             },
-            Ok(_) | Err(UsbError::WouldBlock) => Ok(ArrayVec::new()),
-            Err(e) => Err(e.into()),
+        };
+
+        // Keystrokes from a terminal aren't commands.
+        if self.transport.mode() == LinkMode::AsciiDebug {
+            return Ok(Commands::new());
+        }
+
+        // Applied like any other command, rather than rebooting from the interrupt, so that it
+        // needs an unlock and the lights are turned off first. The host has just closed the
+        // port, so there are no commands after it to wait for.
+        if max_commands > 0 && self.transport.take_bootloader_touch() {
+            keep(&mut self.rx_buffer, &self.read_buf[..count], &mut self.dropped_bytes);
+            let mut commands = Commands::new();
+            commands.push(Command::Bootload);
+            return Ok(commands);
         }
+
+        let Self {
+            parser,
+            read_buf,
+            rx_buffer,
+            dropped_bytes,
+            dropped_commands,
+            malformed_commands,
+            led_frame,
+            ..
+        } = self;
+        let max_commands = max_commands.min(MAX_COMMAND_QUEUE_LEN);
+        let mut commands = Commands::new();
+
+        // Parses `bytes` until they run out or `max_commands` commands are complete. A malformed
+        // command is counted and skipped, and doesn't cost the host the commands before or after
+        // it.
+        let mut parse = |bytes: &mut &[u8]| {
+            while commands.len() < max_commands && !bytes.is_empty() {
+                let parsed = parser.push(bytes, |command| {
+                    if commands.len() < max_commands {
+                        commands.push(command);
+                    } else {
+                        *dropped_commands += 1;
+                    }
+                });
+                match parsed {
+                    Ok(Parsed::Nothing) => {},
+                    Ok(Parsed::LedFrame(colors)) => *led_frame = Vec::from_slice(colors).ok(),
+                    // The parser has already started over from the next byte.
+                    Err(_e) => {
+                        warn!("malformed command: {}", defmt::Debug2Format(&_e));
+                        *malformed_commands += 1;
+                    },
+                }
+            }
+        };
+
+        profiler::measure(Section::CommandParse, || {
+            if rx_buffer.is_empty() {
+                // Nothing is waiting, so the commands are read straight out of the read, and only
+                // the bytes of any there isn't room for yet are kept.
+                let mut bytes = &read_buf[..count];
+                parse(&mut bytes);
+                keep(rx_buffer, bytes, dropped_bytes);
+            } else {
+                keep(rx_buffer, &read_buf[..count], dropped_bytes);
+                let mut bytes = &rx_buffer[..];
+                parse(&mut bytes);
+                let parsed = rx_buffer.len() - bytes.len();
+                rx_buffer.copy_within(parsed.., 0);
+                rx_buffer.truncate(rx_buffer.len() - parsed);
+            }
+        });
+        trace!("received {} bytes, {} commands", count, commands.len());
+        Ok(commands)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.transport.is_connected()
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.transport.is_suspended()
+    }
+
+    /// Whether the host has asked for every command to be acknowledged, see panel_core::ack.
+    pub fn acks(&self) -> bool {
+        self.parser.version() >= ACK_PROTOCOL_VERSION
     }
 
-    /// Sends a new report to the host, blocks until fully written or error occurs.
-    pub fn report(&mut self, report: Report) -> Result<(), Error> {
-        let report_bytes = report.as_arrayvec();
-        let mut write_offset = 0;
+    /// The colors from the latest LED frame the host has streamed, three bytes to an LED, if one
+    /// has come in since the last call.
+    pub fn take_led_frame(&mut self) -> Option<Vec<u8, MAX_PAYLOAD_LEN>> {
+        self.led_frame.take()
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// How many reports have been sent since the host connected, wrapping.
+    pub fn reports_sent(&self) -> u16 {
+        self.reports_sent
+    }
+
+    /// Returns how many bytes from the host, and how many commands, have been dropped for want of
+    /// room since the last call.
+    pub fn take_overflow(&mut self) -> (u32, u32) {
+        let overflow = (self.dropped_bytes, self.dropped_commands);
+        self.dropped_bytes = 0;
+        self.dropped_commands = 0;
+        overflow
+    }
+
+    /// Returns how many commands from the host didn't parse since the last call.
+    pub fn take_malformed(&mut self) -> u32 {
+        let malformed = self.malformed_commands;
+        self.malformed_commands = 0;
+        malformed
+    }
+
+    /// Returns how many frames from the host have been dropped for failing their CRC since the
+    /// last call.
+    pub fn take_corrupt_frames(&mut self) -> u32 {
+        self.parser.take_corrupt_frames()
+    }
+
+    /// Sends a new report to the host unless the transport is busy, in which case `WouldBlock`
+    /// is returned and nothing is written. Once the first bytes are accepted, this blocks until
+    /// the rest of the report is written, or returns `WriteTimeout` if the host stops reading.
+    pub fn try_report(&mut self, report: &Report) -> nb::Result<(), Error> {
+        if self.transport.mode() == LinkMode::AsciiDebug {
+            let mut line = String::<ASCII_LINE_LEN>::new();
+            let _ = write!(line, "{:?}", report);
+
+            let len = self.transport.write(line.as_bytes())?;
+            self.write_from(line.as_bytes(), len)?;
+            self.write_from(b"\r\n", 0)?;
+            self.reports_sent = self.reports_sent.wrapping_add(1);
+            return Ok(());
+        }
+
+        self.send(&report.as_arrayvec(), 1)
+    }
+
+    /// Like `try_report()`, but sends as many of `reports` as fit in one USB packet in one write.
+    /// Returns how many of them were sent, from the front.
+    pub fn try_reports<'r>(
+        &mut self,
+        reports: impl IntoIterator<Item = &'r Report>,
+    ) -> nb::Result<usize, Error> {
+        if self.transport.mode() == LinkMode::AsciiDebug {
+            // Lines of text go out one at a time.
+            return match reports.into_iter().next() {
+                Some(report) => self.try_report(report).map(|()| 1),
+                None => Ok(0),
+            };
+        }
+
+        let packet_len = self.transport.packet_len().min(PACKET_LEN);
+        let payload_len = match (self.framed(), self.sequenced()) {
+            (true, true) => packet_len - framing::OVERHEAD - framing::SEQUENCE_LEN,
+            (true, false) => packet_len - framing::OVERHEAD,
+            (false, _) => packet_len,
+        };
+        let mut packet = Vec::<u8, PACKET_LEN>::new();
+        let mut count = 0;
+        for report in reports {
+            let report_bytes = report.as_arrayvec();
+            if packet.len() + report_bytes.len() > payload_len {
+                // A report which doesn't fit in a packet by itself goes out on its own.
+                if count == 0 {
+                    return self.try_report(report).map(|()| 1);
+                }
+                break;
+            }
+
+            let _ = packet.extend_from_slice(&report_bytes);
+            count += 1;
+        }
+
+        if count > 0 {
+            self.send(&packet, count as u16)?;
+        }
+        Ok(count)
+    }
+
+    /// Writes `payload`, which holds `reports` reports, in a frame if the host asked for them,
+    /// with the same blocking as `try_report()`.
+    fn send(&mut self, payload: &[u8], reports: u16) -> nb::Result<(), Error> {
+        let mut sequenced = Vec::<u8, MAX_PAYLOAD_LEN>::new();
+        let payload = if self.sequenced() {
+            let _ = sequenced.extend_from_slice(&self.reports_sent.to_be_bytes());
+            sequenced.extend_from_slice(payload).map_err(|()| Error::BufferFull)?;
+            &sequenced[..]
+        } else {
+            payload
+        };
+
+        let mut frame_buf = [0u8; MAX_FRAME_LEN];
+        let bytes = if self.framed() { framing::encode(payload, &mut frame_buf) } else { payload };
+
+        let len = self.transport.write(bytes)?;
+        self.write_from(bytes, len)?;
+        self.reports_sent = self.reports_sent.wrapping_add(reports);
+        Ok(())
+    }
+
+    fn framed(&self) -> bool {
+        self.parser.framed()
+    }
+
+    fn sequenced(&self) -> bool {
+        self.parser.version() >= SEQUENCE_PROTOCOL_VERSION
+    }
+
+    /// Writes the rest of `report_bytes`, from `write_offset`. Gives up with `WriteTimeout` if
+    /// the host stops taking them, rather than spinning in the USB interrupt for good.
+    fn write_from(&mut self, report_bytes: &[u8], mut write_offset: usize) -> Result<(), Error> {
         let count = report_bytes.len();
+        let start = Instant::now();
 
         while write_offset < count {
-            match self.usb_serial_device.write(&report_bytes[write_offset..count]) {
-                Ok(len) if len > 0 => {
-                    write_offset += len;
-                },
-                _ => {},
+            // Reports are dropped once USB has been shut down, see clock_security.rs.
+            if clock_security::has_failed() {
+                return Ok(());
             }
-        }
 
+            match self.transport.write(&report_bytes[write_offset..count]) {
+                Ok(len) => write_offset += len,
+                Err(nb::Error::WouldBlock) if start.elapsed() < WRITE_TIMEOUT => {},
+                Err(nb::Error::WouldBlock) => return Err(Error::WriteTimeout),
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+        }
         Ok(())
     }
+}
 
-    #[allow(dead_code)]
-    pub fn debug(&mut self, message: &str) {
-        let report = Report::Debug { message: ArrayString::from(message).unwrap() };
-        let _ = self.report(report);
+/// USB serial, as a CDC ACM device.
+pub struct UsbSerial<'a, B: UsbBus> {
+    usb_device: UsbDevice<'a, B>,
+    usb_serial_device: SerialPort<'a, B>,
+    /// The button and dial for host software which doesn't speak the protocol, see hid.rs.
+    input: Option<InputHid<'a, B>>,
+    /// Whether the port was open at the last read.
+    dtr: bool,
+    /// Set once the host closes the port it opened at 1200, until it's taken.
+    bootloader_touch: bool,
+}
+
+impl<'a, B: UsbBus> UsbSerial<'a, B> {
+    pub fn new(
+        usb_device: UsbDevice<'a, B>,
+        usb_serial_device: SerialPort<'a, B>,
+        input: Option<InputHid<'a, B>>,
+    ) -> Self {
+        Self { usb_device, usb_serial_device, input, dtr: false, bootloader_touch: false }
+    }
+
+    /// Tells the HID interface about an input event, if there is one.
+    pub fn push_input(&mut self, event: &InputEvent) {
+        if let Some(input) = &mut self.input {
+            input.push(event);
+        }
     }
 }
+
+impl<B: UsbBus> Transport for UsbSerial<'_, B> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match &mut self.input {
+            Some(input) => self.usb_device.poll(&mut [&mut self.usb_serial_device, input]),
+            None => self.usb_device.poll(&mut [&mut self.usb_serial_device]),
+        };
+
+        // The host closing the port drops DTR, which is as close to a callback as usbd-serial has.
+        let dtr = self.usb_serial_device.dtr();
+        if self.dtr && !dtr && self.mode() == LinkMode::BootloaderTouch {
+            self.bootloader_touch = true;
+        }
+        self.dtr = dtr;
+
+        match self.usb_serial_device.read(buf) {
+            Ok(count) => Ok(count),
+            Err(UsbError::WouldBlock) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> nb::Result<usize, Error> {
+        match self.usb_serial_device.write(bytes) {
+            Ok(len) => Ok(len),
+            Err(UsbError::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(e) => Err(nb::Error::Other(e.into())),
+        }
+    }
+
+    /// True once the host has configured the device and opened the serial port.
+    fn is_connected(&self) -> bool {
+        self.usb_device.state() == UsbDeviceState::Configured && self.usb_serial_device.dtr()
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.usb_device.state() == UsbDeviceState::Suspend
+    }
+
+    /// usbd-serial holds on to what hasn't been read, and NAKs the host once it's full.
+    fn keeps_unread(&self) -> bool {
+        true
+    }
+
+    fn mode(&self) -> LinkMode {
+        LinkMode::from_baud(self.usb_serial_device.line_coding().data_rate())
+    }
+
+    fn take_bootloader_touch(&mut self) -> bool {
+        core::mem::take(&mut self.bootloader_touch)
+    }
+}
+
+/// Splits a debug message into as many reports as it takes to fit in.
+pub fn debug_reports(message: &str) -> impl Iterator<Item = Report> + '_ {
+    let mut chars = message.chars().peekable();
+
+    core::iter::from_fn(move || {
+        chars.peek()?;
+
+        let mut chunk = ArrayString::new();
+        while let Some(&c) = chars.peek() {
+            if chunk.try_push(c).is_err() {
+                break;
+            }
+            chars.next();
+        }
+
+        Some(Report::Debug { message: chunk })
+    })
+}
+
+/// Adds `bytes` to the end of `rx_buffer`, counting any there isn't room for as dropped.
+fn keep(rx_buffer: &mut Vec<u8, RX_BUFFER_LEN>, bytes: &[u8], dropped_bytes: &mut u32) {
+    let room = rx_buffer.capacity() - rx_buffer.len();
+    let (kept, dropped) = bytes.split_at(room.min(bytes.len()));
+    let _ = rx_buffer.extend_from_slice(kept);
+    *dropped_bytes += dropped.len() as u32;
+}
@@ -0,0 +1,424 @@
+use crate::{eeprom::Eeprom, platform::reset_reason::ResetReason, rgb_led::LedSettings};
+use panel_core::{
+    haptics::{HapticEffects, HapticEvent},
+    ir::{IrAction, IrBinding, IrBindings, IrProtocol},
+    power::SuspendPolicy,
+    schedule::{Schedule, ScheduleEntry, MAX_ENTRIES},
+};
+
+/// Bump this whenever the meaning of an existing key changes, so that settings saved by older
+/// firmware are discarded rather than misinterpreted.
+const SCHEMA_VERSION: u16 = 1;
+
+/// Keys of the settings stored in the emulated EEPROM. Never reuse a number.
+#[repr(u16)]
+#[derive(Clone, Copy)]
+enum Key {
+    SchemaVersion = 0,
+    DebounceTimeMs = 1,
+    LongPressTimeoutMs = 2,
+    PulseIntervalMs = 3,
+    /// Not settings, but counts of low-voltage events and boots kept alongside them.
+    LowVoltageCount = 4,
+    CleanBootCount = 5,
+    WatchdogResetCount = 6,
+    PanicCount = 7,
+    /// The remote buttons bound to each `IrAction`, as their address, and their protocol and
+    /// command in the high and low bytes.
+    IrToggleLightsAddress = 8,
+    IrToggleLightsCommand = 9,
+    IrRecallSceneAddress = 10,
+    IrRecallSceneCommand = 11,
+    /// Local time's offset from UTC in minutes, as an `i16`.
+    UtcOffsetMinutes = 12,
+    /// Three keys for each schedule entry, up to and including 24: its time of day, brightness
+    /// and color temperature.
+    FirstScheduleEntry = 13,
+    /// The haptic effect played for each `HapticEvent`.
+    HapticClickEffect = 25,
+    HapticDetentEffect = 26,
+    HapticLongPressEffect = 27,
+    /// The LED strip's color and effect at boot, as red and green in the high and low bytes, then
+    /// blue and whether it pulses.
+    DefaultLedRedGreen = 28,
+    DefaultLedBluePulse = 29,
+    /// The `SuspendPolicy` while the host has suspended the bus.
+    SuspendPolicy = 30,
+    /// Whether `Report::Status` is sent as soon as a host connects.
+    StatusOnConnect = 31,
+    /// Six keys for each scene slot, up to and including 55: the front light's brightness and
+    /// color temperature, the back light's, then the LED strip's as for `DefaultLedRedGreen` and
+    /// `DefaultLedBluePulse`.
+    FirstScene = 32,
+    DialReportsPerSecond = 56,
+}
+
+/// The settings which can be changed with `Command::SetConfig`, by their number in the command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigKey {
+    DebounceTimeMs = 0,
+    LongPressTimeoutMs = 1,
+    PulseIntervalMs = 2,
+    /// 0 for no limit.
+    DialReportsPerSecond = 3,
+}
+
+impl ConfigKey {
+    pub const ALL: [ConfigKey; 4] = [
+        ConfigKey::DebounceTimeMs,
+        ConfigKey::LongPressTimeoutMs,
+        ConfigKey::PulseIntervalMs,
+        ConfigKey::DialReportsPerSecond,
+    ];
+
+    pub fn from_u8(key: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|&config_key| config_key as u8 == key)
+    }
+}
+
+/// Settings which persist across resets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub debounce_time_ms: u16,
+    pub long_press_timeout_ms: u16,
+    pub pulse_interval_ms: u16,
+    pub dial_reports_per_second: u16,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            debounce_time_ms: 30,
+            long_press_timeout_ms: 1000,
+            pulse_interval_ms: 700,
+            dial_reports_per_second: 50,
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the saved settings, using the default for any which haven't been saved.
+    pub fn load(eeprom: &Eeprom) -> Self {
+        let defaults = Self::default();
+
+        if eeprom.read(Key::SchemaVersion as u16) != Some(SCHEMA_VERSION) {
+            return defaults;
+        }
+
+        let read = |key: Key, default: u16| eeprom.read(key as u16).unwrap_or(default);
+
+        Self {
+            debounce_time_ms: read(Key::DebounceTimeMs, defaults.debounce_time_ms),
+            long_press_timeout_ms: read(Key::LongPressTimeoutMs, defaults.long_press_timeout_ms),
+            pulse_interval_ms: read(Key::PulseIntervalMs, defaults.pulse_interval_ms),
+            dial_reports_per_second: read(
+                Key::DialReportsPerSecond,
+                defaults.dial_reports_per_second,
+            ),
+        }
+    }
+
+    pub fn get(&self, key: ConfigKey) -> u16 {
+        match key {
+            ConfigKey::DebounceTimeMs => self.debounce_time_ms,
+            ConfigKey::LongPressTimeoutMs => self.long_press_timeout_ms,
+            ConfigKey::PulseIntervalMs => self.pulse_interval_ms,
+            ConfigKey::DialReportsPerSecond => self.dial_reports_per_second,
+        }
+    }
+
+    /// Returns false, leaving the setting as it was, if `value` is out of range for `key`. The
+    /// encoder button is sampled every millisecond, and debounced over at most 255 samples.
+    /// Debounce times shorter than a sample, such as 0, or under 5 ms for the expander's
+    /// buttons, are debounced over one.
+    pub fn set(&mut self, key: ConfigKey, value: u16) -> bool {
+        match key {
+            ConfigKey::DebounceTimeMs if value > 255 => return false,
+            ConfigKey::PulseIntervalMs if value == 0 => return false,
+            _ => {},
+        }
+
+        match key {
+            ConfigKey::DebounceTimeMs => self.debounce_time_ms = value,
+            ConfigKey::LongPressTimeoutMs => self.long_press_timeout_ms = value,
+            ConfigKey::PulseIntervalMs => self.pulse_interval_ms = value,
+            ConfigKey::DialReportsPerSecond => self.dial_reports_per_second = value,
+        }
+        true
+    }
+
+    /// Saves any settings which have changed. This may take several milliseconds if a flash page
+    /// needs to be erased.
+    pub fn save(&self, eeprom: &mut Eeprom) {
+        eeprom.write(Key::SchemaVersion as u16, SCHEMA_VERSION);
+        eeprom.write(Key::DebounceTimeMs as u16, self.debounce_time_ms);
+        eeprom.write(Key::LongPressTimeoutMs as u16, self.long_press_timeout_ms);
+        eeprom.write(Key::PulseIntervalMs as u16, self.pulse_interval_ms);
+        eeprom.write(Key::DialReportsPerSecond as u16, self.dial_reports_per_second);
+    }
+}
+
+/// Saves the number of low-voltage events so far. This writes flash, so it's done once the
+/// supply has recovered rather than as the voltage drops, see `voltage_change` in lights.rs.
+pub fn save_low_voltage_count(eeprom: &mut Eeprom, count: u16) {
+    eeprom.write(Key::LowVoltageCount as u16, count);
+}
+
+/// The number of low-voltage events recorded so far.
+pub fn low_voltage_count(eeprom: &Eeprom) -> u16 {
+    eeprom.read(Key::LowVoltageCount as u16).unwrap_or(0)
+}
+
+/// How often the panel has booted, by how the previous run ended, to spot flaky units in the
+/// field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BootCounts {
+    pub clean_boots: u16,
+    pub watchdog_resets: u16,
+    pub panics: u16,
+}
+
+impl BootCounts {
+    pub fn load(eeprom: &Eeprom) -> Self {
+        let read = |key: Key| eeprom.read(key as u16).unwrap_or(0);
+
+        Self {
+            clean_boots: read(Key::CleanBootCount),
+            watchdog_resets: read(Key::WatchdogResetCount),
+            panics: read(Key::PanicCount),
+        }
+    }
+
+    /// Counts the current boot. The panic handler resets the panel from software, so a saved
+    /// panic message takes priority over the reset reason. Low-power and unknown resets aren't
+    /// counted.
+    pub fn record_boot(eeprom: &mut Eeprom, reset_reason: ResetReason, panicked: bool) {
+        let key = match reset_reason {
+            _ if panicked => Key::PanicCount,
+            ResetReason::IndependentWatchdog | ResetReason::WindowWatchdog => {
+                Key::WatchdogResetCount
+            },
+            ResetReason::PowerOn | ResetReason::Pin | ResetReason::Software => Key::CleanBootCount,
+            ResetReason::LowPower | ResetReason::Unknown => return,
+        };
+
+        let count = eeprom.read(key as u16).unwrap_or(0).saturating_add(1);
+        eeprom.write(key as u16, count);
+    }
+}
+
+/// Stands in for the protocol of an IR action which isn't bound to a button.
+const IR_UNBOUND: u8 = 0xff;
+
+fn ir_binding_keys(action: IrAction) -> (Key, Key) {
+    match action {
+        IrAction::ToggleLights => (Key::IrToggleLightsAddress, Key::IrToggleLightsCommand),
+        IrAction::RecallScene => (Key::IrRecallSceneAddress, Key::IrRecallSceneCommand),
+    }
+}
+
+/// The remote buttons bound with `Command::BindIrCode`, which are kept apart from `Settings` as
+/// they're saved as soon as they're changed.
+pub fn load_ir_bindings(eeprom: &Eeprom) -> IrBindings {
+    let mut bindings = IrBindings::default();
+
+    for &action in &IrAction::ALL {
+        let (address_key, command_key) = ir_binding_keys(action);
+        let (address, [protocol, command]) =
+            match (eeprom.read(address_key as u16), eeprom.read(command_key as u16)) {
+                (Some(address), Some(command)) => (address, command.to_be_bytes()),
+                _ => continue,
+            };
+        let binding =
+            IrProtocol::from_u8(protocol).map(|protocol| IrBinding { protocol, address, command });
+        bindings.set(action, binding);
+    }
+
+    bindings
+}
+
+pub fn save_ir_binding(eeprom: &mut Eeprom, action: IrAction, binding: Option<IrBinding>) {
+    let (address_key, command_key) = ir_binding_keys(action);
+    let (address, protocol, command) = match binding {
+        Some(binding) => (binding.address, binding.protocol as u8, binding.command),
+        None => (0, IR_UNBOUND, 0),
+    };
+
+    eeprom.write(address_key as u16, address);
+    eeprom.write(command_key as u16, u16::from_be_bytes([protocol, command]));
+}
+
+/// Stands in for the time of day of a schedule entry which isn't set.
+const SCHEDULE_UNSET: u16 = 0xffff;
+
+fn schedule_entry_keys(index: usize) -> [u16; 3] {
+    let first = Key::FirstScheduleEntry as u16 + 3 * index as u16;
+    [first, first + 1, first + 2]
+}
+
+/// The daily schedule, which like the IR bindings is saved as soon as an entry is changed.
+pub fn load_schedule(eeprom: &Eeprom) -> Schedule {
+    let mut schedule = Schedule::new();
+
+    for index in 0..MAX_ENTRIES {
+        let [time_key, brightness_key, temperature_key] = schedule_entry_keys(index);
+        let values =
+            (eeprom.read(time_key), eeprom.read(brightness_key), eeprom.read(temperature_key));
+        let entry = match values {
+            (Some(minute_of_day), Some(brightness), Some(color_temperature))
+                if minute_of_day != SCHEDULE_UNSET =>
+            {
+                ScheduleEntry { minute_of_day, brightness, color_temperature }
+            },
+            _ => continue,
+        };
+        schedule.set(index, Some(entry));
+    }
+
+    schedule
+}
+
+/// Saves entry `index` of the schedule, which must be less than `schedule::MAX_ENTRIES`.
+pub fn save_schedule_entry(eeprom: &mut Eeprom, index: usize, entry: Option<ScheduleEntry>) {
+    assert!(index < MAX_ENTRIES);
+
+    let [time_key, brightness_key, temperature_key] = schedule_entry_keys(index);
+    let (minute_of_day, brightness, color_temperature) = match entry {
+        Some(entry) => (entry.minute_of_day, entry.brightness, entry.color_temperature),
+        None => (SCHEDULE_UNSET, 0, 0),
+    };
+
+    eeprom.write(time_key, minute_of_day);
+    eeprom.write(brightness_key, brightness);
+    eeprom.write(temperature_key, color_temperature);
+}
+
+pub fn load_utc_offset(eeprom: &Eeprom) -> i16 {
+    eeprom.read(Key::UtcOffsetMinutes as u16).map_or(0, |minutes| minutes as i16)
+}
+
+pub fn save_utc_offset(eeprom: &mut Eeprom, minutes: i16) {
+    eeprom.write(Key::UtcOffsetMinutes as u16, minutes as u16);
+}
+
+fn haptic_effect_key(event: HapticEvent) -> Key {
+    match event {
+        HapticEvent::Click => Key::HapticClickEffect,
+        HapticEvent::Detent => Key::HapticDetentEffect,
+        HapticEvent::LongPress => Key::HapticLongPressEffect,
+    }
+}
+
+/// The haptic effects set with `Command::SetHapticEffect`, saved as soon as they're changed.
+/// Events whose effect hasn't been set keep the default.
+pub fn load_haptic_effects(eeprom: &Eeprom) -> HapticEffects {
+    let mut effects = HapticEffects::default();
+
+    for &event in &HapticEvent::ALL {
+        if let Some(effect) = eeprom.read(haptic_effect_key(event) as u16) {
+            effects.set(event, effect as u8);
+        }
+    }
+
+    effects
+}
+
+pub fn save_haptic_effect(eeprom: &mut Eeprom, event: HapticEvent, effect: u8) {
+    eeprom.write(haptic_effect_key(event) as u16, effect as u16);
+}
+
+/// The LED strip's color and effect until the host sets one, if it hasn't saved its own default
+/// with `Command::SetDefaultLed`.
+const DEFAULT_LED: LedSettings = LedSettings { color: (0, 30, 255), pulse: false };
+
+pub fn load_default_led(eeprom: &Eeprom) -> LedSettings {
+    let values =
+        (eeprom.read(Key::DefaultLedRedGreen as u16), eeprom.read(Key::DefaultLedBluePulse as u16));
+    match values {
+        (Some(red_green), Some(blue_pulse)) => {
+            let [r, g] = red_green.to_be_bytes();
+            let [b, pulse] = blue_pulse.to_be_bytes();
+            LedSettings { color: (r, g, b), pulse: pulse != 0 }
+        },
+        _ => DEFAULT_LED,
+    }
+}
+
+pub fn save_default_led(eeprom: &mut Eeprom, led: LedSettings) {
+    let (r, g, b) = led.color;
+    eeprom.write(Key::DefaultLedRedGreen as u16, u16::from_be_bytes([r, g]));
+    eeprom.write(Key::DefaultLedBluePulse as u16, u16::from_be_bytes([b, led.pulse as u8]));
+}
+
+/// The policy set with `Command::SetSuspendPolicy`, or holding the outputs as they are if it
+/// hasn't been.
+pub fn load_suspend_policy(eeprom: &Eeprom) -> SuspendPolicy {
+    eeprom
+        .read(Key::SuspendPolicy as u16)
+        .and_then(|policy| SuspendPolicy::from_u8(policy as u8))
+        .unwrap_or(SuspendPolicy::Hold)
+}
+
+pub fn save_suspend_policy(eeprom: &mut Eeprom, policy: SuspendPolicy) {
+    eeprom.write(Key::SuspendPolicy as u16, policy as u16);
+}
+
+pub fn load_status_on_connect(eeprom: &Eeprom) -> bool {
+    eeprom.read(Key::StatusOnConnect as u16).is_some_and(|enabled| enabled != 0)
+}
+
+pub fn save_status_on_connect(eeprom: &mut Eeprom, enabled: bool) {
+    eeprom.write(Key::StatusOnConnect as u16, enabled as u16);
+}
+
+/// How many scenes can be saved with `Command::SaveScene`.
+pub const SCENE_SLOTS: usize = 4;
+
+/// The lights and the LED strip, as saved in a scene slot.
+#[derive(Clone, Copy)]
+pub struct Scene {
+    /// Brightness and color temperature.
+    pub front: (u16, u16),
+    pub back: (u16, u16),
+    pub led: LedSettings,
+}
+
+fn scene_keys(slot: usize) -> [u16; 6] {
+    let first = Key::FirstScene as u16 + 6 * slot as u16;
+    [first, first + 1, first + 2, first + 3, first + 4, first + 5]
+}
+
+/// The scene in `slot`, if one has been saved there. `slot` must be less than `SCENE_SLOTS`.
+pub fn load_scene(eeprom: &Eeprom, slot: usize) -> Option<Scene> {
+    assert!(slot < SCENE_SLOTS);
+
+    let mut values = [0; 6];
+    for (value, &key) in values.iter_mut().zip(&scene_keys(slot)) {
+        *value = eeprom.read(key)?;
+    }
+
+    let [r, g] = values[4].to_be_bytes();
+    let [b, pulse] = values[5].to_be_bytes();
+    Some(Scene {
+        front: (values[0], values[1]),
+        back: (values[2], values[3]),
+        led: LedSettings { color: (r, g, b), pulse: pulse != 0 },
+    })
+}
+
+pub fn save_scene(eeprom: &mut Eeprom, slot: usize, scene: &Scene) {
+    assert!(slot < SCENE_SLOTS);
+
+    let (r, g, b) = scene.led.color;
+    let values = [
+        scene.front.0,
+        scene.front.1,
+        scene.back.0,
+        scene.back.1,
+        u16::from_be_bytes([r, g]),
+        u16::from_be_bytes([b, scene.led.pulse as u8]),
+    ];
+    for (&value, &key) in values.iter().zip(&scene_keys(slot)) {
+        eeprom.write(key, value);
+    }
+}
@@ -0,0 +1,60 @@
+use embedded_hal::i2c::I2c;
+use panel_core::display::WIDTH;
+
+// Driver for a 128x64 SSD1306 OLED display, with its address pin tied low. It shares the I2C
+// bus with the ambient light sensor, so the bus is passed in for each operation.
+// https://cdn-shop.adafruit.com/datasheets/SSD1306.pdf
+
+const ADDRESS: u8 = 0x3c;
+
+/// The first byte of every transfer says whether the rest are commands or display data.
+const COMMANDS: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+/// Sets the display up for a module with the usual charge pump and wiring, and turns it on.
+const INIT: [u8; 25] = [
+    0xae, // Display off
+    0xd5, 0x80, // Default oscillator frequency
+    0xa8, 0x3f, // 64 rows
+    0xd3, 0x00, // No vertical offset
+    0x40, // Start at row 0
+    0x8d, 0x14, // Enable the charge pump
+    0x20, 0x02, // Page addressing
+    0xa1, // Column 127 on the left, as the modules are mounted
+    0xc8, // Scan from the bottom row up, likewise
+    0xda, 0x12, // Alternative COM pin configuration
+    0x81, 0x8f, // Contrast
+    0xd9, 0xf1, // Precharge period
+    0xdb, 0x40, // VCOMH deselect level
+    0xa4, // Show the display RAM
+    0xa6, // Not inverted
+    0xaf, // Display on
+];
+
+pub struct Ssd1306(());
+
+impl Ssd1306 {
+    /// Sets up the display. Fails if there's no display on the bus.
+    pub fn new<I: I2c>(i2c: &mut I) -> Result<Self, I::Error> {
+        let mut commands = [COMMANDS; INIT.len() + 1];
+        commands[1..].copy_from_slice(&INIT);
+        i2c.write(ADDRESS, &commands)?;
+
+        Ok(Self(()))
+    }
+
+    /// Replaces one page, eight rows of pixels, of what's shown.
+    pub fn write_page<I: I2c>(
+        &mut self,
+        i2c: &mut I,
+        page: u8,
+        columns: &[u8; WIDTH],
+    ) -> Result<(), I::Error> {
+        // Set the page, and the column back to 0 in its low and high nibbles.
+        i2c.write(ADDRESS, &[COMMANDS, 0xb0 | page, 0x00, 0x10])?;
+
+        let mut data = [DATA; WIDTH + 1];
+        data[1..].copy_from_slice(columns);
+        i2c.write(ADDRESS, &data)
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{
+    cpu_load,
+    lights::Lights,
+    power_monitor::PowerMonitor,
+    queues::{self, ReportQueue},
+    resources::{
+        AnalogInputs, BackLight, EncoderButton, EncoderCounter, Fan, FixtureProbes, FrontLight,
+        I2cBus, Protocol, StatusDisplay,
+    },
+    rgb_led::LedSettings,
+    serial::Report,
+    SYSCLK_HZ,
+};
+use panel_core::{
+    display::{Frame, Status, PAGES},
+    fan,
+    fraction::Fraction,
+    power_budget::PowerBudget,
+    tick::{self, Every, Instant},
+};
+use rtic::Mutex;
+
+// What the panel measures about itself and tells the host, or shows on its display: the fan,
+// the fixtures' temperatures, the LED strip's rail, the CPU load and the overall status.
+
+/// How often the status display is brought up to date, on boards which have one.
+pub const DISPLAY_PERIOD_MS: u32 = 100;
+
+/// How often the fixture temperature probes are read, on boards which have them. Each reading
+/// starts the next conversion, which has to finish before the next period.
+pub const FIXTURE_PROBE_PERIOD_MS: u32 = 1000;
+
+/// How often the current monitor on the LED strip's rail is read, on boards which have one.
+pub const LED_RAIL_PERIOD_MS: u32 = 100;
+
+/// How often the fan is adjusted to the temperature, and its speed reported to the host.
+pub const FAN_PERIOD_MS: u32 = 1000;
+
+/// The most temperature probes read on the fixtures' 1-Wire bus.
+pub const MAX_FIXTURE_PROBES: usize = 4;
+
+/// How often the LED rail's readings are reported to the host. It's read more often than this,
+/// to keep the strip within its budget.
+pub const LED_RAIL_REPORT_PERIOD_MS: u32 = 1000;
+
+/// CPU load is measured over, and reported to the host, once per second.
+pub const CPU_LOAD_PERIOD_MS: u32 = 1_000;
+
+/// Sets the fan's speed from the chip's temperature, which follows the warmth of the lights
+/// around it, and reports it to the host, see panel_core::fan. `last_duty` is the duty cycle
+/// over the period the pulses were counted in.
+pub fn control_fan(
+    fan: &mut Fan,
+    analog: &mut AnalogInputs,
+    mut tach_pulses: impl Mutex<T = u32>,
+    mut reports: impl Mutex<T = ReportQueue>,
+    last_duty: &mut Fraction,
+) {
+    let (controller, fan_pwm) = match (&mut fan.controller, &mut fan.pwm) {
+        (Some(controller), Some(fan_pwm)) => (controller, fan_pwm),
+        _ => return,
+    };
+
+    let pulses = tach_pulses.lock(|pulses| core::mem::replace(pulses, 0));
+    let rpm = fan::rpm(pulses, FAN_PERIOD_MS);
+    if rpm == 0 && *last_duty != Fraction::ZERO {
+        warn!("fan stalled");
+    }
+
+    let celsius = analog.adc.read_temp() as i16;
+    let duty = controller.duty(celsius);
+    fan_pwm.set_duty(duty);
+    *last_duty = duty;
+
+    let report = Report::Fan { rpm, duty: duty.of_u16(u16::MAX), temperature: celsius };
+    let _ = reports.lock(|reports| queues::send_report(reports, report));
+}
+
+/// Reports the temperature of each light fixture to the host, see panel_core::ds18b20.
+pub fn read_fixture_probes(
+    FixtureProbes { bus, probes }: &mut FixtureProbes,
+    mut reports: impl Mutex<T = ReportQueue>,
+) {
+    for probe in 0..probes.len() {
+        if let Some(temperature) = probes.temperature(bus, probe) {
+            let report = Report::FixtureTemperature { probe: probe as u8, temperature };
+            let _ = reports.lock(|reports| queues::send_report(reports, report));
+        } else {
+            warn!("fixture temperature probe {} didn't answer", probe);
+        }
+    }
+
+    if !probes.is_empty() {
+        probes.start_conversion(bus);
+    }
+}
+
+/// Shows the panel's status on the display, see panel_core::display. Only the lines which
+/// changed from what it shows are sent, as the bus is slow enough for a whole frame to hold up
+/// the other tasks.
+pub fn update_display(
+    StatusDisplay { driver, shown }: &mut StatusDisplay,
+    i2c: &mut I2cBus,
+    mut protocol: impl Mutex<T = Protocol>,
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    counter: &EncoderCounter,
+) {
+    let display = match driver {
+        Some(display) => display,
+        None => return,
+    };
+
+    let status = Status {
+        host_connected: protocol.lock(|protocol| protocol.is_connected()),
+        lights: lights.levels(),
+        dial_position: counter.position(),
+    };
+
+    let frame = Frame::render(&status);
+    for page in 0..PAGES {
+        if frame.page(page) == shown.page(page) {
+            continue;
+        }
+        if display.write_page(i2c, page as u8, frame.page(page)).is_err() {
+            warn!("status display didn't respond");
+            return;
+        }
+    }
+    *shown = frame;
+}
+
+/// Measures the LED strip's rail, holding the strip back if it's over budget, see
+/// panel_core::power_budget, and reports it to the host whenever `report` is due.
+pub fn monitor_led_rail(
+    i2c: &mut I2cBus,
+    monitor: &mut Option<PowerMonitor>,
+    mut power_budget: impl Mutex<T = Option<PowerBudget>>,
+    mut reports: impl Mutex<T = ReportQueue>,
+    report: &mut Every,
+) {
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => return,
+    };
+
+    let reading = match monitor.read(i2c) {
+        Ok(reading) => reading,
+        Err(_) => {
+            warn!("LED rail current monitor didn't respond");
+            return;
+        },
+    };
+
+    power_budget.lock(|budget| {
+        if let Some(budget) = budget {
+            budget.measure(reading.milliamps);
+        }
+    });
+
+    if report.is_due(Instant::now()) {
+        let report =
+            Report::LedRail { millivolts: reading.millivolts, milliamps: reading.milliamps };
+        let _ = reports.lock(|reports| queues::send_report(reports, report));
+    }
+}
+
+pub fn report_cpu_load(mut reports: impl Mutex<T = ReportQueue>) {
+    let percent = cpu_load::take_busy_percent(SYSCLK_HZ / 1000 * CPU_LOAD_PERIOD_MS);
+    trace!("cpu load: {}%", percent);
+
+    let _ = reports.lock(|reports| queues::send_report(reports, Report::CpuLoad { percent }));
+}
+
+/// Sends everything a host needs to show the panel's state at once, see Command::GetStatus.
+pub fn send_status(
+    mut lights: Lights<impl Mutex<T = FrontLight>, impl Mutex<T = BackLight>>,
+    mut led_settings: impl Mutex<T = LedSettings>,
+    encoder_button: &EncoderButton,
+    mut reports: impl Mutex<T = ReportQueue>,
+) {
+    let [(front_brightness, front_temperature), (back_brightness, back_temperature)] =
+        lights.levels();
+    let LedSettings { color: (r, g, b), pulse } = led_settings.lock(|settings| *settings);
+
+    let report = Report::Status {
+        front_brightness,
+        front_temperature,
+        back_brightness,
+        back_temperature,
+        r,
+        g,
+        b,
+        pulse,
+        button_pressed: encoder_button.is_pressed(),
+        uptime_ms: tick::uptime().as_ms(),
+    };
+    let _ = reports.lock(|reports| queues::send_report(reports, report));
+}
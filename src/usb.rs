@@ -0,0 +1,178 @@
+use crate::{
+    boot_diagnostics::BootDiagnostics,
+    panic_log::PanicMessage,
+    platform::{device_info::DeviceInfo, reset_reason::ResetReason},
+    queues::{self, CommandQueue, ReportQueue},
+    rgb_led::LedPixels,
+    serial::{self, Report, SerialProtocol, Transport},
+};
+use panel_core::{
+    ack::{self, Rejection},
+    power::PowerManager,
+    subscriptions::Category,
+};
+
+/// What the panel found out about itself at boot, reported to the host once it connects.
+pub struct BootReport {
+    pub reset_reason: Option<ResetReason>,
+    pub device_info: Option<DeviceInfo>,
+    pub diagnostics: Option<BootDiagnostics>,
+    pub panic_message: Option<PanicMessage>,
+}
+
+/// Services the link to the host, queues any commands received from it and sends any queued
+/// reports. Returns true if there are commands to apply.
+pub fn service<T: Transport, const RX_LEN: usize>(
+    protocol: &mut SerialProtocol<T, RX_LEN>,
+    commands: &mut CommandQueue,
+    reports: &mut ReportQueue,
+    boot_report: &mut BootReport,
+) -> bool {
+    // Commands beyond what the queue has room for wait in the link's buffer until
+    // `apply_commands` has caught up.
+    match protocol.poll(commands.capacity() - commands.len()) {
+        Ok(received) => {
+            for command in received {
+                // Answered here rather than queued: pings so that the round trip only measures the
+                // link, and the report count so that it's the count at the time it goes out. Like
+                // any other command, they're then acknowledged if the host asked for that.
+                let answer = match command {
+                    serial::Command::Ping { token } => Some(Report::Pong { token }),
+                    serial::Command::GetReportSequence => {
+                        let sent = protocol.reports_sent().wrapping_add(reports.len() as u16);
+                        Some(Report::ReportSequence { sent })
+                    },
+                    _ => None,
+                };
+                if let Some(report) = answer {
+                    let _ = queues::send_report(reports, report);
+                    if protocol.acks() {
+                        let ack = ack::report(ack::command_id(&command), Ok(()));
+                        let _ = queues::send_report(reports, ack);
+                    }
+                    continue;
+                }
+
+                if commands.enqueue(command).is_err() {
+                    queues::report_error(reports, serial::Error::CommandQueueFull.into());
+                    break;
+                }
+            }
+        },
+        Err(e) => queues::report_error(reports, e.into()),
+    }
+
+    let malformed = protocol.take_malformed();
+    if malformed > 0 {
+        if protocol.acks() {
+            for _ in 0..malformed {
+                let report = ack::report(ack::UNKNOWN_COMMAND, Err(Rejection::Malformed));
+                let _ = queues::send_report(reports, report);
+            }
+        }
+        queues::report_error(reports, serial::Error::MalformedMessage.into());
+    }
+
+    if protocol.is_connected() {
+        if let Some(reason) = boot_report.reset_reason.take() {
+            queues::send_debug(reports, reason.as_str());
+        }
+
+        if let Some(DeviceInfo { unique_id, flash_size_kb, revision }) =
+            boot_report.device_info.take()
+        {
+            let _ = queues::send_report(
+                reports,
+                Report::DeviceInfo { unique_id, flash_size_kb, revision },
+            );
+        }
+
+        if let Some(diagnostics) = boot_report.diagnostics.take() {
+            let report = Report::BootDiagnostics { faults: diagnostics.faults() };
+            let _ = queues::send_report(reports, report);
+        }
+
+        if let Some(message) = boot_report.panic_message.take() {
+            queues::send_debug(reports, &message);
+        }
+
+        // Reports which don't fit in one write stay queued for the next call, so the host only
+        // misses any if it falls so far behind that the queue fills up. The counts are kept for
+        // a host which isn't subscribed to diagnostics, until it is.
+        let diagnostics = queues::subscriptions().is_subscribed(Category::Diagnostics);
+        if diagnostics && !reports.is_full() {
+            let dropped = queues::take_dropped_reports();
+            if dropped > 0 {
+                let count = dropped.min(u16::MAX as u32) as u16;
+                let _ = reports.enqueue(Report::ReportsDropped { count });
+            }
+        }
+
+        if diagnostics && !reports.is_full() {
+            let (dropped_bytes, dropped_commands) = protocol.take_overflow();
+            if dropped_bytes > 0 || dropped_commands > 0 {
+                let bytes = dropped_bytes.min(u16::MAX as u32) as u16;
+                let commands = dropped_commands.min(u16::MAX as u32) as u16;
+                let _ = reports.enqueue(Report::ReceiveOverflow { bytes, commands });
+            }
+        }
+
+        if diagnostics && !reports.is_full() {
+            let corrupt = protocol.take_corrupt_frames();
+            if corrupt > 0 {
+                let count = corrupt.min(u16::MAX as u32) as u16;
+                let _ = reports.enqueue(Report::CorruptFrames { count });
+            }
+        }
+
+        loop {
+            let sent = match protocol.try_reports(reports.iter()) {
+                Ok(0) | Err(nb::Error::WouldBlock) => break,
+                Ok(sent) => sent,
+                Err(nb::Error::Other(e)) => {
+                    // Only the first report is dropped, the rest are tried again next time, so a
+                    // host which has stopped reading doesn't hold up the interrupt for each one.
+                    reports.dequeue();
+                    queues::report_error(reports, e.into());
+                    break;
+                },
+            };
+            for _ in 0..sent {
+                reports.dequeue();
+            }
+        }
+    } else {
+        // Nobody is listening, and anything queued now would be stale by the time they are.
+        while reports.dequeue().is_some() {}
+        queues::take_dropped_reports();
+        // The next host starts off subscribed to everything. A suspended one is the same host,
+        // once it resumes.
+        if !protocol.is_suspended() {
+            queues::subscribe_all();
+        }
+    }
+
+    commands.peek().is_some()
+}
+
+/// Does what's left once `service()` has, the same whichever interrupt the link is serviced
+/// from: spawns `apply_commands` if there are commands, shows the latest LED frame from the
+/// host, and tells the power manager whether there's a host. Only the task can spawn others, so
+/// it passes in how.
+pub fn after_service<T: Transport, const RX_LEN: usize, const LEDS: usize>(
+    protocol: &mut SerialProtocol<T, RX_LEN>,
+    power: &mut PowerManager,
+    led_pixels: &mut LedPixels<LEDS>,
+    commands_received: bool,
+    spawn_apply_commands: impl FnOnce(),
+) {
+    if commands_received {
+        spawn_apply_commands();
+    }
+    if let Some(colors) = protocol.take_led_frame() {
+        led_pixels.show(&colors);
+    }
+
+    power.set_host_present(protocol.is_connected());
+    power.set_host_suspended(protocol.is_suspended());
+}
@@ -0,0 +1,42 @@
+// Which firmware this is, embedded at build time by build.rs, so that the host can find out what
+// a panel in the field is running.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FirmwareVersion {
+    /// The version in Cargo.toml.
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+    /// When the firmware was built, in seconds since the Unix epoch.
+    pub build_timestamp: u32,
+    /// The first 8 hex digits of the git commit it was built from, or 0 if that wasn't known.
+    pub commit: u32,
+    /// Whether there were uncommitted changes on top of that commit.
+    pub dirty: bool,
+}
+
+pub const FIRMWARE_VERSION: FirmwareVersion = FirmwareVersion {
+    major: parse(env!("CARGO_PKG_VERSION_MAJOR"), 10) as u8,
+    minor: parse(env!("CARGO_PKG_VERSION_MINOR"), 10) as u8,
+    patch: parse(env!("CARGO_PKG_VERSION_PATCH"), 10) as u8,
+    build_timestamp: parse(env!("PANEL_BUILD_TIMESTAMP"), 10),
+    commit: parse(env!("PANEL_GIT_COMMIT"), 16),
+    dirty: parse(env!("PANEL_GIT_DIRTY"), 10) != 0,
+};
+
+/// The value of the digits in `s`, in base `radix`. build.rs only passes lowercase digits.
+const fn parse(s: &str, radix: u32) -> u32 {
+    let bytes = s.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let digit = match bytes[i] {
+            byte @ b'0'..=b'9' => byte - b'0',
+            byte @ b'a'..=b'f' => byte - b'a' + 10,
+            _ => 0,
+        };
+        value = value * radix + digit as u32;
+        i += 1;
+    }
+    value
+}